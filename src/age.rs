@@ -0,0 +1,76 @@
+// How long ago a release came out, from a date like `2024-07-21`, for the
+// prompt
+
+const DAY: u64 = 24 * 60 * 60;
+
+// Days from 1970-01-01 to the date, in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // From March, so the leap day is at the end of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    return era * 146097 + day_of_era - 719468;
+}
+
+/// Seconds since the unix epoch at the start of a date like `2024-07-21`,
+/// in UTC. None if it isn't a date, or is before the epoch
+pub fn parse_date(text: &str) -> Option<u64> {
+    let mut parts = text.trim().splitn(3, '-').map(|x| x.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+
+    return Some(days * DAY);
+}
+
+/// Describes an age of `seconds`, like `3 days ago`. Under a day is
+/// `today`
+pub fn describe_age(seconds: u64) -> String {
+    let plural = |count: u64, unit: &str| match count {
+        1 => return format!("1 {unit} ago"),
+        x => return format!("{x} {unit}s ago"),
+    };
+
+    let days = seconds / DAY;
+    match days {
+        0 => return "today".to_string(),
+        1..=13 => return plural(days, "day"),
+        14..=59 => return plural(days / 7, "week"),
+        60..=729 => return plural(days / 30, "month"),
+        _ => return plural(days / 365, "year"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2024-07-21"), Some(1721520000));
+        assert_eq!(parse_date("2024-03-01"), Some(1709251200));
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("1969-12-31"), None);
+        assert_eq!(parse_date("last week"), None);
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(describe_age(0), "today");
+        assert_eq!(describe_age(DAY - 1), "today");
+        assert_eq!(describe_age(DAY), "1 day ago");
+        assert_eq!(describe_age(3 * DAY + 60), "3 days ago");
+        assert_eq!(describe_age(14 * DAY), "2 weeks ago");
+        assert_eq!(describe_age(90 * DAY), "3 months ago");
+        assert_eq!(describe_age(400 * DAY), "13 months ago");
+        assert_eq!(describe_age(800 * DAY), "2 years ago");
+    }
+}
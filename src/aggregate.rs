@@ -0,0 +1,283 @@
+// One report for several machines, for `--aggregate`, from the output of
+// `rustup check` saved on each. A directory has a file for each host,
+// named by the host, like `web1.txt`
+
+use std::{collections::BTreeMap, fs, io, path};
+
+use crate::{
+    json::Value,
+    parse::{self, ParsedUpdate},
+    report, Error,
+};
+
+/// What the check saved from one host found
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostCheck {
+    /// The name of its file, without the extension
+    pub host: String,
+
+    /// Why its file couldn't be read as a check, like `no check output`
+    /// for an empty file, or one with only an ssh error
+    pub updates: Result<Vec<ParsedUpdate>, String>,
+}
+
+impl HostCheck {
+    /// True if any of its toolchains, or rustup, has an update
+    pub fn has_updates(&self) -> bool {
+        match &self.updates {
+            Ok(updates) => return updates.iter().any(|x| x.new.is_some()),
+            Err(_) => return false,
+        }
+    }
+}
+
+// The check in the file at `path`. A file with no entries, or that can't be
+// parsed, is kept as an error, so a host whose check failed isn't shown as
+// up to date
+fn read_host(path: &path::Path) -> Result<Vec<ParsedUpdate>, String> {
+    let lines = crate::check_file_lines(path).map_err(|error| format!("error: {error}"))?;
+    let updates = parse::parse_rustup_check(&lines);
+    if updates.is_empty() {
+        return Err("no check output".to_string());
+    }
+
+    return Ok(updates);
+}
+
+/// Reads the check of each host in `dir`, sorted by host. Each file is
+/// parsed like `--parse-file` does. Hidden files and directories are
+/// skipped. Two files for the same host, like `web1.txt` and `web1.json`,
+/// are an error
+pub fn read_hosts(dir: &path::Path) -> Result<Vec<HostCheck>, Error> {
+    let entries = fs::read_dir(dir).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("Couldn't read {}: {error}", dir.display()),
+        )
+    })?;
+
+    let mut files: BTreeMap<String, path::PathBuf> = BTreeMap::new();
+    for entry in entries {
+        let path = entry?.path();
+        let Some(host) = path.file_stem().map(|x| x.to_string_lossy().to_string()) else {
+            continue;
+        };
+        if host.starts_with('.') || !path.is_file() {
+            continue;
+        }
+
+        if let Some(other) = files.insert(host.clone(), path.clone()) {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} and {} are both for the host {host}",
+                    other.display(),
+                    path.display()
+                ),
+            )));
+        }
+    }
+
+    let hosts = files
+        .into_iter()
+        .map(|(host, path)| {
+            return HostCheck {
+                host,
+                updates: read_host(&path),
+            };
+        })
+        .collect();
+
+    return Ok(hosts);
+}
+
+/// A line for each host, like `web1: 1 update (stable 1.80.0→1.80.1)` or
+/// `web2: no check output`, then which of them have updates pending, and
+/// which couldn't be read
+pub fn describe_hosts(hosts: &[HostCheck]) -> String {
+    let mut text = String::new();
+    for host in hosts {
+        let described = match &host.updates {
+            Ok(updates) => report::describe_updates(updates),
+            Err(error) => error.clone(),
+        };
+        text.push_str(&format!("{}: {described}\n", host.host));
+    }
+
+    let pending: Vec<&str> = hosts
+        .iter()
+        .filter(|x| x.has_updates())
+        .map(|x| x.host.as_str())
+        .collect();
+    let unknown: Vec<&str> = hosts
+        .iter()
+        .filter(|x| x.updates.is_err())
+        .map(|x| x.host.as_str())
+        .collect();
+    let mut summary = match (pending.len(), unknown.is_empty()) {
+        (0, true) => format!("{} host(s), all up to date", hosts.len()),
+        (0, false) => format!("{} host(s), none with updates pending", hosts.len()),
+        (x, _) => format!(
+            "{} host(s), {x} with updates pending: {}",
+            hosts.len(),
+            pending.join(", ")
+        ),
+    };
+    if !unknown.is_empty() {
+        summary.push_str(&format!(
+            ", {} without a check: {}",
+            unknown.len(),
+            unknown.join(", ")
+        ));
+    }
+    text.push_str(&format!("{summary}\n"));
+
+    return text;
+}
+
+/// The hosts as a JSON object, like
+///
+/// `{"hosts":{"web1":[{"current":"1.80.0","name":"stable-x86_64-unknown-linux-gnu","new":"1.80.1"}]},"pending":["web1"]}`
+///
+/// Each host has the `updates` array of `--report`. Hosts whose check
+/// couldn't be read are in `errors` instead, with why, like
+/// `{"web2":"no check output"}`
+pub fn hosts_json(hosts: &[HostCheck]) -> String {
+    let entries = hosts
+        .iter()
+        .filter_map(|x| {
+            let updates = x.updates.as_ref().ok()?;
+            return Some((x.host.clone(), report::update_entries(updates)));
+        })
+        .collect();
+    let errors = hosts
+        .iter()
+        .filter_map(|x| {
+            let error = x.updates.as_ref().err()?;
+            return Some((x.host.clone(), Value::String(error.clone())));
+        })
+        .collect();
+    let pending = hosts
+        .iter()
+        .filter(|x| x.has_updates())
+        .map(|x| return Value::String(x.host.clone()))
+        .collect();
+
+    let object = Value::Object(BTreeMap::from([
+        ("errors".to_string(), Value::Object(errors)),
+        ("hosts".to_string(), Value::Object(entries)),
+        ("pending".to_string(), Value::Array(pending)),
+    ]));
+
+    return format!("{object}\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn two_hosts() {
+        let dir = temp_dir("aggregate_two_hosts");
+        fs::write(
+            dir.join("web1.txt"),
+            "info: syncing channel updates\n\
+             stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1\n\
+             rustup - Up to date : 1.27.1\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("build2"),
+            r#"{"toolchains":[{"name":"stable-x86_64-unknown-linux-gnu","status":"up-to-date","current_version":"1.80.1"}]}"#,
+        )
+        .unwrap();
+        fs::write(dir.join(".hidden"), "not a host").unwrap();
+        fs::create_dir(dir.join("old")).unwrap();
+
+        let hosts = read_hosts(&dir).unwrap();
+        assert_eq!(
+            hosts.iter().map(|x| x.host.as_str()).collect::<Vec<_>>(),
+            ["build2", "web1"]
+        );
+        assert!(!hosts[0].has_updates());
+        assert!(hosts[1].has_updates());
+
+        assert_eq!(
+            describe_hosts(&hosts),
+            "build2: up to date\n\
+             web1: 1 update (stable 1.80.0→1.80.1)\n\
+             2 host(s), 1 with updates pending: web1\n"
+        );
+        assert_eq!(
+            hosts_json(&hosts),
+            "{\"errors\":{},\"hosts\":{\"build2\":[{\"current\":\"1.80.1\",\"name\":\"stable-x86_64-unknown-linux-gnu\",\"new\":null}],\
+             \"web1\":[{\"current\":\"1.80.0\",\"name\":\"stable-x86_64-unknown-linux-gnu\",\"new\":\"1.80.1\"},\
+             {\"current\":\"1.27.1\",\"name\":\"rustup\",\"new\":null}]},\"pending\":[\"web1\"]}\n"
+        );
+
+        assert_eq!(
+            describe_hosts(&hosts[..1]),
+            "build2: up to date\n1 host(s), all up to date\n"
+        );
+        assert!(read_hosts(&dir.join("missing")).is_err());
+    }
+
+    #[test]
+    fn hosts_without_a_check() {
+        let dir = temp_dir("aggregate_hosts_without_a_check");
+        fs::write(
+            dir.join("web1.txt"),
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1\n",
+        )
+        .unwrap();
+        fs::write(dir.join("web2.txt"), "").unwrap();
+        fs::write(
+            dir.join("web3.txt"),
+            "ssh: connect to host web3 port 22: Connection refused\n",
+        )
+        .unwrap();
+        fs::write(dir.join("web4.json"), r#"{"toolchains":["#).unwrap();
+
+        // Each is said to be unknown, and the rest are still read
+        let hosts = read_hosts(&dir).unwrap();
+        assert_eq!(hosts[1].updates, Err("no check output".to_string()));
+        assert_eq!(hosts[2].updates, Err("no check output".to_string()));
+        assert!(hosts[3]
+            .updates
+            .as_ref()
+            .unwrap_err()
+            .starts_with("error: Malformed JSON"));
+        assert!(!hosts[3].has_updates());
+
+        let text = describe_hosts(&hosts);
+        assert!(text.starts_with(
+            "web1: 1 update (stable 1.80.0→1.80.1)\n\
+             web2: no check output\n\
+             web3: no check output\n\
+             web4: error: Malformed JSON"
+        ));
+        assert!(text.ends_with(
+            "4 host(s), 1 with updates pending: web1, 3 without a check: web2, web3, web4\n"
+        ));
+        assert_eq!(
+            describe_hosts(&hosts[1..3]),
+            "web2: no check output\n\
+             web3: no check output\n\
+             2 host(s), none with updates pending, 2 without a check: web2, web3\n"
+        );
+
+        let json = hosts_json(&hosts[..2]);
+        assert!(json.starts_with("{\"errors\":{\"web2\":\"no check output\"},\"hosts\":{\"web1\":"));
+    }
+
+    #[test]
+    fn duplicate_hosts() {
+        let dir = temp_dir("aggregate_duplicate_hosts");
+        fs::write(dir.join("web1.txt"), "rustup - Up to date : 1.27.1\n").unwrap();
+        fs::write(dir.join("web1.json"), r#"{"toolchains":[]}"#).unwrap();
+
+        let error = read_hosts(&dir).unwrap_err().to_string();
+        assert!(error.contains("are both for the host web1"));
+    }
+}
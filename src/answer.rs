@@ -0,0 +1,221 @@
+// What each answer to the update prompt does
+
+use std::io;
+
+use crate::{
+    parse::{Channel, ParsedUpdate},
+    prompt::{TimeoutAction, UpdatePromptAnswer},
+    Config, Error, Outcome,
+};
+
+/// What an answer to the prompt can do. Lets `apply_answer` be run without
+/// changing the snooze or updating, like in tests
+pub trait AnswerActions {
+    /// Snoozes the updates found, for longer each time they're snoozed in
+    /// a row
+    fn snooze(&self) -> io::Result<()>;
+
+    /// Snoozes for an hour, whatever the updates
+    fn snooze_short(&self) -> io::Result<()>;
+
+    /// Updates `toolchains`, or everything if it's empty
+    fn update(&self, toolchains: &[String]) -> Result<Outcome, Error>;
+}
+
+// Toolchains with updates, to pass to the update command. rustup isn't a
+// toolchain, and updates itself with any `rustup update`
+pub fn toolchains_to_update(updates: &[ParsedUpdate]) -> Vec<String> {
+    return updates
+        .iter()
+        .filter(|x| x.new.is_some() && x.channel_kind() != Channel::Rustup)
+        .map(|x| x.name.clone())
+        .collect();
+}
+
+// Snoozes, or not, as `action` says, after the prompt went unanswered for
+// `reason`
+fn snooze_unanswered(
+    actions: &dyn AnswerActions,
+    action: TimeoutAction,
+    reason: &str,
+) -> io::Result<()> {
+    match action {
+        TimeoutAction::AskNextRun => println!("{reason}. Asking later..."),
+        TimeoutAction::SnoozeShort => {
+            println!("{reason}. Asking again in an hour");
+            actions.snooze_short()?;
+        }
+        TimeoutAction::Decline => {
+            println!("{reason}. Treating it as no");
+            actions.snooze()?;
+        }
+    }
+
+    return io::Result::Ok(());
+}
+
+/// Does what `answer` says about the `updates` found, with `actions`
+///
+/// When the update is `required`, as stable is below the minimum version,
+/// saying no or closing the prompt doesn't snooze, so it's asked again next
+/// time
+pub fn apply_answer(
+    answer: UpdatePromptAnswer,
+    config: &Config,
+    updates: &[ParsedUpdate],
+    required: bool,
+    actions: &dyn AnswerActions,
+) -> Result<Outcome, Error> {
+    match answer {
+        // Only from a callback. Updates were found, so it's up to it
+        UpdatePromptAnswer::NoUpdateFound => {
+            println!("Prompt said there's nothing to update");
+
+            return Ok(Outcome::UpToDate);
+        }
+        UpdatePromptAnswer::DoNotUpdate if required => {
+            println!(
+                "User said no updates. Below the minimum version, so will ask again next time"
+            );
+
+            return Ok(Outcome::Declined);
+        }
+        UpdatePromptAnswer::DoNotUpdate => {
+            println!("User said no updates. Setting no update flag");
+            actions.snooze()?;
+
+            return Ok(Outcome::Declined);
+        }
+        UpdatePromptAnswer::Timeout => {
+            snooze_unanswered(actions, config.timeout_action, "Prompt timed out")?;
+
+            return Ok(Outcome::TimedOut);
+        }
+        UpdatePromptAnswer::Closed => {
+            // Like a timeout, a required update is asked again next time
+            let action = match required {
+                true => TimeoutAction::AskNextRun,
+                false => config.close_action,
+            };
+            snooze_unanswered(actions, action, "Prompt was closed")?;
+
+            return Ok(Outcome::Declined);
+        }
+        UpdatePromptAnswer::Update => {
+            println!("Updated Rust in new terminal");
+
+            return actions.update(&toolchains_to_update(updates));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::parse::parse_rustup_check;
+
+    // Records the actions taken, and updates successfully
+    #[derive(Default)]
+    struct FakeActions {
+        taken: RefCell<Vec<String>>,
+    }
+
+    impl AnswerActions for FakeActions {
+        fn snooze(&self) -> io::Result<()> {
+            self.taken.borrow_mut().push("snooze".to_string());
+            return io::Result::Ok(());
+        }
+
+        fn snooze_short(&self) -> io::Result<()> {
+            self.taken.borrow_mut().push("snooze_short".to_string());
+            return io::Result::Ok(());
+        }
+
+        fn update(&self, toolchains: &[String]) -> Result<Outcome, Error> {
+            self.taken
+                .borrow_mut()
+                .push(format!("update {}", toolchains.join(" ")));
+            return Ok(Outcome::Updated);
+        }
+    }
+
+    // Outcome and actions taken after answering `answer`
+    fn apply(
+        answer: UpdatePromptAnswer,
+        config: &Config,
+        required: bool,
+    ) -> (Outcome, Vec<String>) {
+        let updates = parse_rustup_check(&[
+            "stable - Update available : 1.80.0 -> 1.80.1".to_string(),
+            "rustup - Update available : 1.27.0 -> 1.27.1".to_string(),
+        ]);
+        let actions = FakeActions::default();
+
+        let outcome = apply_answer(answer, config, &updates, required, &actions).unwrap();
+
+        return (outcome, actions.taken.into_inner());
+    }
+
+    #[test]
+    fn answers() {
+        let config = Config::default();
+
+        assert_eq!(
+            apply(UpdatePromptAnswer::Update, &config, false),
+            (Outcome::Updated, vec!["update stable".to_string()])
+        );
+        assert_eq!(
+            apply(UpdatePromptAnswer::DoNotUpdate, &config, false),
+            (Outcome::Declined, vec!["snooze".to_string()])
+        );
+        assert_eq!(
+            apply(UpdatePromptAnswer::NoUpdateFound, &config, false),
+            (Outcome::UpToDate, vec![])
+        );
+
+        // Timeouts ask again next run by default, and closing is no
+        assert_eq!(
+            apply(UpdatePromptAnswer::Timeout, &config, false),
+            (Outcome::TimedOut, vec![])
+        );
+        assert_eq!(
+            apply(UpdatePromptAnswer::Closed, &config, false),
+            (Outcome::Declined, vec!["snooze".to_string()])
+        );
+
+        let config = Config {
+            timeout_action: TimeoutAction::SnoozeShort,
+            close_action: TimeoutAction::SnoozeShort,
+            ..Config::default()
+        };
+        assert_eq!(
+            apply(UpdatePromptAnswer::Timeout, &config, false),
+            (Outcome::TimedOut, vec!["snooze_short".to_string()])
+        );
+        assert_eq!(
+            apply(UpdatePromptAnswer::Closed, &config, false),
+            (Outcome::Declined, vec!["snooze_short".to_string()])
+        );
+    }
+
+    #[test]
+    fn required_answers() {
+        let config = Config::default();
+
+        // Never snoozed
+        assert_eq!(
+            apply(UpdatePromptAnswer::DoNotUpdate, &config, true),
+            (Outcome::Declined, vec![])
+        );
+        assert_eq!(
+            apply(UpdatePromptAnswer::Closed, &config, true),
+            (Outcome::Declined, vec![])
+        );
+        assert_eq!(
+            apply(UpdatePromptAnswer::Update, &config, true),
+            (Outcome::Updated, vec!["update stable".to_string()])
+        );
+    }
+}
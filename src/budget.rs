@@ -0,0 +1,97 @@
+// How many prompts have been shown today, for `daily_prompt_budget`. Kept
+// apart from the no-update flag, as a prompt that times out or is closed
+// doesn't snooze, but still counts
+
+use std::{fs, io, path};
+
+use crate::state_file;
+
+const DAY: u64 = 24 * 60 * 60;
+
+// Prompts shown on the day `day`, counted in days since the unix epoch, in
+// UTC
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct PromptCount {
+    day: u64,
+    shown: u64,
+}
+
+impl PromptCount {
+    // File is made of `key=value` lines
+    fn parse(content: &str) -> PromptCount {
+        let mut count = PromptCount::default();
+
+        for (key, value) in state_file::key_values(content) {
+            match key {
+                "day" => count.day = value.trim().parse().unwrap_or(0),
+                "shown" => count.shown = value.trim().parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+
+        return count;
+    }
+
+    fn serialize(&self) -> String {
+        return format!("day={}\nshown={}\n", self.day, self.shown);
+    }
+}
+
+// The count at `path`, all 0 if there isn't a file yet
+fn read_count(path: &path::Path) -> io::Result<PromptCount> {
+    match fs::read_to_string(path) {
+        io::Result::Ok(content) => return io::Result::Ok(PromptCount::parse(&content)),
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(PromptCount::default())
+        }
+        io::Result::Err(error) => return io::Result::Err(error),
+    }
+}
+
+/// How many prompts the count at `path` says were shown on the day of
+/// `now`, a day being from midnight UTC
+pub fn shown_today(path: &path::Path, now: u64) -> io::Result<u64> {
+    let count = read_count(path)?;
+    if count.day != now / DAY {
+        return io::Result::Ok(0);
+    }
+
+    return io::Result::Ok(count.shown);
+}
+
+/// Counts a prompt shown at `now` in the count at `path`, starting again
+/// on a new day
+pub fn record_prompt(path: &path::Path, now: u64) -> io::Result<()> {
+    let count = PromptCount {
+        day: now / DAY,
+        shown: shown_today(path, now)? + 1,
+    };
+
+    return state_file::write_atomic(path, &count.serialize());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn counts_each_day() {
+        let path = temp_dir("budget_counts_each_day").join("prompts");
+        let morning = 19_950 * DAY + 8 * 60 * 60;
+        assert_eq!(shown_today(&path, morning).unwrap(), 0);
+
+        record_prompt(&path, morning).unwrap();
+        record_prompt(&path, morning + 60).unwrap();
+        assert_eq!(shown_today(&path, morning + 12 * 60 * 60).unwrap(), 2);
+
+        // Midnight starts the count again
+        let tomorrow = (morning / DAY + 1) * DAY;
+        assert_eq!(shown_today(&path, tomorrow).unwrap(), 0);
+        record_prompt(&path, tomorrow).unwrap();
+        assert_eq!(shown_today(&path, tomorrow).unwrap(), 1);
+
+        fs::write(&path, "bogus").unwrap();
+        assert_eq!(shown_today(&path, tomorrow).unwrap(), 0);
+    }
+}
@@ -0,0 +1,122 @@
+// Which flags of `rustup check` a rustup doesn't know, saved so an older
+// rustup isn't run once for each flag it's missing, every check
+
+use std::{fs, io, os::unix::fs::MetadataExt, path};
+
+use crate::state_file;
+
+/// The flags a rustup was found to know, and which rustup it was
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckFlags {
+    /// Its path, as it was run
+    pub rustup: String,
+
+    /// When it was last changed, as seconds since the unix epoch, and its
+    /// size. A new rustup changes them, so its flags are found again
+    pub modified: i64,
+    pub size: u64,
+
+    /// If it knows `check --json`
+    pub json: bool,
+
+    /// If it knows `--quiet`
+    pub quiet: bool,
+}
+
+impl CheckFlags {
+    // File is made of `key=value` lines
+    fn parse(content: &str) -> Option<CheckFlags> {
+        let mut rustup = None;
+        let mut modified = None;
+        let mut size = None;
+        let mut json = None;
+        let mut quiet = None;
+
+        for (key, value) in state_file::key_values(content) {
+            match key {
+                "rustup" => rustup = Some(value.to_string()),
+                "modified" => modified = value.trim().parse().ok(),
+                "size" => size = value.trim().parse().ok(),
+                "json" => json = value.trim().parse().ok(),
+                "quiet" => quiet = value.trim().parse().ok(),
+                _ => {}
+            }
+        }
+
+        return Some(CheckFlags {
+            rustup: rustup?,
+            modified: modified?,
+            size: size?,
+            json: json?,
+            quiet: quiet?,
+        });
+    }
+
+    fn serialize(&self) -> String {
+        return format!(
+            "rustup={}\nmodified={}\nsize={}\njson={}\nquiet={}\n",
+            self.rustup, self.modified, self.size, self.json, self.quiet
+        );
+    }
+}
+
+/// When `rustup` was last changed, and its size, as kept in `CheckFlags`.
+/// None if it can't be found
+pub fn rustup_identity(rustup: &path::Path) -> Option<(i64, u64)> {
+    let metadata = fs::metadata(rustup).ok()?;
+
+    return Some((metadata.mtime(), metadata.len()));
+}
+
+/// The flags saved at `path`, if they're for `rustup` as it is now
+///
+/// A missing or malformed file is treated as nothing being saved
+pub fn read_flags(path: &path::Path, rustup: &path::Path) -> Option<CheckFlags> {
+    let flags = CheckFlags::parse(&fs::read_to_string(path).ok()?)?;
+    let (modified, size) = rustup_identity(rustup)?;
+    if flags.rustup != rustup.to_string_lossy() || flags.modified != modified || flags.size != size
+    {
+        return None;
+    }
+
+    return Some(flags);
+}
+
+/// Saves `flags` at `path`, replacing those of any other rustup
+pub fn write_flags(path: &path::Path, flags: &CheckFlags) -> io::Result<()> {
+    return state_file::write_atomic(path, &flags.serialize());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn saved_for_one_rustup() {
+        let dir = temp_dir("check_flags_saved_for_one_rustup");
+        let rustup = dir.join("rustup");
+        fs::write(&rustup, "old rustup").unwrap();
+        let path = dir.join("check_flags");
+        assert_eq!(read_flags(&path, &rustup), None);
+
+        let (modified, size) = rustup_identity(&rustup).unwrap();
+        let flags = CheckFlags {
+            rustup: rustup.to_string_lossy().to_string(),
+            modified,
+            size,
+            json: false,
+            quiet: true,
+        };
+        write_flags(&path, &flags).unwrap();
+        assert_eq!(read_flags(&path, &rustup), Some(flags));
+
+        // Another rustup, or this one once it's updated, is found again
+        assert_eq!(read_flags(&path, &dir.join("missing")), None);
+        fs::write(&rustup, "a newer rustup").unwrap();
+        assert_eq!(read_flags(&path, &rustup), None);
+
+        fs::write(&path, "json=false\n").unwrap();
+        assert_eq!(read_flags(&path, &rustup), None);
+    }
+}
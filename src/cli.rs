@@ -0,0 +1,663 @@
+use std::{io, path};
+
+use crate::{format::Format, prompt::TimeoutAction, root::RootPolicy, snooze, Config};
+
+/// What the program was asked to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Check for updates, prompting the user if there are any
+    Auto,
+
+    /// Update straight away, without checking or prompting. With `wait`,
+    /// blocks until the update finishes instead of opening a terminal
+    UpdateNow { wait: bool },
+
+    /// Update only `toolchains`, like `nightly`, straight away, blocking
+    /// until the update finishes
+    UpdateToolchains { toolchains: Vec<String> },
+
+    /// Link the binary into `~/.local/bin`, optionally running it on login
+    Install,
+
+    /// Undo `Install`
+    Uninstall,
+
+    /// Check components like clippy match the rustc of their toolchain
+    CheckComponents,
+
+    /// Print information about the setup, and problems found with it
+    Diagnose,
+
+    /// Snooze the prompt for `seconds`, without checking for updates
+    Snooze { seconds: u64 },
+
+    /// Check for updates and print what was found in `format`, without
+    /// prompting
+    Status { format: Format },
+
+    /// Show the prompt for the updates found, or made up ones, without
+    /// acting on the answer
+    PreviewPrompt,
+
+    /// Update on the first run after the next boot, rather than now
+    OnNextBoot,
+
+    /// Keep running, checking every `interval` seconds
+    Watch { interval: u64 },
+
+    /// Print how many updates worked and failed
+    Stats,
+
+    /// Explain whether the next check would prompt, going by the snooze
+    Why,
+
+    /// Print how each line of `rustup check` output saved in the file at
+    /// `path` is parsed, for bug reports. Not listed in the README's
+    /// options, as it's for debugging the parser
+    ParseFile { path: path::PathBuf },
+
+    /// Print which hosts have updates pending, from the `rustup check`
+    /// output of each in a file in `dir`, as text, or JSON with `json`
+    Aggregate { dir: path::PathBuf, json: bool },
+
+    /// Print the config after the config file, environment variables and
+    /// command line are read, as TOML, or JSON with `json`
+    ShowConfig { json: bool },
+}
+
+/// Parsed command line arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+
+    /// `--require-ac-power`
+    pub require_ac_power: bool,
+
+    /// `--allow-root`
+    pub allow_root: bool,
+
+    /// `--accessible`
+    pub accessible: bool,
+
+    /// `--offer-install`, to say how to install rustup if it's missing
+    pub offer_install: bool,
+
+    /// `--recheck`, to print the versions installed after an update
+    pub recheck: bool,
+
+    /// `--no-snooze`, to never read or write the no-update flag
+    pub no_snooze: bool,
+
+    /// `--detach`, to update in the background
+    pub detach: bool,
+
+    /// `--verify-rustup`, to refuse to run a rustup that could have been
+    /// swapped out
+    pub verify_rustup: bool,
+
+    /// `--force`, to check for updates even if checked recently
+    pub force: bool,
+
+    /// `--timeout-action <action>`, for what to do when the prompt times out
+    pub timeout_action: Option<TimeoutAction>,
+
+    /// `--verbose`, to print the entries read from `rustup check`
+    pub verbose: bool,
+
+    /// `--dry-run`, to check without prompting or updating
+    pub dry_run: bool,
+
+    /// `--report <path>`, to write a JSON report of the run to
+    pub report_path: Option<path::PathBuf>,
+
+    /// `--metrics <path>`, to write Prometheus metrics about the run to
+    pub metrics_path: Option<path::PathBuf>,
+
+    /// `--config <path>`, to read settings from instead of the usual
+    /// config file
+    pub config_path: Option<path::PathBuf>,
+}
+
+impl Cli {
+    /// Loads the config file, from `--config` if it was given, then
+    /// overrides it with the command line
+    pub fn load_config(&self) -> io::Result<Config> {
+        let mut config = match &self.config_path {
+            Some(path) => Config::load_from(path)?,
+            None => Config::load()?,
+        };
+        self.apply(&mut config);
+
+        return io::Result::Ok(config);
+    }
+
+    /// Overrides the settings in `config` with those given on the command
+    /// line
+    pub fn apply(&self, config: &mut Config) {
+        if self.require_ac_power {
+            config.require_ac_power = true;
+        }
+        if self.allow_root {
+            config.root_policy = RootPolicy::Warn;
+        }
+        if self.accessible {
+            config.accessible = true;
+        }
+        if self.offer_install {
+            config.offer_install = true;
+        }
+        if self.recheck {
+            config.recheck_after_update = true;
+        }
+        if self.no_snooze {
+            config.no_snooze = true;
+        }
+        if self.detach {
+            config.detach = true;
+        }
+        if self.verify_rustup {
+            config.verify_rustup = true;
+        }
+        if self.force {
+            config.check_interval = 0;
+            config.daily_prompt_budget = 0;
+        }
+        if let Some(action) = self.timeout_action {
+            config.timeout_action = action;
+        }
+        if self.verbose {
+            config.verbose = true;
+        }
+        if self.dry_run {
+            config.dry_run = true;
+        }
+        if let Some(path) = &self.report_path {
+            config.report_path = Some(path.clone());
+        }
+        if let Some(path) = &self.metrics_path {
+            config.metrics_path = Some(path.clone());
+        }
+    }
+}
+
+fn invalid_input(message: String) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidInput, message);
+}
+
+/// Parses the command line arguments, not including the program name
+///
+/// Unknown arguments are an `InvalidInput` error
+pub fn parse_args<I>(args: I) -> io::Result<Cli>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut update_now = false;
+    let mut wait = false;
+    let mut format = None;
+    let mut cli = Cli {
+        command: Command::Auto,
+        require_ac_power: false,
+        allow_root: false,
+        accessible: false,
+        offer_install: false,
+        recheck: false,
+        no_snooze: false,
+        detach: false,
+        verify_rustup: false,
+        force: false,
+        timeout_action: None,
+        verbose: false,
+        dry_run: false,
+        report_path: None,
+        metrics_path: None,
+        config_path: None,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--require-ac-power" => cli.require_ac_power = true,
+            "--allow-root" => cli.allow_root = true,
+            "--force" => cli.force = true,
+            "--accessible" => cli.accessible = true,
+            "--offer-install" => cli.offer_install = true,
+            "--recheck" => cli.recheck = true,
+            "--no-snooze" => cli.no_snooze = true,
+            "--detach" => cli.detach = true,
+            "--verify-rustup" => cli.verify_rustup = true,
+            "--timeout-action" => match args.next() {
+                Some(action) => cli.timeout_action = Some(action.parse().map_err(invalid_input)?),
+                None => {
+                    return io::Result::Err(invalid_input(
+                        "--timeout-action needs an action: ask-next-run, snooze-short or decline"
+                            .to_string(),
+                    ))
+                }
+            },
+            "--verbose" => cli.verbose = true,
+            "--dry-run" => cli.dry_run = true,
+            "--report" => match args.next() {
+                Some(path) => cli.report_path = Some(path::PathBuf::from(path)),
+                None => return io::Result::Err(invalid_input("--report needs a path".to_string())),
+            },
+            "--metrics" => match args.next() {
+                Some(path) => cli.metrics_path = Some(path::PathBuf::from(path)),
+                None => {
+                    return io::Result::Err(invalid_input("--metrics needs a path".to_string()))
+                }
+            },
+            "--aggregate" => match args.next() {
+                Some(dir) => {
+                    cli.command = Command::Aggregate {
+                        dir: path::PathBuf::from(dir),
+                        json: false,
+                    }
+                }
+                None => {
+                    return io::Result::Err(invalid_input(
+                        "--aggregate needs a directory".to_string(),
+                    ))
+                }
+            },
+            "--update-now" => update_now = true,
+            "--wait" => wait = true,
+            "--config" => match args.next() {
+                Some(path) => cli.config_path = Some(path::PathBuf::from(path)),
+                None => return io::Result::Err(invalid_input("--config needs a path".to_string())),
+            },
+            "--install" => cli.command = Command::Install,
+            "--uninstall" => cli.command = Command::Uninstall,
+            "--check-components" => cli.command = Command::CheckComponents,
+            "--diagnose" => cli.command = Command::Diagnose,
+            "--oneline" => format = Some(Format::Oneline),
+            "--format" => match args.next() {
+                Some(text) => format = Some(text.parse().map_err(invalid_input)?),
+                None => {
+                    return io::Result::Err(invalid_input(
+                        "--format needs a format: text, json or oneline".to_string(),
+                    ))
+                }
+            },
+            "--show-config" => cli.command = Command::ShowConfig { json: false },
+            "--stats" => cli.command = Command::Stats,
+            "--why" => cli.command = Command::Why,
+            "--parse-file" => match args.next() {
+                Some(path) => {
+                    cli.command = Command::ParseFile {
+                        path: path::PathBuf::from(path),
+                    }
+                }
+                None => {
+                    return io::Result::Err(invalid_input("--parse-file needs a path".to_string()))
+                }
+            },
+            "update" => {
+                cli.command = Command::UpdateToolchains {
+                    toolchains: Vec::new(),
+                }
+            }
+            "--preview-prompt" => cli.command = Command::PreviewPrompt,
+            "--on-next-boot" => cli.command = Command::OnNextBoot,
+            "--watch" => match args.next() {
+                Some(duration) => {
+                    let interval = snooze::parse_duration(&duration).map_err(invalid_input)?;
+                    if interval == 0 {
+                        return io::Result::Err(invalid_input(
+                            "--watch can't check every 0 seconds".to_string(),
+                        ));
+                    }
+                    cli.command = Command::Watch { interval };
+                }
+                None => {
+                    return io::Result::Err(invalid_input(
+                        "--watch needs an interval, like 6h".to_string(),
+                    ))
+                }
+            },
+            "--snooze" => match args.next() {
+                Some(duration) => {
+                    let seconds = snooze::parse_duration(&duration).map_err(invalid_input)?;
+                    cli.command = Command::Snooze { seconds };
+                }
+                None => {
+                    return io::Result::Err(invalid_input(
+                        "--snooze needs a duration, like 12h or 3d".to_string(),
+                    ))
+                }
+            },
+            _ => match &mut cli.command {
+                // Toolchains for `update`, before and between the options
+                Command::UpdateToolchains { toolchains } if !arg.starts_with('-') => {
+                    toolchains.push(arg)
+                }
+                _ => return io::Result::Err(invalid_input(format!("Unknown argument '{arg}'"))),
+            },
+        }
+    }
+
+    if cli.command
+        == (Command::UpdateToolchains {
+            toolchains: Vec::new(),
+        })
+    {
+        return io::Result::Err(invalid_input(
+            "update needs the toolchains to update, like `update nightly`".to_string(),
+        ));
+    }
+
+    // The format is for printing the status, the config, or the hosts
+    match (format, &mut cli.command) {
+        (None, _) => {}
+        (Some(format), Command::Auto) => cli.command = Command::Status { format },
+        (Some(Format::Text), Command::ShowConfig { .. } | Command::Aggregate { .. }) => {}
+        (Some(Format::Json), Command::ShowConfig { json } | Command::Aggregate { json, .. }) => {
+            *json = true
+        }
+        (Some(Format::Oneline), Command::ShowConfig { .. } | Command::Aggregate { .. }) => {
+            return io::Result::Err(invalid_input(
+                "--show-config and --aggregate can only be shown as text or json".to_string(),
+            ))
+        }
+        (Some(_), _) => {
+            return io::Result::Err(invalid_input(
+                "--format and --oneline can't be used with another command, except \
+                 --show-config and --aggregate"
+                    .to_string(),
+            ))
+        }
+    }
+
+    if update_now {
+        if cli.command != Command::Auto {
+            return io::Result::Err(invalid_input(
+                "--update-now can't be used with another command".to_string(),
+            ));
+        }
+
+        if wait && cli.detach {
+            return io::Result::Err(invalid_input(
+                "--wait can't be used with --detach".to_string(),
+            ));
+        }
+
+        cli.command = Command::UpdateNow { wait };
+    } else if wait {
+        return io::Result::Err(invalid_input(
+            "--wait can only be used with --update-now".to_string(),
+        ));
+    }
+
+    if cli.no_snooze && matches!(cli.command, Command::Snooze { .. }) {
+        return io::Result::Err(invalid_input(
+            "--no-snooze can't be used with --snooze".to_string(),
+        ));
+    }
+
+    // Printed while checking, so it would do nothing anywhere else, like
+    // with `--format`, which prints the status
+    if cli.verbose && cli.command != Command::Auto {
+        return io::Result::Err(invalid_input(
+            "--verbose can only be used when checking for updates".to_string(),
+        ));
+    }
+
+    // Only checking for updates can be a dry run, or be reported on
+    if cli.command != Command::Auto
+        && (cli.dry_run || cli.report_path.is_some() || cli.metrics_path.is_some())
+    {
+        return io::Result::Err(invalid_input(
+            "--dry-run, --report and --metrics can only be used when checking for updates"
+                .to_string(),
+        ));
+    }
+
+    return io::Result::Ok(cli);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    fn parse(args: &[&str]) -> io::Result<Cli> {
+        return parse_args(args.iter().map(|x| x.to_string()));
+    }
+
+    #[test]
+    fn no_args() {
+        let cli = parse(&[]).unwrap();
+
+        assert_eq!(cli.command, Command::Auto);
+
+        let mut config = Config::default();
+        cli.apply(&mut config);
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn config_flags() {
+        let cli = parse(&[
+            "--require-ac-power",
+            "--allow-root",
+            "--force",
+            "--accessible",
+            "--offer-install",
+            "--recheck",
+            "--no-snooze",
+            "--detach",
+            "--verify-rustup",
+            "--verbose",
+            "--timeout-action",
+            "decline",
+        ])
+        .unwrap();
+
+        let mut config = Config {
+            daily_prompt_budget: 1,
+            ..Config::default()
+        };
+        cli.apply(&mut config);
+
+        assert!(config.require_ac_power);
+        assert_eq!(config.root_policy, RootPolicy::Warn);
+        assert_eq!(config.check_interval, 0);
+        assert_eq!(config.daily_prompt_budget, 0);
+        assert!(config.accessible);
+        assert!(config.offer_install);
+        assert!(config.recheck_after_update);
+        assert!(config.no_snooze);
+        assert!(config.detach);
+        assert!(config.verify_rustup);
+        assert!(config.verbose);
+        assert_eq!(config.timeout_action, TimeoutAction::Decline);
+
+        assert!(parse(&["--timeout-action"]).is_err());
+        assert!(parse(&["--timeout-action", "never"]).is_err());
+    }
+
+    #[test]
+    fn verbose_conflicts() {
+        assert!(parse(&["--verbose", "--dry-run"]).is_ok());
+        assert_eq!(
+            parse(&["--verbose", "--format", "json"])
+                .unwrap_err()
+                .to_string(),
+            "--verbose can only be used when checking for updates"
+        );
+        assert!(parse(&["--oneline", "--verbose"]).is_err());
+        assert!(parse(&["--verbose", "--update-now"]).is_err());
+    }
+
+    #[test]
+    fn update_now() {
+        assert_eq!(
+            parse(&["--update-now"]).unwrap().command,
+            Command::UpdateNow { wait: false }
+        );
+        assert_eq!(
+            parse(&["--update-now", "--wait"]).unwrap().command,
+            Command::UpdateNow { wait: true }
+        );
+        assert!(parse(&["--wait"]).is_err());
+        assert!(parse(&["--update-now", "--wait", "--detach"]).is_err());
+    }
+
+    #[test]
+    fn update_toolchains() {
+        let cli = parse(&["update", "nightly", "--allow-root", "beta"]).unwrap();
+        assert_eq!(
+            cli.command,
+            Command::UpdateToolchains {
+                toolchains: vec!["nightly".to_string(), "beta".to_string()]
+            }
+        );
+        assert!(cli.allow_root);
+
+        assert!(parse(&["update"]).is_err());
+        assert!(parse(&["update", "nightly", "--update-now"]).is_err());
+        assert!(parse(&["nightly"]).is_err());
+    }
+
+    #[test]
+    fn install() {
+        assert_eq!(parse(&["--install"]).unwrap().command, Command::Install);
+        assert_eq!(parse(&["--uninstall"]).unwrap().command, Command::Uninstall);
+        assert!(parse(&["--install", "--update-now"]).is_err());
+        assert_eq!(
+            parse(&["--check-components"]).unwrap().command,
+            Command::CheckComponents
+        );
+        assert_eq!(parse(&["--diagnose"]).unwrap().command, Command::Diagnose);
+        assert_eq!(
+            parse(&["--snooze", "3d"]).unwrap().command,
+            Command::Snooze {
+                seconds: 3 * 24 * 60 * 60
+            }
+        );
+        assert!(parse(&["--snooze"]).is_err());
+        assert_eq!(
+            parse(&["--oneline"]).unwrap().command,
+            Command::Status {
+                format: Format::Oneline
+            }
+        );
+        assert_eq!(
+            parse(&["--format", "json"]).unwrap().command,
+            Command::Status {
+                format: Format::Json
+            }
+        );
+        assert!(parse(&["--format"]).is_err());
+        assert!(parse(&["--format", "yaml"]).is_err());
+        assert!(parse(&["--format", "json", "--diagnose"]).is_err());
+        assert_eq!(
+            parse(&["--show-config"]).unwrap().command,
+            Command::ShowConfig { json: false }
+        );
+        assert_eq!(
+            parse(&["--format", "json", "--show-config"])
+                .unwrap()
+                .command,
+            Command::ShowConfig { json: true }
+        );
+        assert!(parse(&["--show-config", "--oneline"]).is_err());
+        assert_eq!(
+            parse(&["--aggregate", "checks", "--format", "json"])
+                .unwrap()
+                .command,
+            Command::Aggregate {
+                dir: path::PathBuf::from("checks"),
+                json: true
+            }
+        );
+        assert!(parse(&["--aggregate"]).is_err());
+        assert!(parse(&["--aggregate", "checks", "--oneline"]).is_err());
+        assert_eq!(parse(&["--stats"]).unwrap().command, Command::Stats);
+        assert_eq!(parse(&["--why"]).unwrap().command, Command::Why);
+        assert_eq!(
+            parse(&["--parse-file", "check.txt"]).unwrap().command,
+            Command::ParseFile {
+                path: path::PathBuf::from("check.txt")
+            }
+        );
+        assert!(parse(&["--parse-file"]).is_err());
+        assert_eq!(
+            parse(&["--preview-prompt"]).unwrap().command,
+            Command::PreviewPrompt
+        );
+        assert_eq!(
+            parse(&["--on-next-boot"]).unwrap().command,
+            Command::OnNextBoot
+        );
+        assert!(parse(&["--on-next-boot", "--update-now"]).is_err());
+        assert_eq!(
+            parse(&["--watch", "6h"]).unwrap().command,
+            Command::Watch {
+                interval: 6 * 60 * 60
+            }
+        );
+        assert!(parse(&["--watch"]).is_err());
+        assert!(parse(&["--watch", "0h"]).is_err());
+        assert!(parse(&["--snooze", "soon"]).is_err());
+        assert!(parse(&["--snooze", "3d", "--no-snooze"]).is_err());
+    }
+
+    #[test]
+    fn config_path() {
+        let path = temp_dir("cli_config_path").join("config.toml");
+        fs::write(&path, "require_ac_power = true").unwrap();
+
+        let cli = parse(&["--config", path.to_str().unwrap(), "--allow-root"]).unwrap();
+        assert_eq!(cli.config_path.as_deref(), Some(path.as_path()));
+
+        let config = cli.load_config().unwrap();
+        assert!(config.require_ac_power);
+        assert_eq!(config.root_policy, RootPolicy::Warn);
+
+        assert!(parse(&["--config"]).is_err());
+
+        let missing = parse(&["--config", "/nonexistent/config.toml"]).unwrap();
+        assert_eq!(
+            missing.load_config().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn dry_run_report() {
+        let cli = parse(&[
+            "--dry-run",
+            "--report",
+            "/tmp/report.json",
+            "--metrics",
+            "/tmp/rust.prom",
+        ])
+        .unwrap();
+        assert_eq!(cli.command, Command::Auto);
+
+        let mut config = Config::default();
+        cli.apply(&mut config);
+        assert!(config.dry_run);
+        assert_eq!(
+            config.report_path.as_deref(),
+            Some(path::Path::new("/tmp/report.json"))
+        );
+        assert_eq!(
+            config.metrics_path.as_deref(),
+            Some(path::Path::new("/tmp/rust.prom"))
+        );
+
+        assert!(parse(&["--report"]).is_err());
+        assert!(parse(&["--dry-run", "--update-now"]).is_err());
+        assert!(parse(&["--install", "--report", "/tmp/report.json"]).is_err());
+        assert!(parse(&["--metrics"]).is_err());
+        assert!(parse(&["--install", "--metrics", "/tmp/rust.prom"]).is_err());
+    }
+
+    #[test]
+    fn unknown_arg() {
+        let error = parse(&["--bogus"]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,284 @@
+// Knowing when an update run in a terminal has finished. Some terminals,
+// like gnome-terminal with its single server, start the command in a window
+// of the server and return straight away, so how the terminal exited says
+// nothing about the update. The update script writes its process id to a
+// status file when it starts, and the exit code of the update when it's done
+
+use std::{fs, io, os::unix::fs::OpenOptionsExt, path, thread, time};
+
+use crate::state_file;
+
+/// Longest wait for the script to start after its terminal returned, in
+/// looks at the status file. A terminal server can take a while to open
+/// the window
+pub const START_GRACE: u32 = 30;
+
+/// How far the update script has got, from its status file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptState {
+    /// Nothing written yet, or no file
+    NotStarted,
+
+    /// The script is the process `pid`
+    Running { pid: u32 },
+
+    /// The update exited with `code`. None if it isn't a number
+    Finished { code: Option<i32> },
+}
+
+impl ScriptState {
+    // File is made of `key=value` lines, `pid` then `code`
+    fn parse(content: &str) -> ScriptState {
+        let mut state = ScriptState::NotStarted;
+
+        for (key, value) in state_file::key_values(content) {
+            match key {
+                "code" => {
+                    return ScriptState::Finished {
+                        code: value.trim().parse().ok(),
+                    }
+                }
+                "pid" => {
+                    if let Ok(pid) = value.trim().parse() {
+                        state = ScriptState::Running { pid };
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        return state;
+    }
+}
+
+/// Watches the update script, so tests can simulate one
+pub trait Watcher {
+    /// How far it has got
+    fn state(&self) -> ScriptState;
+
+    /// True if the process `pid` is still running
+    fn is_running(&self, pid: u32) -> bool;
+
+    /// Waits before looking again
+    fn sleep(&self);
+}
+
+/// How the update run in a terminal ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Completion {
+    /// The script ran, and the update exited with `code`. None if it was
+    /// stopped before it finished, like by closing the window
+    Finished { code: Option<i32> },
+
+    /// The script never started, so nothing is known about the update
+    NotStarted,
+}
+
+/// Makes an empty status file at `path`, only readable by this user, for
+/// the script to write to. Errors if something is there already, as it
+/// could be a link to another file
+pub fn create_status_file(path: &path::Path) -> io::Result<()> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+
+    return io::Result::Ok(());
+}
+
+/// Watches the status file at `path` for real
+pub struct FileWatcher {
+    pub path: path::PathBuf,
+}
+
+impl Watcher for FileWatcher {
+    fn state(&self) -> ScriptState {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => return ScriptState::parse(&content),
+            Err(_) => return ScriptState::NotStarted,
+        }
+    }
+
+    fn is_running(&self, pid: u32) -> bool {
+        return path::Path::new(&format!("/proc/{pid}")).exists();
+    }
+
+    fn sleep(&self) {
+        thread::sleep(time::Duration::from_secs(1));
+    }
+}
+
+/// How the update ended, once it has, after the terminal it was run in
+/// exited with `terminal_status`
+///
+/// A terminal that exited 0 before the script started could be one that
+/// returns straight away, so the script is given `grace` looks to start.
+/// One that failed is taken to have never run it
+pub fn wait_for_update(
+    watcher: &dyn Watcher,
+    terminal_status: Option<i32>,
+    grace: u32,
+) -> Completion {
+    let mut waited = 0;
+
+    loop {
+        match watcher.state() {
+            ScriptState::Finished { code } => return Completion::Finished { code },
+            ScriptState::NotStarted if terminal_status != Some(0) || waited >= grace => {
+                return Completion::NotStarted
+            }
+            ScriptState::NotStarted => {
+                waited += 1;
+                watcher.sleep();
+            }
+            ScriptState::Running { pid } if watcher.is_running(pid) => watcher.sleep(),
+            ScriptState::Running { .. } => {
+                // It could have finished since the file was read
+                if let ScriptState::Finished { code } = watcher.state() {
+                    return Completion::Finished { code };
+                }
+
+                return Completion::Finished { code: None };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    // A script that goes through `states`, one each time it's looked at,
+    // running while `alive`
+    struct FakeWatcher {
+        states: RefCell<Vec<ScriptState>>,
+        alive: bool,
+        sleeps: Cell<u32>,
+    }
+
+    impl FakeWatcher {
+        fn new(states: &[ScriptState], alive: bool) -> FakeWatcher {
+            return FakeWatcher {
+                states: RefCell::new(states.iter().rev().copied().collect()),
+                alive,
+                sleeps: Cell::new(0),
+            };
+        }
+    }
+
+    impl Watcher for FakeWatcher {
+        // The next state, staying on the last
+        fn state(&self) -> ScriptState {
+            let mut states = self.states.borrow_mut();
+            if states.len() > 1 {
+                return states.pop().unwrap();
+            }
+
+            return states[0];
+        }
+
+        fn is_running(&self, _: u32) -> bool {
+            return self.alive;
+        }
+
+        fn sleep(&self) {
+            self.sleeps.set(self.sleeps.get() + 1);
+        }
+    }
+
+    #[test]
+    fn parse_status_file() {
+        assert_eq!(ScriptState::parse(""), ScriptState::NotStarted);
+        assert_eq!(
+            ScriptState::parse("pid=1234\n"),
+            ScriptState::Running { pid: 1234 }
+        );
+        assert_eq!(
+            ScriptState::parse("pid=1234\ncode=1\n"),
+            ScriptState::Finished { code: Some(1) }
+        );
+        assert_eq!(
+            ScriptState::parse("pid=1234\ncode=\n"),
+            ScriptState::Finished { code: None }
+        );
+    }
+
+    #[test]
+    fn early_returning_terminal() {
+        let running = ScriptState::Running { pid: 1234 };
+
+        let finished = |code| return Completion::Finished { code };
+
+        // The terminal returned 0 while the update was still running, then
+        // it failed
+        let watcher = FakeWatcher::new(
+            &[
+                running,
+                running,
+                running,
+                ScriptState::Finished { code: Some(1) },
+            ],
+            true,
+        );
+        assert_eq!(wait_for_update(&watcher, Some(0), 5), finished(Some(1)));
+        assert_eq!(watcher.sleeps.get(), 3);
+
+        // A terminal that waits has it finished already
+        let watcher = FakeWatcher::new(&[ScriptState::Finished { code: Some(0) }], true);
+        assert_eq!(wait_for_update(&watcher, Some(0), 5), finished(Some(0)));
+        assert_eq!(watcher.sleeps.get(), 0);
+
+        // The window was closed in the middle of it, or in the countdown
+        let watcher = FakeWatcher::new(&[running], false);
+        assert_eq!(wait_for_update(&watcher, Some(0), 5), finished(None));
+
+        // It finished just after the file was read
+        let watcher = FakeWatcher::new(&[running, ScriptState::Finished { code: Some(0) }], false);
+        assert_eq!(wait_for_update(&watcher, Some(0), 5), finished(Some(0)));
+
+        // The terminal's server started it a little after it returned
+        let watcher = FakeWatcher::new(
+            &[
+                ScriptState::NotStarted,
+                ScriptState::NotStarted,
+                running,
+                ScriptState::Finished { code: Some(0) },
+            ],
+            true,
+        );
+        assert_eq!(wait_for_update(&watcher, Some(0), 5), finished(Some(0)));
+
+        // Never started, which is never a success, even if the terminal
+        // exited 0
+        let watcher = FakeWatcher::new(&[ScriptState::NotStarted], true);
+        assert_eq!(
+            wait_for_update(&watcher, Some(0), 5),
+            Completion::NotStarted
+        );
+        assert_eq!(watcher.sleeps.get(), 5);
+        let watcher = FakeWatcher::new(&[ScriptState::NotStarted], true);
+        assert_eq!(
+            wait_for_update(&watcher, Some(4), 5),
+            Completion::NotStarted
+        );
+        assert_eq!(watcher.sleeps.get(), 0);
+    }
+
+    #[test]
+    fn private_status_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = crate::test_util::temp_dir("completion_status_file").join("status");
+        create_status_file(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Something already there, like a planted link, isn't written to
+        assert_eq!(
+            create_status_file(&path).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+    }
+}
@@ -0,0 +1,254 @@
+// Checks that components like clippy and rustfmt were built from the same
+// release as the rustc of their toolchain. After a partial update they can
+// lag behind, which `rustup check` doesn't report
+
+use std::{io, path};
+
+use crate::{
+    parse::parse_toolchain_list,
+    runner::{self, CommandRunner, CommandSpec},
+    Error,
+};
+
+// Components to check, with the command printing their version
+const COMPONENTS: [(&str, &[&str]); 2] = [
+    ("clippy", &["cargo", "clippy", "--version"]),
+    ("rustfmt", &["rustfmt", "--version"]),
+];
+
+/// A component built from a different release than the toolchain's rustc
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentMismatch {
+    pub toolchain: String,
+    pub component: String,
+
+    /// Output of `rustc --version`
+    pub rustc_version: String,
+
+    /// Output of the component's `--version`
+    pub component_version: String,
+}
+
+impl ComponentMismatch {
+    /// Command to reinstall the component for its toolchain
+    pub fn reinstall_command(&self) -> String {
+        return format!(
+            "rustup component remove {0} --toolchain {1} && rustup component add {0} --toolchain {1}",
+            self.component, self.toolchain
+        );
+    }
+}
+
+// Commit hash and date from the end of a version string, like
+// `(3f5fd8dd4 2024-08-06)`
+fn build_info(version: &str) -> Option<(&str, &str)> {
+    let (_, info) = version.trim().rsplit_once('(')?;
+    let (hash, date) = info.strip_suffix(')')?.split_once(' ')?;
+
+    return Some((hash.trim(), date.trim()));
+}
+
+/// True if a component was built from the same release as rustc, given the
+/// output of both of their `--version`s, like
+///
+/// `rustc 1.80.1 (3f5fd8dd4 2024-08-06)` and `clippy 0.1.80 (3f5fd8d 2024-08-06)`
+///
+/// Component version numbers don't follow rustc's, so the commit and date
+/// are compared instead. Commits can be shortened to different lengths.
+/// If either can't be read, they're treated as matching
+pub fn versions_match(rustc_version: &str, component_version: &str) -> bool {
+    let (Some((rustc_hash, rustc_date)), Some((hash, date))) =
+        (build_info(rustc_version), build_info(component_version))
+    else {
+        return true;
+    };
+
+    return rustc_date == date && (rustc_hash.starts_with(hash) || hash.starts_with(rustc_hash));
+}
+
+/// Names of the installed toolchains, from `rustup toolchain list`, run
+/// with `runner`
+///
+/// Errors with `Error::RustupNotFound` if rustup isn't there, and with
+/// `Error::Io` if it fails
+pub fn installed_toolchains(
+    runner: &dyn CommandRunner,
+    rustup_path: &path::Path,
+) -> Result<Vec<String>, Error> {
+    let command = CommandSpec::new(&rustup_path.to_string_lossy(), &["toolchain", "list"]);
+    let output = match runner.run(&command) {
+        io::Result::Ok(output) => output,
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::RustupNotFound)
+        }
+        io::Result::Err(error) => return Err(Error::Io(error)),
+    };
+
+    if !output.success() {
+        return Err(Error::Io(io::Error::other(format!(
+            "{command} failed with {}",
+            runner::describe_status(output.status)
+        ))));
+    }
+
+    return Ok(parse_toolchain_list(&String::from_utf8(output.stdout)?));
+}
+
+// Version printed by a command run in a toolchain. None if it fails, like
+// when the component isn't installed
+fn toolchain_version(
+    runner: &dyn CommandRunner,
+    rustup_path: &path::Path,
+    toolchain: &str,
+    command: &[&str],
+) -> io::Result<Option<String>> {
+    let mut args = vec!["run", toolchain];
+    args.extend(command);
+
+    let output = runner.run(&CommandSpec::new(&rustup_path.to_string_lossy(), &args))?;
+    if !output.success() {
+        return io::Result::Ok(None);
+    }
+
+    return io::Result::Ok(Some(
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    ));
+}
+
+/// Compares the installed components of every toolchain against its rustc
+pub fn find_mismatches(
+    runner: &dyn CommandRunner,
+    rustup_path: &path::Path,
+) -> Result<Vec<ComponentMismatch>, Error> {
+    let mut mismatches = Vec::new();
+
+    for toolchain in installed_toolchains(runner, rustup_path)? {
+        let Some(rustc_version) =
+            toolchain_version(runner, rustup_path, &toolchain, &["rustc", "--version"])?
+        else {
+            continue;
+        };
+
+        for (component, command) in COMPONENTS {
+            let Some(component_version) =
+                toolchain_version(runner, rustup_path, &toolchain, command)?
+            else {
+                continue;
+            };
+
+            if !versions_match(&rustc_version, &component_version) {
+                mismatches.push(ComponentMismatch {
+                    toolchain: toolchain.clone(),
+                    component: component.to_string(),
+                    rustc_version: rustc_version.clone(),
+                    component_version,
+                });
+            }
+        }
+    }
+
+    return Ok(mismatches);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::{CommandOutput, MockRunner};
+
+    #[test]
+    fn toolchain_list() {
+        let runner = MockRunner::new(|command| {
+            assert_eq!(command.args, ["toolchain", "list"]);
+            return io::Result::Ok(CommandOutput::new(
+                0,
+                "stable-x86_64-unknown-linux-gnu (default)\nnightly-x86_64-unknown-linux-gnu\n",
+                "",
+            ));
+        });
+        assert_eq!(
+            installed_toolchains(&runner, path::Path::new("rustup")).unwrap(),
+            [
+                "stable-x86_64-unknown-linux-gnu",
+                "nightly-x86_64-unknown-linux-gnu"
+            ]
+        );
+
+        let runner = MockRunner::new(|_| io::Result::Err(io::ErrorKind::NotFound.into()));
+        assert!(matches!(
+            installed_toolchains(&runner, path::Path::new("rustup")),
+            Err(Error::RustupNotFound)
+        ));
+
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(1, "", "error")));
+        let error = installed_toolchains(&runner, path::Path::new("rustup")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "rustup toolchain list failed with exit code 1"
+        );
+    }
+
+    #[test]
+    fn matching_versions() {
+        assert!(versions_match(
+            "rustc 1.80.1 (3f5fd8dd4 2024-08-06)",
+            "clippy 0.1.80 (3f5fd8d 2024-08-06)"
+        ));
+        assert!(versions_match(
+            "rustc 1.80.1 (3f5fd8dd4 2024-08-06)",
+            "rustfmt 1.7.1-stable (3f5fd8dd41 2024-08-06)"
+        ));
+    }
+
+    #[test]
+    fn mismatched_versions() {
+        assert!(!versions_match(
+            "rustc 1.80.1 (3f5fd8dd4 2024-08-06)",
+            "clippy 0.1.80 (051478957 2024-07-21)"
+        ));
+        assert!(!versions_match(
+            "rustc 1.80.1 (3f5fd8dd4 2024-08-06)",
+            "clippy 0.1.80 (3f5fd8dd4 2024-07-21)"
+        ));
+    }
+
+    #[test]
+    fn unreadable_versions_match() {
+        assert!(versions_match(
+            "rustc 1.80.1",
+            "clippy 0.1.80 (051478957 2024-07-21)"
+        ));
+        assert!(versions_match(
+            "rustc 1.80.1 (3f5fd8dd4 2024-08-06)",
+            "garbage"
+        ));
+    }
+
+    #[test]
+    fn finds_mismatches() {
+        let runner = MockRunner::new(|command| {
+            let args: Vec<&str> = command.args.iter().map(|x| x.as_str()).collect();
+
+            let output = match args.as_slice() {
+                ["toolchain", "list"] => "stable-x86_64-unknown-linux-gnu (default)\n",
+                ["run", _, "rustc", "--version"] => "rustc 1.80.1 (3f5fd8dd4 2024-08-06)\n",
+                ["run", _, "cargo", "clippy", "--version"] => {
+                    "clippy 0.1.80 (0514789 2024-07-21)\n"
+                }
+                _ => return io::Result::Ok(CommandOutput::new(1, "", "not installed")),
+            };
+
+            return io::Result::Ok(CommandOutput::new(0, output, ""));
+        });
+
+        let mismatches = find_mismatches(&runner, path::Path::new("rustup")).unwrap();
+
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].toolchain, "stable-x86_64-unknown-linux-gnu");
+        assert_eq!(mismatches[0].component, "clippy");
+        assert_eq!(
+            mismatches[0].reinstall_command(),
+            "rustup component remove clippy --toolchain stable-x86_64-unknown-linux-gnu \
+             && rustup component add clippy --toolchain stable-x86_64-unknown-linux-gnu"
+        );
+    }
+}
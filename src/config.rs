@@ -0,0 +1,1455 @@
+use std::{collections::BTreeMap, env, fs, io, path};
+
+use crate::{
+    home, json,
+    last_check::DEFAULT_CHECK_INTERVAL,
+    notify::NotifyStyle,
+    power::POWER_SUPPLY_PATH,
+    prompt::{
+        display_available, NoPromptAction, PromptKind, TimeoutAction, DEFAULT_PROMPT_TIMEOUT,
+    },
+    root::{Elevation, RootPolicy},
+    snooze::{self, NO_UPDATE_FLAG_DELAY},
+    terminal::{self, Terminal},
+    toml,
+    version::Version,
+};
+
+// Path of the config file, relative to the XDG config directory
+const CONFIG_FILE_PATH: &str = "auto_rustup_update/config.toml";
+
+// Where the kernel keeps the name of this machine
+const HOSTNAME_PATH: &str = "/proc/sys/kernel/hostname";
+
+// Name of this machine, for the `[host.<name>]` section of the config file
+fn hostname() -> Option<String> {
+    let name = fs::read_to_string(HOSTNAME_PATH).ok()?;
+
+    return Some(name.trim().to_string()).filter(|x| !x.is_empty());
+}
+
+// The settings for the machine called `hostname`, with those in its
+// `[host.<hostname>]` section over the rest. Sections for other machines
+// are left out
+fn settings_for_host(table: toml::Table, hostname: Option<&str>) -> toml::Table {
+    let prefix = hostname.map(|x| format!("host.{x}."));
+
+    let mut settings = toml::Table::new();
+    let mut host_settings = Vec::new();
+    for (key, value) in table {
+        if !key.starts_with("host.") {
+            settings.insert(key, value);
+        } else if let Some(key) = prefix.as_deref().and_then(|x| key.strip_prefix(x)) {
+            host_settings.push((key.to_string(), value));
+        }
+    }
+    settings.extend(host_settings);
+
+    return settings;
+}
+
+/// Path of the config file, in `$XDG_CONFIG_HOME` or `~/.config`
+pub fn config_path() -> Option<path::PathBuf> {
+    let mut path = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_home) if !config_home.is_empty() => path::PathBuf::from(config_home),
+        _ => home::home_dir().ok()?.join(".config"),
+    };
+    path.push(CONFIG_FILE_PATH);
+
+    return Some(path);
+}
+
+// Reads a boolean setting from the config file, if it's set
+fn get_bool(table: &toml::Table, key: &str) -> Result<Option<bool>, String> {
+    match table.get(key) {
+        None => return Ok(None),
+        Some(value) => match value.as_bool() {
+            Some(x) => return Ok(Some(x)),
+            None => return Err(format!("'{key}' should be true or false")),
+        },
+    }
+}
+
+// Reads a string setting from the config file, if it's set
+fn get_string(table: &toml::Table, key: &str) -> Result<Option<String>, String> {
+    match table.get(key) {
+        None => return Ok(None),
+        Some(value) => match value.as_str() {
+            Some(x) => return Ok(Some(x.to_string())),
+            None => return Err(format!("'{key}' should be a string")),
+        },
+    }
+}
+
+// Reads a whole number setting from the config file, if it's set. It can't
+// be negative
+fn get_unsigned(table: &toml::Table, key: &str) -> Result<Option<u64>, String> {
+    match table.get(key) {
+        None => return Ok(None),
+        Some(value) => match value.as_integer().and_then(|x| u64::try_from(x).ok()) {
+            Some(x) => return Ok(Some(x)),
+            None => return Err(format!("'{key}' should be a whole number, 0 or more")),
+        },
+    }
+}
+
+// Reads a list of strings from the config file, if it's set
+fn get_strings(table: &toml::Table, key: &str) -> Result<Option<Vec<String>>, String> {
+    let value = match table.get(key) {
+        None => return Ok(None),
+        Some(value) => value,
+    };
+
+    let strings: Option<Vec<String>> = value.as_array().and_then(|items| {
+        items
+            .iter()
+            .map(|x| x.as_str().map(|x| x.to_string()))
+            .collect()
+    });
+
+    match strings {
+        Some(x) => return Ok(Some(x)),
+        None => return Err(format!("'{key}' should be a list of strings")),
+    }
+}
+
+// The same value in JSON
+fn json_value(value: toml::Value) -> json::Value {
+    match value {
+        toml::Value::String(x) => return json::Value::String(x),
+        toml::Value::Integer(x) => return json::Value::Number(x as f64),
+        toml::Value::Boolean(x) => return json::Value::Boolean(x),
+        toml::Value::Array(x) => {
+            return json::Value::Array(x.into_iter().map(json_value).collect())
+        }
+    }
+}
+
+// What a setting's value is
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Bool,
+    String,
+    Unsigned,
+    Strings,
+}
+
+// Every setting, with what its value is. Each can be set in the config file,
+// or with an environment variable of its name in capitals after
+// `ENV_PREFIX`. `channel_hooks.<channel>` are also settings, for any channel
+const SETTINGS: &[(&str, Kind)] = &[
+    ("require_ac_power", Kind::Bool),
+    ("allow_root", Kind::Bool),
+    ("update_command", Kind::Strings),
+    ("update_countdown", Kind::Unsigned),
+    ("elevate", Kind::String),
+    ("post_update_hook", Kind::Strings),
+    ("update_available_hook", Kind::Strings),
+    ("auto_update", Kind::Strings),
+    ("skip_nightly_date_bumps", Kind::Bool),
+    ("minimum_rust_version", Kind::String),
+    ("check_interval_minutes", Kind::Unsigned),
+    ("accessible", Kind::Bool),
+    ("rustup_dist_server", Kind::String),
+    ("rustup_update_root", Kind::String),
+    ("timeout_action", Kind::String),
+    ("close_action", Kind::String),
+    ("offer_install", Kind::Bool),
+    ("recheck_after_update", Kind::Bool),
+    ("no_snooze", Kind::Bool),
+    ("detach", Kind::Bool),
+    ("notify", Kind::Bool),
+    ("keep_stats", Kind::Bool),
+    ("probe_network", Kind::Bool),
+    ("ignore_rustup_self", Kind::Bool),
+    ("notify_urgency", Kind::String),
+    ("notify_persistent", Kind::Bool),
+    ("verify_rustup", Kind::Bool),
+    ("rustup_prefix", Kind::String),
+    ("rustup_path", Kind::String),
+    ("flag_path", Kind::String),
+    ("snooze", Kind::String),
+    ("prompt_timeout", Kind::Unsigned),
+    ("daily_prompt_budget", Kind::Unsigned),
+    ("dialog_icon", Kind::String),
+    ("dialog_modal", Kind::Bool),
+    ("backend", Kind::String),
+    ("prompt_socket", Kind::String),
+    ("no_prompt", Kind::String),
+    ("terminal", Kind::String),
+    ("verbose", Kind::Bool),
+    ("dry_run", Kind::Bool),
+    ("report_path", Kind::String),
+    ("metrics_path", Kind::String),
+    #[cfg(feature = "dist-server")]
+    ("use_dist_server", Kind::Bool),
+];
+
+// Start of the environment variables for settings, like
+// `AUTO_RUSTUP_UPDATE_REQUIRE_AC_POWER`
+const ENV_PREFIX: &str = "AUTO_RUSTUP_UPDATE_";
+
+// The setting `key` is for, and what its value is. None if there isn't one
+fn setting_kind(key: &str) -> Option<Kind> {
+    if key.starts_with("channel_hooks.") {
+        return Some(Kind::Strings);
+    }
+
+    return SETTINGS.iter().find(|(name, _)| *name == key).map(|x| x.1);
+}
+
+// Reads `value` of the environment variable `name` as a `kind` setting.
+// Lists are split on spaces
+fn env_value(name: &str, value: &str, kind: Kind) -> Result<toml::Value, String> {
+    match kind {
+        Kind::Bool => match value.to_lowercase().as_str() {
+            "true" | "1" | "yes" => return Ok(toml::Value::Boolean(true)),
+            "false" | "0" | "no" => return Ok(toml::Value::Boolean(false)),
+            _ => return Err(format!("{name} should be true or false")),
+        },
+        Kind::String => return Ok(toml::Value::String(value.to_string())),
+        Kind::Unsigned => match value.parse::<i64>() {
+            Result::Ok(x) if x >= 0 => return Ok(toml::Value::Integer(x)),
+            _ => return Err(format!("{name} should be a whole number, 0 or more")),
+        },
+        Kind::Strings => {
+            return Ok(toml::Value::Array(
+                value
+                    .split_whitespace()
+                    .map(|x| toml::Value::String(x.to_string()))
+                    .collect(),
+            ))
+        }
+    }
+}
+
+// The settings given by `AUTO_RUSTUP_UPDATE_*` variables in `vars`, as if
+// they were in the config file. Empty ones are ignored, and an unknown one
+// is an error, like an unknown setting in the file
+//
+// `AUTO_RUSTUP_UPDATE_CHANNEL_HOOKS_NIGHTLY` is the hook for `nightly`
+fn env_settings<I>(vars: I) -> Result<toml::Table, String>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut table = toml::Table::new();
+    for (name, value) in vars {
+        let Some(suffix) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        if value.is_empty() {
+            continue;
+        }
+
+        let key = suffix.to_lowercase();
+        let key = match key.strip_prefix("channel_hooks_") {
+            Some(channel) => format!("channel_hooks.{channel}"),
+            None => key,
+        };
+        let Some(kind) = setting_kind(&key) else {
+            return Err(format!("Unknown setting {name}"));
+        };
+        table.insert(key, env_value(&name, &value, kind)?);
+    }
+
+    return Ok(table);
+}
+
+/// Settings for a run of the updater
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Don't prompt for updates while the machine is running on battery
+    pub require_ac_power: bool,
+
+    /// Directory listing the power supplies. Only changed for testing
+    pub power_supply_path: path::PathBuf,
+
+    /// Program and arguments run to update Rust. Each argument is passed to
+    /// the program as is, without going through a shell
+    pub update_command: Vec<String>,
+
+    /// Seconds the terminal counts down before the update starts, so it can
+    /// be cancelled with Ctrl-C. 0 to start straight away
+    pub update_countdown: u64,
+
+    /// What to do when running as root
+    pub root_policy: RootPolicy,
+
+    /// How to update a rustup owned by another user, like root
+    pub elevate: Elevation,
+
+    /// Program and arguments run after a successful update, like
+    /// `["cargo", "install-update", "-a"]`. Empty to not run anything
+    pub post_update_hook: Vec<String>,
+
+    /// Program and arguments run when updates are found, before prompting,
+    /// whether they're installed or not. Exiting with 3 skips the prompt.
+    /// Empty to not run anything
+    pub update_available_hook: Vec<String>,
+
+    /// Commands run after a toolchain of a channel updates, by channel,
+    /// like `nightly`. Run after `post_update_hook`
+    pub channel_hooks: BTreeMap<String, Vec<String>>,
+
+    /// Toolchains updated without prompting, by channel like `stable`, or by
+    /// full name. Updates to the rest are prompted for as usual
+    pub auto_update: Vec<String>,
+
+    /// Don't count a new nightly of the same version, like another day's
+    /// 1.83.0-nightly, as an update. Only a new version is prompted for
+    pub skip_nightly_date_bumps: bool,
+
+    /// Oldest default toolchain allowed, or stable without a default. If
+    /// it's older, the prompt is more urgent and can't be snoozed
+    pub minimum_rust_version: Option<Version>,
+
+    /// Seconds after running `rustup check` that its result is reused,
+    /// instead of checking again. 0 to always check
+    pub check_interval: u64,
+
+    /// Make the prompt easier to use with a screen reader. Updates are
+    /// written out as sentences, and it doesn't time out
+    pub accessible: bool,
+
+    /// Dist server for rustup to download from instead of the usual one,
+    /// like a mirror. Set as `RUSTUP_DIST_SERVER`
+    pub rustup_dist_server: Option<String>,
+
+    /// Where rustup downloads its own updates from. Set as
+    /// `RUSTUP_UPDATE_ROOT`
+    pub rustup_update_root: Option<String>,
+
+    /// What to do when the prompt times out
+    pub timeout_action: TimeoutAction,
+
+    /// What to do when the prompt's window is closed without answering.
+    /// Anything but `Decline` tells closing apart from "Not today"
+    pub close_action: TimeoutAction,
+
+    /// When rustup isn't installed, say how to install it and fail. Otherwise
+    /// exit quietly, so it's safe to run on machines without Rust
+    pub offer_install: bool,
+
+    /// After an update, check again and print the versions now installed
+    pub recheck_after_update: bool,
+
+    /// Never read or write the no-update flag, so every run prompts, like
+    /// when the home directory doesn't last between runs
+    pub no_snooze: bool,
+
+    /// Run the update in the background, detached from this program, which
+    /// returns straight away. A notification says how it went
+    pub detach: bool,
+
+    /// Show a desktop notification once an update finishes
+    pub notify: bool,
+
+    /// Count how many updates work and fail, for `--stats`. Only kept on
+    /// this machine
+    pub keep_stats: bool,
+
+    /// Connect to static.rust-lang.org, or the host of the dist server if
+    /// it's set, before checking, to find being offline quickly, without
+    /// waiting for rustup to time out
+    pub probe_network: bool,
+
+    /// Leave rustup itself out of checks and prompts, for a rustup that
+    /// can't update itself, like one from the distribution
+    pub ignore_rustup_self: bool,
+
+    /// How the notification for a finished update is shown. Failures are
+    /// always critical, and stay until dismissed
+    pub notify_style: NotifyStyle,
+
+    /// Refuse to run a rustup that isn't under `rustup_prefix`, is owned by
+    /// another user, or can be written to by anyone
+    pub verify_rustup: bool,
+
+    /// Where rustup has to be for `verify_rustup`. None for `CARGO_HOME`,
+    /// or `~/.cargo`
+    pub rustup_prefix: Option<path::PathBuf>,
+
+    /// rustup to run. None for `~/.cargo/bin/rustup`
+    pub rustup_path: Option<path::PathBuf>,
+
+    /// Where the no-update flag is kept. None for `~/.rustup/donotupdate`
+    pub flag_path: Option<path::PathBuf>,
+
+    /// Seconds the first "Not today" snoozes for. Snoozing the same updates
+    /// again doubles it
+    pub snooze: u64,
+
+    /// Seconds the prompt waits for an answer. None to wait forever
+    pub prompt_timeout: Option<u32>,
+
+    /// How many times a day the prompt can be shown, however it was
+    /// answered. 0 for no limit. `--force` lifts it
+    pub daily_prompt_budget: u64,
+
+    /// Icon of the prompt's window, like the Rust logo. None for the
+    /// backend's own
+    pub dialog_icon: Option<path::PathBuf>,
+
+    /// Ask for the prompt's window to be modal. zenity only keeps it above
+    /// a window it's attached to, and none is given, so it's just a hint to
+    /// the window manager
+    pub dialog_modal: bool,
+
+    /// How the prompt is shown
+    pub backend: PromptKind,
+
+    /// Unix socket the socket backend sends prompts to
+    pub prompt_socket: Option<path::PathBuf>,
+
+    /// What to do when the prompt can't be shown at all, like from cron
+    pub no_prompt_action: NoPromptAction,
+
+    /// Terminal to run the update in. None to try each known terminal
+    pub terminal: Option<Terminal>,
+
+    /// Print the lines of `rustup check` as they were, before they're parsed
+    pub verbose: bool,
+
+    /// Check and report, but never prompt, update, or change the snooze
+    pub dry_run: bool,
+
+    /// File to write a JSON report of each run to
+    pub report_path: Option<path::PathBuf>,
+
+    /// File to write Prometheus metrics about each run to
+    pub metrics_path: Option<path::PathBuf>,
+
+    /// Check for a new stable by asking the Rust dist server, or
+    /// `rustup_dist_server`, rather than running `rustup check`. Used by
+    /// `is_update_available`
+    #[cfg(feature = "dist-server")]
+    pub use_dist_server: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        return Config {
+            require_ac_power: false,
+            power_supply_path: path::PathBuf::from(POWER_SUPPLY_PATH),
+            update_command: vec!["rustup".to_string(), "update".to_string()],
+            update_countdown: 0,
+            root_policy: RootPolicy::Refuse,
+            elevate: Elevation::Never,
+            post_update_hook: Vec::new(),
+            update_available_hook: Vec::new(),
+            channel_hooks: BTreeMap::new(),
+            auto_update: Vec::new(),
+            skip_nightly_date_bumps: false,
+            minimum_rust_version: None,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+            accessible: false,
+            rustup_dist_server: None,
+            rustup_update_root: None,
+            timeout_action: TimeoutAction::AskNextRun,
+            close_action: TimeoutAction::Decline,
+            offer_install: false,
+            recheck_after_update: false,
+            no_snooze: false,
+            detach: false,
+            notify: false,
+            keep_stats: false,
+            probe_network: false,
+            ignore_rustup_self: false,
+            notify_style: NotifyStyle::default(),
+            verify_rustup: false,
+            rustup_prefix: None,
+            rustup_path: None,
+            flag_path: None,
+            snooze: NO_UPDATE_FLAG_DELAY,
+            prompt_timeout: Some(DEFAULT_PROMPT_TIMEOUT),
+            dialog_icon: None,
+            dialog_modal: false,
+            daily_prompt_budget: 0,
+            backend: PromptKind::Auto,
+            prompt_socket: None,
+            no_prompt_action: NoPromptAction::Defer,
+            terminal: None,
+            verbose: false,
+            dry_run: false,
+            report_path: None,
+            metrics_path: None,
+            #[cfg(feature = "dist-server")]
+            use_dist_server: false,
+        };
+    }
+}
+
+/// Builds a `Config` from code, checking the settings make sense together
+#[derive(Debug, Clone)]
+pub struct ConfigBuilder {
+    config: Config,
+    has_display: bool,
+}
+
+impl ConfigBuilder {
+    /// Starts from the default config
+    pub fn new() -> ConfigBuilder {
+        return ConfigBuilder {
+            config: Config::default(),
+            has_display: display_available(),
+        };
+    }
+
+    pub fn rustup_path(mut self, path: impl Into<path::PathBuf>) -> ConfigBuilder {
+        self.config.rustup_path = Some(path.into());
+        return self;
+    }
+
+    pub fn flag_path(mut self, path: impl Into<path::PathBuf>) -> ConfigBuilder {
+        self.config.flag_path = Some(path.into());
+        return self;
+    }
+
+    /// Seconds the first snooze lasts
+    pub fn snooze(mut self, seconds: u64) -> ConfigBuilder {
+        self.config.snooze = seconds;
+        return self;
+    }
+
+    /// Seconds the prompt waits, None to wait forever
+    pub fn timeout(mut self, seconds: Option<u32>) -> ConfigBuilder {
+        self.config.prompt_timeout = seconds;
+        return self;
+    }
+
+    pub fn timeout_action(mut self, action: TimeoutAction) -> ConfigBuilder {
+        self.config.timeout_action = action;
+        return self;
+    }
+
+    /// What closing the prompt's window does
+    pub fn close_action(mut self, action: TimeoutAction) -> ConfigBuilder {
+        self.config.close_action = action;
+        return self;
+    }
+
+    /// Sends prompts to the Unix socket at `path`, with the socket backend
+    pub fn prompt_socket(mut self, path: impl Into<path::PathBuf>) -> ConfigBuilder {
+        self.config.backend = PromptKind::Socket;
+        self.config.prompt_socket = Some(path.into());
+
+        return self;
+    }
+
+    pub fn backend(mut self, backend: PromptKind) -> ConfigBuilder {
+        self.config.backend = backend;
+        return self;
+    }
+
+    /// Always runs the update in `terminal`, rather than the first known
+    /// terminal that launches
+    pub fn terminal(mut self, terminal: Terminal) -> ConfigBuilder {
+        self.config.terminal = Some(terminal);
+        return self;
+    }
+
+    pub fn update_command<S: AsRef<str>>(mut self, command: &[S]) -> ConfigBuilder {
+        self.config.update_command = command.iter().map(|x| x.as_ref().to_string()).collect();
+        return self;
+    }
+
+    pub fn require_ac_power(mut self, require: bool) -> ConfigBuilder {
+        self.config.require_ac_power = require;
+        return self;
+    }
+
+    pub fn root_policy(mut self, policy: RootPolicy) -> ConfigBuilder {
+        self.config.root_policy = policy;
+        return self;
+    }
+
+    pub fn accessible(mut self, accessible: bool) -> ConfigBuilder {
+        self.config.accessible = accessible;
+        return self;
+    }
+
+    /// Seconds a `rustup check` is reused for. It has to be whole minutes,
+    /// like `check_interval_minutes` in the config file
+    pub fn check_interval(mut self, seconds: u64) -> ConfigBuilder {
+        self.config.check_interval = seconds;
+        return self;
+    }
+
+    /// Whether there's a display to show windows on. Found from `DISPLAY`
+    /// and `WAYLAND_DISPLAY`. Only changed for testing
+    pub fn has_display(mut self, has_display: bool) -> ConfigBuilder {
+        self.has_display = has_display;
+        return self;
+    }
+
+    /// The config, or why the settings don't work together
+    pub fn build(self) -> Result<Config, String> {
+        let config = self.config;
+
+        if config.update_command.is_empty() {
+            return Err("The update command can't be empty".to_string());
+        }
+        if config.snooze == 0 {
+            return Err("The snooze can't be 0 seconds".to_string());
+        }
+        if config.prompt_timeout == Some(0) {
+            return Err("The prompt timeout can't be 0 seconds. Use None to wait".to_string());
+        }
+        if !config.check_interval.is_multiple_of(60) {
+            return Err(format!(
+                "The check interval should be whole minutes, not {} seconds",
+                config.check_interval
+            ));
+        }
+        if let (false, PromptKind::Zenity | PromptKind::Notify) = (self.has_display, config.backend)
+        {
+            return Err(format!(
+                "The {} backend needs a display, and neither DISPLAY nor WAYLAND_DISPLAY \
+                 is set. Use the tty or auto backend instead",
+                match config.backend {
+                    PromptKind::Notify => "notify",
+                    _ => "zenity",
+                }
+            ));
+        }
+        if config.backend == PromptKind::Socket && config.prompt_socket.is_none() {
+            return Err("The socket backend needs a prompt socket".to_string());
+        }
+        if let (false, Some(terminal)) = (self.has_display, &config.terminal) {
+            return Err(format!(
+                "{} needs a display, and neither DISPLAY nor WAYLAND_DISPLAY is set",
+                terminal.program
+            ));
+        }
+
+        return Ok(config);
+    }
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        return ConfigBuilder::new();
+    }
+}
+
+impl Config {
+    /// Starts building a config from code. See `ConfigBuilder`
+    pub fn builder() -> ConfigBuilder {
+        return ConfigBuilder::new();
+    }
+
+    /// Builds the config from the config file, if there is one, then the
+    /// `AUTO_RUSTUP_UPDATE_*` environment variables
+    ///
+    /// A malformed config file or variable is an `InvalidData` error
+    pub fn load() -> io::Result<Config> {
+        let Some(path) = config_path() else {
+            return Config::default().with_env();
+        };
+
+        match Config::load_from(&path) {
+            io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                return Config::default().with_env()
+            }
+            result => return result,
+        }
+    }
+
+    /// Builds the config from the given config file, instead of the usual
+    /// one, then the `AUTO_RUSTUP_UPDATE_*` environment variables
+    ///
+    /// Unlike `load`, it's a `NotFound` error if the file doesn't exist
+    pub fn load_from(path: &path::Path) -> io::Result<Config> {
+        let text = fs::read_to_string(path).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("Couldn't read config file {}: {}", path.display(), error),
+            )
+        })?;
+
+        let config = Config::from_toml(&text).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), error),
+            )
+        })?;
+
+        return config.with_env();
+    }
+
+    // This config, with the settings of this process's environment
+    // variables over it
+    fn with_env(mut self) -> io::Result<Config> {
+        // Variables that aren't UTF-8 can't be settings
+        let vars = env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        });
+
+        self.apply_env(vars).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Environment variables: {error}"),
+            )
+        })?;
+
+        return io::Result::Ok(self);
+    }
+
+    /// Builds the config from environment variables alone, like
+    /// `("AUTO_RUSTUP_UPDATE_REQUIRE_AC_POWER", "true")`, without a config
+    /// file. Each setting of the file is the variable of its name in
+    /// capitals after `AUTO_RUSTUP_UPDATE_`. Others are ignored
+    ///
+    /// Booleans are `true` or `false`, and lists are split on spaces
+    pub fn from_env_vars<I>(vars: I) -> Result<Config, String>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut config = Config::default();
+        config.apply_env(vars)?;
+
+        return Ok(config);
+    }
+
+    // Sets everything given by `AUTO_RUSTUP_UPDATE_*` variables in `vars`,
+    // leaving the rest as is
+    fn apply_env<I>(&mut self, vars: I) -> Result<(), String>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        return self.apply_settings(env_settings(vars)?);
+    }
+
+    /// Builds the config from the contents of a config file, for this
+    /// machine
+    pub fn from_toml(text: &str) -> Result<Config, String> {
+        return Config::from_toml_for_host(text, hostname().as_deref());
+    }
+
+    /// Builds the config from the contents of a config file, for the
+    /// machine called `hostname`
+    ///
+    /// Settings in its `[host.<hostname>]` section replace the ones outside
+    /// of it, so one file can be shared by many machines
+    pub fn from_toml_for_host(text: &str, hostname: Option<&str>) -> Result<Config, String> {
+        let mut config = Config::default();
+        config.apply_toml(text, hostname)?;
+
+        return Ok(config);
+    }
+
+    // Sets everything given in the config file for `hostname`, leaving the
+    // rest as is
+    fn apply_toml(&mut self, text: &str, hostname: Option<&str>) -> Result<(), String> {
+        return self.apply_settings(settings_for_host(toml::parse(text)?, hostname));
+    }
+
+    // Sets everything in `table`, from the config file or environment,
+    // leaving the rest as is
+    fn apply_settings(&mut self, table: toml::Table) -> Result<(), String> {
+        for key in table.keys() {
+            if setting_kind(key).is_none() {
+                return Err(format!("Unknown setting '{key}'"));
+            }
+        }
+
+        if let Some(x) = get_bool(&table, "require_ac_power")? {
+            self.require_ac_power = x;
+        }
+        if let Some(x) = get_bool(&table, "allow_root")? {
+            self.root_policy = if x {
+                RootPolicy::Warn
+            } else {
+                RootPolicy::Refuse
+            };
+        }
+        if let Some(x) = get_strings(&table, "update_command")? {
+            if x.is_empty() {
+                return Err("'update_command' can't be empty".to_string());
+            }
+            self.update_command = x;
+        }
+        if let Some(x) = get_string(&table, "elevate")? {
+            self.elevate = x.parse().map_err(|error| format!("'elevate': {error}"))?;
+        }
+        if let Some(x) = get_unsigned(&table, "update_countdown")? {
+            self.update_countdown = x;
+        }
+        if let Some(x) = get_strings(&table, "post_update_hook")? {
+            self.post_update_hook = x;
+        }
+        if let Some(x) = get_strings(&table, "update_available_hook")? {
+            self.update_available_hook = x;
+        }
+        if let Some(x) = get_strings(&table, "auto_update")? {
+            self.auto_update = x;
+        }
+        if let Some(x) = get_bool(&table, "skip_nightly_date_bumps")? {
+            self.skip_nightly_date_bumps = x;
+        }
+        for key in table.keys() {
+            if let Some(channel) = key.strip_prefix("channel_hooks.") {
+                let command = get_strings(&table, key)?.unwrap_or_default();
+                if command.is_empty() {
+                    return Err(format!("'{key}' can't be empty"));
+                }
+                self.channel_hooks.insert(channel.to_string(), command);
+            }
+        }
+        if let Some(x) = get_string(&table, "minimum_rust_version")? {
+            self.minimum_rust_version = Some(
+                x.parse()
+                    .map_err(|error| format!("'minimum_rust_version': {error}"))?,
+            );
+        }
+
+        if let Some(x) = get_string(&table, "rustup_dist_server")? {
+            self.rustup_dist_server = Some(x);
+        }
+        if let Some(x) = get_string(&table, "rustup_update_root")? {
+            self.rustup_update_root = Some(x);
+        }
+        if let Some(x) = get_string(&table, "timeout_action")? {
+            self.timeout_action = x
+                .parse()
+                .map_err(|error| format!("'timeout_action': {error}"))?;
+        }
+        if let Some(x) = get_string(&table, "close_action")? {
+            self.close_action = x
+                .parse()
+                .map_err(|error| format!("'close_action': {error}"))?;
+        }
+        if let Some(x) = get_bool(&table, "offer_install")? {
+            self.offer_install = x;
+        }
+        if let Some(x) = get_bool(&table, "recheck_after_update")? {
+            self.recheck_after_update = x;
+        }
+        if let Some(x) = get_bool(&table, "no_snooze")? {
+            self.no_snooze = x;
+        }
+        if let Some(x) = get_string(&table, "snooze")? {
+            self.snooze = match snooze::parse_duration(&x) {
+                Ok(0) => return Err("'snooze' can't be 0".to_string()),
+                Ok(seconds) => seconds,
+                Err(error) => return Err(format!("'snooze': {error}")),
+            };
+        }
+        if let Some(x) = get_string(&table, "terminal")? {
+            self.terminal = Some(terminal::find_terminal(&x).ok_or_else(|| {
+                let known: Vec<&str> = terminal::TERMINALS.iter().map(|x| x.program).collect();
+                return format!(
+                    "Unknown terminal '{x}'. Expected one of {}",
+                    known.join(", ")
+                );
+            })?);
+        }
+        if let Some(x) = get_bool(&table, "detach")? {
+            self.detach = x;
+        }
+        if let Some(x) = get_bool(&table, "notify")? {
+            self.notify = x;
+        }
+        if let Some(x) = get_bool(&table, "keep_stats")? {
+            self.keep_stats = x;
+        }
+        if let Some(x) = get_bool(&table, "probe_network")? {
+            self.probe_network = x;
+        }
+        if let Some(x) = get_bool(&table, "ignore_rustup_self")? {
+            self.ignore_rustup_self = x;
+        }
+        if let Some(x) = get_string(&table, "notify_urgency")? {
+            self.notify_style.urgency = x
+                .parse()
+                .map_err(|error| format!("'notify_urgency': {error}"))?;
+        }
+        if let Some(x) = get_bool(&table, "notify_persistent")? {
+            self.notify_style.persistent = x;
+        }
+        if let Some(x) = get_bool(&table, "verify_rustup")? {
+            self.verify_rustup = x;
+        }
+        if let Some(x) = get_string(&table, "rustup_prefix")? {
+            self.rustup_prefix = Some(path::PathBuf::from(x));
+        }
+        if let Some(x) = get_string(&table, "rustup_path")? {
+            self.rustup_path = Some(path::PathBuf::from(x));
+        }
+        if let Some(x) = get_string(&table, "flag_path")? {
+            self.flag_path = Some(path::PathBuf::from(x));
+        }
+        if let Some(x) = get_unsigned(&table, "prompt_timeout")? {
+            // 0 waits for an answer however long it takes
+            self.prompt_timeout = match x {
+                0 => None,
+                x => Some(u32::try_from(x).map_err(|_| "'prompt_timeout' is too long")?),
+            };
+        }
+        if let Some(x) = get_unsigned(&table, "daily_prompt_budget")? {
+            self.daily_prompt_budget = x;
+        }
+        if let Some(x) = get_string(&table, "dialog_icon")? {
+            self.dialog_icon = Some(path::PathBuf::from(x));
+        }
+        if let Some(x) = get_bool(&table, "dialog_modal")? {
+            self.dialog_modal = x;
+        }
+        if let Some(x) = get_bool(&table, "verbose")? {
+            self.verbose = x;
+        }
+        if let Some(x) = get_bool(&table, "dry_run")? {
+            self.dry_run = x;
+        }
+        if let Some(x) = get_string(&table, "report_path")? {
+            self.report_path = Some(path::PathBuf::from(x));
+        }
+        if let Some(x) = get_string(&table, "metrics_path")? {
+            self.metrics_path = Some(path::PathBuf::from(x));
+        }
+        #[cfg(feature = "dist-server")]
+        if let Some(x) = get_bool(&table, "use_dist_server")? {
+            self.use_dist_server = x;
+        }
+        if let Some(x) = get_bool(&table, "accessible")? {
+            self.accessible = x;
+        }
+        if let Some(x) = get_string(&table, "backend")? {
+            self.backend = x.parse().map_err(|error| format!("'backend': {error}"))?;
+        }
+        if let Some(x) = get_string(&table, "prompt_socket")? {
+            self.prompt_socket = Some(path::PathBuf::from(x));
+        }
+        if self.backend == PromptKind::Socket && self.prompt_socket.is_none() {
+            return Err("'backend' is socket, but 'prompt_socket' isn't set".to_string());
+        }
+        if let Some(x) = get_string(&table, "no_prompt")? {
+            self.no_prompt_action = x.parse().map_err(|error| format!("'no_prompt': {error}"))?;
+        }
+        if let Some(x) = get_unsigned(&table, "check_interval_minutes")? {
+            self.check_interval = x.saturating_mul(60);
+        }
+
+        return Ok(());
+    }
+
+    // Every setting, by its key in the config file, as `apply_settings`
+    // reads it. Optional settings that aren't set are left out
+    fn settings(&self) -> toml::Table {
+        let mut table = toml::Table::new();
+        let mut set = |key: &str, value: toml::Value| {
+            table.insert(key.to_string(), value);
+        };
+        let bool = |x: bool| toml::Value::Boolean(x);
+        let string = |x: &str| toml::Value::String(x.to_string());
+        let strings =
+            |x: &[String]| toml::Value::Array(x.iter().map(|x| string(x)).collect::<Vec<_>>());
+        let path = |x: &path::Path| string(&x.to_string_lossy());
+        let unsigned = |x: u64| toml::Value::Integer(i64::try_from(x).unwrap_or(i64::MAX));
+
+        set("require_ac_power", bool(self.require_ac_power));
+        set("allow_root", bool(self.root_policy == RootPolicy::Warn));
+        set("update_command", strings(&self.update_command));
+        set("update_countdown", unsigned(self.update_countdown));
+        set("elevate", string(self.elevate.name()));
+        set("post_update_hook", strings(&self.post_update_hook));
+        set(
+            "update_available_hook",
+            strings(&self.update_available_hook),
+        );
+        for (channel, command) in &self.channel_hooks {
+            set(&format!("channel_hooks.{channel}"), strings(command));
+        }
+        set("auto_update", strings(&self.auto_update));
+        set(
+            "skip_nightly_date_bumps",
+            bool(self.skip_nightly_date_bumps),
+        );
+        if let Some(x) = &self.minimum_rust_version {
+            set("minimum_rust_version", string(&x.to_string()));
+        }
+        set("check_interval_minutes", unsigned(self.check_interval / 60));
+        set("accessible", bool(self.accessible));
+        if let Some(x) = &self.rustup_dist_server {
+            set("rustup_dist_server", string(x));
+        }
+        if let Some(x) = &self.rustup_update_root {
+            set("rustup_update_root", string(x));
+        }
+        set("timeout_action", string(self.timeout_action.name()));
+        set("close_action", string(self.close_action.name()));
+        set("offer_install", bool(self.offer_install));
+        set("recheck_after_update", bool(self.recheck_after_update));
+        set("no_snooze", bool(self.no_snooze));
+        set("detach", bool(self.detach));
+        set("notify", bool(self.notify));
+        set("keep_stats", bool(self.keep_stats));
+        set("probe_network", bool(self.probe_network));
+        set("ignore_rustup_self", bool(self.ignore_rustup_self));
+        set("notify_urgency", string(self.notify_style.urgency.name()));
+        set("notify_persistent", bool(self.notify_style.persistent));
+        set("verify_rustup", bool(self.verify_rustup));
+        if let Some(x) = &self.rustup_prefix {
+            set("rustup_prefix", path(x));
+        }
+        if let Some(x) = &self.rustup_path {
+            set("rustup_path", path(x));
+        }
+        if let Some(x) = &self.flag_path {
+            set("flag_path", path(x));
+        }
+        set("snooze", string(&snooze::format_duration(self.snooze)));
+        set(
+            "prompt_timeout",
+            unsigned(self.prompt_timeout.map_or(0, u64::from)),
+        );
+        set("daily_prompt_budget", unsigned(self.daily_prompt_budget));
+        if let Some(x) = &self.dialog_icon {
+            set("dialog_icon", path(x));
+        }
+        set("dialog_modal", bool(self.dialog_modal));
+        set("backend", string(self.backend.name()));
+        if let Some(x) = &self.prompt_socket {
+            set("prompt_socket", path(x));
+        }
+        set("no_prompt", string(self.no_prompt_action.name()));
+        if let Some(x) = &self.terminal {
+            set("terminal", string(x.program));
+        }
+        set("verbose", bool(self.verbose));
+        set("dry_run", bool(self.dry_run));
+        if let Some(x) = &self.report_path {
+            set("report_path", path(x));
+        }
+        if let Some(x) = &self.metrics_path {
+            set("metrics_path", path(x));
+        }
+        #[cfg(feature = "dist-server")]
+        set("use_dist_server", bool(self.use_dist_server));
+
+        return table;
+    }
+
+    /// The config as a config file, which reads back as the same config.
+    /// Optional settings that aren't set are left out
+    pub fn to_toml(&self) -> String {
+        return toml::write(&self.settings());
+    }
+
+    /// The config as a JSON object, with the keys of the config file
+    pub fn to_json(&self) -> String {
+        let settings = self
+            .settings()
+            .into_iter()
+            .map(|(key, value)| (key, json_value(value)))
+            .collect();
+
+        return json::Value::Object(settings).to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{notify::NotifyUrgency, test_util::temp_dir};
+
+    #[test]
+    fn allow_root() {
+        assert_eq!(Config::default().root_policy, RootPolicy::Refuse);
+
+        let config = Config::from_toml("allow_root = true").unwrap();
+        assert_eq!(config.root_policy, RootPolicy::Warn);
+    }
+
+    #[test]
+    fn config_file() {
+        let config = Config::from_toml(
+            r#"
+            require_ac_power = true
+            post_update_hook = ["cargo", "install-update", "-a"]
+            minimum_rust_version = "1.80.0"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.require_ac_power);
+        assert_eq!(config.post_update_hook, ["cargo", "install-update", "-a"]);
+        assert_eq!(config.update_command, Config::default().update_command);
+        assert_eq!(config.minimum_rust_version, Some(Version::new(1, 80, 0)));
+        assert_eq!(config.check_interval, DEFAULT_CHECK_INTERVAL);
+
+        let config = Config::from_toml("check_interval_minutes = 5").unwrap();
+        assert_eq!(config.check_interval, 5 * 60);
+
+        let config = Config::from_toml("timeout_action = \"snooze-short\"").unwrap();
+        assert_eq!(config.timeout_action, TimeoutAction::SnoozeShort);
+        assert_eq!(config.close_action, TimeoutAction::Decline);
+
+        let config = Config::from_toml("close_action = \"ask-next-run\"").unwrap();
+        assert_eq!(config.close_action, TimeoutAction::AskNextRun);
+        assert!(!config.offer_install);
+
+        let config = Config::from_toml("offer_install = true").unwrap();
+        assert!(config.offer_install);
+
+        assert_eq!(Config::default().elevate, Elevation::Never);
+        let config = Config::from_toml("elevate = \"sudo\"").unwrap();
+        assert_eq!(config.elevate, Elevation::Sudo);
+
+        assert_eq!(Config::default().backend, PromptKind::Auto);
+        let config = Config::from_toml("backend = \"tty\"\nno_prompt = \"update\"").unwrap();
+        assert_eq!(config.backend, PromptKind::Tty);
+        assert_eq!(config.no_prompt_action, NoPromptAction::Update);
+        assert!(Config::from_toml("backend = \"kdialog\"").is_err());
+
+        let config =
+            Config::from_toml("backend = \"socket\"\nprompt_socket = \"/run/user/1000/rust.sock\"")
+                .unwrap();
+        assert_eq!(config.backend, PromptKind::Socket);
+        assert_eq!(
+            config.prompt_socket,
+            Some(path::PathBuf::from("/run/user/1000/rust.sock"))
+        );
+        assert!(Config::from_toml("backend = \"socket\"").is_err());
+
+        let config = Config::from_toml("recheck_after_update = true").unwrap();
+        assert!(config.recheck_after_update);
+
+        let config = Config::from_toml(
+            r#"
+            notify = true
+            notify_urgency = "low"
+            notify_persistent = true
+            "#,
+        )
+        .unwrap();
+        assert!(config.notify);
+        assert!(!config.detach);
+        assert!(Config::from_toml("detach = true").unwrap().detach);
+        assert_eq!(
+            config.notify_style,
+            NotifyStyle {
+                urgency: NotifyUrgency::Low,
+                persistent: true
+            }
+        );
+
+        let config = Config::from_toml(
+            r#"
+            verify_rustup = true
+            rustup_prefix = "/opt/cargo"
+            "#,
+        )
+        .unwrap();
+        assert!(config.verify_rustup);
+        assert_eq!(
+            config.rustup_prefix,
+            Some(path::PathBuf::from("/opt/cargo"))
+        );
+
+        let config =
+            Config::from_toml("rustup_dist_server = \"https://mirror.example.com\"").unwrap();
+        assert_eq!(
+            config.rustup_dist_server.as_deref(),
+            Some("https://mirror.example.com")
+        );
+    }
+
+    #[test]
+    fn show_config() {
+        let config = Config::from_env_vars([
+            ("AUTO_RUSTUP_UPDATE_SNOOZE".to_string(), "1d12h".to_string()),
+            (
+                "AUTO_RUSTUP_UPDATE_CHANNEL_HOOKS_NIGHTLY".to_string(),
+                "rustup component add miri".to_string(),
+            ),
+        ])
+        .unwrap();
+
+        let toml = config.to_toml();
+        assert!(toml.contains("snooze = \"1d12h\"\n"));
+        assert!(toml
+            .contains("channel_hooks.nightly = [\"rustup\", \"component\", \"add\", \"miri\"]\n"));
+        assert!(toml.contains("backend = \"auto\"\n"));
+        assert!(!toml.contains("rustup_path"));
+        let json = json::parse(&config.to_json()).unwrap();
+        assert_eq!(json.get("snooze").and_then(|x| x.as_str()), Some("1d12h"));
+        assert_eq!(json.get("prompt_timeout"), Some(&json::Value::Number(10.0)));
+
+        // Everything set reads back the same
+        let config = ConfigBuilder::new()
+            .rustup_path("/opt/cargo/bin/rustup")
+            .flag_path("/tmp/donotupdate")
+            .prompt_socket("/run/prompt.sock")
+            .build()
+            .unwrap();
+        let config = Config {
+            minimum_rust_version: Some(Version::new(1, 80, 0)),
+            root_policy: RootPolicy::Warn,
+            backend: PromptKind::Socket,
+            terminal: terminal::find_terminal("konsole"),
+            prompt_timeout: None,
+            update_countdown: 5,
+            keep_stats: true,
+            rustup_dist_server: Some("https://mirror.example.com".to_string()),
+            report_path: Some(path::PathBuf::from("/tmp/report.json")),
+            ..config
+        };
+        assert_eq!(
+            Config::from_toml_for_host(&config.to_toml(), None),
+            Ok(config)
+        );
+    }
+
+    #[test]
+    fn env_vars() {
+        let vars = [
+            ("AUTO_RUSTUP_UPDATE_REQUIRE_AC_POWER", "true"),
+            ("AUTO_RUSTUP_UPDATE_ALLOW_ROOT", "1"),
+            (
+                "AUTO_RUSTUP_UPDATE_UPDATE_COMMAND",
+                "rustup update --no-self-update",
+            ),
+            ("AUTO_RUSTUP_UPDATE_CHECK_INTERVAL_MINUTES", "5"),
+            ("AUTO_RUSTUP_UPDATE_TIMEOUT_ACTION", "decline"),
+            ("AUTO_RUSTUP_UPDATE_BACKEND", "tty"),
+            ("AUTO_RUSTUP_UPDATE_SNOOZE", "12h"),
+            ("AUTO_RUSTUP_UPDATE_PROMPT_TIMEOUT", "0"),
+            ("AUTO_RUSTUP_UPDATE_RUSTUP_PATH", "/opt/cargo/bin/rustup"),
+            ("AUTO_RUSTUP_UPDATE_NO_SNOOZE", "yes"),
+            (
+                "AUTO_RUSTUP_UPDATE_CHANNEL_HOOKS_NIGHTLY",
+                "rustup component add miri",
+            ),
+            // Empty is unset, and other variables are left alone
+            ("AUTO_RUSTUP_UPDATE_NOTIFY", ""),
+            ("RUSTUP_DIST_SERVER", "https://mirror.example.com"),
+        ];
+        let config =
+            Config::from_env_vars(vars.map(|(name, value)| (name.to_string(), value.to_string())))
+                .unwrap();
+
+        let mut expected = Config {
+            require_ac_power: true,
+            root_policy: RootPolicy::Warn,
+            update_command: vec![
+                "rustup".to_string(),
+                "update".to_string(),
+                "--no-self-update".to_string(),
+            ],
+            check_interval: 5 * 60,
+            timeout_action: TimeoutAction::Decline,
+            backend: PromptKind::Tty,
+            snooze: 12 * 60 * 60,
+            prompt_timeout: None,
+            rustup_path: Some(path::PathBuf::from("/opt/cargo/bin/rustup")),
+            no_snooze: true,
+            ..Config::default()
+        };
+        expected.channel_hooks.insert(
+            "nightly".to_string(),
+            vec![
+                "rustup".to_string(),
+                "component".to_string(),
+                "add".to_string(),
+                "miri".to_string(),
+            ],
+        );
+        assert_eq!(config, expected);
+
+        // The same checks as the config file
+        let parse = |name: &str, value: &str| {
+            return Config::from_env_vars([(name.to_string(), value.to_string())]);
+        };
+        assert_eq!(
+            parse("AUTO_RUSTUP_UPDATE_DETACH", "maybe").unwrap_err(),
+            "AUTO_RUSTUP_UPDATE_DETACH should be true or false"
+        );
+        assert!(parse("AUTO_RUSTUP_UPDATE_CHECK_INTERVAL_MINUTES", "-1").is_err());
+        assert!(parse("AUTO_RUSTUP_UPDATE_BACKEND", "kdialog").is_err());
+        assert!(parse("AUTO_RUSTUP_UPDATE_SNOOZE", "0h").is_err());
+        assert_eq!(
+            parse("AUTO_RUSTUP_UPDATE_BOGUS", "1").unwrap_err(),
+            "Unknown setting AUTO_RUSTUP_UPDATE_BOGUS"
+        );
+
+        // What update_available_hook is given isn't taken as settings, so a
+        // hook can run auto_rustup_update itself
+        let config = Config::from_env_vars([
+            (
+                "RUST_UPDATE_SUMMARY".to_string(),
+                "stable 1.80.0 -> 1.80.1".to_string(),
+            ),
+            (
+                "RUST_UPDATE_TOOLCHAINS".to_string(),
+                "stable-x86_64-unknown-linux-gnu=1.80.1".to_string(),
+            ),
+            ("AUTO_RUSTUP_UPDATE_DETACH".to_string(), "true".to_string()),
+        ])
+        .unwrap();
+        assert!(config.detach);
+    }
+
+    #[test]
+    fn host_sections() {
+        let text = r#"
+            snooze = "12h"
+            require_ac_power = true
+
+            [host.laptop]
+            snooze = "3d"
+            terminal = "konsole"
+
+            [host.desktop]
+            require_ac_power = false
+            "#;
+
+        let laptop = Config::from_toml_for_host(text, Some("laptop")).unwrap();
+        assert_eq!(laptop.snooze, 3 * 24 * 60 * 60);
+        assert_eq!(laptop.terminal.unwrap().program, "konsole");
+        assert!(laptop.require_ac_power);
+
+        let desktop = Config::from_toml_for_host(text, Some("desktop")).unwrap();
+        assert_eq!(desktop.snooze, 12 * 60 * 60);
+        assert_eq!(desktop.terminal, None);
+        assert!(!desktop.require_ac_power);
+
+        // Another machine only has the rest
+        let other = Config::from_toml_for_host(text, None).unwrap();
+        assert_eq!(other.snooze, 12 * 60 * 60);
+        assert!(other.require_ac_power);
+
+        assert!(Config::from_toml_for_host("[host.laptop]\nbogus = 1", Some("laptop")).is_err());
+        assert!(Config::from_toml("terminal = \"/bin/gnome-terminal\"").is_ok());
+        assert!(Config::from_toml("terminal = \"alacritty\"").is_err());
+        assert!(Config::from_toml("snooze = \"0h\"").is_err());
+    }
+
+    #[test]
+    fn auto_update() {
+        assert!(Config::default().auto_update.is_empty());
+
+        let config = Config::from_toml("auto_update = [\"stable\"]").unwrap();
+        assert_eq!(config.auto_update, ["stable"]);
+        assert!(Config::from_toml("auto_update = \"stable\"").is_err());
+
+        assert!(!Config::default().skip_nightly_date_bumps);
+        let config = Config::from_toml("skip_nightly_date_bumps = true").unwrap();
+        assert!(config.skip_nightly_date_bumps);
+    }
+
+    #[test]
+    fn channel_hooks() {
+        let config = Config::from_toml(
+            r#"
+            [channel_hooks]
+            nightly = ["rustup", "component", "add", "miri", "--toolchain", "nightly"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.channel_hooks,
+            BTreeMap::from([(
+                "nightly".to_string(),
+                vec![
+                    "rustup".to_string(),
+                    "component".to_string(),
+                    "add".to_string(),
+                    "miri".to_string(),
+                    "--toolchain".to_string(),
+                    "nightly".to_string(),
+                ]
+            )])
+        );
+
+        assert!(Config::from_toml("[channel_hooks]\nnightly = []").is_err());
+        assert!(Config::from_toml("[channel_hooks]\nnightly = \"rustup\"").is_err());
+    }
+
+    #[test]
+    fn bad_config_file() {
+        assert!(Config::from_toml("bogus = true").is_err());
+        assert!(Config::from_toml("require_ac_power = \"yes\"").is_err());
+        assert!(Config::from_toml("post_update_hook = [1]").is_err());
+        assert!(Config::from_toml("update_command = []").is_err());
+        assert!(Config::from_toml("minimum_rust_version = \"1.80\"").is_err());
+        assert!(Config::from_toml("check_interval_minutes = -1").is_err());
+        assert!(Config::from_toml("timeout_action = \"later\"").is_err());
+        assert!(Config::from_toml("elevate = \"doas\"").is_err());
+        assert!(Config::from_toml("close_action = \"later\"").is_err());
+    }
+
+    #[test]
+    fn builder() {
+        let config = Config::builder()
+            .backend(PromptKind::Tty)
+            .terminal(crate::terminal::TERMINALS[4])
+            .flag_path("/tmp/donotupdate")
+            .snooze(60 * 60)
+            .timeout(None)
+            .has_display(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.backend, PromptKind::Tty);
+        assert_eq!(config.terminal.unwrap().program, "xterm");
+        assert_eq!(
+            config.flag_path,
+            Some(path::PathBuf::from("/tmp/donotupdate"))
+        );
+        assert_eq!(config.snooze, 60 * 60);
+        assert_eq!(config.prompt_timeout, None);
+        assert_eq!(config.update_command, Config::default().update_command);
+    }
+
+    #[test]
+    fn builder_validation() {
+        let builder = Config::builder().has_display(false);
+
+        // A terminal prompt doesn't need a display, and auto falls back to one
+        assert!(builder.clone().backend(PromptKind::Tty).build().is_ok());
+        assert!(builder.clone().backend(PromptKind::Auto).build().is_ok());
+
+        assert!(builder.clone().backend(PromptKind::Zenity).build().is_err());
+        assert!(builder.clone().backend(PromptKind::Notify).build().is_err());
+
+        // Needs the socket, but not a display
+        assert!(builder.clone().backend(PromptKind::Socket).build().is_err());
+        assert!(builder
+            .clone()
+            .prompt_socket("/tmp/rust.sock")
+            .build()
+            .is_ok());
+        assert!(builder
+            .clone()
+            .backend(PromptKind::Tty)
+            .terminal(crate::terminal::TERMINALS[0])
+            .build()
+            .is_err());
+
+        let builder = Config::builder().has_display(true);
+        assert!(builder.clone().update_command::<&str>(&[]).build().is_err());
+        assert!(builder.clone().snooze(0).build().is_err());
+        assert!(builder.clone().timeout(Some(0)).build().is_err());
+        assert!(builder.clone().check_interval(90).build().is_err());
+
+        // So the config file shows it exactly
+        let config = builder.clone().check_interval(0).build().unwrap();
+        assert!(config.to_toml().contains("check_interval_minutes = 0\n"));
+        let config = builder.clone().check_interval(120).build().unwrap();
+        assert!(config.to_toml().contains("check_interval_minutes = 2\n"));
+    }
+
+    #[test]
+    fn load_from_path() {
+        let path = temp_dir("config_load_from").join("config.toml");
+
+        let error = Config::load_from(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("config.toml"));
+
+        fs::write(&path, "require_ac_power = true").unwrap();
+        assert!(Config::load_from(&path).unwrap().require_ac_power);
+    }
+}
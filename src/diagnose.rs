@@ -0,0 +1,274 @@
+// Finds problems with the setup that stop updates working as expected, for
+// `--diagnose`
+
+use std::{
+    collections::HashSet,
+    env, ffi, fs,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{MetadataExt, PermissionsExt},
+    },
+    path,
+};
+
+const W_OK: i32 = 2;
+
+extern "C" {
+    // From the C library, which std already links
+    fn access(path: *const ffi::c_char, mode: i32) -> i32;
+}
+
+// Places rustup is often installed, other than on PATH
+const USUAL_RUSTUP_PATHS: [&str; 3] = [
+    "/usr/bin/rustup",
+    "/usr/local/bin/rustup",
+    "/snap/bin/rustup",
+];
+
+// Read-only stores that package managers install into, and the package
+// manager of each
+const PACKAGE_STORES: [(&str, &str); 2] = [("/nix/store", "Nix"), ("/gnu/store", "Guix")];
+
+// True if `path` is a file that can be run
+fn is_executable(path: &path::Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => return metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => return false,
+    }
+}
+
+/// True if files can be made in `dir`, so a program in it can replace
+/// itself, like rustup updating itself
+///
+/// A directory without write permission isn't writable, even to root. A
+/// read-only filesystem is found by `access`, without making a file
+pub fn is_writable_dir(dir: &path::Path) -> bool {
+    match fs::metadata(dir) {
+        Ok(metadata) if metadata.is_dir() && metadata.permissions().mode() & 0o222 != 0 => {}
+        _ => return false,
+    }
+
+    let Ok(dir) = ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    // Only reads the string, which lives until it returns
+    return unsafe { access(dir.as_ptr(), W_OK) } == 0;
+}
+
+/// Every rustup binary in the directories of `path_var`, then in `extra`
+///
+/// Links to the same binary are only listed once, by the first path found
+pub fn find_rustups(path_var: &ffi::OsStr, extra: &[path::PathBuf]) -> Vec<path::PathBuf> {
+    let candidates = env::split_paths(path_var)
+        .map(|x| x.join("rustup"))
+        .chain(extra.iter().cloned());
+
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+
+    for candidate in candidates {
+        if !is_executable(&candidate) {
+            continue;
+        }
+
+        let real_path = fs::canonicalize(&candidate).unwrap_or(candidate.clone());
+        if seen.insert(real_path) {
+            found.push(candidate);
+        }
+    }
+
+    return found;
+}
+
+// Device and inode of `path`, following links, to tell if two paths are
+// the same file
+fn file_id(path: &path::Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+
+    return Some((metadata.dev(), metadata.ino()));
+}
+
+/// Every rustc in the directories of `path_var` that isn't a rustup proxy,
+/// like one installed by the distro. Proxies are links to one of `rustups`
+///
+/// The first path is the rustc run by `cargo` and by `rustc` from a shell,
+/// if it isn't behind a proxy on `path_var`
+pub fn find_system_rustcs(path_var: &ffi::OsStr, rustups: &[path::PathBuf]) -> Vec<path::PathBuf> {
+    let rustup_ids: HashSet<(u64, u64)> = rustups.iter().filter_map(|x| file_id(x)).collect();
+
+    return env::split_paths(path_var)
+        .map(|x| x.join("rustc"))
+        .filter(|x| is_executable(x))
+        .filter(|x| file_id(x).is_some_and(|id| !rustup_ids.contains(&id)))
+        .collect();
+}
+
+/// The first `program` on `path_var` that can be run
+pub fn first_on_path(path_var: &ffi::OsStr, program: &str) -> Option<path::PathBuf> {
+    return env::split_paths(path_var)
+        .map(|x| x.join(program))
+        .find(|x| is_executable(x));
+}
+
+/// True if `rustup` is installed, but there's no rustup on `path_var`, like
+/// when rustup-init's PATH setup never ran. Running `rustup` by name then
+/// isn't found, even though it's there
+pub fn rustup_off_path(path_var: &ffi::OsStr, rustup: &path::Path) -> bool {
+    return is_executable(rustup) && first_on_path(path_var, "rustup").is_none();
+}
+
+/// The package manager whose store `resolved` is in, like `Nix` for a path
+/// under `/nix/store`. Links should be followed first, as profiles like
+/// `~/.nix-profile/bin` link into the store
+pub fn package_store_manager(resolved: &path::Path) -> Option<&'static str> {
+    return PACKAGE_STORES
+        .iter()
+        .find(|(store, _)| resolved.starts_with(store))
+        .map(|x| x.1);
+}
+
+/// The usual places to look for rustup, other than PATH, including the one
+/// used for updates
+pub fn usual_rustup_paths(home_rustup: path::PathBuf) -> Vec<path::PathBuf> {
+    let mut paths = vec![home_rustup];
+    paths.extend(USUAL_RUSTUP_PATHS.iter().map(path::PathBuf::from));
+
+    return paths;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn fake_rustup(dir: &path::Path) -> path::PathBuf {
+        let path = dir.join("rustup");
+        fs::write(&path, "#!/bin/sh\necho 'rustup 1.27.1'\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        return path;
+    }
+
+    #[test]
+    fn finds_two_rustups() {
+        let dir = temp_dir("diagnose_two_rustups");
+        let distro = dir.join("usr/bin");
+        let cargo = dir.join("cargo/bin");
+        let empty = dir.join("empty");
+        for x in [&distro, &cargo, &empty] {
+            fs::create_dir_all(x).unwrap();
+        }
+
+        let distro_rustup = fake_rustup(&distro);
+        let cargo_rustup = fake_rustup(&cargo);
+
+        let path_var = env::join_paths([&empty, &distro]).unwrap();
+
+        assert_eq!(
+            find_rustups(&path_var, std::slice::from_ref(&cargo_rustup)),
+            [distro_rustup, cargo_rustup]
+        );
+    }
+
+    #[test]
+    fn off_path() {
+        let dir = temp_dir("diagnose_off_path");
+        let cargo = dir.join("cargo/bin");
+        let other = dir.join("usr/bin");
+        fs::create_dir_all(&cargo).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        let rustup = fake_rustup(&cargo);
+
+        // Installed, but PATH was never set up
+        let path_var = env::join_paths([&other]).unwrap();
+        assert!(rustup_off_path(&path_var, &rustup));
+
+        let path_var = env::join_paths([&other, &cargo]).unwrap();
+        assert!(!rustup_off_path(&path_var, &rustup));
+
+        // Not installed is a different problem
+        let path_var = env::join_paths([&other]).unwrap();
+        assert!(!rustup_off_path(&path_var, &other.join("rustup")));
+    }
+
+    #[test]
+    fn writable_dir() {
+        let dir = temp_dir("diagnose_writable");
+        assert!(is_writable_dir(&dir));
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+        assert!(!is_writable_dir(&dir));
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(is_writable_dir(&dir));
+        assert!(!is_writable_dir(&dir.join("missing")));
+    }
+
+    #[test]
+    fn system_rustc() {
+        let dir = temp_dir("diagnose_system_rustc");
+        let cargo = dir.join("cargo/bin");
+        let distro = dir.join("usr/bin");
+        fs::create_dir_all(&cargo).unwrap();
+        fs::create_dir_all(&distro).unwrap();
+
+        // rustup's proxies are links to rustup
+        let rustup = fake_rustup(&cargo);
+        fs::hard_link(&rustup, cargo.join("rustc")).unwrap();
+        std::os::unix::fs::symlink(&rustup, distro.join("cargo")).unwrap();
+
+        let distro_rustc = distro.join("rustc");
+        fs::write(&distro_rustc, "#!/bin/sh\necho 'rustc 1.75.0'\n").unwrap();
+        fs::set_permissions(&distro_rustc, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path_var = env::join_paths([&cargo, &distro]).unwrap();
+        assert_eq!(
+            find_system_rustcs(&path_var, std::slice::from_ref(&rustup)),
+            std::slice::from_ref(&distro_rustc)
+        );
+        assert_eq!(first_on_path(&path_var, "rustc"), Some(cargo.join("rustc")));
+
+        // With the distro first, it's the one that's run
+        let path_var = env::join_paths([&distro, &cargo]).unwrap();
+        assert_eq!(first_on_path(&path_var, "rustc"), Some(distro_rustc));
+    }
+
+    #[test]
+    fn links_found_once() {
+        let dir = temp_dir("diagnose_links");
+        let bin = dir.join("bin");
+        let linked = dir.join("linked");
+        fs::create_dir_all(&bin).unwrap();
+        fs::create_dir_all(&linked).unwrap();
+
+        let rustup = fake_rustup(&bin);
+        std::os::unix::fs::symlink(&rustup, linked.join("rustup")).unwrap();
+
+        // Not executable, so not rustup
+        fs::write(dir.join("rustup"), "").unwrap();
+
+        let path_var = env::join_paths([&bin, &linked, &dir]).unwrap();
+        assert_eq!(find_rustups(&path_var, &[]), [rustup]);
+    }
+
+    #[test]
+    fn package_stores() {
+        let manager = |x: &str| package_store_manager(path::Path::new(x));
+
+        assert_eq!(
+            manager("/nix/store/3x7dwzq014bblazs7kq20p9hyzz0qh8g-rustup-1.27.1/bin/rustup"),
+            Some("Nix")
+        );
+        assert_eq!(
+            manager("/gnu/store/k9a4x2-rustup-1.27.1/bin/rustup"),
+            Some("Guix")
+        );
+        assert_eq!(manager("/home/me/.cargo/bin/rustup"), None);
+        assert_eq!(manager("/usr/bin/rustup"), None);
+
+        // Only the store itself, not a directory that starts the same
+        assert_eq!(manager("/nix/store-backup/bin/rustup"), None);
+        assert_eq!(manager("/opt/nix/store/bin/rustup"), None);
+    }
+}
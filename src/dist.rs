@@ -0,0 +1,352 @@
+// Checks for new versions by asking the Rust dist server directly, instead
+// of running `rustup check`
+
+use crate::{toml, version::Version};
+
+#[cfg(feature = "dist-server")]
+use std::{io, path, time};
+
+#[cfg(feature = "dist-server")]
+use crate::runner::{CommandRunner, CommandSpec};
+
+/// The server rustup downloads from, unless `RUSTUP_DIST_SERVER` is set
+#[cfg(feature = "dist-server")]
+pub const DEFAULT_DIST_SERVER: &str = "https://static.rust-lang.org";
+
+// How long curl is given for each request. The runner kills it a little
+// after, in case it hangs anyway
+#[cfg(feature = "dist-server")]
+const FETCH_SECONDS: u64 = 30;
+#[cfg(feature = "dist-server")]
+const FETCH_TIMEOUT: time::Duration = time::Duration::from_secs(FETCH_SECONDS + 5);
+
+// Channels with a manifest on the dist server
+#[cfg(feature = "dist-server")]
+const CHANNELS: [&str; 3] = ["stable", "beta", "nightly"];
+
+/// Reads the version of Rust from a channel manifest, like
+/// `channel-rust-stable.toml`
+///
+/// The version is under `[pkg.rust]`, looking like
+/// `version = "1.80.1 (3f5fd8dd4 2024-08-06)"`
+pub fn manifest_rust_version(manifest: &str) -> Result<Version, String> {
+    let table = toml::parse(manifest)?;
+
+    let version = table
+        .get("pkg.rust.version")
+        .and_then(|x| x.as_str())
+        .ok_or("Manifest has no pkg.rust.version")?;
+
+    return Version::find(version).ok_or(format!("Manifest version '{version}' is malformed"));
+}
+
+/// Date a channel manifest was released, like `2024-08-08`
+pub fn manifest_date(manifest: &str) -> Result<String, String> {
+    let table = toml::parse(manifest)?;
+
+    return Ok(table
+        .get("date")
+        .and_then(|x| x.as_str())
+        .ok_or("Manifest has no date")?
+        .to_string());
+}
+
+/// URL of the package to download for Rust on `target`, from a channel
+/// manifest
+///
+/// The smaller xz package is used if there is one, as rustup does
+pub fn manifest_download_url(manifest: &str, target: &str) -> Result<String, String> {
+    let table = toml::parse(manifest)?;
+
+    let key = format!("pkg.rust.target.{target}");
+    let url = table
+        .get(&format!("{key}.xz_url"))
+        .or_else(|| table.get(&format!("{key}.url")))
+        .and_then(|x| x.as_str())
+        .ok_or(format!("Manifest has no package for {target}"))?;
+
+    return Ok(url.to_string());
+}
+
+/// Formats a download size for the prompt, like `~180MB`
+pub fn format_size(bytes: u64) -> String {
+    const KB: u64 = 1000;
+    const MB: u64 = 1000 * KB;
+    const GB: u64 = 1000 * MB;
+
+    // Rounded, so never shows as ~0
+    if bytes >= GB {
+        return format!("~{:.1}GB", bytes as f64 / GB as f64);
+    } else if bytes >= MB {
+        return format!("~{}MB", (bytes + MB / 2) / MB);
+    } else if bytes >= KB {
+        return format!("~{}KB", (bytes + KB / 2) / KB);
+    } else {
+        return format!("{bytes}B");
+    }
+}
+
+// URL of the manifest listing the latest release of `channel`, on
+// `dist_server`
+#[cfg(feature = "dist-server")]
+fn channel_manifest_url(dist_server: &str, channel: &str) -> String {
+    return format!(
+        "{}/dist/channel-rust-{channel}.toml",
+        dist_server.trim_end_matches('/')
+    );
+}
+
+// curl, run with `args` then `url`, given `FETCH_SECONDS`
+#[cfg(feature = "dist-server")]
+fn curl_command(args: &[&str], url: &str) -> CommandSpec {
+    let max_time = FETCH_SECONDS.to_string();
+    let mut args = args.to_vec();
+    args.extend(["--max-time", &max_time, url]);
+
+    return CommandSpec::new("curl", &args).with_timeout(FETCH_TIMEOUT);
+}
+
+// Downloads the manifest of a channel from `dist_server` with `runner`.
+// curl is used so no HTTP client needs to be built in
+#[cfg(feature = "dist-server")]
+fn fetch_channel_manifest(
+    runner: &dyn CommandRunner,
+    dist_server: &str,
+    channel: &str,
+) -> io::Result<String> {
+    let output = runner.run(&curl_command(
+        &["--silent", "--show-error", "--fail"],
+        &channel_manifest_url(dist_server, channel),
+    ))?;
+
+    if !output.success() {
+        return io::Result::Err(io::Error::other(format!(
+            "Failed to download the {channel} manifest: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    return String::from_utf8(output.stdout)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error));
+}
+
+/// Version of the installed stable toolchain, read using
+/// `rustup run stable rustc --version` run with `runner`
+///
+/// None if stable isn't installed
+#[cfg(feature = "dist-server")]
+pub fn installed_stable_version(
+    runner: &dyn CommandRunner,
+    rustup_path: &path::Path,
+) -> io::Result<Option<Version>> {
+    let output = runner.run(&CommandSpec::new(
+        &rustup_path.to_string_lossy(),
+        &["run", "stable", "rustc", "--version"],
+    ))?;
+
+    if !output.success() {
+        return io::Result::Ok(None);
+    }
+
+    return io::Result::Ok(Version::find(&String::from_utf8_lossy(&output.stdout)));
+}
+
+/// True if `dist_server` has a newer stable than the one installed
+#[cfg(feature = "dist-server")]
+pub fn stable_update_available(
+    runner: &dyn CommandRunner,
+    rustup_path: &path::Path,
+    dist_server: &str,
+) -> io::Result<bool> {
+    let latest = manifest_rust_version(&fetch_channel_manifest(runner, dist_server, "stable")?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    match installed_stable_version(runner, rustup_path)? {
+        Some(installed) => return io::Result::Ok(installed < latest),
+        None => return io::Result::Ok(false),
+    }
+}
+
+// Size from the headers of a HTTP response. With redirects there's a set of
+// headers for each response, so the last size is the real one
+#[cfg(feature = "dist-server")]
+fn parse_content_length(headers: &str) -> Option<u64> {
+    return headers
+        .lines()
+        .rev()
+        .filter_map(|x| x.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .find_map(|(_, value)| value.trim().parse().ok());
+}
+
+// Size of the file at `url`, asking the server without downloading it
+#[cfg(feature = "dist-server")]
+fn fetch_download_size(runner: &dyn CommandRunner, url: &str) -> io::Result<Option<u64>> {
+    let output = runner.run(&curl_command(
+        &["--silent", "--fail", "--head", "--location"],
+        url,
+    ))?;
+
+    if !output.success() {
+        return io::Result::Ok(None);
+    }
+
+    return io::Result::Ok(parse_content_length(&String::from_utf8_lossy(
+        &output.stdout,
+    )));
+}
+
+/// The manifest of the release `version` of a toolchain like
+/// `stable-x86_64-unknown-linux-gnu`, from its channel on `dist_server`
+///
+/// None if it can't be found, like for rustup itself, a toolchain pinned
+/// to a version, or with no internet. Also None if the channel has moved
+/// on to another release than `version`, as it only lists its latest
+#[cfg(feature = "dist-server")]
+pub fn release_manifest(
+    runner: &dyn CommandRunner,
+    dist_server: &str,
+    toolchain: &str,
+    version: Version,
+) -> Option<String> {
+    let channel = toolchain.split('-').next()?;
+    if !CHANNELS.contains(&channel) {
+        return None;
+    }
+
+    let manifest = fetch_channel_manifest(runner, dist_server, channel).ok()?;
+    if manifest_rust_version(&manifest).ok()? != version {
+        return None;
+    }
+
+    return Some(manifest);
+}
+
+/// Size of the download of Rust for the `target` of a toolchain, from
+/// `manifest`. None if it can't be found
+#[cfg(feature = "dist-server")]
+pub fn download_size(runner: &dyn CommandRunner, manifest: &str, target: &str) -> Option<u64> {
+    let url = manifest_download_url(manifest, target).ok()?;
+
+    return fetch_download_size(runner, &url).ok()?;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimmed down copy of a real channel-rust-stable.toml
+    const SAMPLE_MANIFEST: &str = r#"
+date = "2024-08-08"
+manifest-version = "2"
+
+[pkg.cargo]
+version = "0.81.0 (2dbb1af80 2024-08-20)"
+
+[pkg.cargo.target.x86_64-unknown-linux-gnu]
+available = true
+url = "https://static.rust-lang.org/dist/2024-08-08/cargo-1.80.1-x86_64-unknown-linux-gnu.tar.gz"
+
+[pkg.rust]
+version = "1.80.1 (3f5fd8dd4 2024-08-06)"
+
+[pkg.rust.target.x86_64-unknown-linux-gnu]
+available = true
+url = "https://static.rust-lang.org/dist/2024-08-08/rust-1.80.1-x86_64-unknown-linux-gnu.tar.gz"
+xz_url = "https://static.rust-lang.org/dist/2024-08-08/rust-1.80.1-x86_64-unknown-linux-gnu.tar.xz"
+
+[[pkg.rust.target.x86_64-unknown-linux-gnu.components]]
+pkg = "rustc"
+target = "x86_64-unknown-linux-gnu"
+"#;
+
+    #[test]
+    fn sample_manifest() {
+        assert_eq!(
+            manifest_rust_version(SAMPLE_MANIFEST),
+            Ok(Version::new(1, 80, 1))
+        );
+        assert_eq!(manifest_date(SAMPLE_MANIFEST), Ok("2024-08-08".to_string()));
+    }
+
+    #[test]
+    fn manifest_without_rust() {
+        assert!(manifest_rust_version("date = \"2024-08-08\"").is_err());
+    }
+
+    #[test]
+    fn download_url() {
+        assert_eq!(
+            manifest_download_url(SAMPLE_MANIFEST, "x86_64-unknown-linux-gnu").unwrap(),
+            "https://static.rust-lang.org/dist/2024-08-08/rust-1.80.1-x86_64-unknown-linux-gnu.tar.xz"
+        );
+        assert!(manifest_download_url(SAMPLE_MANIFEST, "aarch64-apple-darwin").is_err());
+    }
+
+    #[test]
+    fn size_formatting() {
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1_499), "~1KB");
+        assert_eq!(format_size(179_600_000), "~180MB");
+        assert_eq!(format_size(1_250_000_000), "~1.2GB");
+    }
+
+    #[cfg(feature = "dist-server")]
+    #[test]
+    fn release_manifests() {
+        use crate::runner::{CommandOutput, MockRunner};
+
+        let runner =
+            MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, SAMPLE_MANIFEST, "")));
+        let toolchain = "stable-x86_64-unknown-linux-gnu";
+        assert_eq!(
+            release_manifest(
+                &runner,
+                "https://mirror.example.com/",
+                toolchain,
+                Version::new(1, 80, 1)
+            )
+            .as_deref(),
+            Some(SAMPLE_MANIFEST)
+        );
+        let curl = &runner.calls()[0];
+        assert_eq!(
+            curl.args.last().map(|x| x.as_str()),
+            Some("https://mirror.example.com/dist/channel-rust-stable.toml")
+        );
+        assert_eq!(curl.timeout, Some(FETCH_TIMEOUT));
+
+        // The channel has moved on since the check
+        assert_eq!(
+            release_manifest(
+                &runner,
+                DEFAULT_DIST_SERVER,
+                toolchain,
+                Version::new(1, 80, 0)
+            ),
+            None
+        );
+
+        // Nothing to fetch for a pinned toolchain
+        assert_eq!(
+            release_manifest(
+                &runner,
+                DEFAULT_DIST_SERVER,
+                "1.80.1-x86_64-unknown-linux-gnu",
+                Version::new(1, 80, 1)
+            ),
+            None
+        );
+        assert_eq!(runner.calls().len(), 2);
+    }
+
+    #[cfg(feature = "dist-server")]
+    #[test]
+    fn content_length() {
+        let headers = "HTTP/1.1 302 Found\r\ncontent-length: 0\r\n\r\n\
+                       HTTP/1.1 200 OK\r\nContent-Length: 179600000\r\n\r\n";
+
+        assert_eq!(parse_content_length(headers), Some(179_600_000));
+        assert_eq!(parse_content_length("HTTP/1.1 200 OK\r\n"), None);
+    }
+}
@@ -0,0 +1,265 @@
+use std::{error, fmt, io, path, string};
+
+/// Errors from checking for and running updates
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file, or running a command, failed
+    Io(io::Error),
+
+    /// `rustup check` printed something that isn't UTF-8
+    Decode(string::FromUtf8Error),
+
+    /// `rustup check` failed because it couldn't download from the Rust
+    /// dist server
+    NoInternet,
+
+    /// rustup isn't installed where it was expected
+    RustupNotFound,
+
+    /// rustup is there, but couldn't be started, as it isn't executable
+    RustupPermissionDenied,
+
+    /// `rustup check` started, but was killed after running for `seconds`,
+    /// like when the network hangs
+    CheckTimeout { seconds: u64 },
+
+    /// `rustup check` failed for a reason other than the internet. `message`
+    /// is the first line of what it printed to stderr
+    RustupFailed {
+        status: Option<i32>,
+        message: String,
+    },
+
+    /// The prompt couldn't be shown, like when zenity isn't installed
+    Prompt(String),
+
+    /// rustup is in the store of a package manager, like Nix, so Rust has
+    /// to be updated through that instead
+    RustupManaged {
+        manager: &'static str,
+        rustup: path::PathBuf,
+    },
+}
+
+impl Error {
+    /// Exit code for the program when it stops because of this error
+    ///
+    /// Each kind of error has its own code, listed in the README, so
+    /// scripts and the journal can tell them apart
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // Bad arguments or config
+            Error::Io(error) if error.kind() == io::ErrorKind::InvalidInput => return 2,
+            Error::Io(_) => return 3,
+            Error::Decode(_) => return 4,
+            Error::NoInternet => return 5,
+            Error::RustupNotFound => return 6,
+            Error::RustupFailed { .. } => return 7,
+            Error::Prompt(_) => return 8,
+            Error::RustupPermissionDenied => return 9,
+            Error::CheckTimeout { .. } => return 10,
+            Error::RustupManaged { .. } => return 11,
+        }
+    }
+
+    /// What to try next, printed after the error. None if the message says
+    /// enough
+    ///
+    /// rustup not starting is a problem with the install, while it hanging
+    /// once started is usually the network, so they're told apart
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Error::RustupNotFound => {
+                return Some("rustup didn't start. Install it from https://rustup.rs")
+            }
+            Error::RustupPermissionDenied => {
+                return Some(
+                    "rustup didn't start. Make it executable with `chmod +x ~/.cargo/bin/rustup`",
+                )
+            }
+            Error::CheckTimeout { .. } => {
+                return Some(
+                    "rustup started, but stopped responding. Check the internet connection, \
+                     and RUSTUP_DIST_SERVER if it's set",
+                )
+            }
+            _ => return None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => return write!(f, "{error}"),
+            Error::Decode(error) => return write!(f, "rustup output isn't UTF-8: {error}"),
+            Error::NoInternet => {
+                return write!(f, "Failed to download file. Check internet connection")
+            }
+            Error::RustupNotFound => {
+                return write!(f, "Can't find rustup. Is it installed for this user?")
+            }
+            Error::RustupFailed { status, message } => {
+                match status {
+                    Some(status) => write!(f, "rustup check failed with exit code {status}")?,
+                    None => write!(f, "rustup check was killed")?,
+                }
+                if !message.is_empty() {
+                    write!(f, ": {message}")?;
+                }
+
+                return fmt::Result::Ok(());
+            }
+            Error::Prompt(message) => return write!(f, "Couldn't show the prompt: {message}"),
+            Error::RustupPermissionDenied => {
+                return write!(f, "Can't run rustup. It isn't executable by this user")
+            }
+            Error::CheckTimeout { seconds } => {
+                return write!(f, "rustup check didn't finish within {seconds} seconds")
+            }
+            Error::RustupManaged { manager, rustup } => {
+                return write!(
+                    f,
+                    "rustup is managed by {manager}, at {}. Update Rust with {manager} instead",
+                    rustup.display()
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(error) => return Some(error),
+            Error::Decode(error) => return Some(error),
+            _ => return None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        return Error::Io(error);
+    }
+}
+
+impl From<string::FromUtf8Error> for Error {
+    fn from(error: string::FromUtf8Error) -> Self {
+        return Error::Decode(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let errors = [
+            Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Unknown argument",
+            )),
+            Error::Io(io::Error::other("Disk full")),
+            Error::Decode(String::from_utf8(vec![0xff]).unwrap_err()),
+            Error::NoInternet,
+            Error::RustupNotFound,
+            Error::RustupFailed {
+                status: Some(1),
+                message: "error: bad settings".to_string(),
+            },
+            Error::Prompt("zenity isn't installed".to_string()),
+            Error::RustupPermissionDenied,
+            Error::CheckTimeout { seconds: 300 },
+            Error::RustupManaged {
+                manager: "Nix",
+                rustup: path::PathBuf::from("/nix/store/abc-rustup-1.27.1/bin/rustup"),
+            },
+        ];
+        let codes: Vec<i32> = errors.iter().map(|x| x.exit_code()).collect();
+
+        assert_eq!(codes, [2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(
+            Error::NoInternet.to_string(),
+            "Failed to download file. Check internet connection"
+        );
+        assert_eq!(
+            Error::RustupNotFound.to_string(),
+            "Can't find rustup. Is it installed for this user?"
+        );
+        assert_eq!(
+            Error::RustupFailed {
+                status: Some(1),
+                message: "error: bad settings".to_string(),
+            }
+            .to_string(),
+            "rustup check failed with exit code 1: error: bad settings"
+        );
+        assert_eq!(
+            Error::RustupFailed {
+                status: None,
+                message: String::new(),
+            }
+            .to_string(),
+            "rustup check was killed"
+        );
+        assert_eq!(
+            Error::Prompt("zenity ended with exit code 255".to_string()).to_string(),
+            "Couldn't show the prompt: zenity ended with exit code 255"
+        );
+        assert_eq!(
+            Error::CheckTimeout { seconds: 300 }.to_string(),
+            "rustup check didn't finish within 300 seconds"
+        );
+        assert_eq!(
+            Error::RustupManaged {
+                manager: "Guix",
+                rustup: path::PathBuf::from("/gnu/store/abc-rustup/bin/rustup"),
+            }
+            .to_string(),
+            "rustup is managed by Guix, at /gnu/store/abc-rustup/bin/rustup. Update Rust with \
+             Guix instead"
+        );
+
+        // Not starting and hanging get different advice
+        assert!(Error::RustupNotFound.hint().unwrap().contains("Install"));
+        assert!(Error::RustupPermissionDenied
+            .hint()
+            .unwrap()
+            .contains("chmod"));
+        assert!(Error::CheckTimeout { seconds: 300 }
+            .hint()
+            .unwrap()
+            .contains("internet"));
+        assert!(Error::NoInternet.hint().is_none());
+
+        // The io error's own message, with it as the source
+        let error = Error::from(io::Error::other("Disk full"));
+        assert_eq!(error.to_string(), "Disk full");
+        assert_eq!(
+            error::Error::source(&error).unwrap().to_string(),
+            "Disk full"
+        );
+        assert!(error::Error::source(&Error::NoInternet).is_none());
+    }
+
+    #[test]
+    fn boxed() {
+        // Usable with `?` in programs returning any error
+        fn run() -> Result<(), Box<dyn error::Error>> {
+            Err(Error::RustupNotFound)?;
+
+            return Ok(());
+        }
+
+        assert_eq!(
+            run().unwrap_err().to_string(),
+            Error::RustupNotFound.to_string()
+        );
+    }
+}
@@ -0,0 +1,159 @@
+// How the read-only status output of `--format` is written. Each format is a
+// `FormatWriter`, so adding one only needs a writer and a name
+
+use std::{collections::BTreeMap, str};
+
+use crate::{json, parse::ParsedUpdate, report, Error};
+
+/// Format of the status printed by `--format`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Format {
+    /// A line for each toolchain, and rustup
+    #[default]
+    Text,
+
+    /// A JSON object, like the report of `--report` without the outcome
+    Json,
+
+    /// A single line, for a shell prompt or status bar
+    Oneline,
+}
+
+impl str::FromStr for Format {
+    type Err = String;
+
+    /// Reads `text`, `json` or `oneline`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "text" => return Ok(Format::Text),
+            "json" => return Ok(Format::Json),
+            "oneline" => return Ok(Format::Oneline),
+            _ => {
+                return Err(format!(
+                    "Unknown format '{text}'. Expected text, json or oneline"
+                ))
+            }
+        }
+    }
+}
+
+/// Writes the result of a check in one format
+pub trait FormatWriter {
+    /// The output for the entries of a check, ending in a new line
+    fn updates(&self, updates: &[ParsedUpdate]) -> String;
+
+    /// The output when checking failed, if the format shows errors itself.
+    /// None to fail with the error instead
+    fn error(&self, _error: &Error) -> Option<String> {
+        return None;
+    }
+}
+
+/// The writer for `format`
+pub fn writer_for(format: Format) -> Box<dyn FormatWriter> {
+    match format {
+        Format::Text => return Box::new(TextWriter),
+        Format::Json => return Box::new(JsonWriter),
+        Format::Oneline => return Box::new(OnelineWriter),
+    }
+}
+
+// Like `stable-x86_64-unknown-linux-gnu: 1.80.0 -> 1.80.1`, or
+// `rustup: 1.27.1, up to date`
+struct TextWriter;
+
+impl FormatWriter for TextWriter {
+    fn updates(&self, updates: &[ParsedUpdate]) -> String {
+        if updates.is_empty() {
+            return "No toolchains found\n".to_string();
+        }
+
+        let version = |x: &Option<_>| match x {
+            Some(x) => return format!("{x}"),
+            None => return "unknown version".to_string(),
+        };
+
+        return updates
+            .iter()
+            .map(|x| match x.new {
+                Some(new) => return format!("{}: {} -> {new}\n", x.name, version(&x.current)),
+                None => return format!("{}: {}, up to date\n", x.name, version(&x.current)),
+            })
+            .collect();
+    }
+}
+
+// Like `{"updates":[{"current":"1.80.0","name":"stable-x86_64-unknown-linux-gnu","new":"1.80.1"}]}`
+struct JsonWriter;
+
+impl FormatWriter for JsonWriter {
+    fn updates(&self, updates: &[ParsedUpdate]) -> String {
+        let status = json::Value::Object(BTreeMap::from([(
+            "updates".to_string(),
+            report::update_entries(updates),
+        )]));
+
+        return format!("{status}\n");
+    }
+}
+
+// Like `rust: 1 update (stable 1.80.0→1.80.1)`. Errors are in the line too,
+// so a status bar always has something to show
+struct OnelineWriter;
+
+impl FormatWriter for OnelineWriter {
+    fn updates(&self, updates: &[ParsedUpdate]) -> String {
+        return format!("{}\n", report::oneline_summary(updates));
+    }
+
+    fn error(&self, error: &Error) -> Option<String> {
+        return Some(format!("rust: couldn't check ({error})\n"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_rustup_check;
+
+    #[test]
+    fn formats() {
+        let updates = parse_rustup_check(&[
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1".to_string(),
+            "rustup - Up to date : 1.27.1".to_string(),
+        ]);
+        let write = |format: &str| writer_for(format.parse().unwrap()).updates(&updates);
+
+        assert_eq!(
+            write("text"),
+            "stable-x86_64-unknown-linux-gnu: 1.80.0 -> 1.80.1\nrustup: 1.27.1, up to date\n"
+        );
+        assert_eq!(
+            write("json"),
+            "{\"updates\":[\
+             {\"current\":\"1.80.0\",\"name\":\"stable-x86_64-unknown-linux-gnu\",\"new\":\"1.80.1\"},\
+             {\"current\":\"1.27.1\",\"name\":\"rustup\",\"new\":null}]}\n"
+        );
+        assert_eq!(write("oneline"), "rust: 1 update (stable 1.80.0→1.80.1)\n");
+
+        assert_eq!(Format::default(), Format::Text);
+        assert!("yaml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn errors() {
+        // Only oneline shows them itself
+        let error = Error::NoInternet;
+        assert_eq!(
+            writer_for(Format::Oneline).error(&error).unwrap(),
+            "rust: couldn't check (Failed to download file. Check internet connection)\n"
+        );
+        assert!(writer_for(Format::Text).error(&error).is_none());
+        assert!(writer_for(Format::Json).error(&error).is_none());
+
+        assert_eq!(
+            writer_for(Format::Text).updates(&[]),
+            "No toolchains found\n"
+        );
+    }
+}
@@ -0,0 +1,86 @@
+// Finds the home directory, which the files kept between runs are in
+
+use std::{env, ffi, io, path};
+
+/// The home directory, given the value of `HOME`
+///
+/// Errors if it's not set, empty, or relative. Those would put files
+/// somewhere surprising, relative to the current directory
+pub fn home_dir_from(home: Option<ffi::OsString>) -> io::Result<path::PathBuf> {
+    let home = home.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME isn't set"))?;
+
+    if home.is_empty() {
+        return io::Result::Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "HOME is empty. Set it to the home directory",
+        ));
+    }
+
+    let home = path::PathBuf::from(home);
+    if !home.is_absolute() {
+        return io::Result::Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "HOME is '{}', which isn't an absolute path. Set it to the home directory",
+                home.display()
+            ),
+        ));
+    }
+
+    return io::Result::Ok(home);
+}
+
+/// The home directory, from `HOME`
+pub fn home_dir() -> io::Result<path::PathBuf> {
+    return home_dir_from(env::var_os("HOME"));
+}
+
+/// `relative` in the home directory, given the value of `HOME`. Errors
+/// like `home_dir_from`
+pub fn home_path_from(home: Option<ffi::OsString>, relative: &str) -> io::Result<path::PathBuf> {
+    return io::Result::Ok(home_dir_from(home)?.join(relative));
+}
+
+/// `relative` in the home directory
+///
+/// Every path in the home directory is found with this, so a bad `HOME` is
+/// always the same error
+pub fn home_path(relative: &str) -> io::Result<path::PathBuf> {
+    return home_path_from(env::var_os("HOME"), relative);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn home_dirs() {
+        assert_eq!(
+            home_dir_from(Some("/home/daisy".into())).unwrap(),
+            path::PathBuf::from("/home/daisy")
+        );
+
+        let error = home_dir_from(None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+        // The same error for every path, ending the program the same way
+        let error = home_path_from(None, ".cargo/bin/rustup").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(error.to_string(), "HOME isn't set");
+        let error = crate::Error::from(error);
+        assert_eq!(error.to_string(), "HOME isn't set");
+        assert_eq!(error.exit_code(), 3);
+        assert_eq!(
+            home_path_from(Some("/home/daisy".into()), ".rustup/donotupdate").unwrap(),
+            path::PathBuf::from("/home/daisy/.rustup/donotupdate")
+        );
+
+        let error = home_dir_from(Some("".into())).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("HOME is empty"));
+
+        let error = home_dir_from(Some("relative/path".into())).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("'relative/path'"));
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    os::unix,
+    path,
+};
+
+use crate::home::home_path;
+
+// Where the binary is linked to, relative to the home directory
+const INSTALL_PATH: &str = ".local/bin/auto_rustup_update";
+
+// Shell profile run on login, relative to the home directory
+const PROFILE_PATH: &str = ".profile";
+
+// Lines added to the shell profile. The invocation line is what's checked
+// for, so it's never added twice
+const PROFILE_COMMENT: &str = "# Added by auto_rustup_update --install";
+const PROFILE_INVOCATION: &str = "\"$HOME/.local/bin/auto_rustup_update\" >/dev/null 2>&1 &";
+
+/// Adds the invocation of this program to the shell profile at `profile`,
+/// creating the profile if needed
+///
+/// Returns false without changing anything if it's already there
+pub fn append_to_profile(profile: &path::Path) -> io::Result<bool> {
+    let content = match fs::read_to_string(profile) {
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+        result => result?,
+    };
+
+    if content.lines().any(|x| x.trim() == PROFILE_INVOCATION) {
+        return io::Result::Ok(false);
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(profile)?;
+
+    // Don't join onto the end of a last line without a new line
+    if !content.is_empty() && !content.ends_with('\n') {
+        writeln!(file)?;
+    }
+    writeln!(file, "{PROFILE_COMMENT}\n{PROFILE_INVOCATION}")?;
+
+    return io::Result::Ok(true);
+}
+
+/// Removes the lines added by `append_to_profile` from the shell profile
+/// at `profile`
+///
+/// Returns false if they weren't there
+pub fn remove_from_profile(profile: &path::Path) -> io::Result<bool> {
+    let content = match fs::read_to_string(profile) {
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(false)
+        }
+        result => result?,
+    };
+
+    let kept: Vec<&str> = content
+        .lines()
+        .filter(|x| x.trim() != PROFILE_COMMENT && x.trim() != PROFILE_INVOCATION)
+        .collect();
+
+    if kept.len() == content.lines().count() {
+        return io::Result::Ok(false);
+    }
+
+    let mut new_content = kept.join("\n");
+    if !new_content.is_empty() {
+        new_content.push('\n');
+    }
+    fs::write(profile, new_content)?;
+
+    return io::Result::Ok(true);
+}
+
+// Asks a yes or no question on the terminal. Anything but yes is no
+fn confirm(question: &str) -> io::Result<bool> {
+    print!("{question} [y/N] ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    return io::Result::Ok(matches!(answer.trim(), "y" | "Y" | "yes"));
+}
+
+/// Links the running binary into `~/.local/bin`, and asks whether to also
+/// run it on login from `~/.profile`
+///
+/// Running it again is safe. An old link is replaced, and the profile is
+/// never added to twice
+pub fn install() -> io::Result<()> {
+    let executable = env::current_exe()?;
+    let link = home_path(INSTALL_PATH)?;
+
+    if let Some(parent) = link.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match fs::symlink_metadata(&link) {
+        io::Result::Ok(metadata) if metadata.file_type().is_symlink() => fs::remove_file(&link)?,
+        io::Result::Ok(_) => {
+            return io::Result::Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} exists and isn't a link. Not replacing it",
+                    link.display()
+                ),
+            ))
+        }
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        io::Result::Err(error) => return io::Result::Err(error),
+    }
+
+    unix::fs::symlink(&executable, &link)?;
+    println!("Linked {} to {}", link.display(), executable.display());
+
+    let profile = home_path(PROFILE_PATH)?;
+    let question = format!(
+        "Also check for updates when you log in, by adding to {}?",
+        profile.display()
+    );
+
+    if confirm(&question)? {
+        if append_to_profile(&profile)? {
+            println!("Added to {}", profile.display());
+        } else {
+            println!("Already in {}", profile.display());
+        }
+    }
+
+    return io::Result::Ok(());
+}
+
+/// Undoes `install`, removing the link and the lines added to the profile
+pub fn uninstall() -> io::Result<()> {
+    let link = home_path(INSTALL_PATH)?;
+
+    match fs::symlink_metadata(&link) {
+        io::Result::Ok(metadata) if metadata.file_type().is_symlink() => {
+            fs::remove_file(&link)?;
+            println!("Removed {}", link.display());
+        }
+        io::Result::Ok(_) => println!("{} isn't a link. Leaving it", link.display()),
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        io::Result::Err(error) => return io::Result::Err(error),
+    }
+
+    let profile = home_path(PROFILE_PATH)?;
+    if remove_from_profile(&profile)? {
+        println!("Removed from {}", profile.display());
+    }
+
+    return io::Result::Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn append_is_idempotent() {
+        let profile = temp_dir("install_append").join(".profile");
+        fs::write(&profile, "export EDITOR=vim").unwrap();
+
+        assert!(append_to_profile(&profile).unwrap());
+        assert!(!append_to_profile(&profile).unwrap());
+
+        assert_eq!(
+            fs::read_to_string(&profile).unwrap(),
+            format!("export EDITOR=vim\n{PROFILE_COMMENT}\n{PROFILE_INVOCATION}\n")
+        );
+    }
+
+    #[test]
+    fn append_creates_profile() {
+        let profile = temp_dir("install_create").join(".profile");
+
+        assert!(append_to_profile(&profile).unwrap());
+        assert_eq!(
+            fs::read_to_string(&profile).unwrap(),
+            format!("{PROFILE_COMMENT}\n{PROFILE_INVOCATION}\n")
+        );
+    }
+
+    #[test]
+    fn remove_undoes_append() {
+        let profile = temp_dir("install_remove").join(".profile");
+        fs::write(&profile, "export EDITOR=vim\n").unwrap();
+
+        assert!(!remove_from_profile(&profile).unwrap());
+
+        append_to_profile(&profile).unwrap();
+        assert!(remove_from_profile(&profile).unwrap());
+
+        assert_eq!(fs::read_to_string(&profile).unwrap(), "export EDITOR=vim\n");
+    }
+}
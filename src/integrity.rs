@@ -0,0 +1,92 @@
+// Checks the rustup binary is one that can be trusted to run, rather than
+// whatever is at the path
+
+use std::{env, fs, io, os::unix::fs::MetadataExt, path};
+
+use crate::home;
+
+/// Where rustup is expected to be installed: `CARGO_HOME`, or `~/.cargo`
+pub fn default_rustup_prefix() -> io::Result<path::PathBuf> {
+    match env::var_os("CARGO_HOME").filter(|x| !x.is_empty()) {
+        Some(cargo_home) => return io::Result::Ok(path::PathBuf::from(cargo_home)),
+        None => return home::home_path(".cargo"),
+    }
+}
+
+/// Errors if `rustup` isn't safe to run
+///
+/// It has to be under `prefix` once symlinks are followed, be owned by the
+/// user `uid`, and not be writable by everyone. Any of those could let
+/// someone else choose what runs
+pub fn verify_rustup(rustup: &path::Path, prefix: &path::Path, uid: u32) -> io::Result<()> {
+    let refuse = |reason: String| {
+        return io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Refusing to run {}: {reason}", rustup.display()),
+        );
+    };
+
+    let resolved = fs::canonicalize(rustup)?;
+    let prefix = fs::canonicalize(prefix)
+        .map_err(|error| refuse(format!("can't find {}: {error}", prefix.display())))?;
+    if !resolved.starts_with(&prefix) {
+        return io::Result::Err(refuse(format!(
+            "it's at {}, which isn't under {}",
+            resolved.display(),
+            prefix.display()
+        )));
+    }
+
+    let metadata = fs::metadata(&resolved)?;
+    if metadata.uid() != uid {
+        return io::Result::Err(refuse(format!(
+            "it's owned by user {}, not {uid}",
+            metadata.uid()
+        )));
+    }
+    if metadata.mode() & 0o002 != 0 {
+        return io::Result::Err(refuse("anyone can write to it".to_string()));
+    }
+
+    return io::Result::Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{root::effective_uid, test_util::temp_dir};
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    #[test]
+    fn rustup_integrity() {
+        let dir = temp_dir("rustup_integrity");
+        let bin = dir.join("cargo/bin");
+        fs::create_dir_all(&bin).unwrap();
+        let rustup = bin.join("rustup");
+        fs::write(&rustup, "").unwrap();
+        fs::set_permissions(&rustup, fs::Permissions::from_mode(0o755)).unwrap();
+        let prefix = dir.join("cargo");
+        let uid = effective_uid().unwrap();
+
+        assert!(verify_rustup(&rustup, &prefix, uid).is_ok());
+
+        // Someone else's
+        let error = verify_rustup(&rustup, &prefix, uid + 1).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(error.to_string().contains("owned by user"));
+
+        // Outside the prefix, including through a symlink
+        assert!(verify_rustup(&rustup, &dir.join("elsewhere"), uid).is_err());
+        let other = dir.join("rustup");
+        fs::write(&other, "").unwrap();
+        let link = bin.join("rustup-link");
+        symlink(&other, &link).unwrap();
+        let error = verify_rustup(&link, &prefix, uid).unwrap_err();
+        assert!(error.to_string().contains("isn't under"));
+
+        fs::set_permissions(&rustup, fs::Permissions::from_mode(0o777)).unwrap();
+        let error = verify_rustup(&rustup, &prefix, uid).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(error.to_string().contains("anyone can write"));
+    }
+}
@@ -0,0 +1,337 @@
+//! A small JSON reader, for machine readable output from rustup, and
+//! writer, for reports
+//!
+//! Numbers are read as `f64`. Objects keep their keys sorted, so the order
+//! of keys in the document is lost
+
+use std::{collections::BTreeMap, fmt, iter::Peekable, str::Chars};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(x) => return Some(x),
+            _ => return None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(x) => return Some(x),
+            _ => return None,
+        }
+    }
+
+    /// Value of `key`, if this is an object with that key
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(x) => return x.get(key),
+            _ => return None,
+        }
+    }
+}
+
+// Writes `string` quoted, escaping what JSON requires
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for x in string.chars() {
+        match x {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            x if x.is_control() => write!(f, "\\u{:04x}", x as u32)?,
+            x => write!(f, "{x}")?,
+        }
+    }
+
+    return write!(f, "\"");
+}
+
+/// Writes the value as a compact JSON document
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => return write!(f, "null"),
+            Value::Boolean(x) => return write!(f, "{x}"),
+            Value::Number(x) => return write!(f, "{x}"),
+            Value::String(x) => return write_string(f, x),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+
+                return write!(f, "]");
+            }
+            Value::Object(object) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in object.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+
+                return write!(f, "}}");
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|x| x.is_ascii_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+
+        match self.chars.next() {
+            Some(x) if x == expected => return Ok(()),
+            Some(x) => return Err(format!("Expected '{expected}', found '{x}'")),
+            None => return Err(format!("Expected '{expected}', found the end")),
+        }
+    }
+
+    // Reads `word` after its first letter was already read
+    fn rest_of_word(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars().skip(1) {
+            if self.chars.next() != Some(expected) {
+                return Err(format!("Expected '{word}'"));
+            }
+        }
+
+        return Ok(value);
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+
+        match self.chars.peek().copied() {
+            Some('{') => return self.parse_object(),
+            Some('[') => return self.parse_array(),
+            Some('"') => return Ok(Value::String(self.parse_string()?)),
+            Some('t') => {
+                self.chars.next();
+                return self.rest_of_word("true", Value::Boolean(true));
+            }
+            Some('f') => {
+                self.chars.next();
+                return self.rest_of_word("false", Value::Boolean(false));
+            }
+            Some('n') => {
+                self.chars.next();
+                return self.rest_of_word("null", Value::Null);
+            }
+            Some(x) if x == '-' || x.is_ascii_digit() => return self.parse_number(),
+            Some(x) => return Err(format!("Unexpected '{x}'")),
+            None => return Err("Expected a value, found the end".to_string()),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Value, String> {
+        let mut number = String::new();
+        while let Some(x) = self
+            .chars
+            .next_if(|x| x.is_ascii_digit() || matches!(x, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            number.push(x);
+        }
+
+        return number
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| format!("Malformed number '{number}'"));
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+
+        let mut string = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.parse_escape()?),
+                Some(x) => string.push(x),
+                None => return Err("Unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<char, String> {
+        match self.chars.next() {
+            Some('"') => return Ok('"'),
+            Some('\\') => return Ok('\\'),
+            Some('/') => return Ok('/'),
+            Some('b') => return Ok('\u{8}'),
+            Some('f') => return Ok('\u{c}'),
+            Some('n') => return Ok('\n'),
+            Some('r') => return Ok('\r'),
+            Some('t') => return Ok('\t'),
+            Some('u') => {
+                let high = self.parse_hex()?;
+
+                // Characters outside the BMP are written as two escapes
+                if (0xD800..0xDC00).contains(&high) {
+                    if self.chars.next() != Some('\\') || self.chars.next() != Some('u') {
+                        return Err("Unpaired surrogate in string".to_string());
+                    }
+                    let low = self.parse_hex()?;
+                    let code = 0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00));
+
+                    return char::from_u32(code).ok_or("Malformed surrogate pair".to_string());
+                }
+
+                return char::from_u32(high).ok_or(format!("Malformed escape '\\u{high:04x}'"));
+            }
+            Some(x) => return Err(format!("Unknown escape '\\{x}'")),
+            None => return Err("Unterminated string".to_string()),
+        }
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, String> {
+        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+
+        return u32::from_str_radix(&hex, 16).map_err(|_| format!("Malformed escape '\\u{hex}'"));
+    }
+
+    fn parse_array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Value::Array(items));
+        }
+
+        loop {
+            items.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return Err("Expected ',' or ']' in array".to_string()),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+
+        let mut object = BTreeMap::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Value::Object(object));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            object.insert(key, self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Value::Object(object)),
+                _ => return Err("Expected ',' or '}' in object".to_string()),
+            }
+        }
+    }
+}
+
+/// Parses a JSON document
+pub fn parse(document: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: document.chars().peekable(),
+    };
+
+    let value = parser.parse_value()?;
+
+    parser.skip_whitespace();
+    if let Some(x) = parser.chars.next() {
+        return Err(format!("Unexpected '{x}' after the end of the document"));
+    }
+
+    return Ok(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let value = parse(
+            r#"{
+                "name": "stable",
+                "list": [1, -2.5, 1e3, true, false, null],
+                "empty": {},
+                "none": []
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(value.get("name").and_then(|x| x.as_str()), Some("stable"));
+        assert_eq!(
+            value.get("list").and_then(|x| x.as_array()).unwrap(),
+            [
+                Value::Number(1.0),
+                Value::Number(-2.5),
+                Value::Number(1000.0),
+                Value::Boolean(true),
+                Value::Boolean(false),
+                Value::Null,
+            ]
+        );
+        assert_eq!(value.get("empty"), Some(&Value::Object(BTreeMap::new())));
+        assert_eq!(value.get("none"), Some(&Value::Array(Vec::new())));
+        assert_eq!(value.get("missing"), None);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            parse(r#""a\"b\\c\n\u00e9\ud83e\udd80""#).unwrap(),
+            Value::String("a\"b\\c\né🦀".to_string())
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let document = r#"{"list":[1,-2.5,true,null],"name":"a\"b\\c\n\u0001é"}"#;
+
+        assert_eq!(parse(document).unwrap().to_string(), document);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse("").is_err());
+        assert!(parse("{\"a\": 1").is_err());
+        assert!(parse("[1, 2,]").is_err());
+        assert!(parse("\"unterminated").is_err());
+        assert!(parse("tru").is_err());
+        assert!(parse("{} {}").is_err());
+        assert!(parse("info: checking for updates").is_err());
+    }
+}
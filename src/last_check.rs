@@ -0,0 +1,132 @@
+use std::{fs, io, path};
+
+use crate::state_file;
+
+/// Shortest time between two runs of `rustup check`, unless forced
+pub const DEFAULT_CHECK_INTERVAL: u64 = 60 * 60;
+
+/// The result of the last `rustup check`, saved so running again soon
+/// after doesn't check again
+///
+/// This is separate from the snooze, which stops prompts. This stops
+/// needless network requests, however the program was started
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastCheck {
+    /// Seconds since the unix epoch
+    pub checked_at: u64,
+
+    /// Lines printed by `rustup check`
+    pub lines: Vec<String>,
+}
+
+impl LastCheck {
+    /// True if the check at `checked_at` was less than `interval` seconds
+    /// before `now`
+    ///
+    /// A check in the future, from the clock changing, isn't fresh
+    pub fn is_fresh(&self, now: u64, interval: u64) -> bool {
+        return now >= self.checked_at && now - self.checked_at < interval;
+    }
+
+    // File is made of `key=value` lines, with a `line` for each line of
+    // `rustup check`
+    fn parse(content: &str) -> Option<LastCheck> {
+        let mut checked_at = None;
+        let mut lines = Vec::new();
+
+        for (key, value) in state_file::key_values(content) {
+            match key {
+                "checked_at" => checked_at = value.trim().parse().ok(),
+                "line" => lines.push(value.to_string()),
+                _ => {}
+            }
+        }
+
+        return Some(LastCheck {
+            checked_at: checked_at?,
+            lines,
+        });
+    }
+
+    fn serialize(&self) -> String {
+        let mut content = format!("checked_at={}\n", self.checked_at);
+        for line in &self.lines {
+            content.push_str(&format!("line={line}\n"));
+        }
+
+        return content;
+    }
+}
+
+/// Reads the last check saved at `path`
+///
+/// A missing or unreadable file is treated as never having checked
+pub fn read_last_check(path: &path::Path) -> io::Result<Option<LastCheck>> {
+    match fs::read_to_string(path) {
+        io::Result::Ok(content) => return io::Result::Ok(LastCheck::parse(&content)),
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(None)
+        }
+        io::Result::Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+            return io::Result::Ok(None)
+        }
+        io::Result::Err(error) => return io::Result::Err(error),
+    }
+}
+
+/// Saves the check at `path`
+pub fn write_last_check(path: &path::Path, last_check: &LastCheck) -> io::Result<()> {
+    return state_file::write_atomic(path, &last_check.serialize());
+}
+
+/// Forgets the last check, so the next run checks again. Not an error if
+/// there wasn't one
+pub fn remove_last_check(path: &path::Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(())
+        }
+        result => return result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn freshness() {
+        let last_check = LastCheck {
+            checked_at: 1000,
+            lines: Vec::new(),
+        };
+
+        assert!(last_check.is_fresh(1000, 60));
+        assert!(last_check.is_fresh(1059, 60));
+        assert!(!last_check.is_fresh(1060, 60));
+        assert!(!last_check.is_fresh(999, 60));
+        assert!(!last_check.is_fresh(1000, 0));
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_dir("last_check_round_trip").join("last_check");
+
+        assert_eq!(read_last_check(&path).unwrap(), None);
+
+        let last_check = LastCheck {
+            checked_at: 1000,
+            lines: vec![
+                "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1".to_string(),
+                "rustup - Up to date : 1.27.1".to_string(),
+            ],
+        };
+        write_last_check(&path, &last_check).unwrap();
+        assert_eq!(read_last_check(&path).unwrap(), Some(last_check));
+
+        remove_last_check(&path).unwrap();
+        remove_last_check(&path).unwrap();
+        assert_eq!(read_last_check(&path).unwrap(), None);
+    }
+}
@@ -1,159 +1,648 @@
 // Clippy configurations
 #![allow(clippy::needless_return)]
+#![cfg_attr(
+    test,
+    allow(clippy::assertions_on_constants, clippy::bool_assert_comparison)
+)]
 
-use std::{collections::HashMap, env, fs, io, os::linux::fs::MetadataExt, path, process, time};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, io, path, time,
+};
 
 use regex::Regex;
 
+mod age;
+mod aggregate;
+mod answer;
+mod budget;
+mod check_flags;
+pub mod cli;
+mod completion;
+mod components;
+mod config;
+mod diagnose;
+mod dist;
+mod error;
+mod format;
+mod home;
+mod install;
+mod integrity;
+mod json;
+mod last_check;
+mod log;
+mod metrics;
+mod network;
+mod next_boot;
+mod notify;
+mod parse;
+mod pending;
+mod policy;
+mod power;
+mod prompt;
+mod report;
+mod root;
+mod runner;
+mod seen;
+mod settings;
+mod snooze;
+mod state_file;
+mod stats;
+mod style;
+mod terminal;
+#[cfg(test)]
+mod test_util;
+mod toml;
+mod version;
+mod watch;
+
+pub use answer::{apply_answer, AnswerActions};
+pub use components::{versions_match, ComponentMismatch};
+pub use config::{Config, ConfigBuilder};
+pub use dist::{format_size, manifest_date, manifest_download_url, manifest_rust_version};
+pub use error::Error;
+pub use format::{writer_for, Format, FormatWriter};
+pub use install::{install, uninstall};
+pub use notify::{NotifyStyle, NotifyUrgency};
+pub use parse::{parse_rustup_check, parse_rustup_check_line, Build, Channel, ParsedUpdate};
+pub use power::on_battery;
+pub use prompt::{
+    backend_for, FakeBackend, FallbackBackend, NoPromptAction, NotifyBackend, Prompt,
+    PromptBackend, PromptKind, SocketBackend, TimeoutAction, TtyBackend, UpdatePromptAnswer,
+    ZenityBackend,
+};
+pub use root::RootPolicy;
+pub use runner::{CommandOutput, CommandRunner, CommandSpec, MockRunner, SystemRunner};
+pub use snooze::SnoozeStatus;
+pub use style::{color_enabled, paint, Color, Stream};
+pub use terminal::{Terminal, TERMINALS};
+pub use version::Version;
+
+use completion::{Completion, ScriptState};
+use root::{Elevation, RootCheck};
+use snooze::{SnoozeFlag, NO_UPDATE_FLAG_DELAY};
+
 // Path relative to the home path of no-update flag
 const RUSTUP_FLAG_PATH: &str = ".rustup/donotupdate";
 const RUSTUP_BIN_PATH: &str = ".cargo/bin/rustup";
+const RUSTUP_LAST_CHECK_PATH: &str = ".rustup/auto_rustup_update_last_check";
+const RUSTUP_PENDING_PROMPT_PATH: &str = ".rustup/auto_rustup_update_pending_prompt";
+const RUSTUP_SEEN_VERSIONS_PATH: &str = ".rustup/auto_rustup_update_seen_versions";
+const RUSTUP_NEXT_BOOT_PATH: &str = ".rustup/auto_rustup_update_next_boot";
+const RUSTUP_STATS_PATH: &str = ".rustup/auto_rustup_update_stats";
 
-// Time taken between writing the no-update flag and
-const NO_UPDATE_FLAG_DELAY: u64 = 60 * 60 * 24;
+// Path relative to the home path of the count of prompts shown today
+const RUSTUP_PROMPTS_PATH: &str = ".rustup/auto_rustup_update_prompts";
 
-// Gets the path to the flag used to set if it should update
-fn get_flag_filepath() -> path::PathBuf {
-    let mut path = path::PathBuf::new();
-    path.push(env::var("HOME").expect("HOME env variable not set!"));
-    path.push(RUSTUP_FLAG_PATH);
+// Path relative to the home path of the flags rustup check was found to know
+const RUSTUP_CHECK_FLAGS_PATH: &str = ".rustup/auto_rustup_update_check_flags";
 
-    return path;
+// How rustup.rs says to install rustup
+const RUSTUP_INSTALL_COMMAND: &str =
+    "curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | sh";
+
+// Path to rustup in the home directory
+fn get_rustup_filepath() -> io::Result<path::PathBuf> {
+    return home::home_path(RUSTUP_BIN_PATH);
+}
+
+// rustup set in the config, or the usual one
+fn rustup_filepath(config: &Config) -> io::Result<path::PathBuf> {
+    match &config.rustup_path {
+        Some(path) => return io::Result::Ok(path.clone()),
+        None => return get_rustup_filepath(),
+    }
 }
 
-fn get_rustup_filepath() -> path::PathBuf {
-    let mut path = path::PathBuf::new();
-    path.push(env::var("HOME").expect("HOME env variable not set!"));
-    path.push(RUSTUP_BIN_PATH);
+// With `verify_rustup`, errors if `rustup` could have been swapped out by
+// someone else
+fn check_rustup_integrity(config: &Config, rustup: &path::Path) -> Result<(), Error> {
+    if !config.verify_rustup {
+        return Ok(());
+    }
+
+    let prefix = match &config.rustup_prefix {
+        Some(prefix) => prefix.clone(),
+        None => integrity::default_rustup_prefix()?,
+    };
+    integrity::verify_rustup(rustup, &prefix, root::effective_uid()?)?;
 
-    return path;
+    return Ok(());
 }
 
-fn read_no_update_flag() -> Option<i64> {
-    let path = get_flag_filepath();
+/// Files kept between runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatePaths {
+    /// The no-update flag
+    pub flag: path::PathBuf,
 
-    match fs::File::open(path) {
-        io::Result::Err(error) => {
-            if error.kind() == io::ErrorKind::NotFound {
-                return None;
-            }
-        }
+    /// The saved result of the last `rustup check`
+    pub last_check: path::PathBuf,
+
+    /// The prompt queued for the next run that can show it
+    pub pending_prompt: path::PathBuf,
+
+    /// The versions seen by the last run, to tell what changed since
+    pub seen_versions: path::PathBuf,
+
+    /// An update put off until the next boot
+    pub next_boot: path::PathBuf,
 
-        io::Result::Ok(file) => return Some(file.metadata().unwrap().st_mtime()),
+    /// How many updates worked and failed, with `keep_stats`
+    pub stats: path::PathBuf,
+
+    /// How many prompts were shown today, with `daily_prompt_budget`
+    pub prompts: path::PathBuf,
+
+    /// Which flags of `rustup check` the rustup was found not to know
+    pub check_flags: path::PathBuf,
+
+    /// rustup's home directory, with the `settings.toml` giving the default
+    /// toolchain
+    pub rustup_home: path::PathBuf,
+}
+
+impl StatePaths {
+    /// The usual paths, in the home directory
+    ///
+    /// Errors if `HOME` isn't set, or isn't an absolute path
+    pub fn from_home() -> io::Result<StatePaths> {
+        return io::Result::Ok(StatePaths {
+            flag: home::home_path(RUSTUP_FLAG_PATH)?,
+            last_check: home::home_path(RUSTUP_LAST_CHECK_PATH)?,
+            pending_prompt: home::home_path(RUSTUP_PENDING_PROMPT_PATH)?,
+            seen_versions: home::home_path(RUSTUP_SEEN_VERSIONS_PATH)?,
+            next_boot: home::home_path(RUSTUP_NEXT_BOOT_PATH)?,
+            stats: home::home_path(RUSTUP_STATS_PATH)?,
+            prompts: home::home_path(RUSTUP_PROMPTS_PATH)?,
+            check_flags: home::home_path(RUSTUP_CHECK_FLAGS_PATH)?,
+            rustup_home: settings::rustup_home()
+                .map_or_else(|| home::home_path(".rustup"), io::Result::Ok)?,
+        });
+    }
+
+    /// The usual paths, other than any set in `config`
+    pub fn for_config(config: &Config) -> io::Result<StatePaths> {
+        let paths = StatePaths::from_home()?;
+
+        return io::Result::Ok(StatePaths {
+            flag: config.flag_path.clone().unwrap_or(paths.flag.clone()),
+            ..paths
+        });
     }
 
-    return None;
+    /// Paths in the directory `dir`, to keep state away from the usual
+    /// files, like in tests
+    pub fn in_dir(dir: &path::Path) -> StatePaths {
+        return StatePaths {
+            flag: dir.join("donotupdate"),
+            last_check: dir.join("last_check"),
+            pending_prompt: dir.join("pending_prompt"),
+            seen_versions: dir.join("seen_versions"),
+            next_boot: dir.join("next_boot"),
+            stats: dir.join("stats"),
+            prompts: dir.join("prompts"),
+            check_flags: dir.join("check_flags"),
+            rustup_home: dir.join("rustup_home"),
+        };
+    }
+}
+
+// Seconds since the unix epoch
+fn unix_now() -> u64 {
+    return time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .expect("Couldn't compare now to unix epoch")
+        .as_secs();
 }
 
 /// Sets the no update flag
 ///
-/// If the argument is true, then set the creation time of the no update
-/// flag is updated, or the flag is created
+/// If the argument is true, then the no update flag is written, snoozing
+/// prompts for a day from now
 ///
 /// Else, then the flag is deleted
 ///
 /// Program doesn't prompt for update if the no-update flag is set less
 /// then a day ago
-fn set_no_update_flag(write_new_flag: bool) -> io::Result<()> {
-    let path = get_flag_filepath();
+fn set_no_update_flag(path: &path::Path, write_new_flag: bool) -> io::Result<()> {
+    if write_new_flag {
+        return snooze::write_flag(path, &SnoozeFlag::new(unix_now(), NO_UPDATE_FLAG_DELAY));
+    }
+
+    return snooze::remove_flag(path);
+}
+
+// Describes the pending updates, so a later snooze can tell if they changed
+fn versions_key(new_versions: &NewVersions) -> String {
+    let mut versions: Vec<String> = new_versions
+        .iter()
+        .filter_map(|(name, version)| version.as_ref().map(|x| format!("{name}={x}")))
+        .collect();
+    versions.sort();
+
+    return versions.join(",");
+}
+
+/// Snoozes the prompt for `new_versions`
+///
+/// Each snooze of the same versions in a row is twice as long as the last,
+/// up to a week. A new version starts again from `base` seconds
+fn snooze_updates(path: &path::Path, new_versions: &NewVersions, base: u64) -> io::Result<()> {
+    let previous = snooze::read_flag(path).unwrap_or(None);
+    let flag = SnoozeFlag::next(
+        previous.as_ref(),
+        &versions_key(new_versions),
+        unix_now(),
+        base,
+    );
+
+    println!(
+        "Snoozing for {}",
+        snooze::describe_duration(flag.until - flag.since)
+    );
+
+    return snooze::write_flag(path, &flag);
+}
+
+/// Checks for updates straight away, then every `interval` seconds, as a
+/// normal run would, until SIGTERM. Used by `--watch`
+pub fn watch(config: &Config, interval: u64) -> Result<(), Error> {
+    check_root(config)?;
+    watch::handle_sigterm();
+
+    // Each check is fresh, rather than the last check being reused
+    let config = Config {
+        check_interval: config.check_interval.min(interval),
+        ..config.clone()
+    };
+
+    println!(
+        "Checking for updates every {}",
+        snooze::describe_duration(interval)
+    );
+    let paths = StatePaths::for_config(&config)?;
+    let backend = backend_for(&config);
+    watch::watch_loop(interval, &watch::SystemSleeper, &mut || {
+        return auto_update_with(&config, &SystemRunner, &paths, backend.as_ref(), None);
+    });
+
+    return Ok(());
+}
+
+/// Puts off updating until the first run after the next boot. Used by
+/// `--on-next-boot`
+pub fn update_on_next_boot(config: &Config) -> io::Result<()> {
+    let intent = next_boot::BootIntent {
+        boot_id: next_boot::current_boot_id()?,
+        recorded_at: unix_now(),
+    };
+    next_boot::write_intent(&StatePaths::for_config(config)?.next_boot, &intent)?;
+
+    println!("Rust will be updated after the next boot");
+
+    return io::Result::Ok(());
+}
 
-    // Delete the flag
-    let result = fs::remove_file(&path);
-    if result.is_err() {
-        let err = result.err().unwrap();
-        match err.kind() {
-            io::ErrorKind::NotFound => {}
-            _ => return io::Result::Err(err),
+// With an update put off until the next boot, updates if this is a later
+// boot than `boot_id`, or otherwise waits. None if there isn't one
+fn run_boot_intent(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    boot_id: &str,
+) -> Result<Option<Outcome>, Error> {
+    if next_boot::rebooted_intent(&paths.next_boot, boot_id)?.is_some() {
+        println!("Updating, as asked for before the reboot");
+
+        // Kept if the update fails, so it's tried again on the next run
+        let outcome = update_now_with(config, runner, paths, &[], false)?;
+        match outcome {
+            Outcome::Updated | Outcome::UpdateStarted => {
+                next_boot::remove_intent(&paths.next_boot)?
+            }
+            _ => println!("The update will be tried again on the next run"),
         }
+
+        return Ok(Some(outcome));
     }
 
-    if write_new_flag {
-        fs::File::create(&path)?;
+    if paths.next_boot.exists() {
+        println!("An update is waiting for the next boot");
+
+        return Ok(Some(Outcome::WaitingForBoot));
     }
 
+    return Ok(None);
+}
+
+/// Snoozes the prompt for `duration` seconds from now, whatever updates
+/// there are. Used by `--snooze`
+pub fn snooze_for(config: &Config, duration: u64) -> io::Result<()> {
+    return snooze_for_at(&StatePaths::for_config(config)?.flag, duration);
+}
+
+fn snooze_for_at(path: &path::Path, duration: u64) -> io::Result<()> {
+    snooze::write_flag(path, &SnoozeFlag::new(unix_now(), duration))?;
+
+    println!("Snoozed for {}", snooze::describe_duration(duration));
+
     return io::Result::Ok(());
 }
 
-/// Returns if the program should prompt the user for an update
+/// Returns whether the user has snoozed the update prompt, and if so, until
+/// when. The no-update flag is at `Config::flag_path` if that's set
 ///
-/// Checks the reboot flag, and returns true if the flag doesn't exist, or
-/// is older than 1 day
-fn should_prompt() -> bool {
-    match read_no_update_flag() {
-        None => return true,
-        Some(write_time) => {
-            // Write time was before 1970, which probably means we should update?
-            if write_time < 0 {
-                return true;
-            }
+/// Errors if the no-update flag exists but can't be read
+pub fn snooze_status(config: &Config) -> io::Result<SnoozeStatus> {
+    return snooze::snooze_status_at(&StatePaths::for_config(config)?.flag, unix_now());
+}
+
+/// Returns if the program should prompt the user for an update at the time
+/// `now`, in seconds since the unix epoch
+///
+/// Checks the no-update flag at `flag_path`, and returns true if the flag
+/// doesn't exist, can't be read, or has expired
+pub fn should_prompt_at(flag_path: &path::Path, now: u64) -> bool {
+    match snooze::snooze_status_at(flag_path, now) {
+        io::Result::Ok(status) => return !status.is_active(),
+        io::Result::Err(_) => return true,
+    }
+}
+
+// Same as `should_prompt_at`, but now
+fn should_prompt(path: &path::Path) -> bool {
+    return should_prompt_at(path, unix_now());
+}
 
-            let now = time::SystemTime::now()
-                .duration_since(time::UNIX_EPOCH)
-                .expect("Couldn't compare now to unix epoch")
-                .as_secs();
-            let diff = now.checked_sub(write_time as u64);
+// True if rustup failed because it couldn't download from the dist server.
+// Only rustup's own error lines are looked at, as a shim can print anything
+// as a warning
+fn is_download_error(stderr: &str) -> bool {
+    return stderr
+        .lines()
+        .any(|x| x.trim_start().starts_with("error: could not download file"));
+}
 
-            if diff.is_none() {
-                // Creation time of the flag is in the apparent future... should update
-                return true;
-            }
+// True if rustup failed because it doesn't know `flag`, from what it
+// printed
+fn is_unsupported_flag(output: &str, flag: &str) -> bool {
+    return output.contains(&format!("'{flag}'"))
+        && [
+            "unexpected argument",
+            "wasn't expected",
+            "unrecognized",
+            "unknown",
+        ]
+        .iter()
+        .any(|x| output.contains(x));
+}
+
+// Directory to run `rustup check` in. An override for the current
+// directory, like a `rust-toolchain.toml`, only adds notes to what it
+// prints, so it's run from the home directory, or `/` without one
+fn check_dir() -> path::PathBuf {
+    return home::home_dir().unwrap_or_else(|_| path::PathBuf::from("/"));
+}
+
+// How long `rustup check` can take before it's killed. It's usually a few
+// seconds, so longer is a hung download
+const CHECK_TIMEOUT: time::Duration = time::Duration::from_secs(5 * 60);
+
+// Runs `rustup --quiet check`, asking for JSON first. Older versions of
+// rustup don't have `--json` or `--quiet`, so each is dropped if rustup
+// doesn't know it, and it's run again. What was dropped is saved for that
+// rustup at `flags_path`, if there is one, so the next check runs it once
+fn run_rustup_check(
+    runner: &dyn CommandRunner,
+    rustup: &path::Path,
+    env: &[(String, String)],
+    flags_path: Option<&path::Path>,
+) -> io::Result<CommandOutput> {
+    let rustup_path = rustup.to_string_lossy().to_string();
+    let dir = check_dir();
+    let saved = flags_path.and_then(|x| check_flags::read_flags(x, rustup));
+    let mut quiet = saved.as_ref().is_none_or(|x| x.quiet);
+    let mut json = saved.as_ref().is_none_or(|x| x.json);
+    let mut dropped = false;
+
+    loop {
+        // `--quiet` is for rustup itself, so goes before `check`
+        let mut args = Vec::new();
+        if quiet {
+            args.push("--quiet");
+        }
+        args.push("check");
+        if json {
+            args.push("--json");
+        }
+
+        // Colors are stripped anyway, but there's less to go wrong without
+        let command = CommandSpec::new(&rustup_path, &args)
+            .with_env(env)
+            .with_env(&[("RUSTUP_TERM_COLOR".to_string(), "never".to_string())])
+            .in_dir(&dir)
+            .with_timeout(CHECK_TIMEOUT);
+        let output = runner.run(&command)?;
 
-            let diff = diff.expect("Checked");
+        // rustup prints usage errors to stderr, but a wrapper around it can
+        // print them to stdout
+        let printed = [&output.stderr[..], &output.stdout[..]].concat();
+        let printed = style::strip_ansi(&String::from_utf8_lossy(&printed));
+        if !output.success() && json && is_unsupported_flag(&printed, "--json") {
+            json = false;
+        } else if !output.success() && quiet && is_unsupported_flag(&printed, "--quiet") {
+            quiet = false;
+        } else {
+            if let (true, Some(path), Some((modified, size))) =
+                (dropped, flags_path, check_flags::rustup_identity(rustup))
+            {
+                let flags = check_flags::CheckFlags {
+                    rustup: rustup_path,
+                    modified,
+                    size,
+                    json,
+                    quiet,
+                };
+                if let Err(error) = check_flags::write_flags(path, &flags) {
+                    log::debug(&format!("Couldn't save the flags rustup knows: {error}"));
+                }
+            }
 
-            return NO_UPDATE_FLAG_DELAY < diff;
+            return io::Result::Ok(output);
         }
+        dropped = true;
     }
 }
 
-/// Run the rustup check command, return a vector of the lines
-///
-/// Panics on the fail of the command
-fn get_rustup_check() -> Vec<String> {
-    let mut rustup_path =
-        path::PathBuf::from(env::var("HOME").expect("Home env variable not set!"));
-    rustup_path.push(RUSTUP_BIN_PATH);
+// True if `line` is one of rustup's log messages, like `info: syncing
+// channel updates`, rather than a toolchain. Some versions and settings
+// print them with the check
+fn is_diagnostic_line(line: &str) -> bool {
+    let line = line.trim_start();
 
-    let output = process::Command::new(get_rustup_filepath())
-        .arg("check")
-        .output();
+    return ["info:", "warn:", "warning:", "verbose:", "debug:", "trace:"]
+        .iter()
+        .any(|x| line.starts_with(x));
+}
+
+// Run the rustup check command, return a vector of the lines
+//
+// Errors if rustup can't be run, fails, or its output isn't UTF-8. Not
+// starting, from a bad path, is told apart from starting and not finishing
+//
+// The flags rustup doesn't know are saved at `flags_path`
+fn get_rustup_check(
+    runner: &dyn CommandRunner,
+    rustup: &path::Path,
+    env: &[(String, String)],
+    flags_path: Option<&path::Path>,
+) -> Result<Vec<String>, Error> {
+    let output = run_rustup_check(runner, rustup, env, flags_path);
 
-    if output.is_err() {
-        eprintln!("Failed to run rustup!");
+    let output = match output {
+        io::Result::Ok(output) => output,
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::RustupNotFound)
+        }
+        io::Result::Err(error) if error.kind() == io::ErrorKind::PermissionDenied => {
+            return Err(Error::RustupPermissionDenied)
+        }
+        io::Result::Err(error) if error.kind() == io::ErrorKind::TimedOut => {
+            return Err(Error::CheckTimeout {
+                seconds: CHECK_TIMEOUT.as_secs(),
+            })
+        }
+        io::Result::Err(error) => return Err(Error::Io(error)),
+    };
 
-        let err = output.expect_err("Checked if error");
+    // If it didn't run successfully. On success stderr is ignored, even if
+    // it looks like an error
+    if !output.success() {
+        // Only used for messages, so odd bytes don't matter
+        let stderr = String::from_utf8_lossy(&output.stderr);
 
-        if err.kind() == io::ErrorKind::NotFound {
-            panic!("Can't find rustup command. Who is this running as?\n")
+        if is_download_error(&stderr) {
+            return Err(Error::NoInternet);
         }
 
-        panic!("{:?}", err);
+        return Err(Error::RustupFailed {
+            status: output.status,
+            message: stderr.lines().next().unwrap_or("").trim().to_string(),
+        });
     }
 
-    let output = output.expect("Checked for error");
+    // Lines are trimmed, as wrappers can end them with CRLF, which would
+    // leave a '\r' on each. Colors are taken out, as a wrapper or a rustup
+    // set to always color would break matching the entries
+    let stdout: String = style::strip_ansi(&String::from_utf8(output.stdout)?);
+    let stdout: String = stdout
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !is_diagnostic_line(x))
+        .filter(|x| {
+            if parse::is_override_note(x) {
+                log::debug(&format!(
+                    "rustup check printed an override note: {}",
+                    x.trim()
+                ));
+                return false;
+            }
 
-    // If it didn't run successfully
-    if !output.status.success() {
-        let stderr: String =
-            String::from_utf8(output.stderr).expect("Failed utf8 decode for std error");
+            return true;
+        })
+        .map(|x| format!("{x}\n"))
+        .collect();
 
-        if stderr.contains("could not download file") {
-            panic!("Failed to download file. Check internet connection");
-        } else {
-            panic!("Unknown error in rustup command!");
+    // With nothing on stdout, the results could be on stderr. Only lines
+    // that are entries are taken, as it also has progress messages, like
+    // `info: syncing channel updates`
+    let stdout = if stdout.trim().is_empty() {
+        let stderr: String = style::strip_ansi(&String::from_utf8_lossy(&output.stderr))
+            .lines()
+            .map(|x| x.trim())
+            .filter(|x| !is_diagnostic_line(x) && parse::parse_rustup_check_line(x).is_some())
+            .map(|x| format!("{x}\n"))
+            .collect();
+        if !stderr.is_empty() {
+            log::debug("rustup check printed its results to stderr");
         }
-    }
 
-    let stdout: String = String::from_utf8(output.stdout).expect("failed utf8 decode for stdout");
+        stderr
+    } else {
+        stdout
+    };
+
+    // JSON output is turned into the same lines as the text
+    if stdout.trim_start().starts_with('{') {
+        return parse::json_check_lines(&stdout).map_err(|error| {
+            Error::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Malformed JSON from rustup check: {error}"),
+            ))
+        });
+    }
 
     // Split by new lines, filter out empty lines, and clone the lines and
     // collect them into a vector
-    return stdout
+    return Ok(stdout
         .split('\n')
         .filter(|x| !x.is_empty())
         .map(|x| x.to_string())
+        .collect());
+}
+
+// The lines of a check, without the one for rustup itself if the config
+// says to ignore it, like when it's managed by the distribution and always
+// says it has an update
+fn without_rustup_self(config: &Config, lines: Vec<String>) -> Vec<String> {
+    if !config.ignore_rustup_self {
+        return lines;
+    }
+
+    return lines
+        .into_iter()
+        .filter(|x| x.split_once(" - ").map(|x| x.0.trim()) != Some("rustup"))
         .collect();
 }
 
+// Same as `get_rustup_check`, but reuses the last check saved at
+// `last_check_path` if it was less than `interval` seconds before `now`.
+// Otherwise checks and saves the new result
+fn get_rustup_check_cached(
+    runner: &dyn CommandRunner,
+    last_check_path: &path::Path,
+    now: u64,
+    interval: u64,
+    rustup: &path::Path,
+    env: &[(String, String)],
+    flags_path: Option<&path::Path>,
+) -> Result<Vec<String>, Error> {
+    if let Some(last_check) = last_check::read_last_check(last_check_path)? {
+        if last_check.is_fresh(now, interval) {
+            // On stderr, to keep it out of `--oneline`
+            eprintln!(
+                "Checked {} minute(s) ago, using that result. Use --force to check again",
+                (now - last_check.checked_at) / 60
+            );
+
+            return Ok(last_check.lines);
+        }
+    }
+
+    let lines = get_rustup_check(runner, rustup, env, flags_path)?;
+
+    let last_check = last_check::LastCheck {
+        checked_at: now,
+        lines: lines.clone(),
+    };
+    if let Err(error) = last_check::write_last_check(last_check_path, &last_check) {
+        eprintln!("Couldn't save the result of the check: {error}");
+    }
+
+    return Ok(lines);
+}
+
 /// Takes the lines from the rustup command and returns the version
 /// strings of any new versions of Rust and Rustup
 fn get_new_versions(rustup_check_lines: Vec<&str>) -> HashMap<&str, Option<&str>> {
@@ -162,11 +651,12 @@ fn get_new_versions(rustup_check_lines: Vec<&str>) -> HashMap<&str, Option<&str>
     let sem_ver_regex = Regex::new(r"[0-9]+\.[0-9]+\.[0-9]+").unwrap();
 
     for line in rustup_check_lines {
-        // Name of toolchain to update
-        let name = line
-            .split(" - ")
-            .next()
-            .expect("Rustup output is malformed");
+        // Name of toolchain to update. Other lines, like headers, are
+        // skipped
+        let Some((name, _)) = line.split_once(" - ") else {
+            log::debug(&format!("Skipping rustup check line '{line}'"));
+            continue;
+        };
 
         // No update needed
         if line.contains("Up to date") {
@@ -175,293 +665,5199 @@ fn get_new_versions(rustup_check_lines: Vec<&str>) -> HashMap<&str, Option<&str>
         // Updates are needed
         else if line.contains("Update available") {
             // Get the last sem ver string ('1.80.1' and the like) from the rustup line
-            let new_version = sem_ver_regex
-                .find_iter(line)
-                .last()
-                .expect("No regex matches")
-                .as_str();
-
-            new_versions.insert(name, Some(new_version));
+            match sem_ver_regex.find_iter(line).last() {
+                Some(new_version) => new_versions.insert(name, Some(new_version.as_str())),
+                None => {
+                    log::debug(&format!("No new version in rustup check line '{line}'"));
+                    continue;
+                }
+            };
         } else {
-            panic!("Rustup line '{line}' is malformed!")
+            log::debug(&format!("Skipping rustup check line '{line}'"));
         }
     }
 
     return new_versions;
 }
 
-#[derive(PartialEq, Debug)]
-enum UpdatePromptAnswer {
-    NoUpdateFound,
-    Update,
-    DoNotUpdate,
-    Timeout,
+/// New version of each toolchain and rustup, None if it's up to date. Owns
+/// its strings, unlike the result of `get_new_versions`
+pub type NewVersions = HashMap<String, Option<String>>;
+
+// Copies the strings borrowed from the rustup output
+fn to_owned_versions(new_versions: HashMap<&str, Option<&str>>) -> NewVersions {
+    return new_versions
+        .into_iter()
+        .map(|(name, version)| (name.to_string(), version.map(|x| x.to_string())))
+        .collect();
 }
 
-/// Analyse the output from the new versions, and prompt the user for an update if needed.
-fn prompt_for_update(new_versions: HashMap<&str, Option<&str>>) -> UpdatePromptAnswer {
-    // Example:
+/// Takes the lines from the rustup command and returns the version
+/// strings of any new versions of Rust and Rustup
+///
+/// Same as `get_new_versions`, but the result doesn't borrow from the lines
+pub fn get_new_versions_owned(rustup_check_lines: &[String]) -> NewVersions {
+    return to_owned_versions(get_new_versions(
+        rustup_check_lines.iter().map(|x| x.as_str()).collect(),
+    ));
+}
 
-    // zenity --question --title="Rust Update" --no-wrap
-    // --text="Rust 1.80.1\nRustup 1.6.0\nUpdate?" --timeout=10 --ok-label="Update"
-    // --cancel-label="Not today"
+/// Returns true if there is a new version of any toolchain or of rustup.
+/// rustup's own update doesn't count with `Config::ignore_rustup_self`
+///
+/// With the `dist-server` feature and `Config::use_dist_server` set, only
+/// stable is checked, by comparing the installed version to the latest
+/// version on the Rust dist server. This doesn't need `rustup check`
+pub fn is_update_available(config: &Config) -> Result<bool, Error> {
+    return is_update_available_with(config, &SystemRunner, &StatePaths::for_config(config)?);
+}
 
-    // Check no new versions were found
-    if new_versions.values().all(|new_ver| new_ver.is_none()) {
-        return UpdatePromptAnswer::NoUpdateFound;
+// Same as `is_update_available`, running commands with `runner`
+fn is_update_available_with(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+) -> Result<bool, Error> {
+    #[cfg(feature = "dist-server")]
+    if config.use_dist_server {
+        return Ok(dist::stable_update_available(
+            runner,
+            &rustup_filepath(config)?,
+            dist_server(config)
+                .as_deref()
+                .unwrap_or(dist::DEFAULT_DIST_SERVER),
+        )?);
     }
 
-    let mut args = vec![
-        "--question",
-        "--title=Rust Update",
-        "--no-wrap",
-        "--timeout=10",
-        "--ok-label=Update",
-        "--cancel-label=Not today",
-    ];
-
-    // Create --text parameter containing new program versions
-    let mut text = String::new();
+    let lines = get_rustup_check(
+        runner,
+        &rustup_filepath(config)?,
+        &rustup_env(config),
+        Some(&paths.check_flags),
+    )?;
+    let new_versions = get_new_versions_owned(&without_rustup_self(config, lines));
 
-    for (program, new_version) in new_versions {
-        if let Some(version) = new_version {
-            text.push_str(&format!("{}: {}\n", program, version));
-        }
-    }
+    return Ok(new_versions.values().any(|x| x.is_some()));
+}
 
-    // Cut new line character
-    if let Some(stripped_text) = text.strip_suffix("\n") {
-        text = stripped_text.to_owned();
-    }
+/// Download sizes in bytes of the toolchains with updates
+pub type DownloadSizes = HashMap<String, u64>;
 
-    text = format!("--text={}\nUpdate?", text);
-    args.push(&text);
+/// What's known about each update besides its version, for the prompt
+#[derive(Debug, Clone, Default)]
+pub struct UpdateDetails {
+    /// Download sizes in bytes
+    pub sizes: DownloadSizes,
 
-    let prompt_response = process::Command::new("zenity").args(args).spawn();
+    /// Seconds since each new version was released
+    pub released_ago: HashMap<String, u64>,
+}
 
-    if prompt_response.is_err() {
-        let error = prompt_response.expect_err("Checked");
+// The release date and download size of the new version of `update`, from
+// the manifest of that release on the dist server, fetched with `runner`
+#[cfg(feature = "dist-server")]
+fn dist_details(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    update: &ParsedUpdate,
+) -> (Option<String>, Option<u64>) {
+    let dist_server = dist_server(config);
+    let dist_server = dist_server.as_deref().unwrap_or(dist::DEFAULT_DIST_SERVER);
+    let Some(manifest) = update
+        .new
+        .and_then(|x| dist::release_manifest(runner, dist_server, &update.name, x))
+    else {
+        return (None, None);
+    };
 
-        if error.kind() == io::ErrorKind::NotFound {
-            panic!("Can't run zenity command. Is zenity installed?");
-        } else {
-            panic!("Failed to run zenity command due to {:?}", error);
-        }
-    }
+    let size = update
+        .target()
+        .and_then(|x| dist::download_size(runner, &manifest, x));
 
-    let prompt_response = prompt_response
-        .expect("Checked")
-        .wait()
-        .expect("Failed to get zenity output");
+    return (dist::manifest_date(&manifest).ok(), size);
+}
 
-    match prompt_response.code() {
-        Some(0) => return UpdatePromptAnswer::Update,
-        Some(1) => return UpdatePromptAnswer::DoNotUpdate,
-        Some(5) => return UpdatePromptAnswer::Timeout,
-        x => panic!("zenity returned with unexpected error: {:?}", x),
-    }
+// Nothing is known without the `dist-server` feature
+#[cfg(not(feature = "dist-server"))]
+fn dist_details(
+    _config: &Config,
+    _runner: &dyn CommandRunner,
+    _update: &ParsedUpdate,
+) -> (Option<String>, Option<u64>) {
+    return (None, None);
 }
 
-fn run_update() -> bool {
-    let args = [
-        "--",
-        "/bin/sh",
-        "-c",
-        "rustup update; echo 'Finished!'; sleep 10",
-    ];
+// Details of the updates with new versions, with how long ago they were
+// released at `now`
+//
+// When they were released is from the build `rustup check` gave, or else
+// the dist server, which also gives the download size
+fn update_details(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    updates: &[ParsedUpdate],
+    now: u64,
+) -> UpdateDetails {
+    let mut details = UpdateDetails::default();
 
-    let result = process::Command::new("/bin/gnome-terminal")
-        .args(args)
-        .output()
-        .expect("Update command failed");
+    for update in updates.iter().filter(|x| x.new.is_some()) {
+        let (dist_date, size) = dist_details(config, runner, update);
+        if let Some(size) = size {
+            details.sizes.insert(update.name.clone(), size);
+        }
 
-    dbg!(&result);
+        let date = update
+            .new_build
+            .as_ref()
+            .map(|x| x.date.clone())
+            .or(dist_date);
+        if let Some(released) = date.as_deref().and_then(age::parse_date) {
+            details
+                .released_ago
+                .insert(update.name.clone(), now.saturating_sub(released));
+        }
+    }
 
-    return result.status.success();
+    return details;
 }
 
-/// Main function
-///
-/// Automaticity checks for new Rust versions prompting user to update
-/// Rust. Updates Rust in terminal window if asked. Doesn't ask for a day
-/// if told not to update
-///
-/// Panics if no internet connection
+/// Builds the text of the prompt listing the new versions
 ///
-/// Panics if couldn't find the `rustup` or `zenity` command
+/// The user's default toolchain is marked, as it's likely the one they care
+/// about most. Download sizes, and how long ago each version was released,
+/// are shown where they're known
 ///
-/// Panics if rustup update doesn't work successfully
-pub fn auto_update() -> io::Result<()> {
-    let rustup_lines = get_rustup_check();
-    let new_versions = get_new_versions(rustup_lines.iter().map(|x| x.as_str()).collect());
-
-    // No new versions
-    if new_versions.values().all(|x| x.is_none()) {
-        // Remove do not update flag
-        set_no_update_flag(false)?;
+/// When `accessible`, each update is a full sentence, to read well with a
+/// screen reader, instead of a compact list
+fn build_prompt_text(
+    new_versions: &NewVersions,
+    default_toolchain: Option<&str>,
+    details: &UpdateDetails,
+    accessible: bool,
+) -> String {
+    let mut lines = Vec::new();
 
-        println!("No new updates available");
-
-        return io::Result::Ok(());
-    }
+    for (program, new_version) in new_versions {
+        if let Some(version) = new_version {
+            let is_default =
+                default_toolchain.is_some_and(|x| settings::is_default_toolchain(program, x));
+            let size = details.sizes.get(program).map(|x| format_size(*x));
+            let released = details
+                .released_ago
+                .get(program)
+                .map(|x| age::describe_age(*x));
 
-    println!("Updates found:");
-    println!("{:?}", new_versions);
+            let line = if accessible {
+                let mut line = program.to_string();
+                if is_default {
+                    line.push_str(", your default toolchain,");
+                }
+                line.push_str(&format!(" updates to version {version}"));
+                if let Some(size) = size {
+                    // "~" isn't read out well
+                    line.push_str(&format!(
+                        ", a download of about {}",
+                        size.trim_start_matches('~')
+                    ));
+                }
+                if let Some(released) = released {
+                    line.push_str(&format!(", released {released}"));
+                }
 
-    if should_prompt() {
-        match prompt_for_update(new_versions) {
-            UpdatePromptAnswer::NoUpdateFound => {
-                panic!("This should have been handled above")
-            }
-            UpdatePromptAnswer::DoNotUpdate => {
-                println!("User said no updates. Setting no update flag");
-                set_no_update_flag(true)?;
-            }
-            UpdatePromptAnswer::Timeout => {
-                println!("Prompt timed out. Asking later...")
-            }
-            UpdatePromptAnswer::Update => {
-                println!("Updated Rust in new terminal");
-                if run_update() {
-                    println!("Update complete")
+                format!("{line}.")
+            } else {
+                let mut line = if is_default {
+                    format!("{} (default): {}", program, version)
                 } else {
-                    panic!("Update didn't run successfully!")
+                    format!("{}: {}", program, version)
+                };
+                if let Some(size) = size {
+                    line = format!("{line}, {size}");
                 }
-            }
+                if let Some(released) = released {
+                    line = format!("{line}, released {released}");
+                }
+
+                line
+            };
+
+            lines.push(line);
         }
-    } else {
-        println!("User said no update in the past... won't prompt for a while")
     }
 
-    return io::Result::Ok(());
-}
+    // HashMap order is random, so sort to keep the prompt the same each time
+    lines.sort();
 
-#[cfg(test)]
-mod tests {
-    use crate::*;
+    if accessible {
+        let count = match lines.len() {
+            1 => "1 update".to_string(),
+            x => format!("{x} updates"),
+        };
 
-    #[test]
-    fn pass() {
-        assert!(true);
+        return format!(
+            "{count} available.\n{}\nDo you want to update now?",
+            lines.join("\n")
+        );
     }
 
-    #[test]
-    fn rustup_command_test() {
-        let rustup_output = get_rustup_check();
-        assert_eq!(rustup_output.len(), 2);
+    return format!("{}\nUpdate?", lines.join("\n"));
+}
 
-        assert!(rustup_output[1].contains("rustup"));
-    }
+// Describes an update, like `stable 1.80.1 → 1.81.0`, with its target if
+// another of `updates` has the same channel
+fn describe_update(update: &ParsedUpdate, updates: &[ParsedUpdate]) -> String {
+    let version = |x: Option<Version>| x.map(|x| x.to_string()).unwrap_or("?".to_string());
 
-    #[ignore = "Only passes without internet"]
-    #[test]
-    #[should_panic]
-    fn rustup_no_internet() {
-        get_rustup_check();
+    return format!(
+        "{} {} → {}",
+        update.short_name(updates),
+        version(update.current),
+        version(update.new)
+    );
+}
+
+// Describes the version installed, with its build if it's known, like
+// `stable-x86_64-unknown-linux-gnu: 1.80.0, commit 051478957 from 2024-07-21`
+fn describe_installed(update: &ParsedUpdate) -> String {
+    let mut description = match update.current {
+        Some(current) => format!("{}: {current}", update.name),
+        None => format!("{}: unknown version", update.name),
+    };
+    if let Some(build) = &update.current_build {
+        description.push_str(&format!(", commit {} from {}", build.commit, build.date));
     }
 
-    #[test]
-    fn rustup_no_update() {
-        let input = vec![
-            "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.0 (051478957 2024-07-21)",
-            "rustup - Up to date : 1.27.1",
-        ];
+    return description;
+}
 
-        let results = get_new_versions(input);
+// Why the prompt for `updates` says to be careful, if stable moves to a new
+// release
+fn new_release_reason(updates: &[ParsedUpdate]) -> Option<String> {
+    return policy::new_stable_release(updates).map(|x| {
+        return format!(
+            "{} is a new release, not only fixes. Check the release notes for changes \
+             that could affect your code",
+            describe_update(x, updates)
+        );
+    });
+}
 
-        assert_eq!(results.get("stable-x86_64-unknown-linux-gnu"), Some(&None));
-        assert_eq!(results.get("rustup"), Some(&None));
+// The version stable moves to, if it's a new major release, to type to
+// confirm it in a terminal
+fn major_release_word(updates: &[ParsedUpdate]) -> Option<String> {
+    let update = policy::new_stable_release(updates)?;
+    if policy::version_jump(update.current?, update.new?) != policy::Jump::Major {
+        return None;
     }
 
-    #[test]
-    fn rustup_patch() {
-        let input = vec![
-            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1 (051478957 2024-07-21)",
-            "rustup - Up to date : 1.27.1",
-        ];
+    return update.new.map(|x| x.to_string());
+}
 
-        let results = get_new_versions(input);
+/// How much attention the prompt asks for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Urgency<'a> {
+    Normal,
 
-        assert_eq!(
-            results.get("stable-x86_64-unknown-linux-gnu"),
-            Some(&Some("1.80.1"))
-        );
+    /// Stable moves to a new minor or major release, as the text says. It
+    /// can break more than a patch release. For a major release, the version
+    /// it moves to is the word to type to confirm, in a terminal
+    NewRelease(&'a str, Option<&'a str>),
 
-        assert_eq!(results.get("rustup"), Some(&None));
-    }
+    /// Stable is below the minimum version, as the text warns
+    Required(&'a str),
+}
 
-    #[test]
-    fn no_prompt() {
-        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
-        input.insert("Rust", None);
-        input.insert("Rustup", None);
+/// Analyse the output from the new versions, and prompt the user for an update if needed.
+///
+/// Unless the `urgency` is normal, its reason is shown above the new
+/// versions, with a different title. A new release waits three times as
+/// long. A required update doesn't time out
+///
+/// An `accessible` prompt doesn't time out either, giving the user as long
+/// as they need
+///
+/// Each of the `notes` is shown on its own line after the new versions
+fn prompt_for_update(
+    backend: &dyn PromptBackend,
+    new_versions: &NewVersions,
+    default_toolchain: Option<&str>,
+    urgency: Urgency<'_>,
+    details: &UpdateDetails,
+    config: &Config,
+    notes: &[String],
+) -> Result<UpdatePromptAnswer, Error> {
+    let accessible = config.accessible;
 
-        assert_eq!(prompt_for_update(input), UpdatePromptAnswer::NoUpdateFound);
+    // Check no new versions were found
+    if new_versions.values().all(|new_ver| new_ver.is_none()) {
+        return Ok(UpdatePromptAnswer::NoUpdateFound);
     }
 
-    #[ignore = "Makes prompt, is annoying"]
-    #[test]
-    fn prompt_update() {
-        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
-        input.insert("Rust", Some("1.81.0 Update me!"));
-        input.insert("Rustup", Some("1.27.3"));
-
-        assert_eq!(prompt_for_update(input), UpdatePromptAnswer::Update);
+    // Text containing new program versions
+    let mut text = build_prompt_text(new_versions, default_toolchain, details, accessible);
+    if !notes.is_empty() {
+        text = format!("{}\n\n{text}", notes.join("\n"));
     }
 
-    #[ignore = "Makes prompt, is annoying"]
-    #[test]
-    fn prompt_do_not_update() {
-        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
-        input.insert("Rust", Some("2.0.0 Don't update me please!!"));
-        input.insert("Rustup", None);
+    let updates: BTreeMap<String, String> = new_versions
+        .iter()
+        .filter_map(|(name, new)| Some((name.clone(), new.clone()?)))
+        .collect();
 
-        assert_eq!(prompt_for_update(input), UpdatePromptAnswer::DoNotUpdate);
+    let mut prompt = match urgency {
+        Urgency::Normal => Prompt {
+            title: "Rust Update".to_string(),
+            text,
+            updates,
+            timeout: config.prompt_timeout,
+            ok_label: "Update".to_string(),
+            cancel_label: "Not today".to_string(),
+            accessible,
+            detect_close: config.close_action != TimeoutAction::Decline,
+            confirm_word: None,
+            icon: config.dialog_icon.clone(),
+            modal: config.dialog_modal,
+        },
+        // Given longer to read, and not closed by accident
+        Urgency::NewRelease(reason, confirm_word) => Prompt {
+            title: "Rust Update: New Release".to_string(),
+            text: format!("{reason}\n\n{text}"),
+            updates,
+            timeout: config.prompt_timeout.map(|x| x.saturating_mul(3)),
+            ok_label: "Update".to_string(),
+            cancel_label: "Not today".to_string(),
+            accessible,
+            detect_close: config.close_action != TimeoutAction::Decline,
+            confirm_word: confirm_word.map(|x| x.to_string()),
+            icon: config.dialog_icon.clone(),
+            modal: config.dialog_modal,
+        },
+        Urgency::Required(warning) => Prompt {
+            title: "Rust Update Required".to_string(),
+            text: format!("{warning}\n\n{text}"),
+            updates,
+            timeout: None,
+            ok_label: "Update".to_string(),
+            cancel_label: "Not now".to_string(),
+            accessible,
+            detect_close: false,
+            confirm_word: None,
+            icon: config.dialog_icon.clone(),
+            modal: config.dialog_modal,
+        },
+    };
+    if accessible {
+        prompt.timeout = None;
     }
 
-    #[ignore = "Makes prompt, is annoying"]
-    #[test]
-    fn timeout_prompt() {
-        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
-        input.insert("Rust", Some("2.0.0 Timeout!!!"));
-        input.insert("Rustup", Some("Please don't press a button"));
+    return backend.ask(&prompt);
+}
+
+// Directories using a toolchain pinned to a release, from `rustup override
+// set` or a toolchain file in the current directory. Updates don't reach
+// these, as `rustup update` leaves pinned toolchains alone
+fn pinned_directories() -> Vec<settings::ToolchainOverride> {
+    let mut pinned = settings::rustup_home()
+        .map(|x| settings::read_overrides(&x))
+        .unwrap_or_default();
 
-        assert_eq!(prompt_for_update(input), UpdatePromptAnswer::Timeout);
+    if let Ok(directory) = env::current_dir() {
+        if let Some(toolchain) = settings::read_toolchain_file(&directory) {
+            pinned.push(settings::ToolchainOverride {
+                directory,
+                toolchain,
+            });
+        }
     }
 
-    #[test]
-    fn should_prompt_test() {
-        // Based on a flag in the filesystem. Can't be run in parallel with other tests if they modify the
+    pinned.retain(|x| settings::is_pinned(&x.toolchain));
 
-        println!("No flag");
-        set_no_update_flag(false).unwrap();
-        assert_eq!(should_prompt(), true);
+    return pinned;
+}
 
-        println!("New flag");
-        set_no_update_flag(true).unwrap();
-        assert_eq!(should_prompt(), false);
+// Tells the user which directories an update won't change
+fn pinned_notes(pinned: &[settings::ToolchainOverride]) -> Vec<String> {
+    return pinned
+        .iter()
+        .map(|x| {
+            return format!(
+                "{} is pinned to {}, so it won't be updated",
+                x.directory.display(),
+                x.toolchain
+            );
+        })
+        .collect();
+}
 
-        println!("Second new flag");
-        set_no_update_flag(true).unwrap();
-        assert_eq!(should_prompt(), false);
+// Script run in the terminal. The update command is passed to the shell as
+// its own arguments and run with "$@", so it's never parsed by the shell
+// and can't run anything else, even if it contains ';' or '$(...)'
+//
+// Its process id, then the exit code of the update, are written to the
+// status file `{status}`, for terminals that return before it's done
+const UPDATE_SCRIPT: &str = r#""$@"; echo "code=$?" >> {status}; echo 'Finished!'; sleep 10"#;
 
-        println!("Second no flag");
-        set_no_update_flag(false).unwrap();
-        assert_eq!(should_prompt(), true);
+// Put first, so the script is known to have started before any countdown
+const STARTED_SCRIPT: &str = r#"echo "pid=$$" >> {status}; "#;
 
-        println!("All passed");
-    }
+// Put before `UPDATE_SCRIPT` to count down from `{seconds}` first. Ctrl-C
+// stops the shell before the update starts
+const COUNTDOWN_SCRIPT: &str = "echo 'Press Ctrl-C to cancel the update'; n={seconds}; \
+     while [ \"$n\" -gt 0 ]; do printf 'Starting in %s... ' \"$n\"; sleep 1; n=$((n - 1)); done; \
+     echo; ";
 
-    #[ignore = "Depends on the file system"]
-    #[test]
-    fn should_prompt_after_day() {
-        // Touch the file so it was modified a day ago
-        assert_eq!(should_prompt(), true);
+// The script run in the terminal, counting down for `countdown` seconds
+// before the update, and writing how it's going to `status`
+fn update_script(countdown: u64, status: &path::Path) -> String {
+    let status = shell_quote(&status.display().to_string());
+    let mut script = STARTED_SCRIPT.replace("{status}", &status);
+    if countdown > 0 {
+        script.push_str(&COUNTDOWN_SCRIPT.replace("{seconds}", &countdown.to_string()));
+    }
+    script.push_str(&UPDATE_SCRIPT.replace("{status}", &status));
+
+    return script;
+}
+
+// Counts status files made by this process, so each has a name of its own
+static STATUS_FILES_MADE: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+// Where the update script in a terminal writes how it's going. In
+// XDG_RUNTIME_DIR, or else ~/.rustup, as only this user can write there,
+// unlike the shared temporary directory
+fn status_file_path() -> io::Result<path::PathBuf> {
+    let name = format!(
+        "auto_rustup_update_{}_{}.status",
+        std::process::id(),
+        STATUS_FILES_MADE.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    );
+
+    match env::var_os("XDG_RUNTIME_DIR").filter(|x| !x.is_empty()) {
+        Some(dir) => return io::Result::Ok(path::PathBuf::from(dir).join(name)),
+        None => return home::home_path(&format!(".rustup/{name}")),
+    }
+}
+
+// Script run for a detached update, with the update command as its
+// arguments like `UPDATE_SCRIPT`. `{updated}` and `{failed}` are the
+// notify-send commands for each result. The toolchains that failed, from
+// rustup's summary, are in `$results` for `{failed}`, like ` Failed to
+// update nightly-x86_64-unknown-linux-gnu.`
+const DETACHED_UPDATE_SCRIPT: &str = r#"if output=$("$@" 2>&1); then {updated}; else
+results=$(printf '%s\n' "$output" | awk '/ update failed - / { printf "%s%s", sep, $1; sep = ", " }')
+if [ -n "$results" ]; then results=" Failed to update $results."; fi
+{failed}; fi"#;
+
+// Quotes `text` for the shell, so it's read as one word as it is
+fn shell_quote(text: &str) -> String {
+    return format!("'{}'", text.replace('\'', r"'\''"));
+}
+
+// Variables rustup reads to download from somewhere other than the usual
+// servers, like a mirror
+const RUSTUP_ENV_VARS: [&str; 2] = ["RUSTUP_DIST_SERVER", "RUSTUP_UPDATE_ROOT"];
+
+// The server rustup downloads toolchains from, if it isn't the usual one,
+// like a mirror. From the config, or else this process's environment
+fn dist_server(config: &Config) -> Option<String> {
+    return rustup_env(config)
+        .into_iter()
+        .find(|(name, _)| name == "RUSTUP_DIST_SERVER")
+        .map(|(_, value)| value);
+}
+
+/// Environment variables to run rustup with. Set in the config, or else
+/// passed on from this process
+///
+/// Some terminals start commands without the environment of the program
+/// that launched them, so these are always passed on explicitly
+fn rustup_env(config: &Config) -> Vec<(String, String)> {
+    let configured = [&config.rustup_dist_server, &config.rustup_update_root];
+
+    return RUSTUP_ENV_VARS
+        .iter()
+        .zip(configured)
+        .filter_map(|(name, configured)| {
+            let value = configured.clone().or_else(|| env::var(name).ok())?;
+
+            return Some((name.to_string(), value));
+        })
+        .collect();
+}
+
+// True if rustup can't update itself, as its directory is read-only, like
+// a system install
+fn self_update_blocked(config: &Config) -> bool {
+    let Ok(rustup) = rustup_filepath(config) else {
+        return false;
+    };
+
+    return match rustup.parent() {
+        Some(dir) => dir.exists() && !diagnose::is_writable_dir(dir),
+        None => false,
+    };
+}
+
+// The update command, updating only `toolchains` if there are any, like
+// `rustup update stable`. Arguments are only added to `rustup update`, as
+// other commands might not take toolchain names
+//
+// When rustup can't update itself, it's told not to try, so the toolchains
+// still update
+fn update_command_for(config: &Config, toolchains: &[String]) -> Vec<String> {
+    let mut command = config.update_command.clone();
+
+    let is_rustup_update = match command.as_slice() {
+        [program, subcommand, ..] => {
+            path::Path::new(program).file_name() == Some("rustup".as_ref())
+                && subcommand == "update"
+        }
+        _ => false,
+    };
+    if is_rustup_update {
+        // The rustup that was verified, not whichever is first on PATH
+        if let (true, Ok(rustup)) = (config.verify_rustup, rustup_filepath(config)) {
+            if command[0] == "rustup" {
+                command[0] = rustup.display().to_string();
+            }
+        }
+        // When it's run as root, rustup can write to its directory, so it
+        // can update itself
+        if self_update_blocked(config) && elevation_for(config).is_none() {
+            command.push("--no-self-update".to_string());
+        }
+        command.extend(toolchains.iter().cloned());
+    }
+
+    return command;
+}
+
+// How `elevate` elevates this update. A detached update has no terminal for
+// sudo to ask in, so it uses pkexec instead
+fn elevation_kind(config: &Config) -> Elevation {
+    match (config.elevate, config.detach) {
+        (Elevation::Sudo, true) => return Elevation::Pkexec,
+        (elevate, _) => return elevate,
+    }
+}
+
+// The program the update is run through to update a rustup that belongs to
+// someone else, like `sudo`, and where that rustup is. None if `elevate`
+// is off, or it's ours
+fn elevation_for(config: &Config) -> Option<(&'static str, path::PathBuf)> {
+    let elevate = elevation_kind(config);
+    if elevate == Elevation::Never {
+        return None;
+    }
+
+    let rustup = rustup_filepath(config).ok()?;
+    let program = root::elevation_program(
+        elevate,
+        root::owner_uid(&rustup).ok()?,
+        root::effective_uid().ok()?,
+    )?;
+
+    return Some((program, rustup));
+}
+
+// `update_command` run through `program`, like `sudo`, to update the rustup
+// at `rustup`, which belongs to someone else
+//
+// Neither sudo or pkexec keep the environment, so `env` sets the variables
+// `env` again. rustup is run by its path, as root's PATH might not have it
+fn elevated_command(
+    program: &str,
+    rustup: &path::Path,
+    env: &[(String, String)],
+    update_command: &[String],
+) -> Vec<String> {
+    let mut command = vec![program.to_string()];
+    if program == "sudo" {
+        // Says why it's asking. sudo expands `%p` to the user
+        command.push("-p".to_string());
+        command.push(format!(
+            "Updating the rustup at {} needs root, as it belongs to another user. \
+             Password for %p: ",
+            rustup.display().to_string().replace('%', "%%")
+        ));
+    }
+    command.push("/usr/bin/env".to_string());
+    command.extend(env.iter().map(|(key, value)| format!("{key}={value}")));
+
+    let mut update_command = update_command.to_vec();
+    if update_command.first().map(|x| x.as_str()) == Some("rustup") {
+        update_command[0] = rustup.display().to_string();
+    }
+    command.extend(update_command);
+
+    return command;
+}
+
+// The update command as it's run, through sudo or pkexec if rustup belongs
+// to someone else and `elevate` says to
+fn update_command_to_run(config: &Config, toolchains: &[String]) -> Vec<String> {
+    let command = update_command_for(config, toolchains);
+    let Some((program, rustup)) = elevation_for(config) else {
+        return command;
+    };
+
+    // Where a system install keeps its toolchains
+    let mut elevated_env = rustup_env(config);
+    for name in ["RUSTUP_HOME", "CARGO_HOME"] {
+        if let Ok(value) = env::var(name) {
+            elevated_env.push((name.to_string(), value));
+        }
+    }
+
+    return elevated_command(program, &rustup, &elevated_env, &command);
+}
+
+/// Arguments for `terminal` to run `update_command` in a shell, after
+/// counting down for `countdown` seconds, then wait so the output can be
+/// read. How it's going is written to the file `status`
+///
+/// The variables `env` are set with `env`, as the terminal might not pass
+/// on its own environment
+fn terminal_update_args(
+    terminal: &Terminal,
+    update_command: &[String],
+    env: &[(String, String)],
+    countdown: u64,
+    status: &path::Path,
+) -> Vec<String> {
+    let script = update_script(countdown, status);
+    let mut args: Vec<String> = [terminal.exec_flag, "/bin/sh", "-c", &script, "sh"]
+        .iter()
+        .map(|x| x.to_string())
+        .collect();
+    if !env.is_empty() {
+        args.push("env".to_string());
+        args.extend(env.iter().map(|(key, value)| format!("{key}={value}")));
+    }
+    args.extend(update_command.iter().cloned());
+
+    return args;
+}
+
+// Runs the update in a new terminal window, returning the exit code of the
+// update once it's finished, even if the terminal returned before it did
+//
+// Each terminal is tried in turn until one launches. A terminal can be
+// installed but still fail to launch, like gnome-terminal without a dbus
+// session. If none launch, the update is run without a terminal
+//
+// Only `toolchains` are updated, or everything if it's empty
+fn run_update(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    toolchains: &[String],
+) -> Result<(Option<i32>, Vec<parse::UpdateResult>), Error> {
+    // Only the terminal in the config, if there is one
+    let terminals = match &config.terminal {
+        Some(terminal) => std::slice::from_ref(terminal),
+        None => &TERMINALS[..],
+    };
+    let status_path = status_file_path()?;
+    if let Some(dir) = status_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    for terminal in terminals {
+        let command = CommandSpec::new(
+            terminal.program,
+            &terminal_update_args(
+                terminal,
+                &update_command_to_run(config, toolchains),
+                &rustup_env(config),
+                config.update_countdown,
+                &status_path,
+            ),
+        );
+
+        // Made new for each terminal, so one that failed can't have left
+        // anything in it
+        let _ = std::fs::remove_file(&status_path);
+        completion::create_status_file(&status_path)?;
+        match runner.run(&command) {
+            io::Result::Ok(result) => {
+                let watcher = runner.watcher(&status_path);
+                if let ScriptState::Running { .. } = watcher.state() {
+                    println!("The terminal returned before the update finished. Waiting for it");
+                }
+                let completion = completion::wait_for_update(
+                    watcher.as_ref(),
+                    result.status,
+                    completion::START_GRACE,
+                );
+                let _ = std::fs::remove_file(&status_path);
+
+                // The output was in the terminal, so what failed is found by
+                // checking again
+                match completion {
+                    Completion::Finished { code: Some(0) } => return Ok((Some(0), Vec::new())),
+                    Completion::Finished { code } => {
+                        return Ok((code, still_out_of_date(config, runner, paths, toolchains)))
+                    }
+                    // Failed before opening a window, like without a display
+                    Completion::NotStarted if !result.success() => {
+                        eprintln!(
+                            "{} exited with {} before the update started",
+                            terminal.program,
+                            runner::describe_status(result.status)
+                        );
+                    }
+                    Completion::NotStarted => {
+                        println!(
+                            "The update didn't start in {}, so it isn't known if it ran",
+                            terminal.program
+                        );
+                        return Ok((None, still_out_of_date(config, runner, paths, toolchains)));
+                    }
+                }
+            }
+            io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            io::Result::Err(error) => {
+                eprintln!("Couldn't launch {}: {error}", terminal.program);
+            }
+        }
+    }
+
+    let _ = std::fs::remove_file(&status_path);
+    println!("No terminal could be launched. Updating here instead");
+
+    return run_update_headless(config, runner, toolchains).map_err(|error| {
+        let terminals: Vec<&str> = terminals.iter().map(|x| x.program).collect();
+
+        return Error::Io(io::Error::other(format!(
+            "Couldn't launch a terminal (tried {}), or run the update without one: {error}",
+            terminals.join(", ")
+        )));
+    });
+}
+
+// Starts the update in the background, detached so it carries on after this
+// program exits. It shows a notification when it finishes, as nothing else
+// would say how it went. Only `toolchains` are updated, or everything if
+// it's empty
+fn run_update_detached(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    toolchains: &[String],
+) -> Result<(), Error> {
+    let updated = match toolchains.is_empty() {
+        true => "Rust".to_string(),
+        false => toolchains.join(", "),
+    };
+    let notify_send = |summary: &str, body: &str, style: notify::NotifyStyle| {
+        let args: Vec<String> = notify::notify_send_args(summary, body, style)
+            .iter()
+            .map(|x| shell_quote(x))
+            .collect();
+
+        return format!("notify-send {}", args.join(" "));
+    };
+    let script = DETACHED_UPDATE_SCRIPT
+        .replace(
+            "{updated}",
+            &notify_send(
+                "Rust updated",
+                &format!("Updated {updated}"),
+                config.notify_style,
+            ),
+        )
+        .replace(
+            "{failed}",
+            // Out of the quotes, so the shell puts the results in
+            &notify_send(
+                "Rust update failed",
+                &format!(
+                    "Updating {updated} failed.{{results}} Run the update in a terminal to see \
+                     why"
+                ),
+                notify::NotifyStyle::FAILURE,
+            )
+            .replace("{results}", r#"'"$results"'"#),
+        );
+
+    let mut args = vec!["-c".to_string(), script, "sh".to_string()];
+    args.extend(update_command_to_run(config, toolchains));
+
+    let pid =
+        runner.spawn_detached(&CommandSpec::new("/bin/sh", &args).with_env(&rustup_env(config)))?;
+    log::debug(&format!(
+        "Started the update in the background as process {pid}"
+    ));
+
+    return Ok(());
+}
+
+/// Runs the update command in this process without a terminal window,
+/// returning its exit code, and how each toolchain went if it failed
+///
+/// The output is printed once the update finishes. Only `toolchains` are
+/// updated, or everything if it's empty
+fn run_update_headless(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    toolchains: &[String],
+) -> Result<(Option<i32>, Vec<parse::UpdateResult>), Error> {
+    let command = update_command_to_run(config, toolchains);
+    // The config file catches this, but a `Config` can be made by hand
+    let Some((program, args)) = command.split_first() else {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "update_command is empty, so there's nothing to update with",
+        )));
+    };
+
+    let output = runner.run(&CommandSpec::new(program, args).with_env(&rustup_env(config)))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    print!("{stdout}");
+    eprint!("{stderr}");
+
+    // Some toolchains can update when others don't
+    if !output.success() {
+        let results = parse::parse_update_output(&format!("{stdout}\n{stderr}"));
+        return Ok((output.status, results));
+    }
+
+    return Ok((output.status, Vec::new()));
+}
+
+// The toolchains of `toolchains`, or any if it's empty, that `rustup check`
+// still has an update for, as failed. For an update whose output wasn't
+// seen, like one in a terminal. Empty if the check fails
+fn still_out_of_date(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    toolchains: &[String],
+) -> Vec<parse::UpdateResult> {
+    let lines = rustup_filepath(config)
+        .map_err(Error::Io)
+        .and_then(|rustup| {
+            get_rustup_check(
+                runner,
+                &rustup,
+                &rustup_env(config),
+                Some(&paths.check_flags),
+            )
+        });
+    let lines = match lines {
+        Ok(lines) => lines,
+        Err(error) => {
+            log::debug(&format!("Couldn't check what failed to update: {error}"));
+            return Vec::new();
+        }
+    };
+
+    return parse::parse_rustup_check(&lines)
+        .into_iter()
+        .filter(|x| x.new.is_some() && x.channel_kind() != Channel::Rustup)
+        .filter(|x| toolchains.is_empty() || toolchains.contains(&x.name))
+        .map(|x| parse::UpdateResult {
+            name: x.name,
+            status: parse::UpdateStatus::Failed,
+        })
+        .collect();
+}
+
+// Which toolchains `rustup update` failed to update, and which it did,
+// from its summary. None if none of them failed
+fn describe_update_results(results: &[parse::UpdateResult]) -> Option<String> {
+    let names = |statuses: &[parse::UpdateStatus]| {
+        return results
+            .iter()
+            .filter(|x| statuses.contains(&x.status))
+            .map(|x| x.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ");
+    };
+
+    let failed = names(&[parse::UpdateStatus::Failed]);
+    if failed.is_empty() {
+        return None;
+    }
+
+    let updated = names(&[parse::UpdateStatus::Updated, parse::UpdateStatus::Installed]);
+    if updated.is_empty() {
+        return Some(format!("Failed to update {failed}"));
+    }
+
+    return Some(format!("Failed to update {failed}. Updated {updated}"));
+}
+
+/// Runs the post update hook from the config, if there is one
+///
+/// The hook's output is printed. A hook that can't be found is skipped, as
+/// is a hook that fails, with a warning
+fn run_post_update_hook(config: &Config, runner: &dyn CommandRunner) {
+    run_hook("Post update hook", &config.post_update_hook, runner);
+}
+
+// Runs the command of a hook, called `name` in messages, printing what it
+// printed. A hook that isn't installed, or fails, doesn't stop anything else
+fn run_hook(name: &str, hook: &[String], runner: &dyn CommandRunner) {
+    run_hook_with_env(name, hook, &[], &[], runner);
+}
+
+// Same as `run_hook`, also setting the environment variables `env`. Exiting
+// with one of `meaningful` isn't reported as failing. Returns the hook's exit
+// code, None if it didn't run or was killed
+fn run_hook_with_env(
+    name: &str,
+    hook: &[String],
+    env: &[(String, String)],
+    meaningful: &[i32],
+    runner: &dyn CommandRunner,
+) -> Option<i32> {
+    let (program, args) = hook.split_first()?;
+
+    let command = CommandSpec::new(program, args).with_env(env);
+    println!("Running {}: {command}", name.to_lowercase());
+
+    match runner.run(&command) {
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            println!("{name} '{program}' not found. Skipping");
+            return None;
+        }
+        io::Result::Err(error) => {
+            eprintln!("Failed to run {}: {error}", name.to_lowercase());
+            return None;
+        }
+        io::Result::Ok(output) => {
+            print!("{}", String::from_utf8_lossy(&output.stdout));
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+
+            let meaningful = output.status.is_some_and(|x| meaningful.contains(&x));
+            if !output.success() && !meaningful {
+                eprintln!(
+                    "{name} failed with {}",
+                    runner::describe_status(output.status)
+                );
+            }
+            return output.status;
+        }
+    }
+}
+
+// Exit code of the update available hook that skips the prompt this run
+const SKIP_PROMPT_EXIT_CODE: i32 = 3;
+
+// Runs the update available hook, given the updates found in
+// `RUST_UPDATE_SUMMARY`, the `--oneline` summary, and in
+// `RUST_UPDATE_TOOLCHAINS`, like `stable-x86_64-unknown-linux-gnu=1.80.1
+// rustup=1.27.1`. True if it exited with `SKIP_PROMPT_EXIT_CODE`
+fn run_update_available_hook(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    updates: &[ParsedUpdate],
+) -> bool {
+    let toolchains: Vec<String> = updates
+        .iter()
+        .filter_map(|x| Some(format!("{}={}", x.name, x.new?)))
+        .collect();
+    let env = [
+        (
+            "RUST_UPDATE_SUMMARY".to_string(),
+            report::oneline_summary(updates),
+        ),
+        ("RUST_UPDATE_TOOLCHAINS".to_string(), toolchains.join(" ")),
+    ];
+
+    let code = run_hook_with_env(
+        "Update available hook",
+        &config.update_available_hook,
+        &env,
+        &[SKIP_PROMPT_EXIT_CODE],
+        runner,
+    );
+
+    return code == Some(SKIP_PROMPT_EXIT_CODE);
+}
+
+// Runs the hooks for the channels of the toolchains that were updated, once
+// for each channel
+fn run_channel_hooks(config: &Config, runner: &dyn CommandRunner, updates: &[ParsedUpdate]) {
+    let mut channels: Vec<&str> = updates
+        .iter()
+        .filter(|x| x.new.is_some())
+        .map(|x| x.channel())
+        .collect();
+    channels.sort();
+    channels.dedup();
+
+    for channel in channels {
+        if let Some(hook) = config.channel_hooks.get(channel) {
+            run_hook(&format!("Hook for {channel}"), hook, runner);
+        }
+    }
+}
+
+/// How a run of the updater ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// No new versions were found
+    UpToDate,
+
+    /// Updates were found, but the user snoozed them in the past
+    Snoozed,
+
+    /// Updates were found, but the machine is running on battery
+    OnBattery,
+
+    /// The user said not to update
+    Declined,
+
+    /// The prompt timed out without an answer
+    TimedOut,
+
+    /// Updates were found, but it's a dry run, so nothing was done
+    DryRun,
+
+    /// The update was started in the background, with `--detach`, so
+    /// whether it worked isn't known yet
+    UpdateStarted,
+
+    /// Updates were found, but the prompt couldn't be shown, so it was
+    /// queued for the next run that can show it
+    PromptQueued,
+
+    /// rustup isn't installed, so there was nothing to check
+    NoRustup,
+
+    /// No new versions were found, but the default toolchain is older than
+    /// `minimum_rust_version`, `current`
+    BelowMinimum { current: Version },
+
+    /// An update was put off until the next boot, which hasn't happened
+    WaitingForBoot,
+
+    /// Updates were found, but the prompt wasn't shown, as the update
+    /// available hook said not to, it was already shown as many times
+    /// today as `daily_prompt_budget` allows, or rustup is managed by a
+    /// package manager
+    PromptSkipped,
+
+    /// Rust was updated
+    Updated,
+
+    /// The update ran, but failed with the exit code `code`. None if it was
+    /// killed by a signal. `results` says how each toolchain went, when
+    /// that's known, as some can update when others fail
+    UpdateFailed {
+        code: Option<i32>,
+        results: Vec<parse::UpdateResult>,
+    },
+}
+
+impl Outcome {
+    /// Name of the outcome in reports, like `up_to_date`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::UpToDate => return "up_to_date",
+            Outcome::Snoozed => return "snoozed",
+            Outcome::OnBattery => return "on_battery",
+            Outcome::Declined => return "declined",
+            Outcome::TimedOut => return "timed_out",
+            Outcome::DryRun => return "dry_run",
+            Outcome::PromptQueued => return "prompt_queued",
+            Outcome::NoRustup => return "no_rustup",
+            Outcome::BelowMinimum { .. } => return "below_minimum",
+            Outcome::WaitingForBoot => return "waiting_for_boot",
+            Outcome::PromptSkipped => return "prompt_skipped",
+            Outcome::Updated => return "updated",
+            Outcome::UpdateFailed { .. } => return "update_failed",
+            Outcome::UpdateStarted => return "update_started",
+        }
+    }
+
+    /// Exit code for the program to return
+    ///
+    /// Zero unless the update failed, in which case it's the exit code of
+    /// the update, or Rust is below the minimum version, which is 1
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::UpdateFailed { code, .. } => return code.filter(|x| *x != 0).unwrap_or(1),
+            Outcome::BelowMinimum { .. } => return 1,
+            _ => return 0,
+        }
+    }
+}
+
+/// Updates Rust, then runs the post update hook if the update worked
+///
+/// When `wait` is true, the update runs in this process without a
+/// terminal, so the real exit code of the update is known
+fn update_rust(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    toolchains: &[String],
+    wait: bool,
+) -> Result<Outcome, Error> {
+    if let Some((manager, rustup)) = managed_rustup(config) {
+        return Err(Error::RustupManaged { manager, rustup });
+    }
+    let rustup = rustup_filepath(config)?;
+    if rustup.exists() {
+        check_rustup_integrity(config, &rustup)?;
+    }
+    warn_if_rustup_off_path(config);
+
+    if let Some((program, rustup)) = elevation_for(config) {
+        println!(
+            "{} belongs to another user, so the update runs with {program}, which asks for a \
+             password",
+            rustup.display()
+        );
+    } else if self_update_blocked(config) {
+        println!(
+            "{} is read-only, so rustup can't update itself. Updating only the toolchains",
+            rustup_filepath(config)?.display()
+        );
+    }
+
+    if config.detach && !wait {
+        run_update_detached(config, runner, toolchains)?;
+
+        return Ok(Outcome::UpdateStarted);
+    }
+
+    let (status, results) = if wait {
+        run_update_headless(config, runner, toolchains)?
+    } else {
+        run_update(config, runner, paths, toolchains)?
+    };
+
+    if status != Some(0) {
+        return Ok(Outcome::UpdateFailed {
+            code: status,
+            results,
+        });
+    }
+
+    run_post_update_hook(config, runner);
+
+    return Ok(Outcome::Updated);
+}
+
+// The package manager, like Nix, whose store has the rustup the update
+// would run, and where it is once links are followed. None if it isn't in
+// one, or the update command isn't `rustup`
+fn managed_rustup(config: &Config) -> Option<(&'static str, path::PathBuf)> {
+    let command = update_command_for(config, &[]);
+    let program = path::Path::new(command.first()?);
+    if program.file_name() != Some("rustup".as_ref()) {
+        return None;
+    }
+
+    // By name, it's whichever is first on PATH
+    let rustup = match program.components().count() {
+        1 => diagnose::first_on_path(&env::var_os("PATH").unwrap_or_default(), "rustup")?,
+        _ => program.to_path_buf(),
+    };
+    let resolved = std::fs::canonicalize(rustup).ok()?;
+
+    return Some((diagnose::package_store_manager(&resolved)?, resolved));
+}
+
+// Warns if the update runs `rustup` by name, but it isn't on PATH, though
+// it's installed. The update would fail with it not being found
+fn warn_if_rustup_off_path(config: &Config) {
+    if update_command_for(config, &[]).first().map(|x| x.as_str()) != Some("rustup") {
+        return;
+    }
+    let Ok(rustup) = rustup_filepath(config) else {
+        return;
+    };
+
+    if diagnose::rustup_off_path(&env::var_os("PATH").unwrap_or_default(), &rustup) {
+        eprintln!("{}", off_path_warning(&rustup));
+    }
+}
+
+// Explains setting up PATH for the rustup at `rustup`
+fn off_path_warning(rustup: &path::Path) -> String {
+    let dir = rustup.parent().unwrap_or(rustup);
+
+    return style::warning(&format!(
+        "rustup is installed at {}, but {} isn't on PATH, so `rustup` won't be found. Run \
+         `source ~/.cargo/env`, and add it to your shell's profile so it lasts",
+        rustup.display(),
+        dir.display()
+    ));
+}
+
+// Errors if running as root, and the root policy says to refuse
+fn check_root(config: &Config) -> Result<(), Error> {
+    match root::root_check(root::effective_uid()?, config.root_policy) {
+        RootCheck::NotRoot => {}
+        RootCheck::Warn => {
+            eprintln!(
+                "{}",
+                style::warning("running as root. This updates root's toolchains, not yours")
+            )
+        }
+        RootCheck::Refuse => {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Refusing to run as root, which would update root's toolchains. \
+                 Is the service installed as a user unit? Use --allow-root to run anyway",
+            )))
+        }
+    }
+
+    return Ok(());
+}
+
+/// Updates Rust straight away, without checking for updates or prompting
+///
+/// When `wait` is true, blocks until the update is finished, without
+/// opening a terminal. Otherwise the update runs in a new terminal window
+pub fn update_now(config: &Config, wait: bool) -> Result<Outcome, Error> {
+    check_root(config)?;
+
+    return update_now_with(
+        config,
+        &SystemRunner,
+        &StatePaths::for_config(config)?,
+        &[],
+        wait,
+    );
+}
+
+/// Updates only `toolchains`, like `nightly` or
+/// `stable-x86_64-unknown-linux-gnu`, straight away, without checking for
+/// updates or prompting. Blocks until the update is finished
+///
+/// Errors without updating anything if any of them aren't installed
+pub fn update_toolchains(config: &Config, toolchains: &[String]) -> Result<Outcome, Error> {
+    check_root(config)?;
+
+    return update_toolchains_with(
+        config,
+        &SystemRunner,
+        &StatePaths::for_config(config)?,
+        toolchains,
+    );
+}
+
+// `update_toolchains`, running commands with `runner`
+fn update_toolchains_with(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    toolchains: &[String],
+) -> Result<Outcome, Error> {
+    let installed = components::installed_toolchains(runner, &rustup_filepath(config)?)?;
+
+    // A channel, like `nightly`, is the toolchain of that channel for any
+    // target
+    let unknown: Vec<&str> = toolchains
+        .iter()
+        .filter(|name| {
+            return !installed
+                .iter()
+                .any(|x| x == *name || parse::split_toolchain_name(x).0 == name.as_str());
+        })
+        .map(|x| x.as_str())
+        .collect();
+    if !unknown.is_empty() {
+        return Err(Error::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Not installed: {}. Installed toolchains are {}",
+                unknown.join(", "),
+                installed.join(", ")
+            ),
+        )));
+    }
+
+    return update_now_with(config, runner, paths, toolchains, true);
+}
+
+// Lines saying the version each of `toolchains` is on, like `Now on stable
+// 1.80.1`, from a check after updating them. Everything is listed if
+// `toolchains` is empty. Toolchains sharing a channel are told apart by
+// target
+fn installed_versions(updates: &[ParsedUpdate], toolchains: &[String]) -> Vec<String> {
+    return updates
+        .iter()
+        .filter(|x| toolchains.is_empty() || toolchains.contains(&x.name))
+        .filter_map(|x| {
+            let current = x.current?;
+            let mut line = format!("Now on {} {current}", x.short_name(updates));
+            if let Some(new) = x.new {
+                line.push_str(&format!(", but {new} is still available"));
+            }
+
+            return Some(line);
+        })
+        .collect();
+}
+
+// Checks again after an update, printing the versions now installed. The
+// update itself worked, so a failed check is only printed
+fn recheck_versions(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    toolchains: &[String],
+) {
+    let lines = rustup_filepath(config)
+        .map_err(Error::Io)
+        .and_then(|rustup| {
+            get_rustup_check(
+                runner,
+                &rustup,
+                &rustup_env(config),
+                Some(&paths.check_flags),
+            )
+        });
+
+    match lines {
+        Ok(lines) => {
+            let updates = parse::parse_rustup_check(&lines);
+            for line in installed_versions(&updates, toolchains) {
+                println!("{line}");
+            }
+        }
+        Err(error) => eprintln!("Couldn't check the versions installed: {error}"),
+    }
+}
+
+// Shows a notification saying how the update of `toolchains` went
+fn notify_update(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    outcome: &Outcome,
+    toolchains: &[String],
+) {
+    let updated = match toolchains.is_empty() {
+        true => "Rust".to_string(),
+        false => toolchains.join(", "),
+    };
+
+    match outcome {
+        Outcome::Updated => notify::notify(
+            runner,
+            "Rust updated",
+            &format!("Updated {updated}"),
+            config.notify_style,
+        ),
+        Outcome::UpdateFailed { code, results } => {
+            let mut body = format!(
+                "Updating {updated} failed with exit code {}",
+                code.map(|x| x.to_string()).unwrap_or("none".to_string())
+            );
+            if let Some(description) = describe_update_results(results) {
+                body = format!("{body}. {description}");
+            }
+
+            notify::notify(
+                runner,
+                "Rust update failed",
+                &body,
+                notify::NotifyStyle::FAILURE,
+            );
+        }
+        _ => {}
+    }
+}
+
+fn update_now_with(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    toolchains: &[String],
+    wait: bool,
+) -> Result<Outcome, Error> {
+    let result = update_rust(config, runner, paths, toolchains, wait);
+    if config.keep_stats {
+        record_stats(&paths.stats, &result);
+    }
+    let outcome = result?;
+
+    if config.notify {
+        notify_update(config, runner, &outcome, toolchains);
+    }
+
+    match &outcome {
+        Outcome::UpdateStarted => {
+            println!("Updating in the background. A notification will say when it's done");
+
+            // Like a finished update, it shouldn't be asked about again
+            if !config.no_snooze {
+                set_no_update_flag(&paths.flag, false)?;
+            }
+            last_check::remove_last_check(&paths.last_check)?;
+        }
+        Outcome::Updated => {
+            println!(
+                "{}",
+                style::paint("Update complete", Color::Green, Stream::Stdout)
+            );
+            if !config.no_snooze {
+                set_no_update_flag(&paths.flag, false)?;
+            }
+
+            // The saved check still lists the updates just installed
+            last_check::remove_last_check(&paths.last_check)?;
+
+            if config.recheck_after_update {
+                recheck_versions(config, runner, paths, toolchains);
+            }
+        }
+        Outcome::UpdateFailed { results, .. } => {
+            let message = describe_update_results(results)
+                .unwrap_or("Update didn't run successfully!".to_string());
+            eprintln!("{}", style::paint(&message, Color::Red, Stream::Stderr));
+        }
+        _ => eprintln!(
+            "{}",
+            style::paint(
+                "Update didn't run successfully!",
+                Color::Red,
+                Stream::Stderr
+            )
+        ),
+    }
+
+    return Ok(outcome);
+}
+
+// Counts how the update went in the stats at `path`. Failing to save them
+// doesn't fail the update
+fn record_stats(path: &path::Path, result: &Result<Outcome, Error>) {
+    let saved = match result {
+        Ok(Outcome::Updated) => stats::record_success(path),
+        Ok(Outcome::UpdateFailed { code, results }) => {
+            let mut reason = format!("The update failed with {}", runner::describe_status(*code));
+            if let Some(description) = describe_update_results(results) {
+                reason = format!("{reason}. {description}");
+            }
+
+            stats::record_failure(path, unix_now(), &reason)
+        }
+        Err(error) => stats::record_failure(path, unix_now(), &error.to_string()),
+        // Like a detached update, which hasn't finished yet
+        Ok(_) => return,
+    };
+
+    if let io::Result::Err(error) = saved {
+        eprintln!("Couldn't save the update stats: {error}");
+    }
+}
+
+/// Prints how many updates worked and failed, and why the last failure
+/// did. Used by `--stats`
+pub fn print_stats(config: &Config) -> io::Result<()> {
+    if !config.keep_stats {
+        println!("Stats aren't being kept. Set keep_stats = true in the config file to keep them");
+    }
+
+    let stats = stats::read_stats(&StatePaths::for_config(config)?.stats)?;
+    print!("{}", stats.describe(unix_now()));
+
+    return io::Result::Ok(());
+}
+
+/// Explains whether the next check would prompt, going by the no-update
+/// flag, without checking for updates. Used by `--why`
+pub fn print_why(config: &Config) -> io::Result<()> {
+    let paths = StatePaths::for_config(config)?;
+    print!("{}", explain_prompt(config, &paths.flag, unix_now()));
+
+    return io::Result::Ok(());
+}
+
+// Why the flag at `path` does or doesn't stop a prompt at `now`, the same
+// way `should_prompt_at` decides it
+fn explain_prompt(config: &Config, path: &path::Path, now: u64) -> String {
+    let mut text = format!(
+        "No-update flag: {}\nSnooze length: {}\n",
+        path.display(),
+        snooze::format_duration(config.snooze)
+    );
+
+    if config.no_snooze {
+        text.push_str("The flag isn't read, as no_snooze is set\nWould prompt: yes\n");
+        return text;
+    }
+
+    let flag = match snooze::read_flag(path) {
+        io::Result::Ok(Some(flag)) => flag,
+        io::Result::Ok(None) => {
+            text.push_str("The flag isn't set\nWould prompt: yes\n");
+            return text;
+        }
+        io::Result::Err(error) => {
+            text.push_str(&format!(
+                "The flag can't be read, so it's ignored: {error}\nWould prompt: yes\n"
+            ));
+            return text;
+        }
+    };
+
+    if flag.since > now {
+        text.push_str(&format!(
+            "Snoozed at {}, in the future, so it's ignored\n",
+            flag.since
+        ));
+    } else {
+        text.push_str(&format!(
+            "Snoozed {} ago, at {}, for {}\n",
+            snooze::format_duration(now - flag.since),
+            flag.since,
+            snooze::format_duration(flag.until.saturating_sub(flag.since))
+        ));
+    }
+
+    match SnoozeStatus::from_flag(Some(flag.clone()), now) {
+        SnoozeStatus::Active { remaining, .. } => text.push_str(&format!(
+            "Remaining: {}\nWould prompt: no\n",
+            snooze::format_duration(remaining.as_secs())
+        )),
+        _ if flag.since > now => text.push_str("Would prompt: yes\n"),
+        _ => text.push_str(&format!(
+            "Ran out {} ago\nWould prompt: yes\n",
+            snooze::format_duration(now - flag.until)
+        )),
+    }
+
+    return text;
+}
+
+/// Names of the installed toolchains, like `stable-x86_64-unknown-linux-gnu`,
+/// from `rustup toolchain list`. Whether they have updates isn't checked
+pub fn list_installed_toolchains() -> Result<Vec<String>, Error> {
+    return components::installed_toolchains(&SystemRunner, &get_rustup_filepath()?);
+}
+
+/// Checks every toolchain's clippy and rustfmt were built from the same
+/// release as its rustc, printing how to reinstall any that weren't
+pub fn check_components() -> Result<Vec<ComponentMismatch>, Error> {
+    let mismatches = components::find_mismatches(&SystemRunner, &get_rustup_filepath()?)?;
+
+    if mismatches.is_empty() {
+        println!("All components match their toolchains");
+    }
+
+    for mismatch in &mismatches {
+        println!(
+            "{} of {} doesn't match rustc\n  rustc: {}\n  {}: {}",
+            mismatch.component,
+            mismatch.toolchain,
+            mismatch.rustc_version,
+            mismatch.component,
+            mismatch.component_version
+        );
+        println!("Reinstall it with:\n  {}", mismatch.reinstall_command());
+    }
+
+    return Ok(mismatches);
+}
+
+// First line of `rustup --version`, like `rustup 1.27.1 (54dd3d00f 2024-04-24)`.
+// Also works for rustc
+fn rustup_version(runner: &dyn CommandRunner, rustup: &path::Path) -> Option<String> {
+    let output = runner
+        .run(&CommandSpec::new(&rustup.to_string_lossy(), &["--version"]))
+        .ok()?;
+    if !output.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    return Some(stdout.lines().next()?.trim().to_string());
+}
+
+/// Prints a one line summary of the updates, like
+/// `rust: 1 update (stable 1.80.0→1.80.1)`, for a shell prompt or status bar
+///
+/// Never prompts or updates. A recent check is reused, as with a normal run.
+/// If checking fails, that's printed in the same line instead. Errors if
+/// it couldn't get that far, like when the state directory can't be found
+pub fn print_oneline(config: &Config) -> Result<(), Error> {
+    return print_status(config, Format::Oneline);
+}
+
+/// Prints the result of checking for updates in `format`. Used by
+/// `--format`
+///
+/// Never prompts or updates. A recent check is reused, as with a normal run.
+/// Errors if checking fails, unless the format prints that itself
+pub fn print_status(config: &Config, format: Format) -> Result<(), Error> {
+    let output = status_with(
+        config,
+        &SystemRunner,
+        &StatePaths::for_config(config)?,
+        format,
+    )?;
+    print!("{output}");
+
+    return Ok(());
+}
+
+// The output of `print_status`, running commands with `runner`
+//
+// Only rustup is run, never a prompt, notification, terminal or download,
+// so it works on minimal systems with nothing else installed
+fn status_with(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    format: Format,
+) -> Result<String, Error> {
+    let check = || {
+        return get_rustup_check_cached(
+            runner,
+            &paths.last_check,
+            unix_now(),
+            config.check_interval,
+            &rustup_filepath(config)?,
+            &rustup_env(config),
+            Some(&paths.check_flags),
+        );
+    };
+    let writer = format::writer_for(format);
+
+    match check() {
+        Ok(lines) => {
+            let lines = without_rustup_self(config, lines);
+            return Ok(writer.updates(&parse::parse_rustup_check(&lines)));
+        }
+        Err(error) => match writer.error(&error) {
+            Some(output) => return Ok(output),
+            None => return Err(error),
+        },
+    }
+}
+
+/// Prints how each line of `rustup check` output saved in the file at
+/// `path` is parsed, with everything read from it. Used by `--parse-file`,
+/// so a line that's misread can be attached to a bug report
+pub fn print_parsed_file(path: &path::Path) -> Result<(), Error> {
+    print!("{}", describe_parsed_file(path)?);
+
+    return Ok(());
+}
+
+/// Prints which hosts have updates pending, from the `rustup check` output
+/// saved from each in a file in `dir`, named by the host. As JSON with
+/// `json`. Used by `--aggregate`
+pub fn print_aggregate(dir: &path::Path, json: bool) -> Result<(), Error> {
+    let hosts = aggregate::read_hosts(dir)?;
+    match json {
+        true => print!("{}", aggregate::hosts_json(&hosts)),
+        false => print!("{}", aggregate::describe_hosts(&hosts)),
+    }
+
+    return Ok(());
+}
+
+// The lines of `rustup check` output saved in the file at `path`, as text
+// or JSON. Colors are taken out, like from the check itself
+fn check_file_lines(path: &path::Path) -> Result<Vec<String>, Error> {
+    let text = std::fs::read_to_string(path).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("Couldn't read {}: {error}", path.display()),
+        )
+    })?;
+    let text = style::strip_ansi(&text);
+
+    // JSON is read as the lines it stands for
+    let lines = if text.trim_start().starts_with('{') {
+        parse::json_check_lines(&text).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Malformed JSON in {}: {error}", path.display()),
+            )
+        })?
+    } else {
+        text.lines()
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect()
+    };
+
+    return Ok(lines);
+}
+
+// What `print_parsed_file` prints. Lines are skipped or parsed the same
+// way as the output of `rustup check` itself
+fn describe_parsed_file(path: &path::Path) -> Result<String, Error> {
+    let lines = check_file_lines(path)?;
+
+    let mut output = String::new();
+    for (number, line) in lines.iter().enumerate() {
+        output.push_str(&format!("Line {}: {line}\n", number + 1));
+
+        if is_diagnostic_line(line) {
+            output.push_str("  Skipped, as one of rustup's log messages\n");
+        } else if parse::is_override_note(line) {
+            output.push_str("  Skipped, as a note about an override\n");
+        } else {
+            match parse::parse_rustup_check_line(line) {
+                Some(update) => output.push_str(&describe_parsed(&update)),
+                None => output.push_str("  Not a toolchain entry\n"),
+            }
+        }
+    }
+
+    return Ok(output);
+}
+
+// Everything parsed from an entry, a field to a line
+fn describe_parsed(update: &ParsedUpdate) -> String {
+    let version = |version: Option<Version>, build: &Option<parse::Build>| {
+        let Some(version) = version else {
+            return "none".to_string();
+        };
+
+        match build {
+            Some(build) => {
+                return format!("{version}, commit {} from {}", build.commit, build.date)
+            }
+            None => return version.to_string(),
+        }
+    };
+    let kind = match (update.current, update.new) {
+        (_, None) => "up to date",
+        (None, Some(_)) => "update from an unknown version",
+        (Some(current), Some(new)) => match policy::version_jump(current, new) {
+            policy::Jump::Major => "major update",
+            policy::Jump::Minor => "minor update",
+            policy::Jump::Patch => "patch update",
+            policy::Jump::None => "update to a new build of the same version",
+        },
+    };
+
+    return format!(
+        "  Name: {}\n  Channel: {}\n  Target: {}\n  Current: {}\n  New: {}\n  Kind: {kind}\n",
+        update.name,
+        update.channel(),
+        update.target().unwrap_or("none"),
+        version(update.current, &update.current_build),
+        version(update.new, &update.new_build),
+    );
+}
+
+/// Prints `config` as a config file, or as JSON with `json`. Used by
+/// `--show-config`, to see what the config file, environment variables and
+/// command line add up to
+pub fn show_config(config: &Config, json: bool) {
+    if json {
+        println!("{}", config.to_json());
+    } else {
+        print!("{}", config.to_toml());
+    }
+}
+
+// Updates shown by `--preview-prompt` when there aren't real ones
+const PREVIEW_UPDATES: [&str; 2] = [
+    "stable - Update available : 1.80.0 -> 1.80.1",
+    "rustup - Update available : 1.27.0 -> 1.27.1",
+];
+
+/// Shows the prompt as it would be for the updates found, or for made up
+/// ones if there aren't any, then returns the answer without acting on it.
+/// Used by `--preview-prompt`
+pub fn preview_prompt(config: &Config) -> Result<UpdatePromptAnswer, Error> {
+    return preview_prompt_with(
+        config,
+        &SystemRunner,
+        &StatePaths::for_config(config)?,
+        backend_for(config).as_ref(),
+    );
+}
+
+// Same as `preview_prompt`, checking with `runner`, and prompting with
+// `backend`. Nothing is updated or snoozed, whatever the answer
+fn preview_prompt_with(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    backend: &dyn PromptBackend,
+) -> Result<UpdatePromptAnswer, Error> {
+    let check = get_rustup_check_cached(
+        runner,
+        &paths.last_check,
+        unix_now(),
+        config.check_interval,
+        &rustup_filepath(config)?,
+        &rustup_env(config),
+        Some(&paths.check_flags),
+    );
+
+    let mut lines = without_rustup_self(config, check.unwrap_or_default());
+    let made_up = get_new_versions_owned(&lines).values().all(|x| x.is_none());
+    if made_up {
+        println!("No updates found, so previewing made up ones");
+        lines = PREVIEW_UPDATES.iter().map(|x| x.to_string()).collect();
+    }
+
+    let new_versions = get_new_versions_owned(&lines);
+    let updates = parse::parse_rustup_check(&lines);
+    let default_toolchain = settings::read_default_toolchain(&paths.rustup_home);
+    let new_release = new_release_reason(&updates);
+    let major_release = major_release_word(&updates);
+    let urgency = match &new_release {
+        Some(reason) => Urgency::NewRelease(reason, major_release.as_deref()),
+        None => Urgency::Normal,
+    };
+
+    let answer = prompt_for_update(
+        backend,
+        &new_versions,
+        default_toolchain.as_deref(),
+        urgency,
+        // Made up versions aren't on the dist server
+        &if made_up {
+            UpdateDetails::default()
+        } else {
+            update_details(config, runner, &updates, unix_now())
+        },
+        config,
+        &[],
+    )?;
+    println!("Answered {answer:?}. It's a preview, so nothing was done");
+
+    return Ok(answer);
+}
+
+/// Prints information about the setup, for finding out why updates don't
+/// work as expected
+///
+/// Warns if there's more than one rustup installed. They can disagree
+/// about versions, and `rustup update` only updates the toolchains of one
+pub fn diagnose() -> Result<(), Error> {
+    let runner = SystemRunner;
+
+    match config::config_path() {
+        Some(path) if path.exists() => println!("Config file: {}", path.display()),
+        Some(path) => println!("Config file: {} (doesn't exist)", path.display()),
+        None => println!("Config file: none, as HOME isn't set to an absolute path"),
+    }
+
+    let rustup_path = get_rustup_filepath()?;
+    println!("Updating with: {}", rustup_path.display());
+    if let Some(dir) = rustup_path.parent().filter(|x| x.exists()) {
+        if !diagnose::is_writable_dir(dir) {
+            println!(
+                "  {} is read-only, so rustup can't update itself",
+                dir.display()
+            );
+        }
+    }
+
+    if diagnose::rustup_off_path(&env::var_os("PATH").unwrap_or_default(), &rustup_path) {
+        eprintln!("{}", off_path_warning(&rustup_path));
+    }
+
+    let rustups = diagnose::find_rustups(
+        &env::var_os("PATH").unwrap_or_default(),
+        &diagnose::usual_rustup_paths(rustup_path),
+    );
+
+    println!("rustup installations:");
+    if rustups.is_empty() {
+        println!("  None found");
+    }
+    for rustup in &rustups {
+        let version = rustup_version(&runner, rustup);
+        let manager = std::fs::canonicalize(rustup)
+            .ok()
+            .and_then(|x| diagnose::package_store_manager(&x));
+        println!(
+            "  {}: {}{}",
+            rustup.display(),
+            version.as_deref().unwrap_or("unknown version"),
+            manager
+                .map(|x| format!(", managed by {x}, so it's updated with {x}"))
+                .unwrap_or_default()
+        );
+    }
+
+    if rustups.len() > 1 {
+        eprintln!(
+            "{}",
+            style::warning(&format!(
+                "found {} rustup installations. They can disagree about versions, and \
+                 updating only updates the toolchains of one",
+                rustups.len()
+            ))
+        );
+    }
+
+    // From the last check, so nothing is downloaded
+    let last_check = StatePaths::from_home()
+        .and_then(|x| last_check::read_last_check(&x.last_check))
+        .unwrap_or(None);
+    if let Some(last_check) = last_check {
+        println!(
+            "Toolchains at the last check, {} minute(s) ago:",
+            unix_now().saturating_sub(last_check.checked_at) / 60
+        );
+        for update in parse::parse_rustup_check(&last_check.lines) {
+            println!("  {}", describe_installed(&update));
+        }
+    }
+
+    // Only shown, never updated
+    let path_var = env::var_os("PATH").unwrap_or_default();
+    let system_rustcs = diagnose::find_system_rustcs(&path_var, &rustups);
+    if !system_rustcs.is_empty() {
+        println!("rustc not from rustup:");
+    }
+    for rustc in &system_rustcs {
+        let version = rustup_version(&runner, rustc);
+        println!(
+            "  {}: {}",
+            rustc.display(),
+            version.as_deref().unwrap_or("unknown version")
+        );
+    }
+    if let Some(first) = diagnose::first_on_path(&path_var, "rustc") {
+        if system_rustcs.contains(&first) {
+            eprintln!(
+                "{}",
+                style::warning(&format!(
+                    "{} is first on PATH, so cargo uses it rather than rustup's toolchains. \
+                     It isn't updated by rustup",
+                    first.display()
+                ))
+            );
+        }
+    }
+
+    return Ok(());
+}
+
+/// Main function
+///
+/// Automaticity checks for new Rust versions prompting user to update
+/// Rust. Updates Rust in terminal window if asked. Doesn't ask for a day
+/// if told not to update
+///
+/// Errors with `Error::NoInternet` if there's no internet connection
+///
+/// Panics if couldn't find the `rustup` or `zenity` command
+///
+/// Panics if rustup update doesn't work successfully
+pub fn auto_update() -> Result<Outcome, Error> {
+    return auto_update_with_config(&Config::default(), None);
+}
+
+/// Asks whether to update, given the toolchains, and rustup, with updates.
+/// Lets embedders show the prompt in their own UI
+pub type PromptCallback<'a> = &'a mut dyn FnMut(&[ParsedUpdate]) -> UpdatePromptAnswer;
+
+/// Same as `auto_update`, but with the given settings
+///
+/// With a `prompt` callback, it's asked instead of showing a zenity dialog.
+/// If it answers `NoUpdateFound`, nothing is updated or snoozed
+///
+/// Errors without checking if running as root, unless the root policy
+/// says to only warn
+pub fn auto_update_with_config(
+    config: &Config,
+    prompt: Option<PromptCallback<'_>>,
+) -> Result<Outcome, Error> {
+    check_root(config)?;
+
+    return auto_update_with(
+        config,
+        &SystemRunner,
+        &StatePaths::for_config(config)?,
+        backend_for(config).as_ref(),
+        prompt,
+    );
+}
+
+/// Same as `auto_update_with_config`, but commands are run with `runner`,
+/// the prompt is shown with `backend`, and state is kept in `paths`
+///
+/// Doesn't check if running as root. Lets the whole run be simulated
+/// without touching the system, like in tests
+pub fn auto_update_with_runner(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    backend: &dyn PromptBackend,
+    paths: &StatePaths,
+) -> Result<Outcome, Error> {
+    return auto_update_with(config, runner, paths, backend, None);
+}
+
+fn auto_update_with(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    backend: &dyn PromptBackend,
+    prompt: Option<PromptCallback<'_>>,
+) -> Result<Outcome, Error> {
+    // Before checking, as updating after a reboot doesn't need to
+    if paths.next_boot.exists() && !config.dry_run {
+        let boot_id = next_boot::current_boot_id()?;
+        if let Some(outcome) = run_boot_intent(config, runner, paths, &boot_id)? {
+            return Ok(outcome);
+        }
+    }
+
+    let now = unix_now();
+    let rustup = rustup_filepath(config)?;
+    // A missing rustup is found by the check
+    if rustup.exists() {
+        check_rustup_integrity(config, &rustup)?;
+    }
+    // The server rustup downloads from, which is a mirror's if it's set
+    let dist_server = dist_server(config);
+
+    // Only when rustup would check, not when a recent check is reused
+    if config.probe_network
+        && !last_check::read_last_check(&paths.last_check)?
+            .is_some_and(|x| x.is_fresh(now, config.check_interval))
+    {
+        match network::probe_address(dist_server.as_deref()) {
+            Some(address) => {
+                network::probe(&network::TcpConnector, &address, network::PROBE_TIMEOUT)?
+            }
+            None => log::debug("The dist server isn't one to connect to. Not probing it"),
+        }
+    }
+    let rustup_lines = match get_rustup_check_cached(
+        runner,
+        &paths.last_check,
+        now,
+        config.check_interval,
+        &rustup,
+        &rustup_env(config),
+        Some(&paths.check_flags),
+    ) {
+        Ok(lines) => lines,
+        // Without rustup there's nothing to update. Quiet, unless asked, as
+        // this can run from a login script shared with machines without Rust
+        Err(Error::RustupNotFound) if !config.offer_install => {
+            log::debug(&format!(
+                "rustup not found at {}. Nothing to check",
+                rustup.display()
+            ));
+            return Ok(Outcome::NoRustup);
+        }
+        Err(Error::RustupNotFound) => {
+            println!("rustup isn't installed. Install it with:\n  {RUSTUP_INSTALL_COMMAND}");
+            return Err(Error::RustupNotFound);
+        }
+        Err(error) => return Err(error),
+    };
+
+    // On stderr, so anything reading stdout sees the same output either way.
+    // These are the entries read from its output, or the saved check, not
+    // every line it printed
+    if config.verbose {
+        eprintln!("Entries read from rustup check:");
+        for line in &rustup_lines {
+            eprintln!("  {line}");
+        }
+    }
+
+    let rustup_lines = without_rustup_self(config, rustup_lines);
+    let updates = parse::parse_rustup_check(&rustup_lines);
+
+    let newest_tested = policy::NEWEST_TESTED_RUSTUP;
+    if let Some(version) = policy::untested_rustup(&updates, newest_tested) {
+        eprintln!(
+            "{}",
+            style::warning(&format!(
+                "rustup {version} is newer than {}.{}, the newest version tested. Updates \
+                 might not be found correctly",
+                newest_tested.major, newest_tested.minor
+            ))
+        );
+    }
+
+    print_changes(config, paths, &updates)?;
+
+    // Read before anything forgets the check, like an update
+    let checked_at = match last_check::read_last_check(&paths.last_check)? {
+        Some(last_check) => last_check.checked_at,
+        None => now,
+    };
+
+    let outcome = respond_to_check(
+        config,
+        runner,
+        paths,
+        backend,
+        prompt,
+        &rustup_lines,
+        &updates,
+    )?;
+
+    // Written however the run ended, including dry runs
+    if let Some(report_path) = &config.report_path {
+        report::write_report(report_path, &outcome, &updates, now)?;
+    }
+    if let Some(metrics_path) = &config.metrics_path {
+        metrics::write_metrics(metrics_path, &updates, checked_at)?;
+    }
+
+    return Ok(outcome);
+}
+
+// Answers the prompt for real, changing the snooze and updating
+struct RunActions<'a> {
+    config: &'a Config,
+    runner: &'a dyn CommandRunner,
+    paths: &'a StatePaths,
+    new_versions: &'a NewVersions,
+    updates: &'a [ParsedUpdate],
+    pinned: &'a [settings::ToolchainOverride],
+}
+
+impl AnswerActions for RunActions<'_> {
+    fn snooze(&self) -> io::Result<()> {
+        if self.config.no_snooze {
+            println!("Snoozing is off, so asking again next run");
+            return io::Result::Ok(());
+        }
+
+        return snooze_updates(&self.paths.flag, self.new_versions, self.config.snooze);
+    }
+
+    fn snooze_short(&self) -> io::Result<()> {
+        if self.config.no_snooze {
+            println!("Snoozing is off, so asking again next run");
+            return io::Result::Ok(());
+        }
+
+        return snooze::write_flag(
+            &self.paths.flag,
+            &SnoozeFlag::new(unix_now(), snooze::SHORT_SNOOZE),
+        );
+    }
+
+    fn update(&self, toolchains: &[String]) -> Result<Outcome, Error> {
+        // Also starts the snoozes again from a day
+        let outcome = update_now_with(self.config, self.runner, self.paths, toolchains, false)?;
+
+        if outcome == Outcome::Updated {
+            run_channel_hooks(self.config, self.runner, self.updates);
+
+            for note in pinned_notes(self.pinned) {
+                println!("{note}");
+            }
+        }
+
+        return Ok(outcome);
+    }
+}
+
+// Prints what changed since the versions seen by the last run, then saves
+// these ones for the next. Nothing is printed on the first run
+fn print_changes(config: &Config, paths: &StatePaths, updates: &[ParsedUpdate]) -> io::Result<()> {
+    if let Some(previous) = seen::read_seen(&paths.seen_versions)? {
+        let changes = seen::changes(&previous, updates);
+        if !changes.is_empty() {
+            println!("Since the last run:");
+        }
+        for change in changes {
+            println!("  {change}");
+        }
+    }
+
+    // A dry run leaves everything as it was
+    if config.dry_run {
+        return io::Result::Ok(());
+    }
+
+    return seen::write_seen(&paths.seen_versions, updates);
+}
+
+// Updates `toolchains`, which are allowed to auto update, without asking.
+// The snooze is left alone, as it's for the updates still to prompt for
+fn auto_update_toolchains(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    updates: &[ParsedUpdate],
+    toolchains: &[String],
+) -> Result<Outcome, Error> {
+    println!("Updating {} without asking", toolchains.join(", "));
+
+    // Counted, and cleaned up after, the same as an update that was asked for
+    let outcome = update_now_with(config, runner, paths, toolchains, true)?;
+    if outcome == Outcome::Updated {
+        let updated: Vec<ParsedUpdate> = updates
+            .iter()
+            .filter(|x| toolchains.contains(&x.name))
+            .cloned()
+            .collect();
+        run_channel_hooks(config, runner, &updated);
+    }
+
+    return Ok(outcome);
+}
+
+// Snoozes, prompts and updates, given the result of the check.
+// `rustup_lines` are the lines `rustup check` printed, and `updates` what
+// was parsed from them. Toolchains allowed to auto update are updated
+// without asking, and `prompt`, if given, asks in place of `backend`
+fn respond_to_check(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    paths: &StatePaths,
+    backend: &dyn PromptBackend,
+    prompt: Option<PromptCallback<'_>>,
+    rustup_lines: &[String],
+    updates: &[ParsedUpdate],
+) -> Result<Outcome, Error> {
+    let mut new_versions = get_new_versions_owned(rustup_lines);
+
+    // Another day's nightly of the same version isn't worth a prompt, so
+    // it's treated as up to date
+    let skipped: Vec<ParsedUpdate>;
+    let mut updates = updates;
+    if config.skip_nightly_date_bumps && updates.iter().any(policy::date_only_nightly) {
+        skipped = updates
+            .iter()
+            .cloned()
+            .map(|mut x| {
+                if policy::date_only_nightly(&x) {
+                    println!("Skipping {}, a new nightly of the same version", x.name);
+                    new_versions.insert(x.name.clone(), None);
+                    x.new = None;
+                    x.new_build = None;
+                }
+
+                return x;
+            })
+            .collect();
+        updates = &skipped;
+    }
+
+    let default_toolchain = settings::read_default_toolchain(&paths.rustup_home);
+
+    // Rust being too old is more urgent than a normal update
+    let mut minimum_warning = None;
+    let mut below_minimum = None;
+    if let Some(minimum) = config.minimum_rust_version {
+        if let Some(toolchain) =
+            policy::below_minimum_version(updates, minimum, default_toolchain.as_deref())
+        {
+            let current = toolchain.current.unwrap_or(minimum);
+            let warning = format!(
+                "{} {current} is below the minimum supported version {minimum}",
+                toolchain.short_name(updates)
+            );
+            eprintln!("{}", style::warning(&warning));
+
+            minimum_warning = Some(warning);
+            below_minimum = Some(current);
+        }
+    }
+
+    // No new versions
+    if new_versions.values().all(|x| x.is_none()) {
+        // Remove do not update flag
+        if !config.dry_run {
+            if !config.no_snooze {
+                set_no_update_flag(&paths.flag, false)?;
+            }
+
+            // Nothing left to prompt about
+            pending::take_pending(&paths.pending_prompt)?;
+        }
+
+        println!("No new updates available");
+
+        // Nothing to update to, but not fine either
+        if let Some(current) = below_minimum {
+            return Ok(Outcome::BelowMinimum { current });
+        }
+
+        return Ok(Outcome::UpToDate);
+    }
+
+    println!("Updates found:");
+    println!("{:?}", new_versions);
+
+    // Whatever happens next, even if snoozed
+    let hook_skips_prompt = !config.dry_run && run_update_available_hook(config, runner, updates);
+
+    // The update would be refused, so asking, or updating without asking,
+    // would only end in an error, every run
+    if !config.dry_run {
+        if let Some((manager, rustup)) = managed_rustup(config) {
+            println!(
+                "rustup is managed by {manager}, at {}, so it isn't updated here. Update Rust \
+                 with {manager} instead",
+                rustup.display()
+            );
+
+            return Ok(Outcome::PromptSkipped);
+        }
+    }
+
+    // Those allowed to auto update are updated first. Only the rest are
+    // prompted for
+    let auto: Vec<String> = updates
+        .iter()
+        .filter(|x| {
+            x.new.is_some()
+                && x.channel_kind() != Channel::Rustup
+                && config
+                    .auto_update
+                    .iter()
+                    .any(|allowed| *allowed == x.name || allowed == x.channel())
+        })
+        .map(|x| x.name.clone())
+        .collect();
+    let remaining: Vec<ParsedUpdate>;
+    if !auto.is_empty()
+        && !config.dry_run
+        && !(config.require_ac_power && on_battery(&config.power_supply_path)?)
+    {
+        let outcome = auto_update_toolchains(config, runner, paths, updates, &auto)?;
+
+        // rustup updates itself along with them
+        remaining = updates
+            .iter()
+            .filter(|x| !auto.contains(&x.name) && x.channel_kind() != Channel::Rustup)
+            .cloned()
+            .collect();
+        new_versions.retain(|name, _| !auto.contains(name) && name != "rustup");
+
+        if outcome != Outcome::Updated || new_versions.values().all(|x| x.is_none()) {
+            return Ok(outcome);
+        }
+        updates = &remaining;
+    }
+
+    if minimum_warning.is_none() && !config.no_snooze && !should_prompt(&paths.flag) {
+        println!("User said no update in the past... won't prompt for a while");
+
+        return Ok(Outcome::Snoozed);
+    }
+
+    // Snoozed for a short while, so a laptop left unplugged isn't checked
+    // and asked again every run
+    if config.require_ac_power && on_battery(&config.power_supply_path)? {
+        if config.dry_run || config.no_snooze {
+            println!("Running on battery. Won't update until plugged in...");
+        } else {
+            println!(
+                "Running on battery, so snoozing for {}. Won't update until plugged in...",
+                snooze::describe_duration(snooze::SHORT_SNOOZE)
+            );
+            snooze::write_flag(
+                &paths.flag,
+                &SnoozeFlag::new(unix_now(), snooze::SHORT_SNOOZE),
+            )?;
+        }
+
+        return Ok(Outcome::OnBattery);
+    }
+
+    if let Some(default_toolchain) = &default_toolchain {
+        let default_updating = new_versions.iter().any(|(name, new_version)| {
+            new_version.is_some() && settings::is_default_toolchain(name, default_toolchain)
+        });
+
+        if default_updating {
+            println!("Your default toolchain {default_toolchain} has an update");
+        }
+    }
+
+    if config.dry_run {
+        println!("Dry run, so not prompting or updating");
+
+        return Ok(Outcome::DryRun);
+    }
+
+    if hook_skips_prompt {
+        println!("The update available hook said not to prompt this time");
+
+        return Ok(Outcome::PromptSkipped);
+    }
+
+    let budget = config.daily_prompt_budget;
+    if minimum_warning.is_none()
+        && budget > 0
+        && budget::shown_today(&paths.prompts, unix_now())? >= budget
+    {
+        println!("Already prompted today. Prompting again tomorrow, or with --force");
+
+        return Ok(Outcome::PromptSkipped);
+    }
+
+    // A callback shows the prompt itself, so it never needs a display
+    let no_prompt = prompt.is_none() && !backend.can_show();
+    if no_prompt && config.no_prompt_action == NoPromptAction::Defer {
+        pending::write_pending(
+            &paths.pending_prompt,
+            &pending::PendingPrompt {
+                queued_at: unix_now(),
+                versions: versions_key(&new_versions),
+            },
+        )?;
+        println!("No display to prompt on. Prompting on the next run that has one");
+
+        return Ok(Outcome::PromptQueued);
+    }
+
+    if let Some(pending) = pending::take_pending(&paths.pending_prompt)? {
+        println!(
+            "Updates were found {} minute(s) ago, without a display to prompt on",
+            unix_now().saturating_sub(pending.queued_at) / 60
+        );
+    }
+
+    let pinned = pinned_directories();
+
+    let new_release = new_release_reason(updates);
+    let major_release = major_release_word(updates);
+    let urgency = match (&minimum_warning, &new_release) {
+        (Some(warning), _) => Urgency::Required(warning),
+        (None, Some(reason)) => Urgency::NewRelease(reason, major_release.as_deref()),
+        (None, None) => Urgency::Normal,
+    };
+
+    let answer = match prompt {
+        None if no_prompt => {
+            println!("No way to prompt, so updating without asking");
+
+            UpdatePromptAnswer::Update
+        }
+        Some(prompt) => {
+            let with_updates: Vec<ParsedUpdate> = updates
+                .iter()
+                .filter(|x| x.new.is_some())
+                .cloned()
+                .collect();
+
+            prompt(&with_updates)
+        }
+        None => prompt_for_update(
+            backend,
+            &new_versions,
+            default_toolchain.as_deref(),
+            urgency,
+            &update_details(config, runner, updates, unix_now()),
+            config,
+            &pinned_notes(&pinned),
+        )?,
+    };
+
+    // Whatever the answer, even closing it or letting it time out
+    if budget > 0 && answer != UpdatePromptAnswer::NoUpdateFound && !no_prompt {
+        budget::record_prompt(&paths.prompts, unix_now())?;
+    }
+
+    let actions = RunActions {
+        config,
+        runner,
+        paths,
+        new_versions: &new_versions,
+        updates,
+        pinned: &pinned,
+    };
+
+    return apply_answer(answer, config, updates, minimum_warning.is_some(), &actions);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use crate::*;
+
+    #[test]
+    fn pass() {
+        assert!(true);
+    }
+
+    #[test]
+    fn rustup_command_test() {
+        let rustup = get_rustup_filepath().unwrap();
+        let rustup_output = get_rustup_check(&SystemRunner, &rustup, &[], None).unwrap();
+
+        // A line for each toolchain installed, then rustup
+        let toolchains = components::installed_toolchains(&SystemRunner, &rustup).unwrap();
+        assert_eq!(rustup_output.len(), toolchains.len() + 1);
+        assert!(rustup_output.last().unwrap().contains("rustup"));
+    }
+
+    #[ignore = "Only passes without internet"]
+    #[test]
+    fn rustup_no_internet() {
+        assert!(matches!(
+            get_rustup_check(&SystemRunner, &get_rustup_filepath().unwrap(), &[], None),
+            Err(Error::NoInternet)
+        ));
+    }
+
+    #[test]
+    fn rustup_invalid_utf8() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput {
+                status: Some(0),
+                stdout: b"rustup - Up to date : 1.27.1 \xff\n".to_vec(),
+                stderr: Vec::new(),
+            })
+        });
+
+        assert!(matches!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None),
+            Err(Error::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn rustup_invalid_utf8_stderr() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput {
+                status: Some(1),
+                stdout: Vec::new(),
+                stderr: b"error: could not download file \xff".to_vec(),
+            })
+        });
+
+        // Reaches the no internet error, rather than failing to decode
+        assert!(matches!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None),
+            Err(Error::NoInternet)
+        ));
+    }
+
+    #[test]
+    fn no_rustup() {
+        let runner = MockRunner::new(|_| io::Result::Err(io::ErrorKind::NotFound.into()));
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        let paths = test_paths("no_rustup");
+
+        // Quietly does nothing, so it's fine on a machine without Rust
+        let outcome =
+            auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::NoRustup);
+        assert_eq!(outcome.exit_code(), 0);
+        assert!(backend.prompts().is_empty());
+        assert!(!paths.flag.exists());
+
+        let config = Config {
+            offer_install: true,
+            ..Config::default()
+        };
+        assert!(matches!(
+            auto_update_with_runner(&config, &runner, &backend, &paths),
+            Err(Error::RustupNotFound)
+        ));
+    }
+
+    #[test]
+    fn below_minimum_up_to_date() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "stable-x86_64-unknown-linux-gnu - Up to date : 1.79.0\n\
+                 rustup - Up to date : 1.27.1\n",
+                "",
+            ))
+        });
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        let config = Config {
+            minimum_rust_version: Some(Version::new(1, 80, 0)),
+            ..Config::default()
+        };
+
+        // Nothing to prompt for, but it isn't up to date either
+        let outcome = auto_update_with_runner(
+            &config,
+            &runner,
+            &backend,
+            &test_paths("below_minimum_up_to_date"),
+        )
+        .unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::BelowMinimum {
+                current: Version::new(1, 79, 0)
+            }
+        );
+        assert_eq!(outcome.exit_code(), 1);
+        assert!(backend.prompts().is_empty());
+    }
+
+    #[test]
+    fn rustup_check_crlf() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1\r\n\
+                 nightly-x86_64-unknown-linux-gnu - Up to date : 1.82.0-nightly\r\r\n\
+                 \r\n\
+                 rustup - Up to date : 1.27.1 \r\n",
+                "",
+            ))
+        });
+        let lines = get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap();
+
+        assert_eq!(
+            lines,
+            [
+                "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1",
+                "nightly-x86_64-unknown-linux-gnu - Up to date : 1.82.0-nightly",
+                "rustup - Up to date : 1.27.1",
+            ]
+        );
+        let updates = parse::parse_rustup_check(&lines);
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[0].new, Some(Version::new(1, 80, 1)));
+        assert_eq!(updates[2].current, Some(Version::new(1, 27, 1)));
+    }
+
+    #[test]
+    fn colored_rustup_check() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "\x1b[1mstable-x86_64-unknown-linux-gnu - \x1b[0m\x1b[1;33mUpdate available\x1b[0m : \
+                 1.80.0 -> \x1b[1m1.80.1\x1b[0m\n\
+                 \x1b[1mrustup - \x1b[0m\x1b[1;32mUp to date\x1b[0m : 1.27.1\n",
+                "",
+            ))
+        });
+        let lines = get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap();
+
+        assert_eq!(
+            lines,
+            [
+                "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1",
+                "rustup - Up to date : 1.27.1",
+            ]
+        );
+        let updates = parse::parse_rustup_check(&lines);
+        assert_eq!(updates[0].current, Some(Version::new(1, 80, 0)));
+        assert_eq!(updates[0].new, Some(Version::new(1, 80, 1)));
+        assert_eq!(updates[1].new, None);
+
+        // Asked not to color in the first place
+        assert!(runner.calls()[0]
+            .env
+            .contains(&("RUSTUP_TERM_COLOR".to_string(), "never".to_string())));
+    }
+
+    #[test]
+    fn rustup_check_on_stderr() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "",
+                "info: syncing channel updates for 'stable-x86_64-unknown-linux-gnu'\n\
+                 stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1\n\
+                 rustup - Up to date : 1.27.1\n",
+            ))
+        });
+        assert_eq!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap(),
+            [
+                "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1",
+                "rustup - Up to date : 1.27.1",
+            ]
+        );
+
+        // Progress alone isn't a result, and stdout wins when it has them
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "",
+                "info: syncing channel updates\nerror - something : happened\n",
+            ))
+        });
+        assert!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None)
+                .unwrap()
+                .is_empty()
+        );
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "rustup - Up to date : 1.27.1\n",
+                "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1\n",
+            ))
+        });
+        assert_eq!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap(),
+            ["rustup - Up to date : 1.27.1"]
+        );
+    }
+
+    #[test]
+    fn update_named_toolchains() {
+        let runner = MockRunner::new(|command| match command.args[0].as_str() {
+            "toolchain" => {
+                return io::Result::Ok(CommandOutput::new(
+                    0,
+                    "stable-x86_64-unknown-linux-gnu (default)\n\
+                     nightly-x86_64-unknown-linux-gnu\n",
+                    "",
+                ))
+            }
+            _ => return io::Result::Ok(CommandOutput::new(0, "", "")),
+        });
+        let toolchains = [
+            "nightly".to_string(),
+            "stable-x86_64-unknown-linux-gnu".to_string(),
+        ];
+
+        let outcome = update_toolchains_with(
+            &Config::default(),
+            &runner,
+            &test_paths("update_named_toolchains"),
+            &toolchains,
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::Updated);
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[1].args,
+            ["update", "nightly", "stable-x86_64-unknown-linux-gnu"]
+        );
+
+        // Nothing is updated if any aren't installed
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "stable-x86_64-unknown-linux-gnu\n",
+                "",
+            ))
+        });
+        let error = update_toolchains_with(
+            &Config::default(),
+            &runner,
+            &test_paths("update_unknown_toolchains"),
+            &["stable".to_string(), "beta".to_string()],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Not installed: beta. Installed toolchains are stable-x86_64-unknown-linux-gnu"
+        );
+        assert_eq!(error.exit_code(), 2);
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn rustup_check_errors() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Err(io::Error::new(io::ErrorKind::NotFound, "No such file"))
+        });
+        assert!(matches!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None),
+            Err(Error::RustupNotFound)
+        ));
+
+        // Found, but couldn't be started
+        let runner = MockRunner::new(|_| {
+            io::Result::Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Permission denied",
+            ))
+        });
+        assert!(matches!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None),
+            Err(Error::RustupPermissionDenied)
+        ));
+
+        // Started, then killed for taking too long
+        let runner = MockRunner::new(|command| {
+            assert_eq!(command.timeout, Some(CHECK_TIMEOUT));
+            return io::Result::Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "rustup was killed after 300 seconds",
+            ));
+        });
+        assert!(matches!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None),
+            Err(Error::CheckTimeout { seconds: 300 })
+        ));
+
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                1,
+                "",
+                "error: invalid toolchain name\nmore detail\n",
+            ))
+        });
+        match get_rustup_check(&runner, path::Path::new("rustup"), &[], None) {
+            Err(Error::RustupFailed { status, message }) => {
+                assert_eq!(status, Some(1));
+                assert_eq!(message, "error: invalid toolchain name");
+            }
+            x => panic!("Expected RustupFailed, got {x:?}"),
+        }
+    }
+
+    #[test]
+    fn rustup_noisy_stderr_on_success() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1\n",
+                "warning: shim: could not download file of release notes\n\
+                 error: could not download file (cached, ignoring)\n",
+            ))
+        });
+
+        assert_eq!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap(),
+            ["stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1"]
+        );
+    }
+
+    #[test]
+    fn download_error_matching() {
+        assert!(is_download_error(
+            "info: syncing channel updates\n\
+             error: could not download file from 'https://static.rust-lang.org/dist/channel-rust-stable.toml.sha256'"
+        ));
+        assert!(!is_download_error(
+            "warning: could not download file from mirror, trying another"
+        ));
+    }
+
+    #[test]
+    fn rustup_check_rate_limited() {
+        let path = test_util::temp_dir("rustup_check_rate_limited").join("last_check");
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1\n",
+                "",
+            ))
+        });
+
+        let first = get_rustup_check_cached(
+            &runner,
+            &path,
+            1000,
+            3600,
+            path::Path::new("rustup"),
+            &[],
+            None,
+        )
+        .unwrap();
+        let second = get_rustup_check_cached(
+            &runner,
+            &path,
+            1000 + 3599,
+            3600,
+            path::Path::new("rustup"),
+            &[],
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(runner.calls().len(), 1);
+        assert_eq!(first, second);
+
+        // Checks again when outside the window, or forced with no window
+        get_rustup_check_cached(
+            &runner,
+            &path,
+            1000 + 3600,
+            3600,
+            path::Path::new("rustup"),
+            &[],
+            None,
+        )
+        .unwrap();
+        assert_eq!(runner.calls().len(), 2);
+
+        get_rustup_check_cached(
+            &runner,
+            &path,
+            1000 + 3600,
+            0,
+            path::Path::new("rustup"),
+            &[],
+            None,
+        )
+        .unwrap();
+        assert_eq!(runner.calls().len(), 3);
+    }
+
+    #[test]
+    fn rustup_check_json() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                r#"{"toolchains": [{"name": "stable", "status": "update-available",
+                    "current_version": "1.80.0", "new_version": "1.80.1"}]}"#,
+                "",
+            ))
+        });
+
+        assert_eq!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap(),
+            ["stable - Update available : 1.80.0 -> 1.80.1"]
+        );
+        assert_eq!(runner.calls()[0].args, ["--quiet", "check", "--json"]);
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn rustup_check_quiet_fallback() {
+        let runner = MockRunner::new(|command| {
+            if command.args.contains(&"--quiet".to_string()) {
+                return io::Result::Ok(CommandOutput::new(
+                    1,
+                    "",
+                    "error: unexpected argument '--quiet' found",
+                ));
+            }
+
+            return io::Result::Ok(CommandOutput::new(
+                0,
+                "info: syncing channel updates for 'stable-x86_64-unknown-linux-gnu'\n\
+                 stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1\n\
+                 warning: the 'cargo' binary is installed twice\n",
+                "",
+            ));
+        });
+
+        // Diagnostic lines are stripped before parsing
+        assert_eq!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap(),
+            ["stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1"]
+        );
+        assert_eq!(runner.calls()[1].args, ["check", "--json"]);
+
+        // Other failures aren't retried
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(1, "", "error: bad settings file"))
+        });
+        assert!(get_rustup_check(&runner, path::Path::new("rustup"), &[], None).is_err());
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn rustup_check_override_note() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "note: the toolchain 'nightly-2024-07-01' is in use - overridden by \
+                 '/home/daisy/project/rust-toolchain.toml'\n\
+                 stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1\n",
+                "",
+            ))
+        });
+
+        assert_eq!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap(),
+            ["stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1"]
+        );
+
+        // Away from any override in the current directory
+        assert_eq!(runner.calls()[0].dir, Some(home::home_dir().unwrap()));
+    }
+
+    #[test]
+    fn rustup_check_text_fallback() {
+        let runner = MockRunner::new(|command| {
+            if command.args.contains(&"--json".to_string()) {
+                return io::Result::Ok(CommandOutput::new(
+                    1,
+                    "",
+                    "error: unexpected argument '--json' found\n\nUsage: rustup[EXE] check [OPTIONS]",
+                ));
+            }
+
+            return io::Result::Ok(CommandOutput::new(0, "rustup - Up to date : 1.27.1\n", ""));
+        });
+
+        assert_eq!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap(),
+            ["rustup - Up to date : 1.27.1"]
+        );
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].args, ["--quiet", "check"]);
+
+        // Usage errors on stdout, as a wrapper around rustup can print them
+        let runner = MockRunner::new(|command| {
+            if command.args.contains(&"--json".to_string()) {
+                return io::Result::Ok(CommandOutput::new(
+                    1,
+                    "error: unexpected argument '--json' found\n",
+                    "",
+                ));
+            }
+
+            return io::Result::Ok(CommandOutput::new(0, "rustup - Up to date : 1.27.1\n", ""));
+        });
+        assert_eq!(
+            get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap(),
+            ["rustup - Up to date : 1.27.1"]
+        );
+
+        // What it didn't know is remembered for that rustup
+        let dir = test_util::temp_dir("rustup_check_text_fallback");
+        let rustup = dir.join("rustup");
+        fs::write(&rustup, "old rustup").unwrap();
+        let flags = dir.join("check_flags");
+        run_rustup_check(&runner, &rustup, &[], Some(&flags)).unwrap();
+        assert_eq!(runner.calls().len(), 4);
+        let output = run_rustup_check(&runner, &rustup, &[], Some(&flags)).unwrap();
+        assert!(output.success());
+        assert_eq!(runner.calls().len(), 5);
+        assert_eq!(runner.calls()[4].args, ["--quiet", "check"]);
+    }
+
+    #[test]
+    fn rustup_no_update() {
+        let input = vec![
+            "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.0 (051478957 2024-07-21)",
+            "rustup - Up to date : 1.27.1",
+        ];
+
+        let results = get_new_versions(input);
+
+        assert_eq!(results.get("stable-x86_64-unknown-linux-gnu"), Some(&None));
+        assert_eq!(results.get("rustup"), Some(&None));
+    }
+
+    #[test]
+    fn rustup_header_lines() {
+        let input = vec![
+            "info: checking for updates to toolchains",
+            "   ",
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1",
+            "note: something - unrelated",
+            "rustup - Up to date : 1.27.1",
+        ];
+
+        let results = get_new_versions(input);
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results.get("stable-x86_64-unknown-linux-gnu"),
+            Some(&Some("1.80.1"))
+        );
+        assert_eq!(results.get("rustup"), Some(&None));
+    }
+
+    #[test]
+    fn rustup_patch() {
+        let input = vec![
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1 (051478957 2024-07-21)",
+            "rustup - Up to date : 1.27.1",
+        ];
+
+        let results = get_new_versions(input);
+
+        assert_eq!(
+            results.get("stable-x86_64-unknown-linux-gnu"),
+            Some(&Some("1.80.1"))
+        );
+
+        assert_eq!(results.get("rustup"), Some(&None));
+    }
+
+    #[test]
+    fn rustup_patch_owned() {
+        let input = vec![
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1 (051478957 2024-07-21)".to_string(),
+            "rustup - Up to date : 1.27.1".to_string(),
+        ];
+
+        let results = get_new_versions_owned(&input);
+        drop(input);
+
+        assert_eq!(
+            results.get("stable-x86_64-unknown-linux-gnu"),
+            Some(&Some("1.80.1".to_string()))
+        );
+        assert_eq!(results.get("rustup"), Some(&None));
+    }
+
+    #[test]
+    fn no_prompt() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("Rust", None);
+        input.insert("Rustup", None);
+
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+
+        assert_eq!(
+            prompt_for_update(
+                &backend,
+                &to_owned_versions(input),
+                None,
+                Urgency::Normal,
+                &UpdateDetails::default(),
+                &Config::default(),
+                &[],
+            )
+            .unwrap(),
+            UpdatePromptAnswer::NoUpdateFound
+        );
+        assert!(backend.prompts().is_empty());
+    }
+
+    #[test]
+    fn versions_key_is_sorted() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("stable", Some("1.80.1"));
+        input.insert("rustup", Some("1.27.2"));
+        input.insert("nightly", None);
+
+        assert_eq!(
+            versions_key(&to_owned_versions(input)),
+            "rustup=1.27.2,stable=1.80.1"
+        );
+    }
+
+    #[test]
+    fn prompt_text_marks_default() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("stable-x86_64-unknown-linux-gnu", Some("1.80.1"));
+        input.insert("rustup", Some("1.27.2"));
+        input.insert("nightly-x86_64-unknown-linux-gnu", None);
+
+        let input = to_owned_versions(input);
+
+        assert_eq!(
+            build_prompt_text(
+                &input,
+                Some("stable-x86_64-unknown-linux-gnu"),
+                &UpdateDetails::default(),
+                false
+            ),
+            "rustup: 1.27.2\nstable-x86_64-unknown-linux-gnu (default): 1.80.1\nUpdate?"
+        );
+        assert_eq!(
+            build_prompt_text(&input, None, &UpdateDetails::default(), false),
+            "rustup: 1.27.2\nstable-x86_64-unknown-linux-gnu: 1.80.1\nUpdate?"
+        );
+    }
+
+    #[test]
+    fn prompt_text_shows_sizes() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("stable-x86_64-unknown-linux-gnu", Some("1.80.1"));
+        input.insert("rustup", Some("1.27.2"));
+
+        let mut details = UpdateDetails::default();
+        details
+            .sizes
+            .insert("stable-x86_64-unknown-linux-gnu".to_string(), 179_600_000);
+
+        assert_eq!(
+            build_prompt_text(&to_owned_versions(input.clone()), None, &details, false),
+            "rustup: 1.27.2\nstable-x86_64-unknown-linux-gnu: 1.80.1, ~180MB\nUpdate?"
+        );
+
+        details.released_ago.insert(
+            "stable-x86_64-unknown-linux-gnu".to_string(),
+            3 * 24 * 60 * 60,
+        );
+        assert_eq!(
+            build_prompt_text(&to_owned_versions(input), None, &details, false),
+            "rustup: 1.27.2\n\
+             stable-x86_64-unknown-linux-gnu: 1.80.1, ~180MB, released 3 days ago\nUpdate?"
+        );
+    }
+
+    #[test]
+    fn release_ages() {
+        let lines = [
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1 \
+             (3f5fd8dd4 2024-08-06)"
+                .to_string(),
+            "rustup - Update available : 1.27.0 -> 1.27.1".to_string(),
+            "nightly-x86_64-unknown-linux-gnu - Up to date : 1.82.0 (0d8b3346a 2024-08-01)"
+                .to_string(),
+        ];
+        // Nothing else to go on, as the dist server can't be reached
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(7, "", "")));
+        let details = update_details(
+            &Config::default(),
+            &runner,
+            &parse::parse_rustup_check(&lines),
+            age::parse_date("2024-08-09").unwrap() + 60,
+        );
+
+        // Left out without a date, and for those up to date
+        assert_eq!(
+            details.released_ago,
+            HashMap::from([(
+                "stable-x86_64-unknown-linux-gnu".to_string(),
+                3 * 24 * 60 * 60 + 60
+            )])
+        );
+    }
+
+    #[test]
+    fn accessible_prompt_text() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("stable-x86_64-unknown-linux-gnu", Some("1.80.1"));
+        input.insert("rustup", Some("1.27.2"));
+        input.insert("nightly-x86_64-unknown-linux-gnu", None);
+
+        let mut details = UpdateDetails::default();
+        details
+            .sizes
+            .insert("stable-x86_64-unknown-linux-gnu".to_string(), 179_600_000);
+
+        assert_eq!(
+            build_prompt_text(
+                &to_owned_versions(input),
+                Some("stable-x86_64-unknown-linux-gnu"),
+                &details,
+                true
+            ),
+            "2 updates available.\n\
+             rustup updates to version 1.27.2.\n\
+             stable-x86_64-unknown-linux-gnu, your default toolchain, updates to version 1.80.1, \
+             a download of about 180MB.\n\
+             Do you want to update now?"
+        );
+    }
+
+    #[test]
+    fn accessible_prompt_waits() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("stable", Some("1.80.1"));
+
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        prompt_for_update(
+            &backend,
+            &to_owned_versions(input),
+            None,
+            Urgency::Normal,
+            &UpdateDetails::default(),
+            &Config {
+                accessible: true,
+                ..Config::default()
+            },
+            &[],
+        )
+        .unwrap();
+
+        let prompt = &backend.prompts()[0];
+        assert!(prompt.accessible);
+        assert_eq!(prompt.timeout, None);
+        assert_eq!(
+            prompt.text,
+            "1 update available.\nstable updates to version 1.80.1.\nDo you want to update now?"
+        );
+    }
+
+    #[test]
+    fn prompt_update() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("Rust", Some("1.81.0 Update me!"));
+        input.insert("Rustup", Some("1.27.3"));
+
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+
+        assert_eq!(
+            prompt_for_update(
+                &backend,
+                &to_owned_versions(input),
+                None,
+                Urgency::Normal,
+                &UpdateDetails::default(),
+                &Config::default(),
+                &[],
+            )
+            .unwrap(),
+            UpdatePromptAnswer::Update
+        );
+
+        let prompts = backend.prompts();
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].title, "Rust Update");
+        assert_eq!(
+            prompts[0].text,
+            "Rust: 1.81.0 Update me!\nRustup: 1.27.3\nUpdate?"
+        );
+        assert_eq!(prompts[0].timeout, Some(10));
+
+        let config = Config {
+            prompt_timeout: Some(30),
+            ..Config::default()
+        };
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("stable", Some("1.80.1"));
+        prompt_for_update(
+            &backend,
+            &to_owned_versions(input),
+            None,
+            Urgency::Normal,
+            &UpdateDetails::default(),
+            &config,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(backend.prompts()[1].timeout, Some(30));
+    }
+
+    #[test]
+    fn prompt_do_not_update() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("Rust", Some("2.0.0 Don't update me please!!"));
+        input.insert("Rustup", None);
+
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+
+        assert_eq!(
+            prompt_for_update(
+                &backend,
+                &to_owned_versions(input),
+                None,
+                Urgency::Normal,
+                &UpdateDetails::default(),
+                &Config::default(),
+                &[],
+            )
+            .unwrap(),
+            UpdatePromptAnswer::DoNotUpdate
+        );
+
+        // Up to date programs aren't listed
+        assert_eq!(
+            backend.prompts()[0].text,
+            "Rust: 2.0.0 Don't update me please!!\nUpdate?"
+        );
+    }
+
+    #[test]
+    fn timeout_prompt() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("Rust", Some("2.0.0 Timeout!!!"));
+        input.insert("Rustup", Some("Please don't press a button"));
+
+        let backend = FakeBackend::new(UpdatePromptAnswer::Timeout);
+
+        assert_eq!(
+            prompt_for_update(
+                &backend,
+                &to_owned_versions(input),
+                None,
+                Urgency::Normal,
+                &UpdateDetails::default(),
+                &Config::default(),
+                &[],
+            )
+            .unwrap(),
+            UpdatePromptAnswer::Timeout
+        );
+    }
+
+    #[test]
+    fn required_prompt() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("stable", Some("1.80.1"));
+
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+        prompt_for_update(
+            &backend,
+            &to_owned_versions(input),
+            None,
+            Urgency::Required("Too old"),
+            &UpdateDetails::default(),
+            &Config::default(),
+            &[],
+        )
+        .unwrap();
+
+        let prompt = &backend.prompts()[0];
+        assert_eq!(prompt.title, "Rust Update Required");
+        assert_eq!(prompt.text, "Too old\n\nstable: 1.80.1\nUpdate?");
+        assert_eq!(prompt.timeout, None);
+        assert_eq!(prompt.cancel_label, "Not now");
+    }
+
+    #[test]
+    fn new_release_prompt() {
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "stable-x86_64-unknown-linux-gnu - Update available : 1.80.1 -> 1.81.0\n",
+                "",
+            ))
+        });
+        let backend = FakeBackend::new(UpdatePromptAnswer::Timeout);
+        auto_update_with_runner(
+            &Config::default(),
+            &runner,
+            &backend,
+            &test_paths("new_release_prompt"),
+        )
+        .unwrap();
+
+        let prompt = &backend.prompts()[0];
+        assert_eq!(prompt.title, "Rust Update: New Release");
+        assert!(prompt
+            .text
+            .starts_with("stable 1.80.1 → 1.81.0 is a new release"));
+        assert_eq!(prompt.timeout, Some(30));
+        assert_eq!(prompt.confirm_word, None);
+
+        // A major release is confirmed by typing its version, in a terminal
+        let backend = FakeBackend::new(UpdatePromptAnswer::Timeout);
+        auto_update_with_runner(
+            &Config::default(),
+            &MockRunner::new(|_| {
+                io::Result::Ok(CommandOutput::new(
+                    0,
+                    "stable-x86_64-unknown-linux-gnu - Update available : 1.80.1 -> 2.0.0\n",
+                    "",
+                ))
+            }),
+            &backend,
+            &test_paths("major_release_prompt"),
+        )
+        .unwrap();
+        let prompt = &backend.prompts()[0];
+        assert_eq!(prompt.title, "Rust Update: New Release");
+        assert_eq!(prompt.confirm_word.as_deref(), Some("2.0.0"));
+
+        // A patch release is a normal prompt
+        let backend = FakeBackend::new(UpdatePromptAnswer::Timeout);
+        auto_update_with_runner(
+            &Config::default(),
+            &MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, ""))),
+            &backend,
+            &test_paths("patch_release_prompt"),
+        )
+        .unwrap();
+        assert_eq!(backend.prompts()[0].title, "Rust Update");
+    }
+
+    #[test]
+    fn ignore_rustup_self() {
+        let config = Config {
+            ignore_rustup_self: true,
+            ..Config::default()
+        };
+
+        // Only rustup has an update, so there's nothing to ask about
+        let runner = MockRunner::new(|_| {
+            io::Result::Ok(CommandOutput::new(
+                0,
+                "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1\n\
+                 rustup - Update available : 1.27.0 -> 1.27.1\n",
+                "",
+            ))
+        });
+        let both = "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1\n\
+                    rustup - Update available : 1.27.0 -> 1.27.1\n";
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        let outcome = auto_update_with_runner(
+            &config,
+            &runner,
+            &backend,
+            &test_paths("ignore_rustup_self"),
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::UpToDate);
+        assert!(backend.prompts().is_empty());
+
+        // With a toolchain update too, only it is asked about
+        let backend = FakeBackend::new(UpdatePromptAnswer::Timeout);
+        auto_update_with_runner(
+            &config,
+            &MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, both, ""))),
+            &backend,
+            &test_paths("ignore_rustup_self_with_stable"),
+        )
+        .unwrap();
+        let prompt = &backend.prompts()[0];
+        assert_eq!(
+            prompt.updates.keys().collect::<Vec<_>>(),
+            ["stable-x86_64-unknown-linux-gnu"]
+        );
+        assert!(!prompt.text.contains("rustup"));
+
+        let status = status_with(
+            &config,
+            &MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, both, ""))),
+            &test_paths("ignore_rustup_self_status"),
+            Format::Json,
+        )
+        .unwrap();
+        assert!(!status.contains("\"rustup\""));
+    }
+
+    #[test]
+    fn update_available_without_rustup_self() {
+        let only_rustup = "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1\n\
+                           rustup - Update available : 1.27.0 -> 1.27.1\n";
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, only_rustup, "")));
+        let paths = test_paths("update_available_without_rustup_self");
+
+        assert!(is_update_available_with(&Config::default(), &runner, &paths).unwrap());
+
+        let config = Config {
+            ignore_rustup_self: true,
+            ..Config::default()
+        };
+        assert!(!is_update_available_with(&config, &runner, &paths).unwrap());
+
+        // A toolchain update still counts
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        assert!(is_update_available_with(&config, &runner, &paths).unwrap());
+    }
+
+    #[test]
+    fn same_channel_targets() {
+        let check = "stable-x86_64-unknown-linux-gnu - Update available : 1.80.1 -> 1.81.0\n\
+                     stable-aarch64-unknown-linux-gnu - Update available : 1.80.1 -> 1.81.0\n";
+        let runner = MockRunner::new(|cmd| match cmd.program.as_str() {
+            "curl" => io::Result::Ok(CommandOutput::new(7, "", "")),
+            _ => io::Result::Ok(CommandOutput::new(0, check, "")),
+        });
+        let backend = FakeBackend::new(UpdatePromptAnswer::Timeout);
+        let paths = test_paths("same_channel_targets");
+        fs::create_dir(&paths.rustup_home).unwrap();
+        fs::write(
+            paths.rustup_home.join("settings.toml"),
+            "default_host_triple = \"aarch64-unknown-linux-gnu\"\n\
+             default_toolchain = \"stable\"\n",
+        )
+        .unwrap();
+        auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+
+        // Both are kept, by their full names
+        let prompt = &backend.prompts()[0];
+        assert_eq!(
+            prompt.updates.keys().collect::<Vec<_>>(),
+            [
+                "stable-aarch64-unknown-linux-gnu",
+                "stable-x86_64-unknown-linux-gnu"
+            ]
+        );
+        // Only the default toolchain is marked
+        assert!(
+            prompt
+                .text
+                .contains("\nstable-aarch64-unknown-linux-gnu (default): 1.81.0\n"),
+            "{}",
+            prompt.text
+        );
+        assert!(prompt
+            .text
+            .contains("\nstable-x86_64-unknown-linux-gnu: 1.81.0\n"));
+        assert!(prompt
+            .text
+            .starts_with("stable (x86_64-unknown-linux-gnu) 1.80.1 → 1.81.0 is a new release"));
+
+        let updates = parse::parse_rustup_check(
+            &get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap(),
+        );
+        assert_eq!(
+            report::oneline_summary(&updates),
+            "rust: 2 updates (stable (x86_64-unknown-linux-gnu) 1.80.1→1.81.0, \
+             stable (aarch64-unknown-linux-gnu) 1.80.1→1.81.0)"
+        );
+    }
+
+    #[test]
+    fn installed_descriptions() {
+        let updates = parse::parse_rustup_check(&[
+            "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.0 (051478957 2024-07-21)"
+                .to_string(),
+            "rustup - Up to date : 1.27.1".to_string(),
+        ]);
+
+        assert_eq!(
+            describe_installed(&updates[0]),
+            "stable-x86_64-unknown-linux-gnu: 1.80.0, commit 051478957 from 2024-07-21"
+        );
+        assert_eq!(describe_installed(&updates[1]), "rustup: 1.27.1");
+    }
+
+    #[test]
+    fn pinned_directories_noted() {
+        let mut input: HashMap<&str, Option<&str>> = HashMap::new();
+        input.insert("stable", Some("1.80.1"));
+
+        let pinned = [settings::ToolchainOverride {
+            directory: path::PathBuf::from("/home/daisy/code/old_project"),
+            toolchain: "1.75.0".to_string(),
+        }];
+
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        prompt_for_update(
+            &backend,
+            &to_owned_versions(input),
+            None,
+            Urgency::Normal,
+            &UpdateDetails::default(),
+            &Config::default(),
+            &pinned_notes(&pinned),
+        )
+        .unwrap();
+
+        assert_eq!(
+            backend.prompts()[0].text,
+            "/home/daisy/code/old_project is pinned to 1.75.0, so it won't be updated\n\n\
+             stable: 1.80.1\nUpdate?"
+        );
+    }
+
+    fn test_paths(name: &str) -> StatePaths {
+        return StatePaths::in_dir(&test_util::temp_dir(name));
+    }
+
+    const STABLE_UPDATE: &str =
+        "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1\n\
+                                 rustup - Up to date : 1.27.1\n";
+
+    #[test]
+    fn on_battery_snoozes() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        let power = test_util::temp_dir("on_battery_snoozes_power").join("BAT0");
+        fs::create_dir_all(&power).unwrap();
+        fs::write(power.join("type"), "Battery\n").unwrap();
+        fs::write(power.join("status"), "Discharging\n").unwrap();
+        let config = Config {
+            require_ac_power: true,
+            power_supply_path: power.parent().unwrap().to_path_buf(),
+            ..Config::default()
+        };
+
+        let paths = test_paths("on_battery_snoozes");
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::OnBattery);
+        assert!(backend.prompts().is_empty());
+        assert!(!should_prompt(&paths.flag));
+
+        // Unless snoozing is off
+        let config = Config {
+            no_snooze: true,
+            ..config
+        };
+        let paths = test_paths("on_battery_no_snooze");
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::OnBattery);
+        assert!(!paths.flag.exists());
+    }
+
+    #[test]
+    fn timeout_actions() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let backend = FakeBackend::new(UpdatePromptAnswer::Timeout);
+
+        // Returns the length of the snooze written, if any
+        let snooze_after_timeout = |action: TimeoutAction, name: &str| {
+            let config = Config {
+                timeout_action: action,
+                ..Config::default()
+            };
+            let paths = test_paths(name);
+
+            let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+            assert_eq!(outcome, Outcome::TimedOut);
+
+            return snooze::read_flag(&paths.flag)
+                .unwrap()
+                .map(|x| x.until - x.since);
+        };
+
+        assert_eq!(
+            snooze_after_timeout(TimeoutAction::AskNextRun, "timeout_ask_next_run"),
+            None
+        );
+        assert_eq!(
+            snooze_after_timeout(TimeoutAction::SnoozeShort, "timeout_snooze_short"),
+            Some(snooze::SHORT_SNOOZE)
+        );
+        assert_eq!(
+            snooze_after_timeout(TimeoutAction::Decline, "timeout_decline"),
+            Some(NO_UPDATE_FLAG_DELAY)
+        );
+    }
+
+    #[test]
+    fn closed_prompt() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let backend = FakeBackend::new(UpdatePromptAnswer::Closed);
+
+        let config = Config::default();
+        let paths = test_paths("closed_prompt_default");
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Declined);
+        assert!(!backend.prompts()[0].detect_close);
+        let flag = snooze::read_flag(&paths.flag).unwrap().unwrap();
+        assert_eq!(flag.until - flag.since, NO_UPDATE_FLAG_DELAY);
+
+        let config = Config {
+            close_action: TimeoutAction::SnoozeShort,
+            ..Config::default()
+        };
+        let paths = test_paths("closed_prompt_short");
+        auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert!(backend.prompts()[1].detect_close);
+        let flag = snooze::read_flag(&paths.flag).unwrap().unwrap();
+        assert_eq!(flag.until - flag.since, snooze::SHORT_SNOOZE);
+    }
+
+    #[test]
+    fn prompt_queued_without_display() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let paths = test_paths("prompt_queued_without_display");
+
+        let outcome = auto_update_with_runner(
+            &Config::default(),
+            &runner,
+            &FakeBackend::without_display(),
+            &paths,
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::PromptQueued);
+        assert!(paths.pending_prompt.exists());
+        assert!(should_prompt(&paths.flag));
+
+        // The next run with a display prompts, and clears the queue
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+        let outcome =
+            auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Declined);
+        assert_eq!(backend.prompts().len(), 1);
+        assert!(!paths.pending_prompt.exists());
+    }
+
+    #[test]
+    fn auto_update_allowlist() {
+        let runner = MockRunner::new(|command| {
+            if command.args.contains(&"check".to_string()) {
+                return io::Result::Ok(CommandOutput::new(
+                    0,
+                    "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1\n\
+                     nightly-x86_64-unknown-linux-gnu - Update available : 1.82.0 -> 1.83.0\n\
+                     rustup - Update available : 1.27.0 -> 1.27.1\n",
+                    "",
+                ));
+            }
+
+            return io::Result::Ok(CommandOutput::new(0, "", ""));
+        });
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        let paths = test_paths("auto_update_allowlist");
+        let config = Config {
+            auto_update: vec!["stable".to_string()],
+            ..Config::default()
+        };
+
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Updated);
+
+        // Stable updated straight away, without a terminal
+        let headless = runner.calls_to("rustup");
+        assert_eq!(headless.len(), 1);
+        assert_eq!(
+            headless[0].args,
+            ["update", "stable-x86_64-unknown-linux-gnu"]
+        );
+
+        // Then only nightly was prompted for, and updated
+        let text = &backend.prompts()[0].text;
+        assert!(text.contains("nightly-x86_64-unknown-linux-gnu: 1.83.0"));
+        assert!(!text.contains("stable"));
+        assert!(!text.contains("rustup"));
+        assert_eq!(
+            backend.prompts()[0].updates,
+            BTreeMap::from([(
+                "nightly-x86_64-unknown-linux-gnu".to_string(),
+                "1.83.0".to_string()
+            )])
+        );
+        let terminal = &runner.calls_to("/bin/gnome-terminal")[0];
+        assert!(terminal.args.ends_with(&[
+            "update".to_string(),
+            "nightly-x86_64-unknown-linux-gnu".to_string()
+        ]));
+
+        // With only allowed updates, there's no prompt. They're still
+        // counted, and the snooze is lifted, like any other update
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+        let config = Config {
+            auto_update: vec!["stable".to_string(), "nightly".to_string()],
+            keep_stats: true,
+            ..Config::default()
+        };
+        let paths = test_paths("auto_update_allowlist_all");
+        set_no_update_flag(&paths.flag, true).unwrap();
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Updated);
+        assert!(backend.prompts().is_empty());
+        assert_eq!(stats::read_stats(&paths.stats).unwrap().succeeded, 1);
+        assert!(should_prompt(&paths.flag));
+    }
+
+    #[test]
+    fn nightly_date_bumps() {
+        let check = |versions: &'static str| {
+            return MockRunner::new(move |_| {
+                return io::Result::Ok(CommandOutput::new(
+                    0,
+                    &format!("nightly-x86_64-unknown-linux-gnu - Update available : {versions}\n"),
+                    "",
+                ));
+            });
+        };
+        let config = Config {
+            skip_nightly_date_bumps: true,
+            ..Config::default()
+        };
+
+        // The same version on a later day isn't prompted for
+        let runner =
+            check("1.83.0-nightly (90ab8eaed 2024-09-06) -> 1.83.0-nightly (a9cb2b5a5 2024-09-07)");
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+        let paths = test_paths("nightly_date_bumps");
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::UpToDate);
+        assert!(backend.prompts().is_empty());
+
+        // Unless it's turned off
+        let paths = test_paths("nightly_date_bumps_off");
+        let outcome =
+            auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Declined);
+        assert_eq!(backend.prompts().len(), 1);
+
+        // A new version is
+        let runner =
+            check("1.83.0-nightly (90ab8eaed 2024-09-06) -> 1.84.0-nightly (a9cb2b5a5 2024-10-18)");
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+        let paths = test_paths("nightly_new_version");
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Declined);
+        assert!(backend.prompts()[0].text.contains("1.84.0"));
+    }
+
+    #[test]
+    fn update_without_prompt() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let paths = test_paths("update_without_prompt");
+        let config = Config {
+            no_prompt_action: NoPromptAction::Update,
+            ..Config::default()
+        };
+
+        // Nothing in the chain can be shown, so it updates without asking
+        let backend = FallbackBackend::new(vec![
+            Box::new(FakeBackend::without_display()),
+            Box::new(FakeBackend::without_display()),
+        ]);
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Updated);
+        assert!(!paths.pending_prompt.exists());
+        assert_eq!(runner.calls_to("/bin/gnome-terminal").len(), 1);
+    }
+
+    #[test]
+    fn update_after_reboot() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+        let paths = test_paths("update_after_reboot");
+        let intent = |boot_id: &str| next_boot::BootIntent {
+            boot_id: boot_id.to_string(),
+            recorded_at: 1000,
+        };
+
+        // The same boot only waits
+        let boot_id = next_boot::current_boot_id().unwrap();
+        next_boot::write_intent(&paths.next_boot, &intent(&boot_id)).unwrap();
+        let outcome =
+            auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::WaitingForBoot);
+        assert!(runner.calls().is_empty());
+
+        // After a reboot it updates straight away, without checking or
+        // prompting, once
+        next_boot::write_intent(&paths.next_boot, &intent("an-earlier-boot")).unwrap();
+        let outcome =
+            auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Updated);
+        assert_eq!(runner.calls().len(), 1);
+        assert_eq!(runner.calls_to("/bin/gnome-terminal").len(), 1);
+        assert!(backend.prompts().is_empty());
+        assert!(!paths.next_boot.exists());
+
+        let outcome =
+            auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Declined);
+
+        // A failed update is tried again
+        let failing = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(1, "", "")));
+        next_boot::write_intent(&paths.next_boot, &intent("an-earlier-boot")).unwrap();
+        let outcome =
+            auto_update_with_runner(&Config::default(), &failing, &backend, &paths).unwrap();
+        assert!(matches!(outcome, Outcome::UpdateFailed { .. }));
+        assert!(paths.next_boot.exists());
+    }
+
+    #[test]
+    fn prompt_callback_declined() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let paths = test_paths("prompt_callback_declined");
+
+        let mut asked = Vec::new();
+        let mut prompt = |updates: &[ParsedUpdate]| {
+            asked.push(updates.to_vec());
+            return UpdatePromptAnswer::DoNotUpdate;
+        };
+
+        let outcome = auto_update_with(
+            &Config::default(),
+            &runner,
+            &paths,
+            &ZenityBackend,
+            Some(&mut prompt),
+        );
+        assert_eq!(outcome.unwrap(), Outcome::Declined);
+
+        // Only the toolchains with updates are given
+        assert_eq!(
+            asked,
+            [[ParsedUpdate {
+                name: "stable-x86_64-unknown-linux-gnu".to_string(),
+                current: Some(Version::new(1, 80, 0)),
+                new: Some(Version::new(1, 80, 1)),
+                current_build: None,
+                new_build: None,
+            }]]
+        );
+
+        // Only rustup check ran, and the answer snoozed the prompt
+        assert_eq!(runner.calls().len(), 1);
+        assert!(!should_prompt(&paths.flag));
+    }
+
+    #[test]
+    fn prompt_callback_update() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let paths = test_paths("prompt_callback_update");
+
+        let mut prompt = |_: &[ParsedUpdate]| UpdatePromptAnswer::Update;
+
+        let outcome = auto_update_with(
+            &Config::default(),
+            &runner,
+            &paths,
+            &ZenityBackend,
+            Some(&mut prompt),
+        );
+        assert_eq!(outcome.unwrap(), Outcome::Updated);
+
+        assert_eq!(runner.calls_to("/bin/gnome-terminal").len(), 1);
+        assert!(!paths.last_check.exists());
+    }
+
+    #[test]
+    fn should_prompt_test() {
+        let path = test_util::temp_dir("should_prompt").join("donotupdate");
+
+        // No flag
+        set_no_update_flag(&path, false).unwrap();
+        assert!(should_prompt(&path));
+
+        // New flag
+        set_no_update_flag(&path, true).unwrap();
+        assert!(!should_prompt(&path));
+
+        // Second new flag
+        set_no_update_flag(&path, true).unwrap();
+        assert!(!should_prompt(&path));
+
+        // Second no flag
+        set_no_update_flag(&path, false).unwrap();
+        assert!(should_prompt(&path));
+    }
+
+    #[test]
+    fn snooze_status_of_flag_path() {
+        let path = test_util::temp_dir("snooze_status_of_flag_path").join("donotupdate");
+        let config = Config {
+            flag_path: Some(path.clone()),
+            ..Config::default()
+        };
+
+        assert!(!snooze_status(&config).unwrap().is_active());
+        set_no_update_flag(&path, true).unwrap();
+        assert!(snooze_status(&config).unwrap().is_active());
+    }
+
+    #[test]
+    fn snooze_for_duration() {
+        let path = test_util::temp_dir("snooze_for_duration").join("donotupdate");
+
+        snooze_for_at(&path, 12 * 60 * 60).unwrap();
+
+        let flag = snooze::read_flag(&path).unwrap().unwrap();
+        assert_eq!(flag.until - flag.since, 12 * 60 * 60);
+        assert!(!should_prompt(&path));
+    }
+
+    #[test]
+    fn should_prompt_after_day() {
+        let path = test_util::temp_dir("should_prompt_after_day").join("donotupdate");
+        let now = 1_723_400_000;
+        snooze::write_flag(&path, &SnoozeFlag::new(now, NO_UPDATE_FLAG_DELAY)).unwrap();
+
+        assert!(!should_prompt_at(&path, now));
+        assert!(!should_prompt_at(&path, now + NO_UPDATE_FLAG_DELAY - 1));
+        assert!(should_prompt_at(&path, now + NO_UPDATE_FLAG_DELAY));
+
+        // A flag from the apparent future doesn't stop the prompt
+        assert!(should_prompt_at(&path, now - 1));
     }
 
-    #[ignore = "Terminal opens, annoying"]
+    #[test]
+    fn why() {
+        let path = test_util::temp_dir("why").join("donotupdate");
+        let now = 1_723_400_000;
+        let config = Config::default();
+        let heading = format!("No-update flag: {}\nSnooze length: 1d\n", path.display());
+
+        assert_eq!(
+            explain_prompt(&config, &path, now),
+            format!("{heading}The flag isn't set\nWould prompt: yes\n")
+        );
+
+        snooze::write_flag(&path, &SnoozeFlag::new(now - 90 * 60, 2 * 60 * 60)).unwrap();
+        assert_eq!(
+            explain_prompt(&config, &path, now),
+            format!(
+                "{heading}Snoozed 1h30m ago, at 1723394600, for 2h\n\
+                 Remaining: 30m\n\
+                 Would prompt: no\n"
+            )
+        );
+        assert!(!should_prompt_at(&path, now));
+
+        // The same answer as `should_prompt_at` once it runs out
+        assert!(explain_prompt(&config, &path, now + 60 * 60)
+            .ends_with("Ran out 30m ago\nWould prompt: yes\n"));
+        assert!(should_prompt_at(&path, now + 60 * 60));
+        assert!(explain_prompt(&config, &path, now - 2 * 60 * 60)
+            .ends_with("in the future, so it's ignored\nWould prompt: yes\n"));
+
+        let config = Config {
+            no_snooze: true,
+            ..Config::default()
+        };
+        assert!(
+            explain_prompt(&config, &path, now).ends_with("no_snooze is set\nWould prompt: yes\n")
+        );
+    }
+
+    #[test]
+    fn elevated_update() {
+        let rustup = path::Path::new("/opt/rust%/bin/rustup");
+        let env = [("RUSTUP_HOME".to_string(), "/opt/rustup".to_string())];
+        let update = ["rustup".to_string(), "update".to_string()];
+
+        assert_eq!(
+            elevated_command("sudo", rustup, &env, &update),
+            [
+                "sudo",
+                "-p",
+                "Updating the rustup at /opt/rust%%/bin/rustup needs root, as it belongs to \
+                 another user. Password for %p: ",
+                "/usr/bin/env",
+                "RUSTUP_HOME=/opt/rustup",
+                "/opt/rust%/bin/rustup",
+                "update"
+            ]
+        );
+        assert_eq!(
+            elevated_command("pkexec", rustup, &[], &update),
+            ["pkexec", "/usr/bin/env", "/opt/rust%/bin/rustup", "update"]
+        );
+
+        // Our own rustup isn't elevated, even when it's set to be
+        let config = Config {
+            elevate: Elevation::Sudo,
+            rustup_path: Some(std::env::current_exe().unwrap()),
+            ..Config::default()
+        };
+        assert_eq!(
+            update_command_to_run(&config, &[]),
+            update_command_for(&config, &[])
+        );
+
+        // sudo can't ask for a password without a terminal
+        assert_eq!(elevation_kind(&config), Elevation::Sudo);
+        let detached = Config {
+            detach: true,
+            ..config
+        };
+        assert_eq!(elevation_kind(&detached), Elevation::Pkexec);
+    }
+
+    #[test]
+    fn update_command_not_injected() {
+        let update_command = vec![
+            "echo".to_string(),
+            "rustup update; echo injected".to_string(),
+        ];
+        let status = path::Path::new("/tmp/status");
+        let args = terminal_update_args(&TERMINALS[0], &update_command, &[], 0, status);
+
+        assert_eq!(
+            &args[..5],
+            ["--", "/bin/sh", "-c", &update_script(0, status), "sh"]
+        );
+        assert_eq!(&args[5..], update_command);
+
+        // Run the shell part without the terminal, and without the wait
+        let output = std::process::Command::new(&args[1])
+            .args([&args[2], r#""$@""#])
+            .args(&args[4..])
+            .output()
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "rustup update; echo injected\n"
+        );
+    }
+
+    #[test]
+    fn update_countdown() {
+        let update_command = ["echo".to_string(), "updating".to_string()];
+        let status = path::Path::new("/tmp/status");
+        let args = terminal_update_args(&TERMINALS[0], &update_command, &[], 1, status);
+        let quoted = shell_quote("/tmp/status");
+        let started = STARTED_SCRIPT.replace("{status}", &quoted);
+        let update = UPDATE_SCRIPT.replace("{status}", &quoted);
+
+        // Known to have started before the countdown, so cancelling it
+        // isn't taken as an update that worked
+        assert!(args[3].starts_with(&format!(
+            "{started}echo 'Press Ctrl-C to cancel the update'; n=1;"
+        )));
+        assert!(args[3].ends_with(&update));
+
+        // Without the status file, or the wait at the end
+        let script = args[3].replace(&started, "").replace(&update, r#""$@""#);
+        let output = std::process::Command::new("/bin/sh")
+            .args(["-c", &script])
+            .args(&args[4..])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "Press Ctrl-C to cancel the update\nStarting in 1... \nupdating\n"
+        );
+    }
+
+    #[test]
+    fn post_update_hook_runs_after_update() {
+        let config = Config {
+            post_update_hook: vec![
+                "cargo".to_string(),
+                "install-update".to_string(),
+                "-a".to_string(),
+            ],
+            ..Config::default()
+        };
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, "", "")));
+
+        assert_eq!(
+            update_rust(
+                &config,
+                &runner,
+                &test_paths("post_update_hook_runs_after_update_state"),
+                &[],
+                false
+            )
+            .unwrap(),
+            Outcome::Updated
+        );
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].program, "/bin/gnome-terminal");
+        assert_eq!(
+            calls[1],
+            CommandSpec::new("cargo", &["install-update", "-a"])
+        );
+    }
+
+    #[test]
+    fn daily_prompt_budget() {
+        let config = Config {
+            daily_prompt_budget: 1,
+            ..Config::default()
+        };
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let paths = test_paths("daily_prompt_budget");
+
+        // Timing out doesn't snooze, but it was still shown
+        let backend = FakeBackend::new(UpdatePromptAnswer::Timeout);
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::TimedOut);
+        assert_eq!(backend.prompts().len(), 1);
+
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::PromptSkipped);
+        assert_eq!(backend.prompts().len(), 1);
+
+        // Without a budget, as with --force
+        let outcome =
+            auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::TimedOut);
+        assert_eq!(backend.prompts().len(), 2);
+    }
+
+    #[test]
+    fn update_available_hook() {
+        let config = Config {
+            update_available_hook: vec!["post-updates".to_string()],
+            ..Config::default()
+        };
+        let runner = |hook_code| {
+            return MockRunner::new(move |command| match command.program.as_str() {
+                "post-updates" => io::Result::Ok(CommandOutput::new(hook_code, "", "")),
+                _ => io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")),
+            });
+        };
+
+        // Given the updates, then prompted as usual
+        let hook_runner = runner(0);
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+        let outcome = auto_update_with_runner(
+            &config,
+            &hook_runner,
+            &backend,
+            &test_paths("update_available_hook"),
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::Declined);
+        assert_eq!(backend.prompts().len(), 1);
+
+        let calls = hook_runner.calls_to("post-updates");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(
+            calls[0].env,
+            [
+                (
+                    "RUST_UPDATE_SUMMARY".to_string(),
+                    "rust: 1 update (stable 1.80.0→1.80.1)".to_string()
+                ),
+                (
+                    "RUST_UPDATE_TOOLCHAINS".to_string(),
+                    "stable-x86_64-unknown-linux-gnu=1.80.1".to_string()
+                ),
+            ]
+        );
+
+        // Exiting with 3 skips the prompt
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        let outcome = auto_update_with_runner(
+            &config,
+            &runner(SKIP_PROMPT_EXIT_CODE),
+            &backend,
+            &test_paths("update_available_hook_skips"),
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::PromptSkipped);
+        assert!(backend.prompts().is_empty());
+
+        // Failing otherwise doesn't
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+        let outcome = auto_update_with_runner(
+            &config,
+            &runner(1),
+            &backend,
+            &test_paths("update_available_hook_fails"),
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::Declined);
+    }
+
+    #[test]
+    fn channel_hook_runs_for_updated_channel() {
+        let config = Config {
+            channel_hooks: std::collections::BTreeMap::from([
+                (
+                    "nightly".to_string(),
+                    vec![
+                        "rustup".to_string(),
+                        "component".to_string(),
+                        "add".to_string(),
+                        "miri".to_string(),
+                    ],
+                ),
+                ("beta".to_string(), vec!["beta-hook".to_string()]),
+            ]),
+            ..Config::default()
+        };
+        let runner = MockRunner::new(|command| {
+            if command.args.contains(&"check".to_string()) {
+                return io::Result::Ok(CommandOutput::new(
+                    0,
+                    "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1\n\
+                     nightly-x86_64-unknown-linux-gnu - Update available : 1.82.0 -> 1.82.0\n\
+                     beta-x86_64-unknown-linux-gnu - Up to date : 1.81.0\n",
+                    "",
+                ));
+            }
+            return io::Result::Ok(CommandOutput::new(0, "", ""));
+        });
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+
+        let outcome =
+            auto_update_with_runner(&config, &runner, &backend, &test_paths("channel_hook_runs"))
+                .unwrap();
+        assert_eq!(outcome, Outcome::Updated);
+
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            &CommandSpec::new("rustup", &["component", "add", "miri"])
+        );
+        assert!(runner.calls_to("beta-hook").is_empty());
+    }
+
+    #[test]
+    fn rustup_env_passed_on() {
+        let config = Config {
+            rustup_dist_server: Some("https://mirror.example.com".to_string()),
+            ..Config::default()
+        };
+        let mirror = (
+            "RUSTUP_DIST_SERVER".to_string(),
+            "https://mirror.example.com".to_string(),
+        );
+        let runner = MockRunner::new(|command| {
+            if command.args.contains(&"check".to_string()) {
+                return io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, ""));
+            }
+            return io::Result::Ok(CommandOutput::new(0, "", ""));
+        });
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+
+        auto_update_with_runner(&config, &runner, &backend, &test_paths("rustup_env")).unwrap();
+
+        let calls = runner.calls();
+        assert!(calls[0].args.contains(&"check".to_string()));
+        assert!(calls[0].env.contains(&mirror));
+
+        // The terminal gets it as part of the command
+        let terminal = &runner.calls_to("/bin/gnome-terminal")[0];
+        assert!(terminal.args.ends_with(&[
+            "env".to_string(),
+            "RUSTUP_DIST_SERVER=https://mirror.example.com".to_string(),
+            "rustup".to_string(),
+            "update".to_string(),
+            "stable-x86_64-unknown-linux-gnu".to_string(),
+        ]));
+
+        // Without a terminal, it's set directly
+        update_rust(
+            &config,
+            &runner,
+            &test_paths("rustup_env_passed_on_update"),
+            &[],
+            true,
+        )
+        .unwrap();
+        let headless = runner.calls_to("rustup");
+        assert_eq!(headless[0].args, ["update"]);
+        assert!(headless[0].env.contains(&mirror));
+    }
+
+    #[test]
+    fn update_only_pending_toolchains() {
+        let updates = parse::parse_rustup_check(&[
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1".to_string(),
+            "nightly-x86_64-unknown-linux-gnu - Up to date : 1.82.0-nightly".to_string(),
+            "rustup - Update available : 1.27.0 -> 1.27.1".to_string(),
+        ]);
+        let toolchains = answer::toolchains_to_update(&updates);
+        assert_eq!(toolchains, ["stable-x86_64-unknown-linux-gnu"]);
+
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, "", "")));
+        let config = Config::default();
+        run_update(
+            &config,
+            &runner,
+            &test_paths("update_only_pending_toolchains_update"),
+            &toolchains,
+        )
+        .unwrap();
+        run_update_headless(&config, &runner, &toolchains).unwrap();
+
+        // The terminal runs the same command as without one
+        let terminal = &runner.calls_to("/bin/gnome-terminal")[0];
+        let headless = &runner.calls_to("rustup")[0];
+        let mut headless_command = vec![headless.program.clone()];
+        headless_command.extend(headless.args.iter().cloned());
+        assert!(terminal.args.ends_with(&headless_command));
+        assert_eq!(headless.args, ["update", "stable-x86_64-unknown-linux-gnu"]);
+
+        // rustup in a read-only directory doesn't update itself
+        let dir = test_util::temp_dir("update_read_only_rustup");
+        let config = Config {
+            rustup_path: Some(dir.join("rustup")),
+            ..Config::default()
+        };
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+        assert_eq!(
+            update_command_for(&config, &toolchains),
+            [
+                "rustup",
+                "update",
+                "--no-self-update",
+                "stable-x86_64-unknown-linux-gnu"
+            ]
+        );
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(
+            update_command_for(&config, &toolchains),
+            ["rustup", "update", "stable-x86_64-unknown-linux-gnu"]
+        );
+
+        // Other commands might not take toolchains
+        let config = Config {
+            update_command: vec!["my-updater".to_string()],
+            ..Config::default()
+        };
+        assert_eq!(update_command_for(&config, &toolchains), ["my-updater"]);
+    }
+
+    #[test]
+    fn verified_rustup() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        let dir = test_util::temp_dir("verified_rustup");
+        let rustup = dir.join("bin/rustup");
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(&rustup, "").unwrap();
+        fs::set_permissions(&rustup, fs::Permissions::from_mode(0o777)).unwrap();
+        let config = Config {
+            verify_rustup: true,
+            rustup_prefix: Some(dir.clone()),
+            rustup_path: Some(rustup.clone()),
+            ..Config::default()
+        };
+
+        // Never run, or even checked with
+        let error = auto_update_with_runner(&config, &runner, &backend, &test_paths("verified"))
+            .unwrap_err();
+        assert!(error.to_string().contains("anyone can write"));
+        assert!(runner.calls().is_empty());
+        assert!(update_rust(
+            &config,
+            &runner,
+            &test_paths("verified_rustup_update"),
+            &[],
+            false
+        )
+        .is_err());
+
+        // Updated with the rustup that was checked
+        fs::set_permissions(&rustup, fs::Permissions::from_mode(0o755)).unwrap();
+        let outcome =
+            auto_update_with_runner(&config, &runner, &backend, &test_paths("verified")).unwrap();
+        assert_eq!(outcome, Outcome::Updated);
+        let terminal = &runner.calls_to("/bin/gnome-terminal")[0];
+        assert!(terminal.args.contains(&rustup.display().to_string()));
+    }
+
+    #[test]
+    fn recheck_after_update() {
+        let runner = MockRunner::new(|command| {
+            if command.args.contains(&"check".to_string()) {
+                return io::Result::Ok(CommandOutput::new(
+                    0,
+                    "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1\n\
+                     nightly-x86_64-unknown-linux-gnu - Up to date : 1.82.0\n\
+                     rustup - Up to date : 1.27.1\n",
+                    "",
+                ));
+            }
+            return io::Result::Ok(CommandOutput::new(0, "", ""));
+        });
+        let config = Config {
+            recheck_after_update: true,
+            ..Config::default()
+        };
+        let toolchains = ["stable-x86_64-unknown-linux-gnu".to_string()];
+
+        let outcome = update_now_with(
+            &config,
+            &runner,
+            &test_paths("recheck_after_update"),
+            &toolchains,
+            true,
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::Updated);
+
+        // Checked after the update
+        let calls = runner.calls();
+        assert_eq!(calls[0].args, ["update", "stable-x86_64-unknown-linux-gnu"]);
+        assert!(calls[1].args.contains(&"check".to_string()));
+
+        let lines = get_rustup_check(&runner, path::Path::new("rustup"), &[], None).unwrap();
+        let updates = parse::parse_rustup_check(&lines);
+        assert_eq!(
+            installed_versions(&updates, &toolchains),
+            ["Now on stable 1.80.1"]
+        );
+        assert_eq!(
+            installed_versions(&updates, &[]),
+            [
+                "Now on stable 1.80.1",
+                "Now on nightly 1.82.0",
+                "Now on rustup 1.27.1"
+            ]
+        );
+
+        // Not checked unless asked
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, "", "")));
+        let paths = test_paths("no_recheck_after_update");
+        update_now_with(&Config::default(), &runner, &paths, &toolchains, true).unwrap();
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn parse_file() {
+        let fixture =
+            path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/rustup_check.txt");
+
+        let output = describe_parsed_file(&fixture).unwrap();
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                "Line 1: info: syncing channel updates for 'stable-x86_64-unknown-linux-gnu'",
+                "  Skipped, as one of rustup's log messages",
+                "Line 2: stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 (051478957 \
+                 2024-07-21) -> 1.81.0 (eeb90cda1 2024-09-04)",
+                "  Name: stable-x86_64-unknown-linux-gnu",
+                "  Channel: stable",
+                "  Target: x86_64-unknown-linux-gnu",
+                "  Current: 1.80.0, commit 051478957 from 2024-07-21",
+                "  New: 1.81.0, commit eeb90cda1 from 2024-09-04",
+                "  Kind: minor update",
+                "Line 3: nightly-x86_64-unknown-linux-gnu - Update available : 1.83.0-nightly \
+                 (1f3bf231e 2024-09-01) -> 1.83.0-nightly (94885bc69 2024-09-12)",
+                "  Name: nightly-x86_64-unknown-linux-gnu",
+                "  Channel: nightly",
+                "  Target: x86_64-unknown-linux-gnu",
+                "  Current: 1.83.0, commit 1f3bf231e from 2024-09-01",
+                "  New: 1.83.0, commit 94885bc69 from 2024-09-12",
+                "  Kind: update to a new build of the same version",
+                "Line 4: note: 1.75.0-x86_64-unknown-linux-gnu is overridden by \
+                 '/home/me/project/rust-toolchain.toml'",
+                "  Skipped, as a note about an override",
+                "Line 5: rustup - Up to date : 1.27.1",
+                "  Name: rustup",
+                "  Channel: rustup",
+                "  Target: none",
+                "  Current: 1.27.1",
+                "  New: none",
+                "  Kind: up to date",
+                "Line 6: this line isn't from rustup check",
+                "  Not a toolchain entry",
+            ]
+        );
+
+        let error = describe_parsed_file(path::Path::new("/nonexistent/check.txt")).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Couldn't read /nonexistent/check.txt"));
+    }
+
+    #[test]
+    fn status_only_runs_rustup() {
+        let config = Config {
+            rustup_path: Some(path::PathBuf::from("/opt/cargo/bin/rustup")),
+            ..Config::default()
+        };
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let paths = test_paths("status_only_runs_rustup");
+
+        let output = status_with(&config, &runner, &paths, Format::Text).unwrap();
+        assert_eq!(
+            output,
+            "stable-x86_64-unknown-linux-gnu: 1.80.0 -> 1.80.1\nrustup: 1.27.1, up to date\n"
+        );
+        let programs: Vec<String> = runner.calls().into_iter().map(|x| x.program).collect();
+        assert_eq!(programs, ["/opt/cargo/bin/rustup"]);
+
+        // Failures are in the line for oneline, and an error otherwise
+        let runner = MockRunner::new(|_| io::Result::Err(io::ErrorKind::NotFound.into()));
+        let paths = test_paths("status_without_rustup");
+        assert!(matches!(
+            status_with(&config, &runner, &paths, Format::Json),
+            Err(Error::RustupNotFound)
+        ));
+        assert!(status_with(&config, &runner, &paths, Format::Oneline)
+            .unwrap()
+            .starts_with("rust: "));
+    }
+
+    #[test]
+    fn update_stats() {
+        let config = Config {
+            keep_stats: true,
+            ..Config::default()
+        };
+        let paths = test_paths("update_stats");
+
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, "", "")));
+        update_now_with(&config, &runner, &paths, &[], true).unwrap();
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(1, "", "")));
+        update_now_with(&config, &runner, &paths, &[], true).unwrap();
+
+        let stats = stats::read_stats(&paths.stats).unwrap();
+        assert_eq!(stats.succeeded, 1);
+        assert_eq!(stats.failed, 1);
+        assert!(stats.last_failure_at.is_some());
+        assert_eq!(
+            stats.last_failure_reason.as_deref(),
+            Some("The update failed with exit code 1")
+        );
+
+        // Not kept unless asked
+        let paths = test_paths("update_no_stats");
+        update_now_with(&Config::default(), &runner, &paths, &[], true).unwrap();
+        assert!(!paths.stats.exists());
+    }
+
+    #[test]
+    fn update_never_started() {
+        // The terminal exited 0, but the script never ran, like a terminal
+        // server that didn't open a window
+        let runner =
+            MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, "", ""))).without_scripts();
+        let config = Config {
+            post_update_hook: vec!["cargo".to_string(), "install-update".to_string()],
+            ..Config::default()
+        };
+        let outcome = update_now_with(
+            &config,
+            &runner,
+            &test_paths("update_never_started"),
+            &[],
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            outcome,
+            Outcome::UpdateFailed {
+                code: None,
+                results: Vec::new()
+            }
+        );
+        assert!(runner.calls_to("cargo").is_empty());
+    }
+
+    #[test]
+    fn partial_update_failure() {
+        let output =
+            "  stable-x86_64-unknown-linux-gnu updated - rustc 1.80.1 (3f5fd8dd4 2024-08-06)\n\
+                      nightly-x86_64-unknown-linux-gnu update failed - rustc 1.82.0-nightly\n\
+                      beta-x86_64-unknown-linux-gnu unchanged - rustc 1.81.0-beta.7\n";
+        assert_eq!(
+            describe_update_results(&parse::parse_update_output(output)).unwrap(),
+            "Failed to update nightly-x86_64-unknown-linux-gnu. Updated \
+             stable-x86_64-unknown-linux-gnu"
+        );
+
+        let failed = "nightly-x86_64-unknown-linux-gnu update failed - rustc 1.82.0-nightly\n";
+        assert_eq!(
+            describe_update_results(&parse::parse_update_output(failed)).unwrap(),
+            "Failed to update nightly-x86_64-unknown-linux-gnu"
+        );
+        let unchanged = "beta-x86_64-unknown-linux-gnu unchanged - rustc 1.81.0-beta.7\n";
+        assert_eq!(
+            describe_update_results(&parse::parse_update_output(unchanged)),
+            None
+        );
+
+        // Still a failed update, which says what failed
+        let runner = MockRunner::new(move |_| io::Result::Ok(CommandOutput::new(1, output, "")));
+        let config = Config {
+            keep_stats: true,
+            ..Config::default()
+        };
+        let paths = test_paths("partial_update_failure");
+        let outcome = update_now_with(&config, &runner, &paths, &[], true).unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::UpdateFailed {
+                code: Some(1),
+                results: parse::parse_update_output(output)
+            }
+        );
+        assert_eq!(
+            stats::read_stats(&paths.stats)
+                .unwrap()
+                .last_failure_reason
+                .unwrap(),
+            "The update failed with exit code 1. Failed to update \
+             nightly-x86_64-unknown-linux-gnu. Updated stable-x86_64-unknown-linux-gnu"
+        );
+
+        // In a terminal, what's still out of date afterwards failed
+        let runner = MockRunner::new(|command| {
+            if command.args.contains(&"check".to_string()) {
+                return io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, ""));
+            }
+            return io::Result::Ok(CommandOutput::new(1, "", ""));
+        });
+        let outcome = update_rust(
+            &Config::default(),
+            &runner,
+            &test_paths("partial_update_failure_update"),
+            &[],
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::UpdateFailed {
+                code: Some(1),
+                results: vec![parse::UpdateResult {
+                    name: "stable-x86_64-unknown-linux-gnu".to_string(),
+                    status: parse::UpdateStatus::Failed,
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn update_notifications() {
+        let config = Config {
+            notify: true,
+            ..Config::default()
+        };
+        let toolchains = ["stable-x86_64-unknown-linux-gnu".to_string()];
+
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, "", "")));
+        update_now_with(
+            &config,
+            &runner,
+            &test_paths("notify_updated"),
+            &toolchains,
+            true,
+        )
+        .unwrap();
+        let notification = &runner.calls_to("notify-send")[0];
+        assert_eq!(notification.args[0], "--urgency=normal");
+        assert!(notification
+            .args
+            .ends_with(&["Updated stable-x86_64-unknown-linux-gnu".to_string()]));
+
+        // Failures stay until they're dismissed
+        let runner = MockRunner::new(|command| match command.program.as_str() {
+            "rustup" => io::Result::Ok(CommandOutput::new(1, "", "")),
+            _ => io::Result::Ok(CommandOutput::new(0, "", "")),
+        });
+        update_now_with(
+            &config,
+            &runner,
+            &test_paths("notify_failed"),
+            &toolchains,
+            true,
+        )
+        .unwrap();
+        let notification = &runner.calls_to("notify-send")[0];
+        assert_eq!(
+            notification.args[..2],
+            ["--urgency=critical", "--expire-time=0"]
+        );
+    }
+
+    #[test]
+    fn no_snooze() {
+        let config = Config {
+            no_snooze: true,
+            ..Config::default()
+        };
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+        let paths = test_paths("no_snooze");
+
+        // Not written when saying no
+        let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+        let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(outcome, Outcome::Declined);
+        assert!(!paths.flag.exists());
+
+        // Nor read, so a snooze already there doesn't stop the prompt, and
+        // is left as it was
+        snooze_updates(&paths.flag, &HashMap::new(), NO_UPDATE_FLAG_DELAY).unwrap();
+        let flag = fs::read_to_string(&paths.flag).unwrap();
+        let backend = FakeBackend::new(UpdatePromptAnswer::Timeout);
+        let config = Config {
+            timeout_action: TimeoutAction::SnoozeShort,
+            ..config
+        };
+        auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(backend.prompts().len(), 1);
+        assert_eq!(fs::read_to_string(&paths.flag).unwrap(), flag);
+
+        // Updating doesn't remove it either
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+        assert_eq!(fs::read_to_string(&paths.flag).unwrap(), flag);
+    }
+
+    #[test]
+    fn detached_update() {
+        let config = Config {
+            detach: true,
+            ..Config::default()
+        };
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, "", "")));
+        let toolchains = ["stable-x86_64-unknown-linux-gnu".to_string()];
+
+        let outcome = update_now_with(
+            &config,
+            &runner,
+            &test_paths("detached_update"),
+            &toolchains,
+            false,
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::UpdateStarted);
+
+        // Only the shell was started, without a terminal or hooks
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].program, "/bin/sh");
+        assert!(calls[0].args.ends_with(&[
+            "sh".to_string(),
+            "rustup".to_string(),
+            "update".to_string(),
+            "stable-x86_64-unknown-linux-gnu".to_string()
+        ]));
+        assert!(calls[0].args[1].contains("'Rust update failed'"));
+
+        // The script runs, notifying how the update went
+        let script = &calls[0].args[1];
+        let output = SystemRunner
+            .run(&CommandSpec::new(
+                "/bin/sh",
+                &["-c", &script.replace("notify-send", "echo"), "sh", "false"],
+            ))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "--urgency=critical --expire-time=0 --app-name=auto_rustup_update Rust update failed \
+             Updating stable-x86_64-unknown-linux-gnu failed. Run the update in a terminal to see why\n"
+        );
+
+        // With what failed, from the summary
+        let failing =
+            "echo '  stable-x86_64-unknown-linux-gnu update failed - rustc 1.80.0'; exit 1";
+        let output = SystemRunner
+            .run(&CommandSpec::new(
+                "/bin/sh",
+                &[
+                    "-c",
+                    &script.replace("notify-send", "echo"),
+                    "sh",
+                    "sh",
+                    "-c",
+                    failing,
+                ],
+            ))
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains(
+            "Updating stable-x86_64-unknown-linux-gnu failed. Failed to update \
+             stable-x86_64-unknown-linux-gnu. Run the update"
+        ));
+
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn preview_never_acts() {
+        let answers = [
+            UpdatePromptAnswer::Update,
+            UpdatePromptAnswer::DoNotUpdate,
+            UpdatePromptAnswer::Timeout,
+            UpdatePromptAnswer::Closed,
+        ];
+
+        for answer in answers {
+            let runner =
+                MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, STABLE_UPDATE, "")));
+            let backend = FakeBackend::new(answer);
+            let paths = test_paths("preview_never_acts");
+
+            let preview = preview_prompt_with(&Config::default(), &runner, &paths, &backend);
+            assert_eq!(preview.unwrap(), answer);
+
+            // Only checked, and looked up on the dist server, never updated
+            // or snoozed
+            assert!(runner
+                .calls()
+                .iter()
+                .all(|x| x.args.contains(&"check".to_string()) || x.program == "curl"));
+            assert!(!paths.flag.exists());
+            assert!(backend.prompts()[0].text.contains("1.80.1"));
+        }
+
+        // Without updates, or rustup, made up ones are shown
+        let runner = MockRunner::new(|_| io::Result::Err(io::ErrorKind::NotFound.into()));
+        let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+        preview_prompt_with(
+            &Config::default(),
+            &runner,
+            &test_paths("preview_made_up"),
+            &backend,
+        )
+        .unwrap();
+        assert_eq!(backend.prompts()[0].title, "Rust Update");
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn terminal_fallback() {
+        let runner = MockRunner::new(|command| match command.program.as_str() {
+            "/bin/gnome-terminal" => io::Result::Err(io::Error::other("No dbus session")),
+            "konsole" => io::Result::Err(io::ErrorKind::NotFound.into()),
+            _ => io::Result::Ok(CommandOutput::new(0, "", "")),
+        });
+
+        assert_eq!(
+            run_update(
+                &Config::default(),
+                &runner,
+                &test_paths("terminal_fallback_update"),
+                &[]
+            )
+            .unwrap()
+            .0,
+            Some(0)
+        );
+
+        let programs: Vec<String> = runner.calls().into_iter().map(|x| x.program).collect();
+        assert_eq!(
+            programs,
+            ["/bin/gnome-terminal", "konsole", "xfce4-terminal"]
+        );
+        assert_eq!(runner.calls()[2].args[0], "-x");
+    }
+
+    #[test]
+    fn terminal_fallback_exit_code() {
+        // Terminals that exit non-zero without running the script, then one
+        // fails to be found, so the update runs here
+        let runner = MockRunner::new(|command| match command.program.as_str() {
+            "rustup" => io::Result::Ok(CommandOutput::new(0, "", "")),
+            "xterm" => io::Result::Err(io::ErrorKind::NotFound.into()),
+            _ => io::Result::Ok(CommandOutput::new(1, "", "cannot open display")),
+        })
+        .without_scripts();
+
+        assert_eq!(
+            run_update(
+                &Config::default(),
+                &runner,
+                &test_paths("terminal_fallback_exit_code_update"),
+                &[]
+            )
+            .unwrap()
+            .0,
+            Some(0)
+        );
+        let programs: Vec<String> = runner.calls().into_iter().map(|x| x.program).collect();
+        assert_eq!(programs.len(), TERMINALS.len() + 1);
+        assert_eq!(programs.last().unwrap(), "rustup");
+    }
+
+    #[test]
+    fn forced_terminal() {
+        let runner = MockRunner::new(|command| match command.program.as_str() {
+            "xterm" => io::Result::Err(io::ErrorKind::NotFound.into()),
+            _ => io::Result::Ok(CommandOutput::new(0, "", "")),
+        });
+        let config = Config {
+            terminal: Some(TERMINALS[4]),
+            ..Config::default()
+        };
+
+        // Doesn't fall back to the other terminals, only to no terminal
+        assert_eq!(
+            run_update(&config, &runner, &test_paths("forced_terminal_update"), &[])
+                .unwrap()
+                .0,
+            Some(0)
+        );
+        let programs: Vec<String> = runner.calls().into_iter().map(|x| x.program).collect();
+        assert_eq!(programs, ["xterm", "rustup"]);
+    }
+
+    #[test]
+    fn terminal_fallback_headless() {
+        let runner = MockRunner::new(|command| match command.program.as_str() {
+            "rustup" => io::Result::Ok(CommandOutput::new(0, "", "")),
+            _ => io::Result::Err(io::ErrorKind::NotFound.into()),
+        });
+
+        assert_eq!(
+            update_rust(
+                &Config::default(),
+                &runner,
+                &test_paths("terminal_fallback_headless_update"),
+                &[],
+                false
+            )
+            .unwrap(),
+            Outcome::Updated
+        );
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            &CommandSpec::new("rustup", &["update"])
+        );
+    }
+
+    #[test]
+    fn terminal_fallback_all_fail() {
+        let runner = MockRunner::new(|_| io::Result::Err(io::Error::other("No display")));
+
+        let error = run_update(
+            &Config::default(),
+            &runner,
+            &test_paths("terminal_fallback_all_fail_update"),
+            &[],
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("Couldn't launch a terminal"));
+        assert_eq!(runner.calls().len(), TERMINALS.len() + 1);
+    }
+
+    #[test]
+    fn post_update_hook_skipped() {
+        let config = Config {
+            post_update_hook: vec!["cargo-not-installed".to_string()],
+            ..Config::default()
+        };
+
+        // Update fails, so the hook isn't run
+        let failing = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(1, "", "")));
+        assert_eq!(
+            update_rust(
+                &config,
+                &failing,
+                &test_paths("post_update_hook_skipped_update"),
+                &[],
+                false
+            )
+            .unwrap(),
+            Outcome::UpdateFailed {
+                code: Some(1),
+                results: Vec::new()
+            }
+        );
+        assert!(failing.calls_to("cargo-not-installed").is_empty());
+
+        // Hook isn't installed, which doesn't fail the update
+        let missing_hook = MockRunner::new(|command| {
+            if command.program == "cargo-not-installed" {
+                return io::Result::Err(io::ErrorKind::NotFound.into());
+            }
+            return io::Result::Ok(CommandOutput::new(0, "", ""));
+        });
+        assert_eq!(
+            update_rust(
+                &config,
+                &missing_hook,
+                &test_paths("post_update_hook_skipped_2_update"),
+                &[],
+                false
+            )
+            .unwrap(),
+            Outcome::Updated
+        );
+        assert_eq!(missing_hook.calls_to("cargo-not-installed").len(), 1);
+    }
+
+    #[test]
+    fn headless_update_exit_code() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(3, "", "error\n")));
+
+        let outcome = update_rust(
+            &Config::default(),
+            &runner,
+            &test_paths("headless_update_exit_code_update"),
+            &[],
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(
+            runner.calls(),
+            vec![CommandSpec::new("rustup", &["update"])]
+        );
+        assert_eq!(
+            outcome,
+            Outcome::UpdateFailed {
+                code: Some(3),
+                results: Vec::new()
+            }
+        );
+        assert_eq!(outcome.exit_code(), 3);
+
+        // An empty command is an error, rather than a panic
+        let config = Config {
+            update_command: Vec::new(),
+            ..Config::default()
+        };
+        assert!(run_update_headless(&config, &runner, &[]).is_err());
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn outcome_exit_codes() {
+        assert_eq!(Outcome::Updated.exit_code(), 0);
+        assert_eq!(Outcome::Declined.exit_code(), 0);
+        assert_eq!(
+            Outcome::UpdateFailed {
+                code: None,
+                results: Vec::new()
+            }
+            .exit_code(),
+            1
+        );
+    }
+
+    #[ignore = "Terminal opens, annoying"]
     #[test]
     fn update_test() {
-        assert!(run_update())
+        assert_eq!(
+            run_update(
+                &Config::default(),
+                &SystemRunner,
+                &test_paths("update_test_state"),
+                &[]
+            )
+            .unwrap()
+            .0,
+            Some(0)
+        )
     }
 }
@@ -0,0 +1,46 @@
+// Debug messages, shown when `RUST_LOG` asks for them, like
+// `RUST_LOG=debug` or `RUST_LOG=auto_rustup_update=trace`
+
+use std::env;
+
+// Name used to only turn on messages from this program
+const TARGET: &str = "auto_rustup_update";
+
+// True if the `RUST_LOG` value `rust_log` turns on debug messages for this
+// program. Each comma separated directive is a level, or `target=level`
+fn debug_enabled(rust_log: &str) -> bool {
+    return rust_log.split(',').any(|directive| {
+        let (target, level) = match directive.trim().split_once('=') {
+            Some((target, level)) => (Some(target.trim()), level.trim()),
+            None => (None, directive.trim()),
+        };
+
+        return target.is_none_or(|x| x == TARGET)
+            && matches!(level.to_lowercase().as_str(), "debug" | "trace");
+    });
+}
+
+/// Prints `message` to stderr, if debug messages are turned on
+pub fn debug(message: &str) {
+    if env::var("RUST_LOG").is_ok_and(|x| debug_enabled(&x)) {
+        eprintln!("[debug] {message}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_log_levels() {
+        assert!(debug_enabled("debug"));
+        assert!(debug_enabled("TRACE"));
+        assert!(debug_enabled("auto_rustup_update=debug"));
+        assert!(debug_enabled("warn,auto_rustup_update=trace"));
+
+        assert!(!debug_enabled(""));
+        assert!(!debug_enabled("info"));
+        assert!(!debug_enabled("hyper=debug"));
+        assert!(!debug_enabled("auto_rustup_update=warn"));
+    }
+}
@@ -1,10 +1,95 @@
 // Clippy configurations
 #![allow(clippy::needless_return)]
 
-use std::io;
+use std::{env, process};
 
-fn main() -> io::Result<()> {
-    auto_rustup_update::auto_update()?;
+use auto_rustup_update::{cli, paint, Color, Error, Stream};
 
-    return io::Result::Ok(());
+// Runs the command, returning the exit code
+fn run() -> Result<i32, Error> {
+    let cli = cli::parse_args(env::args().skip(1))?;
+
+    let config = cli.load_config()?;
+
+    let outcome = match cli.command {
+        cli::Command::Auto => auto_rustup_update::auto_update_with_config(&config, None)?,
+        cli::Command::UpdateNow { wait } => auto_rustup_update::update_now(&config, wait)?,
+        cli::Command::UpdateToolchains { toolchains } => {
+            auto_rustup_update::update_toolchains(&config, &toolchains)?
+        }
+        cli::Command::Install => {
+            auto_rustup_update::install()?;
+            return Ok(0);
+        }
+        cli::Command::Uninstall => {
+            auto_rustup_update::uninstall()?;
+            return Ok(0);
+        }
+        cli::Command::Diagnose => {
+            auto_rustup_update::diagnose()?;
+            return Ok(0);
+        }
+        cli::Command::Snooze { seconds } => {
+            auto_rustup_update::snooze_for(&config, seconds)?;
+            return Ok(0);
+        }
+        cli::Command::Status { format } => {
+            // The status is in the text, so scripts don't need to check the
+            // exit code
+            auto_rustup_update::print_status(&config, format)?;
+            return Ok(0);
+        }
+        cli::Command::Watch { interval } => {
+            auto_rustup_update::watch(&config, interval)?;
+            return Ok(0);
+        }
+        cli::Command::OnNextBoot => {
+            auto_rustup_update::update_on_next_boot(&config)?;
+            return Ok(0);
+        }
+        cli::Command::ParseFile { path } => {
+            auto_rustup_update::print_parsed_file(&path)?;
+            return Ok(0);
+        }
+        cli::Command::Aggregate { dir, json } => {
+            auto_rustup_update::print_aggregate(&dir, json)?;
+            return Ok(0);
+        }
+        cli::Command::Stats => {
+            auto_rustup_update::print_stats(&config)?;
+            return Ok(0);
+        }
+        cli::Command::Why => {
+            auto_rustup_update::print_why(&config)?;
+            return Ok(0);
+        }
+        cli::Command::ShowConfig { json } => {
+            auto_rustup_update::show_config(&config, json);
+            return Ok(0);
+        }
+        cli::Command::PreviewPrompt => {
+            auto_rustup_update::preview_prompt(&config)?;
+            return Ok(0);
+        }
+        cli::Command::CheckComponents => {
+            let mismatches = auto_rustup_update::check_components()?;
+            return Ok(if mismatches.is_empty() { 0 } else { 1 });
+        }
+    };
+
+    return Ok(outcome.exit_code());
+}
+
+fn main() {
+    // Expected errors are one line in the journal, not a backtrace
+    match run() {
+        Ok(code) => process::exit(code),
+        Err(error) => {
+            eprintln!("{} {error}", paint("Error:", Color::Red, Stream::Stderr));
+            if let Some(hint) = error.hint() {
+                eprintln!("{hint}");
+            }
+            process::exit(error.exit_code());
+        }
+    }
 }
@@ -0,0 +1,97 @@
+// Metrics written by `--metrics`, in the Prometheus text format, for
+// node_exporter's textfile collector
+
+use std::{io, path};
+
+use crate::{parse::ParsedUpdate, state_file};
+
+// Label values are quoted, so quotes, backslashes and new lines are escaped
+fn escape_label(value: &str) -> String {
+    return value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+}
+
+/// Builds the metrics, like
+///
+/// ```text
+/// rust_update_available{toolchain="stable",target="x86_64-unknown-linux-gnu"} 1
+/// rust_last_check_timestamp 1723400000
+/// ```
+///
+/// Each entry of the check gets `rust_update_available`, 1 if it has an
+/// update. The target label is only there if the name has one
+pub fn build_metrics(updates: &[ParsedUpdate], checked_at: u64) -> String {
+    let mut metrics = String::from(
+        "# HELP rust_update_available Whether the toolchain has an update\n\
+         # TYPE rust_update_available gauge\n",
+    );
+    for update in updates {
+        let mut labels = format!("toolchain=\"{}\"", escape_label(update.channel()));
+        if let Some(target) = update.target() {
+            labels.push_str(&format!(",target=\"{}\"", escape_label(target)));
+        }
+
+        metrics.push_str(&format!(
+            "rust_update_available{{{labels}}} {}\n",
+            update.new.is_some() as u8
+        ));
+    }
+
+    metrics.push_str(&format!(
+        "# HELP rust_last_check_timestamp When rustup check last ran, in seconds since the \
+         unix epoch\n\
+         # TYPE rust_last_check_timestamp gauge\n\
+         rust_last_check_timestamp {checked_at}\n"
+    ));
+
+    return metrics;
+}
+
+/// Writes the metrics to `path`, replacing the last ones
+///
+/// They're written next to it first, then renamed over it, so the collector
+/// never reads half a file. The temporary file ends in `.tmp`, not `.prom`,
+/// so the collector skips it
+pub fn write_metrics(
+    path: &path::Path,
+    updates: &[ParsedUpdate],
+    checked_at: u64,
+) -> io::Result<()> {
+    return state_file::write_atomic(path, &build_metrics(updates, checked_at));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse::parse_rustup_check, test_util::temp_dir};
+    use std::fs;
+
+    #[test]
+    fn metrics_file() {
+        let updates = parse_rustup_check(&[
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1".to_string(),
+            "nightly-x86_64-unknown-linux-gnu - Up to date : 1.82.0-nightly".to_string(),
+            "rustup - Up to date : 1.27.1".to_string(),
+        ]);
+        let path = temp_dir("metrics_file").join("rust.prom");
+
+        write_metrics(&path, &updates, 1723400000).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# HELP rust_update_available Whether the toolchain has an update\n\
+             # TYPE rust_update_available gauge\n\
+             rust_update_available{toolchain=\"stable\",target=\"x86_64-unknown-linux-gnu\"} 1\n\
+             rust_update_available{toolchain=\"nightly\",target=\"x86_64-unknown-linux-gnu\"} 0\n\
+             rust_update_available{toolchain=\"rustup\"} 0\n\
+             # HELP rust_last_check_timestamp When rustup check last ran, in seconds since \
+             the unix epoch\n\
+             # TYPE rust_last_check_timestamp gauge\n\
+             rust_last_check_timestamp 1723400000\n"
+        );
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        assert_eq!(escape_label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -0,0 +1,124 @@
+// An update put off until the next boot, with `--on-next-boot`. The first
+// run after a reboot, like from the timer's `OnBootSec`, updates
+
+use std::{fs, io, path};
+
+use crate::state_file;
+
+// Changes every boot
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+/// An update asked for on the boot `boot_id`, to run on a later one
+#[derive(Debug, Clone, PartialEq)]
+pub struct BootIntent {
+    /// The boot it was asked for on
+    pub boot_id: String,
+
+    /// Seconds since the unix epoch
+    pub recorded_at: u64,
+}
+
+impl BootIntent {
+    // File is made of `key=value` lines
+    fn parse(content: &str) -> BootIntent {
+        let mut boot_id = String::new();
+        let mut recorded_at = 0;
+
+        for (key, value) in state_file::key_values(content) {
+            match key {
+                "boot_id" => boot_id = value.trim().to_string(),
+                "recorded_at" => recorded_at = value.trim().parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+
+        return BootIntent {
+            boot_id,
+            recorded_at,
+        };
+    }
+
+    fn serialize(&self) -> String {
+        return format!(
+            "boot_id={}\nrecorded_at={}\n",
+            self.boot_id, self.recorded_at
+        );
+    }
+}
+
+/// Id of the boot this is running on
+pub fn current_boot_id() -> io::Result<String> {
+    return io::Result::Ok(fs::read_to_string(BOOT_ID_PATH)?.trim().to_string());
+}
+
+/// Reads the intent at `path`, None if there isn't one
+pub fn read_intent(path: &path::Path) -> io::Result<Option<BootIntent>> {
+    match fs::read_to_string(path) {
+        io::Result::Ok(content) => return io::Result::Ok(Some(BootIntent::parse(&content))),
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(None)
+        }
+        io::Result::Err(error) => return io::Result::Err(error),
+    }
+}
+
+/// Saves `intent` at `path`, replacing any already there
+pub fn write_intent(path: &path::Path, intent: &BootIntent) -> io::Result<()> {
+    return state_file::write_atomic(path, &intent.serialize());
+}
+
+/// The intent at `path`, if it was asked for on a boot other than
+/// `boot_id`. None if it's for this boot or there isn't one
+///
+/// It's left there, to be removed with `remove_intent` once the update
+/// works. A malformed intent has no boot id, so it's returned
+pub fn rebooted_intent(path: &path::Path, boot_id: &str) -> io::Result<Option<BootIntent>> {
+    match read_intent(path)? {
+        Some(intent) if intent.boot_id != boot_id => return io::Result::Ok(Some(intent)),
+        _ => return io::Result::Ok(None),
+    }
+}
+
+/// Removes the intent at `path`, if there is one
+pub fn remove_intent(path: &path::Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(())
+        }
+        x => return x,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn record_and_consume() {
+        let path = temp_dir("next_boot_record_and_consume").join("next_boot");
+        assert_eq!(rebooted_intent(&path, "boot-1").unwrap(), None);
+
+        let intent = BootIntent {
+            boot_id: "boot-1".to_string(),
+            recorded_at: 1000,
+        };
+        write_intent(&path, &intent).unwrap();
+        assert_eq!(read_intent(&path).unwrap(), Some(intent.clone()));
+
+        // Kept until the next boot, then until it's removed
+        assert_eq!(rebooted_intent(&path, "boot-1").unwrap(), None);
+        assert!(path.exists());
+
+        assert_eq!(rebooted_intent(&path, "boot-2").unwrap(), Some(intent));
+        assert!(path.exists());
+        remove_intent(&path).unwrap();
+        assert_eq!(rebooted_intent(&path, "boot-2").unwrap(), None);
+        remove_intent(&path).unwrap();
+
+        fs::write(&path, "bogus").unwrap();
+        assert!(rebooted_intent(&path, "boot-2").unwrap().is_some());
+
+        assert!(!current_boot_id().unwrap().is_empty());
+    }
+}
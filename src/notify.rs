@@ -0,0 +1,146 @@
+// Desktop notifications about updates, shown with notify-send
+
+use std::{io, str};
+
+use crate::{runner::describe_status, CommandRunner, CommandSpec};
+
+/// How much a notification asks for attention, as notify-send's
+/// `--urgency`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotifyUrgency {
+    Low,
+    Normal,
+    Critical,
+}
+
+impl NotifyUrgency {
+    /// Its name, as read by `from_str`
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotifyUrgency::Low => return "low",
+            NotifyUrgency::Normal => return "normal",
+            NotifyUrgency::Critical => return "critical",
+        }
+    }
+}
+
+impl str::FromStr for NotifyUrgency {
+    type Err = String;
+
+    /// Reads `low`, `normal` or `critical`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "low" => return Ok(NotifyUrgency::Low),
+            "normal" => return Ok(NotifyUrgency::Normal),
+            "critical" => return Ok(NotifyUrgency::Critical),
+            _ => {
+                return Err(format!(
+                    "Unknown urgency '{text}'. Expected low, normal or critical"
+                ))
+            }
+        }
+    }
+}
+
+/// How a notification is shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NotifyStyle {
+    pub urgency: NotifyUrgency,
+
+    /// Stays until it's dismissed, rather than going after a few seconds
+    pub persistent: bool,
+}
+
+impl NotifyStyle {
+    /// For failures, which shouldn't be missed
+    pub const FAILURE: NotifyStyle = NotifyStyle {
+        urgency: NotifyUrgency::Critical,
+        persistent: true,
+    };
+}
+
+impl Default for NotifyStyle {
+    fn default() -> Self {
+        return NotifyStyle {
+            urgency: NotifyUrgency::Normal,
+            persistent: false,
+        };
+    }
+}
+
+/// Arguments to notify-send to show `summary` and `body` in `style`
+pub fn notify_send_args(summary: &str, body: &str, style: NotifyStyle) -> Vec<String> {
+    let mut args = vec![format!("--urgency={}", style.urgency.name())];
+
+    // An expire time of 0 never expires
+    if style.persistent {
+        args.push("--expire-time=0".to_string());
+    }
+    args.push("--app-name=auto_rustup_update".to_string());
+    args.push(summary.to_string());
+    args.push(body.to_string());
+
+    return args;
+}
+
+/// Shows a notification with notify-send. Only printed if it can't be
+/// shown, as the notification is extra to what's printed
+pub fn notify(runner: &dyn CommandRunner, summary: &str, body: &str, style: NotifyStyle) {
+    let command = CommandSpec::new("notify-send", &notify_send_args(summary, body, style));
+
+    match runner.run(&command) {
+        io::Result::Ok(output) if output.success() => {}
+        io::Result::Ok(output) => {
+            eprintln!("notify-send ended with {}", describe_status(output.status));
+        }
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            eprintln!("Can't show a notification. Is notify-send installed?");
+        }
+        io::Result::Err(error) => eprintln!("Couldn't show a notification: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_send_arguments() {
+        let args = |urgency: NotifyUrgency, persistent: bool| {
+            let style = NotifyStyle {
+                urgency,
+                persistent,
+            };
+
+            return notify_send_args("Rust updated", "Now on stable 1.80.1", style);
+        };
+        let rest = [
+            "--app-name=auto_rustup_update",
+            "Rust updated",
+            "Now on stable 1.80.1",
+        ];
+
+        for (urgency, name) in [
+            (NotifyUrgency::Low, "low"),
+            (NotifyUrgency::Normal, "normal"),
+            (NotifyUrgency::Critical, "critical"),
+        ] {
+            let mut expected = vec![format!("--urgency={name}")];
+            expected.extend(rest.iter().map(|x| x.to_string()));
+            assert_eq!(args(urgency, false), expected);
+
+            expected.insert(1, "--expire-time=0".to_string());
+            assert_eq!(args(urgency, true), expected);
+        }
+
+        assert_eq!(
+            NotifyStyle::FAILURE,
+            NotifyStyle {
+                urgency: NotifyUrgency::Critical,
+                persistent: true
+            }
+        );
+        assert_eq!("critical".parse(), Ok(NotifyUrgency::Critical));
+        assert!("urgent".parse::<NotifyUrgency>().is_err());
+    }
+}
@@ -0,0 +1,650 @@
+use regex::Regex;
+
+use crate::{json, version::Version};
+
+/// The commit and date a toolchain was built from, like the
+/// `(051478957 2024-07-21)` after its version
+#[derive(Debug, Clone, PartialEq)]
+pub struct Build {
+    /// Short hash of the commit, like `051478957`
+    pub commit: String,
+
+    /// Date of the commit, like `2024-07-21`
+    pub date: String,
+}
+
+impl Build {
+    /// Finds the first build in some text, like `1.80.0 (051478957
+    /// 2024-07-21)`. None if there isn't one
+    pub fn find(text: &str) -> Option<Build> {
+        let build_regex = Regex::new(r"\(([0-9a-f]+) ([0-9]{4}-[0-9]{2}-[0-9]{2})\)").unwrap();
+        let captures = build_regex.captures(text)?;
+
+        return Some(Build {
+            commit: captures[1].to_string(),
+            date: captures[2].to_string(),
+        });
+    }
+}
+
+/// What a toolchain follows, read from the channel part of its name
+#[derive(Debug, Clone, PartialEq)]
+pub enum Channel {
+    Stable,
+    Beta,
+
+    /// With the date of an archived nightly, like `2024-08-01` for
+    /// `nightly-2024-08-01`. None for the latest nightly
+    Nightly {
+        date: Option<String>,
+    },
+
+    /// rustup itself, rather than a toolchain
+    Rustup,
+
+    /// A release, like `1.75.0`. A minor version, like `1.75`, is its first
+    /// release
+    Pinned(Version),
+
+    /// Anything else, like a toolchain linked with `rustup toolchain link`,
+    /// by name
+    Custom(String),
+}
+
+impl Channel {
+    /// The channel of the toolchain `name`, like `Stable` for
+    /// `stable-x86_64-unknown-linux-gnu`
+    pub fn of(name: &str) -> Channel {
+        if name == "rustup" {
+            return Channel::Rustup;
+        }
+
+        let channel = split_toolchain_name(name).0;
+        match channel {
+            "stable" => return Channel::Stable,
+            "beta" => return Channel::Beta,
+            "nightly" => return Channel::Nightly { date: None },
+            _ => {}
+        }
+        if let Some(date) = channel.strip_prefix("nightly-") {
+            return Channel::Nightly {
+                date: Some(date.to_string()),
+            };
+        }
+
+        let pinned = match channel.split('.').count() {
+            2 => format!("{channel}.0").parse(),
+            _ => channel.parse(),
+        };
+        match pinned {
+            Ok(version) => return Channel::Pinned(version),
+            Err(_) => return Channel::Custom(channel.to_string()),
+        }
+    }
+}
+
+/// A toolchain, or rustup itself, from a line of `rustup check`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedUpdate {
+    /// Name of the toolchain, like `stable-x86_64-unknown-linux-gnu`, or
+    /// `rustup`
+    pub name: String,
+
+    /// Version installed now
+    pub current: Option<Version>,
+
+    /// Version that can be updated to. None if up to date
+    pub new: Option<Version>,
+
+    /// Build of the version installed now, if `rustup check` gave it
+    pub current_build: Option<Build>,
+
+    /// Build of the version that can be updated to, if `rustup check` gave
+    /// it
+    pub new_build: Option<Build>,
+}
+
+impl ParsedUpdate {
+    /// The channel part of the name, like `stable`, `nightly-2024-08-01` or
+    /// `1.75.0`
+    pub fn channel(&self) -> &str {
+        return split_toolchain_name(&self.name).0;
+    }
+
+    /// What the toolchain follows, from its name
+    pub fn channel_kind(&self) -> Channel {
+        return Channel::of(&self.name);
+    }
+
+    /// The target part of the name, like `x86_64-unknown-linux-gnu`
+    pub fn target(&self) -> Option<&str> {
+        return split_toolchain_name(&self.name).1;
+    }
+
+    /// Its channel, to show, with its target too if another of `updates`
+    /// has the same channel, like `stable (aarch64-unknown-linux-gnu)` when
+    /// cross compiling
+    pub fn short_name(&self, updates: &[ParsedUpdate]) -> String {
+        let shared = updates
+            .iter()
+            .any(|x| x.name != self.name && x.channel() == self.channel());
+
+        match self.target() {
+            Some(target) if shared => return format!("{} ({target})", self.channel()),
+            _ => return self.channel().to_string(),
+        }
+    }
+}
+
+/// True for the first part of a toolchain name that's a channel, like
+/// `stable`, or a version, like `1.75` or `1.75.0`
+pub fn is_channel(part: &str) -> bool {
+    return matches!(part, "stable" | "beta" | "nightly")
+        || (part.starts_with(|x: char| x.is_ascii_digit())
+            && part.chars().all(|x| x.is_ascii_digit() || x == '.'));
+}
+
+// True if the parts are a date, like ["2024", "08", "01"]
+fn is_date(parts: &[&str]) -> bool {
+    return parts.len() == 3
+        && [4, 2, 2].iter().zip(parts).all(|(length, part)| {
+            part.len() == *length && part.chars().all(|x| x.is_ascii_digit())
+        });
+}
+
+/// Splits a toolchain name into its channel and target, like
+/// `nightly-2024-08-01-x86_64-unknown-linux-gnu` into `nightly-2024-08-01`
+/// and `x86_64-unknown-linux-gnu`
+///
+/// A name that doesn't start with a channel or version, like a linked
+/// custom toolchain or `rustup`, is all channel
+pub fn split_toolchain_name(name: &str) -> (&str, Option<&str>) {
+    let parts: Vec<&str> = name.split('-').collect();
+    if !is_channel(parts[0]) {
+        return (name, None);
+    }
+
+    let channel_parts = if parts.len() >= 4 && is_date(&parts[1..4]) {
+        4
+    } else {
+        1
+    };
+
+    // Length of the channel parts, and the dashes between them
+    let channel_length = parts[..channel_parts]
+        .iter()
+        .map(|x| x.len() + 1)
+        .sum::<usize>()
+        - 1;
+
+    match name[channel_length..].strip_prefix('-') {
+        Some(target) if !target.is_empty() => return (&name[..channel_length], Some(target)),
+        _ => return (&name[..channel_length], None),
+    }
+}
+
+/// True if `line` is a note rustup prints about the toolchain overridden
+/// for the current directory, like from a `rust-toolchain.toml`, rather
+/// than a toolchain entry
+pub fn is_override_note(line: &str) -> bool {
+    let line = line.trim_start().to_lowercase();
+
+    return line.contains("overrid") && (line.starts_with("note:") || !line.contains(" - "));
+}
+
+/// Parses a line of `rustup check`, like
+///
+/// `stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1 (051478957 2024-07-21)`
+///
+/// None if the line isn't a toolchain entry
+pub fn parse_rustup_check_line(line: &str) -> Option<ParsedUpdate> {
+    if is_override_note(line) {
+        return None;
+    }
+
+    let (name, status) = line.split_once(" - ")?;
+    let (state, versions) = status.split_once(':').unwrap_or((status, ""));
+
+    // The text of the current and new versions, each with their build
+    let (current, new) = if state.contains("Update available") {
+        match versions.split_once("->") {
+            Some((current, new)) => (current, Some(new)),
+            None => ("", Some(versions)),
+        }
+    } else if state.contains("Up to date") {
+        (versions, None)
+    } else {
+        return None;
+    };
+
+    let new_version = match new {
+        Some(new) => Some(Version::find(new)?),
+        None => None,
+    };
+
+    // A toolchain pinned to a version can be listed without one
+    let pinned = split_toolchain_name(name.trim()).0.parse().ok();
+    let current_version = match new {
+        Some(_) => Version::find(current),
+        None => Version::find(current).or(pinned),
+    };
+
+    return Some(ParsedUpdate {
+        name: name.trim().to_string(),
+        current: current_version,
+        new: new_version,
+        current_build: Build::find(current),
+        new_build: new.and_then(Build::find),
+    });
+}
+
+/// Names of the toolchains in the output of `rustup toolchain list`, in
+/// order. Lines look like `stable-x86_64-unknown-linux-gnu (default)`, and
+/// the markers after the name are left out
+pub fn parse_toolchain_list(output: &str) -> Vec<String> {
+    return output
+        .lines()
+        .filter(|x| !x.starts_with("no installed toolchains"))
+        .filter_map(|x| x.split_whitespace().next())
+        .map(|x| x.to_string())
+        .collect();
+}
+
+/// How `rustup update` went for a toolchain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateStatus {
+    Installed,
+    Updated,
+    Unchanged,
+    Failed,
+}
+
+/// A toolchain's line in the summary at the end of `rustup update`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateResult {
+    pub name: String,
+    pub status: UpdateStatus,
+}
+
+/// The toolchains in the summary at the end of the output of `rustup
+/// update`, with how each went. Lines look like
+///
+/// `stable-x86_64-unknown-linux-gnu updated - rustc 1.80.1 (3f5fd8dd4 2024-08-06) (from rustc 1.80.0 (051478957 2024-07-21))`
+///
+/// or `nightly-x86_64-unknown-linux-gnu update failed - rustc 1.82.0-nightly`.
+/// Other lines, like `info:` ones, are skipped
+pub fn parse_update_output(output: &str) -> Vec<UpdateResult> {
+    let summary_regex =
+        Regex::new(r"^(\S+) (installed|updated|unchanged|update failed) - ").unwrap();
+
+    return output
+        .lines()
+        .filter_map(|line| {
+            let captures = summary_regex.captures(line.trim())?;
+            let status = match &captures[2] {
+                "installed" => UpdateStatus::Installed,
+                "updated" => UpdateStatus::Updated,
+                "unchanged" => UpdateStatus::Unchanged,
+                _ => UpdateStatus::Failed,
+            };
+
+            return Some(UpdateResult {
+                name: captures[1].to_string(),
+                status,
+            });
+        })
+        .collect();
+}
+
+/// Parses every toolchain entry in the lines of `rustup check`
+pub fn parse_rustup_check(rustup_check_lines: &[String]) -> Vec<ParsedUpdate> {
+    return rustup_check_lines
+        .iter()
+        .filter_map(|x| parse_rustup_check_line(x))
+        .collect();
+}
+
+// Text line for an entry of `rustup check --json`, in the same form as
+// plain `rustup check`. None if the entry isn't understood
+fn json_entry_line(name: &str, entry: &json::Value) -> Option<String> {
+    let current = entry.get("current_version").and_then(|x| x.as_str());
+
+    match entry.get("status").and_then(|x| x.as_str())? {
+        "update-available" => {
+            let new = entry.get("new_version").and_then(|x| x.as_str())?;
+
+            match current {
+                Some(current) => {
+                    return Some(format!("{name} - Update available : {current} -> {new}"))
+                }
+                None => return Some(format!("{name} - Update available : {new}")),
+            }
+        }
+        "up-to-date" => return Some(format!("{name} - Up to date : {}", current.unwrap_or(""))),
+        _ => return None,
+    }
+}
+
+/// Turns the output of `rustup check --json` into the lines plain
+/// `rustup check` would print, so both are parsed the same way
+///
+/// The output is expected to look like
+///
+/// ```text
+/// {
+///     "toolchains": [
+///         {
+///             "name": "stable-x86_64-unknown-linux-gnu",
+///             "status": "update-available",
+///             "current_version": "1.80.0 (051478957 2024-07-21)",
+///             "new_version": "1.80.1 (3f5fd8dd4 2024-08-06)"
+///         }
+///     ],
+///     "rustup": { "status": "up-to-date", "current_version": "1.27.1" }
+/// }
+/// ```
+///
+/// Entries with an unknown status are skipped
+pub fn json_check_lines(document: &str) -> Result<Vec<String>, String> {
+    let value = json::parse(document)?;
+
+    let toolchains = value
+        .get("toolchains")
+        .and_then(|x| x.as_array())
+        .ok_or("Expected a list of 'toolchains'")?;
+
+    let mut lines = Vec::new();
+    for toolchain in toolchains {
+        let name = toolchain
+            .get("name")
+            .and_then(|x| x.as_str())
+            .ok_or("Toolchain without a 'name'")?;
+
+        lines.extend(json_entry_line(name, toolchain));
+    }
+
+    if let Some(rustup) = value.get("rustup") {
+        lines.extend(json_entry_line("rustup", rustup));
+    }
+
+    return Ok(lines);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_available() {
+        let update = parse_rustup_check_line(
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1 (051478957 2024-07-21)",
+        )
+        .unwrap();
+
+        assert_eq!(
+            update,
+            ParsedUpdate {
+                name: "stable-x86_64-unknown-linux-gnu".to_string(),
+                current: Some(Version::new(1, 80, 0)),
+                new: Some(Version::new(1, 80, 1)),
+                current_build: None,
+                new_build: Some(Build {
+                    commit: "051478957".to_string(),
+                    date: "2024-07-21".to_string(),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn override_note() {
+        let lines = [
+            "note: the toolchain 'nightly-2024-07-01' is in use - overridden by \
+             '/home/daisy/project/rust-toolchain.toml'"
+                .to_string(),
+            "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1".to_string(),
+        ];
+        assert!(is_override_note(&lines[0]));
+        assert!(!is_override_note(&lines[1]));
+        assert!(is_override_note(
+            "nightly-2024-07-01-x86_64-unknown-linux-gnu (overridden by '/home/daisy/project')"
+        ));
+
+        let updates = parse_rustup_check(&lines);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].name, "stable-x86_64-unknown-linux-gnu");
+    }
+
+    #[test]
+    fn builds() {
+        let update = parse_rustup_check_line(
+            "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.0 (051478957 2024-07-21)",
+        )
+        .unwrap();
+        assert_eq!(
+            update.current_build,
+            Some(Build {
+                commit: "051478957".to_string(),
+                date: "2024-07-21".to_string(),
+            })
+        );
+        assert_eq!(update.new_build, None);
+
+        // Without the build, the versions are still read
+        let update =
+            parse_rustup_check_line("stable-x86_64-unknown-linux-gnu - Up to date : 1.80.0")
+                .unwrap();
+        assert_eq!(update.current, Some(Version::new(1, 80, 0)));
+        assert_eq!(update.current_build, None);
+
+        assert_eq!(Build::find("1.80.0 (not a build)"), None);
+    }
+
+    #[test]
+    fn up_to_date() {
+        let update = parse_rustup_check_line("rustup - Up to date : 1.27.1").unwrap();
+
+        assert_eq!(update.name, "rustup");
+        assert_eq!(update.current, Some(Version::new(1, 27, 1)));
+        assert_eq!(update.new, None);
+    }
+
+    #[test]
+    fn version_pinned() {
+        let update =
+            parse_rustup_check_line("1.75.0-x86_64-unknown-linux-gnu - Up to date").unwrap();
+
+        assert_eq!(update.name, "1.75.0-x86_64-unknown-linux-gnu");
+        assert_eq!(update.channel(), "1.75.0");
+        assert_eq!(update.target(), Some("x86_64-unknown-linux-gnu"));
+        assert_eq!(update.current, Some(Version::new(1, 75, 0)));
+        assert_eq!(update.new, None);
+
+        let update = parse_rustup_check_line(
+            "1.75-x86_64-unknown-linux-gnu - Up to date : 1.75.0 (82e1608df 2023-12-21)",
+        )
+        .unwrap();
+        assert_eq!(update.channel(), "1.75");
+        assert_eq!(update.current, Some(Version::new(1, 75, 0)));
+    }
+
+    #[test]
+    fn channel_kinds() {
+        let kinds = [
+            ("stable-x86_64-unknown-linux-gnu", Channel::Stable),
+            ("stable", Channel::Stable),
+            ("beta-aarch64-apple-darwin", Channel::Beta),
+            (
+                "nightly-x86_64-unknown-linux-gnu",
+                Channel::Nightly { date: None },
+            ),
+            (
+                "nightly-2024-08-01-x86_64-unknown-linux-gnu",
+                Channel::Nightly {
+                    date: Some("2024-08-01".to_string()),
+                },
+            ),
+            ("rustup", Channel::Rustup),
+            (
+                "1.75.0-x86_64-unknown-linux-gnu",
+                Channel::Pinned(Version::new(1, 75, 0)),
+            ),
+            ("1.75", Channel::Pinned(Version::new(1, 75, 0))),
+            ("my-build", Channel::Custom("my-build".to_string())),
+            ("stage1", Channel::Custom("stage1".to_string())),
+        ];
+
+        for (name, kind) in kinds {
+            assert_eq!(Channel::of(name), kind, "{name}");
+        }
+        let update = parse_rustup_check_line("beta-x86_64-unknown-linux-gnu - Up to date").unwrap();
+        assert_eq!(update.channel_kind(), Channel::Beta);
+    }
+
+    #[test]
+    fn update_output() {
+        let output = "info: syncing channel updates for 'stable-x86_64-unknown-linux-gnu'\n\
+                      error: component download failed for rust-std-x86_64-unknown-linux-gnu\n\
+                      \n  stable-x86_64-unknown-linux-gnu updated - rustc 1.80.1 (3f5fd8dd4 2024-08-06) \
+                      (from rustc 1.80.0 (051478957 2024-07-21))\n\
+                      nightly-x86_64-unknown-linux-gnu update failed - rustc 1.82.0-nightly \
+                      (90ab8eaed 2024-09-06)\n\
+                      beta-x86_64-unknown-linux-gnu unchanged - rustc 1.81.0-beta.7\n\
+                      1.75.0-x86_64-unknown-linux-gnu installed - rustc 1.75.0\n\
+                      info: cleaning up downloads & tmp directories\n";
+        let result = |name: &str, status| UpdateResult {
+            name: name.to_string(),
+            status,
+        };
+
+        assert_eq!(
+            parse_update_output(output),
+            [
+                result("stable-x86_64-unknown-linux-gnu", UpdateStatus::Updated),
+                result("nightly-x86_64-unknown-linux-gnu", UpdateStatus::Failed),
+                result("beta-x86_64-unknown-linux-gnu", UpdateStatus::Unchanged),
+                result("1.75.0-x86_64-unknown-linux-gnu", UpdateStatus::Installed),
+            ]
+        );
+        assert!(parse_update_output("error: no such toolchain").is_empty());
+    }
+
+    #[test]
+    fn short_names() {
+        let updates = parse_rustup_check(&[
+            "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.1".to_string(),
+            "stable-aarch64-unknown-linux-gnu - Up to date : 1.80.1".to_string(),
+            "nightly-x86_64-unknown-linux-gnu - Up to date : 1.82.0".to_string(),
+            "rustup - Up to date : 1.27.1".to_string(),
+        ]);
+
+        let names: Vec<String> = updates.iter().map(|x| x.short_name(&updates)).collect();
+        assert_eq!(
+            names,
+            [
+                "stable (x86_64-unknown-linux-gnu)",
+                "stable (aarch64-unknown-linux-gnu)",
+                "nightly",
+                "rustup"
+            ]
+        );
+    }
+
+    #[test]
+    fn toolchain_names() {
+        assert_eq!(
+            split_toolchain_name("stable-x86_64-unknown-linux-gnu"),
+            ("stable", Some("x86_64-unknown-linux-gnu"))
+        );
+        assert_eq!(
+            split_toolchain_name("nightly-2024-08-01-aarch64-apple-darwin"),
+            ("nightly-2024-08-01", Some("aarch64-apple-darwin"))
+        );
+        assert_eq!(
+            split_toolchain_name("nightly-2024-08-01"),
+            ("nightly-2024-08-01", None)
+        );
+        assert_eq!(
+            split_toolchain_name("1.80.1-x86_64-unknown-linux-musl"),
+            ("1.80.1", Some("x86_64-unknown-linux-musl"))
+        );
+        assert_eq!(split_toolchain_name("beta"), ("beta", None));
+        assert_eq!(split_toolchain_name("rustup"), ("rustup", None));
+        assert_eq!(
+            split_toolchain_name("my-local-build"),
+            ("my-local-build", None)
+        );
+    }
+
+    #[test]
+    fn toolchain_list() {
+        assert_eq!(
+            parse_toolchain_list(
+                "stable-x86_64-unknown-linux-gnu (default)\n\
+                 nightly-x86_64-unknown-linux-gnu (active)\n\
+                 1.75.0-x86_64-unknown-linux-gnu\n\
+                 my-local-build\n"
+            ),
+            [
+                "stable-x86_64-unknown-linux-gnu",
+                "nightly-x86_64-unknown-linux-gnu",
+                "1.75.0-x86_64-unknown-linux-gnu",
+                "my-local-build",
+            ]
+        );
+        assert!(parse_toolchain_list("no installed toolchains\n").is_empty());
+        assert!(parse_toolchain_list("").is_empty());
+    }
+
+    #[test]
+    fn not_an_entry() {
+        assert_eq!(parse_rustup_check_line("info: checking for updates"), None);
+        assert_eq!(parse_rustup_check_line("stable - Something else"), None);
+    }
+
+    #[test]
+    fn json_output() {
+        let lines = json_check_lines(
+            r#"{
+                "toolchains": [
+                    {
+                        "name": "stable-x86_64-unknown-linux-gnu",
+                        "status": "update-available",
+                        "current_version": "1.80.0 (051478957 2024-07-21)",
+                        "new_version": "1.80.1 (3f5fd8dd4 2024-08-06)"
+                    },
+                    {
+                        "name": "nightly-x86_64-unknown-linux-gnu",
+                        "status": "up-to-date",
+                        "current_version": "1.82.0-nightly (2f3dc46465 2024-08-20)"
+                    },
+                    { "name": "beta", "status": "something-new" }
+                ],
+                "rustup": { "status": "up-to-date", "current_version": "1.27.1" }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            lines,
+            [
+                "stable-x86_64-unknown-linux-gnu - Update available : \
+                 1.80.0 (051478957 2024-07-21) -> 1.80.1 (3f5fd8dd4 2024-08-06)",
+                "nightly-x86_64-unknown-linux-gnu - Up to date : 1.82.0-nightly (2f3dc46465 2024-08-20)",
+                "rustup - Up to date : 1.27.1",
+            ]
+        );
+
+        // Reads the same as the text lines would
+        let updates = parse_rustup_check(&lines);
+        assert_eq!(updates[0].new, Some(Version::new(1, 80, 1)));
+        assert_eq!(updates[1].new, None);
+    }
+
+    #[test]
+    fn malformed_json_output() {
+        assert!(json_check_lines("{}").is_err());
+        assert!(json_check_lines(r#"{"toolchains": [{}]}"#).is_err());
+        assert!(json_check_lines("{").is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::{fs, io, path};
+
+use crate::state_file;
+
+/// A prompt that couldn't be shown, as there was no display, like from
+/// cron. The next run that can show a prompt shows it straight away
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingPrompt {
+    /// Seconds since the unix epoch
+    pub queued_at: u64,
+
+    /// The updates found, in the same form as the snooze's `versions`
+    pub versions: String,
+}
+
+impl PendingPrompt {
+    // File is made of `key=value` lines
+    fn parse(content: &str) -> Option<PendingPrompt> {
+        let mut queued_at = None;
+        let mut versions = String::new();
+
+        for (key, value) in state_file::key_values(content) {
+            match key {
+                "queued_at" => queued_at = value.trim().parse().ok(),
+                "versions" => versions = value.trim().to_string(),
+                _ => {}
+            }
+        }
+
+        return Some(PendingPrompt {
+            queued_at: queued_at?,
+            versions,
+        });
+    }
+
+    fn serialize(&self) -> String {
+        return format!("queued_at={}\nversions={}\n", self.queued_at, self.versions);
+    }
+}
+
+/// Reads the pending prompt at `path`, None if there isn't one
+///
+/// A malformed file is treated as a prompt queued at the start of the
+/// epoch, so it's still shown
+pub fn read_pending(path: &path::Path) -> io::Result<Option<PendingPrompt>> {
+    match fs::read_to_string(path) {
+        io::Result::Ok(content) => {
+            return io::Result::Ok(Some(PendingPrompt::parse(&content).unwrap_or(
+                PendingPrompt {
+                    queued_at: 0,
+                    versions: String::new(),
+                },
+            )))
+        }
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(None)
+        }
+        io::Result::Err(error) => return io::Result::Err(error),
+    }
+}
+
+/// Queues `pending` at `path`, replacing any prompt already queued
+pub fn write_pending(path: &path::Path, pending: &PendingPrompt) -> io::Result<()> {
+    return state_file::write_atomic(path, &pending.serialize());
+}
+
+/// Takes the pending prompt at `path` out of the queue, returning it. None
+/// if there wasn't one
+pub fn take_pending(path: &path::Path) -> io::Result<Option<PendingPrompt>> {
+    let pending = read_pending(path)?;
+    if pending.is_some() {
+        fs::remove_file(path)?;
+    }
+
+    return io::Result::Ok(pending);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn queue_and_take() {
+        let path = temp_dir("pending_queue_and_take").join("pending_prompt");
+
+        assert_eq!(take_pending(&path).unwrap(), None);
+
+        let pending = PendingPrompt {
+            queued_at: 1000,
+            versions: "stable=1.80.1".to_string(),
+        };
+        write_pending(&path, &pending).unwrap();
+        assert_eq!(read_pending(&path).unwrap(), Some(pending.clone()));
+
+        assert_eq!(take_pending(&path).unwrap(), Some(pending));
+        assert_eq!(take_pending(&path).unwrap(), None);
+
+        fs::write(&path, "bogus").unwrap();
+        assert_eq!(take_pending(&path).unwrap().unwrap().queued_at, 0);
+    }
+}
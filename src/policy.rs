@@ -0,0 +1,330 @@
+use crate::{
+    parse::{Channel, ParsedUpdate},
+    settings,
+    version::Version,
+};
+
+/// Newest release of rustup that the output of `rustup check` was tested
+/// with. Patch releases of it are expected to print the same
+pub const NEWEST_TESTED_RUSTUP: Version = Version {
+    major: 1,
+    minor: 28,
+    patch: 0,
+};
+
+/// Version of the installed rustup, if it's a newer release than
+/// `newest_tested`, so might print `rustup check` differently
+///
+/// Only the major and minor versions are compared
+pub fn untested_rustup(updates: &[ParsedUpdate], newest_tested: Version) -> Option<Version> {
+    let current = updates
+        .iter()
+        .find(|x| x.channel_kind() == Channel::Rustup)?
+        .current?;
+
+    if (current.major, current.minor) > (newest_tested.major, newest_tested.minor) {
+        return Some(current);
+    }
+
+    return None;
+}
+
+/// How big a move from one version to another is, by semver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Jump {
+    /// The same version, or an older one
+    None,
+    Patch,
+    Minor,
+    Major,
+}
+
+/// How big the move from `current` to `new` is
+pub fn version_jump(current: Version, new: Version) -> Jump {
+    if new <= current {
+        return Jump::None;
+    }
+    if new.major != current.major {
+        return Jump::Major;
+    }
+    if new.minor != current.minor {
+        return Jump::Minor;
+    }
+
+    return Jump::Patch;
+}
+
+/// The stable toolchain update that moves to a new minor or major release,
+/// if there is one, which can break more than a patch release
+///
+/// Other channels move to new releases all the time, so aren't included
+pub fn new_stable_release(updates: &[ParsedUpdate]) -> Option<&ParsedUpdate> {
+    return updates
+        .iter()
+        .filter(|x| x.channel_kind() == Channel::Stable)
+        .find(|x| {
+            return match (x.current, x.new) {
+                (Some(current), Some(new)) => version_jump(current, new) >= Jump::Minor,
+                _ => false,
+            };
+        });
+}
+
+/// True if `update` is the nightly channel moving to a newer nightly of the
+/// same version, like `1.83.0-nightly (90ab8eaed 2024-09-06)` to
+/// `1.83.0-nightly (a9cb2b5a5 2024-09-07)`, which happens every day
+///
+/// Without both versions it can't tell, so it's false
+pub fn date_only_nightly(update: &ParsedUpdate) -> bool {
+    if update.channel_kind() != (Channel::Nightly { date: None }) {
+        return false;
+    }
+
+    match (update.current, update.new) {
+        (Some(current), Some(new)) => return version_jump(current, new) == Jump::None,
+        _ => return false,
+    }
+}
+
+/// The default toolchain, if it's older than `minimum`. When the default
+/// isn't known, or isn't in `updates`, the stable toolchains are checked
+///
+/// Being below the minimum is separate from having an update available. A
+/// toolchain can be up to date with its channel but still too old
+pub fn below_minimum_version<'a>(
+    updates: &'a [ParsedUpdate],
+    minimum: Version,
+    default_toolchain: Option<&str>,
+) -> Option<&'a ParsedUpdate> {
+    let toolchains: Vec<&ParsedUpdate> = updates
+        .iter()
+        .filter(|x| x.channel_kind() != Channel::Rustup)
+        .collect();
+    let default: Vec<&ParsedUpdate> = toolchains
+        .iter()
+        .copied()
+        .filter(|x| default_toolchain.is_some_and(|d| settings::is_default_toolchain(&x.name, d)))
+        .collect();
+    let checked = match default.is_empty() {
+        true => toolchains
+            .into_iter()
+            .filter(|x| x.channel_kind() == Channel::Stable)
+            .collect(),
+        false => default,
+    };
+
+    return checked
+        .into_iter()
+        .find(|x| x.current.is_some_and(|current| current < minimum));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stable(current: Version) -> Vec<ParsedUpdate> {
+        return vec![
+            ParsedUpdate {
+                name: "stable-x86_64-unknown-linux-gnu".to_string(),
+                current: Some(current),
+                new: None,
+                current_build: None,
+                new_build: None,
+            },
+            ParsedUpdate {
+                name: "rustup".to_string(),
+                current: Some(Version::new(1, 27, 1)),
+                new: None,
+                current_build: None,
+                new_build: None,
+            },
+        ];
+    }
+
+    #[test]
+    fn below_minimum() {
+        let updates = stable(Version::new(1, 79, 0));
+        assert_eq!(
+            below_minimum_version(&updates, Version::new(1, 80, 0), None),
+            Some(&updates[0])
+        );
+        assert_eq!(
+            below_minimum_version(
+                &updates,
+                Version::new(1, 80, 0),
+                Some("stable-x86_64-unknown-linux-gnu")
+            ),
+            Some(&updates[0])
+        );
+    }
+
+    #[test]
+    fn at_minimum() {
+        assert_eq!(
+            below_minimum_version(
+                &stable(Version::new(1, 80, 0)),
+                Version::new(1, 80, 0),
+                None
+            ),
+            None
+        );
+        assert_eq!(
+            below_minimum_version(
+                &stable(Version::new(1, 81, 0)),
+                Version::new(1, 80, 0),
+                None
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn minimum_of_default_toolchain() {
+        let mut updates = stable(Version::new(1, 81, 0));
+        updates.push(ParsedUpdate {
+            name: "1.75-x86_64-unknown-linux-gnu".to_string(),
+            current: Some(Version::new(1, 75, 0)),
+            new: None,
+            current_build: None,
+            new_build: None,
+        });
+
+        // A pinned toolchain that isn't the default doesn't count
+        assert_eq!(
+            below_minimum_version(
+                &updates,
+                Version::new(1, 80, 0),
+                Some("stable-x86_64-unknown-linux-gnu")
+            ),
+            None
+        );
+        assert_eq!(
+            below_minimum_version(
+                &updates,
+                Version::new(1, 80, 0),
+                Some("1.75-x86_64-unknown-linux-gnu")
+            ),
+            Some(&updates[2])
+        );
+
+        // A default that isn't installed falls back to stable
+        assert_eq!(
+            below_minimum_version(
+                &updates,
+                Version::new(1, 82, 0),
+                Some("nightly-x86_64-unknown-linux-gnu")
+            ),
+            Some(&updates[0])
+        );
+    }
+
+    #[test]
+    fn version_jumps() {
+        let jump = |new: Version| version_jump(Version::new(1, 80, 0), new);
+
+        assert_eq!(jump(Version::new(1, 80, 0)), Jump::None);
+        assert_eq!(jump(Version::new(1, 79, 9)), Jump::None);
+        assert_eq!(jump(Version::new(1, 80, 1)), Jump::Patch);
+        assert_eq!(jump(Version::new(1, 81, 0)), Jump::Minor);
+        assert_eq!(jump(Version::new(2, 0, 0)), Jump::Major);
+    }
+
+    #[test]
+    fn new_stable_releases() {
+        let update = |name: &str, new: Version| {
+            return vec![ParsedUpdate {
+                name: name.to_string(),
+                current: Some(Version::new(1, 80, 0)),
+                new: Some(new),
+                current_build: None,
+                new_build: None,
+            }];
+        };
+        let name = "stable-x86_64-unknown-linux-gnu";
+
+        assert!(new_stable_release(&update(name, Version::new(1, 80, 1))).is_none());
+        assert!(new_stable_release(&update(name, Version::new(1, 81, 0))).is_some());
+        assert!(new_stable_release(&update(name, Version::new(2, 0, 0))).is_some());
+        assert!(new_stable_release(&update("beta", Version::new(1, 81, 0))).is_none());
+        assert!(new_stable_release(&stable(Version::new(1, 80, 0))).is_none());
+    }
+
+    #[test]
+    fn date_only_nightlies() {
+        let nightly = |line: &str| {
+            return crate::parse::parse_rustup_check_line(&format!(
+                "nightly-x86_64-unknown-linux-gnu - Update available : {line}"
+            ))
+            .unwrap();
+        };
+
+        assert!(date_only_nightly(&nightly(
+            "1.83.0-nightly (90ab8eaed 2024-09-06) -> 1.83.0-nightly (a9cb2b5a5 2024-09-07)"
+        )));
+        assert!(!date_only_nightly(&nightly(
+            "1.83.0-nightly (90ab8eaed 2024-09-06) -> 1.84.0-nightly (a9cb2b5a5 2024-10-18)"
+        )));
+        assert!(!date_only_nightly(&nightly(
+            "1.84.0-nightly (a9cb2b5a5 2024-10-18)"
+        )));
+
+        // Only nightly changes daily
+        let beta = crate::parse::parse_rustup_check_line(
+            "beta-x86_64-unknown-linux-gnu - Update available : 1.82.0-beta.3 -> 1.82.0-beta.4",
+        )
+        .unwrap();
+        assert!(!date_only_nightly(&beta));
+    }
+
+    #[test]
+    fn untested_rustup_versions() {
+        let rustup = |current: Version| {
+            let mut updates = stable(Version::new(1, 80, 0));
+            updates[1].current = Some(current);
+
+            return updates;
+        };
+        let tested = Version::new(1, 28, 0);
+
+        assert_eq!(
+            untested_rustup(&rustup(Version::new(1, 27, 1)), tested),
+            None
+        );
+        assert_eq!(
+            untested_rustup(&rustup(Version::new(1, 28, 2)), tested),
+            None
+        );
+        assert_eq!(
+            untested_rustup(&rustup(Version::new(1, 29, 0)), tested),
+            Some(Version::new(1, 29, 0))
+        );
+        assert_eq!(
+            untested_rustup(&rustup(Version::new(2, 0, 0)), tested),
+            Some(Version::new(2, 0, 0))
+        );
+
+        // Nothing to go on without a rustup line
+        assert_eq!(
+            untested_rustup(&stable(Version::new(1, 80, 0))[..1], tested),
+            None
+        );
+    }
+
+    #[test]
+    fn rustup_version_ignored() {
+        assert_eq!(
+            below_minimum_version(
+                &stable(Version::new(1, 80, 0)),
+                Version::new(1, 28, 0),
+                None
+            ),
+            None
+        );
+
+        let only_rustup = &stable(Version::new(1, 80, 0))[1..];
+        assert_eq!(
+            below_minimum_version(only_rustup, Version::new(2, 0, 0), Some("rustup")),
+            None
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::{fs, io, path};
+
+// Where the kernel lists the power supplies of the machine
+pub const POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+
+// Reads a single sysfs attribute of a power supply, trimming the new line
+fn read_attribute(supply: &path::Path, attribute: &str) -> Option<String> {
+    return fs::read_to_string(supply.join(attribute))
+        .ok()
+        .map(|x| x.trim().to_string());
+}
+
+/// Returns true if the machine is running from its battery
+///
+/// Looks at every power supply in `power_supply_path` (normally
+/// `/sys/class/power_supply`). If any adapter reports being online, the
+/// machine is on AC power. If the machine exposes no adapters, the battery
+/// status is used instead
+///
+/// Machines without a battery, or without the power supply directory, are
+/// never on battery
+pub fn on_battery(power_supply_path: &path::Path) -> io::Result<bool> {
+    let entries = match fs::read_dir(power_supply_path) {
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(false)
+        }
+        result => result?,
+    };
+
+    let mut has_battery = false;
+    let mut discharging = false;
+    let mut adapter_seen = false;
+    let mut adapter_online = false;
+
+    for entry in entries {
+        let supply = entry?.path();
+
+        match read_attribute(&supply, "type").as_deref() {
+            Some("Battery") => {
+                has_battery = true;
+                if read_attribute(&supply, "status").as_deref() == Some("Discharging") {
+                    discharging = true;
+                }
+            }
+            Some("Mains") | Some("USB") | Some("USB_C") => {
+                adapter_seen = true;
+                if read_attribute(&supply, "online").as_deref() == Some("1") {
+                    adapter_online = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if adapter_online {
+        return io::Result::Ok(false);
+    }
+
+    if adapter_seen {
+        return io::Result::Ok(has_battery);
+    }
+
+    return io::Result::Ok(discharging);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    // Makes a fake power supply directory. Each supply is a name and a list
+    // of attribute files with their content
+    fn fixture(name: &str, supplies: &[(&str, &[(&str, &str)])]) -> path::PathBuf {
+        let dir = temp_dir(name);
+
+        for (supply, attributes) in supplies {
+            let supply_dir = dir.join(supply);
+            fs::create_dir_all(&supply_dir).unwrap();
+
+            for (attribute, value) in attributes.iter() {
+                fs::write(supply_dir.join(attribute), format!("{value}\n")).unwrap();
+            }
+        }
+
+        return dir;
+    }
+
+    #[test]
+    fn plugged_in() {
+        let dir = fixture(
+            "power_plugged_in",
+            &[
+                ("AC", &[("type", "Mains"), ("online", "1")]),
+                ("BAT0", &[("type", "Battery"), ("status", "Charging")]),
+            ],
+        );
+
+        assert_eq!(on_battery(&dir).unwrap(), false);
+    }
+
+    #[test]
+    fn unplugged() {
+        let dir = fixture(
+            "power_unplugged",
+            &[
+                ("AC", &[("type", "Mains"), ("online", "0")]),
+                ("BAT0", &[("type", "Battery"), ("status", "Discharging")]),
+            ],
+        );
+
+        assert_eq!(on_battery(&dir).unwrap(), true);
+    }
+
+    #[test]
+    fn battery_only_uses_status() {
+        let discharging = fixture(
+            "power_battery_discharging",
+            &[("BAT0", &[("type", "Battery"), ("status", "Discharging")])],
+        );
+        let full = fixture(
+            "power_battery_full",
+            &[("BAT0", &[("type", "Battery"), ("status", "Full")])],
+        );
+
+        assert_eq!(on_battery(&discharging).unwrap(), true);
+        assert_eq!(on_battery(&full).unwrap(), false);
+    }
+
+    #[test]
+    fn desktop() {
+        let no_battery = fixture(
+            "power_desktop",
+            &[("AC", &[("type", "Mains"), ("online", "0")])],
+        );
+
+        assert_eq!(on_battery(&no_battery).unwrap(), false);
+        assert_eq!(
+            on_battery(&no_battery.join("does_not_exist")).unwrap(),
+            false
+        );
+    }
+}
@@ -0,0 +1,1056 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    env, fs,
+    io::{self, BufRead, IsTerminal, Write},
+    os::unix::{fs::FileTypeExt, net::UnixStream},
+    path, process, str,
+    sync::{mpsc, Mutex, MutexGuard, OnceLock},
+    thread, time,
+};
+
+use crate::{diagnose, json, runner::describe_status, Config, Error};
+
+/// What the user answered to the update prompt
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdatePromptAnswer {
+    NoUpdateFound,
+    Update,
+    DoNotUpdate,
+    Timeout,
+
+    /// The window was closed without clicking a button
+    Closed,
+}
+
+/// What to do when the prompt times out without an answer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeoutAction {
+    /// Nothing, so the next run prompts again
+    AskNextRun,
+
+    /// Snooze for an hour
+    SnoozeShort,
+
+    /// The same as answering "Not today"
+    Decline,
+}
+
+impl TimeoutAction {
+    /// Its name, as read by `from_str`
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimeoutAction::AskNextRun => return "ask-next-run",
+            TimeoutAction::SnoozeShort => return "snooze-short",
+            TimeoutAction::Decline => return "decline",
+        }
+    }
+}
+
+impl str::FromStr for TimeoutAction {
+    type Err = String;
+
+    /// Reads `ask-next-run`, `snooze-short` or `decline`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "ask-next-run" => return Ok(TimeoutAction::AskNextRun),
+            "snooze-short" => return Ok(TimeoutAction::SnoozeShort),
+            "decline" => return Ok(TimeoutAction::Decline),
+            _ => {
+                return Err(format!(
+                "Unknown timeout action '{text}'. Expected ask-next-run, snooze-short or decline"
+            ))
+            }
+        }
+    }
+}
+
+/// Seconds the prompt waits for an answer, unless set otherwise
+pub const DEFAULT_PROMPT_TIMEOUT: u32 = 10;
+
+/// What to do when there's no way to prompt, like from cron
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoPromptAction {
+    /// Queue the prompt for the next run that can show it
+    Defer,
+
+    /// Update without asking
+    Update,
+}
+
+impl NoPromptAction {
+    /// Its name, as read by `from_str`
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoPromptAction::Defer => return "defer",
+            NoPromptAction::Update => return "update",
+        }
+    }
+}
+
+impl str::FromStr for NoPromptAction {
+    type Err = String;
+
+    /// Reads `defer` or `update`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "defer" => return Ok(NoPromptAction::Defer),
+            "update" => return Ok(NoPromptAction::Update),
+            _ => return Err(format!("Unknown action '{text}'. Expected defer or update")),
+        }
+    }
+}
+
+/// How the prompt is shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PromptKind {
+    /// The first of a zenity dialog, a notification, and the terminal that
+    /// can be shown. Each is tried in turn if the one before fails
+    Auto,
+
+    /// A zenity dialog, which needs a display
+    Zenity,
+
+    /// A notification with buttons, which needs a display and notify-send
+    Notify,
+
+    /// A question in the terminal the updater was run from
+    Tty,
+
+    /// A request sent over the Unix socket `Config::prompt_socket`, for a
+    /// program of its own to ask
+    Socket,
+}
+
+impl PromptKind {
+    /// Its name, as read by `from_str`
+    pub fn name(&self) -> &'static str {
+        match self {
+            PromptKind::Auto => return "auto",
+            PromptKind::Zenity => return "zenity",
+            PromptKind::Notify => return "notify",
+            PromptKind::Tty => return "tty",
+            PromptKind::Socket => return "socket",
+        }
+    }
+}
+
+impl str::FromStr for PromptKind {
+    type Err = String;
+
+    /// Reads `auto`, `zenity`, `notify`, `tty` or `socket`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "auto" => return Ok(PromptKind::Auto),
+            "zenity" => return Ok(PromptKind::Zenity),
+            "notify" => return Ok(PromptKind::Notify),
+            "tty" => return Ok(PromptKind::Tty),
+            "socket" => return Ok(PromptKind::Socket),
+            _ => {
+                return Err(format!(
+                    "Unknown backend '{text}'. Expected auto, zenity, notify, tty or socket"
+                ))
+            }
+        }
+    }
+}
+
+/// The backend that shows prompts for `config.backend`
+///
+/// This is the only place backends are chosen
+pub fn backend_for(config: &Config) -> Box<dyn PromptBackend> {
+    match config.backend {
+        PromptKind::Auto => {
+            return Box::new(FallbackBackend::new(vec![
+                Box::new(ZenityBackend),
+                Box::new(NotifyBackend),
+                Box::new(TtyBackend),
+            ]))
+        }
+        PromptKind::Zenity => return Box::new(ZenityBackend),
+        PromptKind::Notify => return Box::new(NotifyBackend),
+        PromptKind::Tty => return Box::new(TtyBackend),
+        // The config can't have a socket backend without the socket. If
+        // it's built by hand, there's nothing to connect to
+        PromptKind::Socket => {
+            return Box::new(SocketBackend::new(
+                config.prompt_socket.clone().unwrap_or_default(),
+            ))
+        }
+    }
+}
+
+/// A question asking the user whether to update
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt {
+    pub title: String,
+    pub text: String,
+
+    /// The toolchains with updates, and rustup, by name, with the version
+    /// each updates to. Also in the text
+    pub updates: BTreeMap<String, String>,
+
+    /// Seconds until the prompt gives up. None to wait forever
+    pub timeout: Option<u32>,
+
+    pub ok_label: String,
+    pub cancel_label: String,
+
+    /// Shown as simply as possible, for screen readers
+    pub accessible: bool,
+
+    /// Tell closing the window apart from the cancel button, where the
+    /// backend can. Otherwise closing it is the same as cancelling
+    pub detect_close: bool,
+
+    /// A word to type, instead of `y`, to update in the terminal, like the
+    /// version of a major release. `yes` is also accepted
+    pub confirm_word: Option<String>,
+
+    /// Icon of the window, where the backend has one
+    pub icon: Option<path::PathBuf>,
+
+    /// Ask for the window to be modal, where the backend can
+    pub modal: bool,
+}
+
+/// True if there's a display to show windows on, from `DISPLAY` or
+/// `WAYLAND_DISPLAY`
+pub fn display_available() -> bool {
+    return ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|x| env::var_os(x).is_some_and(|x| !x.is_empty()));
+}
+
+/// Shows update prompts to the user
+pub trait PromptBackend {
+    /// Asks the user the prompt, waiting for their answer
+    ///
+    /// Errors with `Error::Prompt` if it couldn't be shown
+    fn ask(&self, prompt: &Prompt) -> Result<UpdatePromptAnswer, Error>;
+
+    /// False if the prompt can't be shown now, like a dialog without a
+    /// display. The prompt is then queued for a later run
+    fn can_show(&self) -> bool {
+        return true;
+    }
+}
+
+/// Asks using a zenity dialog
+pub struct ZenityBackend;
+
+// Arguments for zenity to ask `prompt`, like
+//
+// zenity --question --title="Rust Update" --no-wrap
+// --text="Rust 1.80.1\nRustup 1.6.0\nUpdate?" --timeout=10 --ok-label="Update"
+// --cancel-label="Not today"
+fn zenity_args(prompt: &Prompt) -> Vec<String> {
+    let mut args = vec![
+        "--question".to_string(),
+        format!("--title={}", prompt.title),
+    ];
+    if let Some(icon) = &prompt.icon {
+        args.push(format!("--window-icon={}", icon.display()));
+    }
+    if prompt.modal {
+        args.push("--modal".to_string());
+    }
+
+    // Long unwrapped lines read badly with screen readers and with large
+    // text
+    if !prompt.accessible {
+        args.push("--no-wrap".to_string());
+    }
+    if let Some(timeout) = prompt.timeout {
+        args.push(format!("--timeout={timeout}"));
+    }
+    if prompt.detect_close {
+        // Closing the window and cancelling both exit with 1. With only
+        // extra buttons, the button clicked is printed, and closing prints
+        // nothing
+        args.push("--switch".to_string());
+        args.push(format!("--extra-button={}", prompt.cancel_label));
+        args.push(format!("--extra-button={}", prompt.ok_label));
+    } else {
+        args.push(format!("--ok-label={}", prompt.ok_label));
+        args.push(format!("--cancel-label={}", prompt.cancel_label));
+    }
+    args.push(format!("--text={}", prompt.text));
+
+    return args;
+}
+
+impl PromptBackend for ZenityBackend {
+    fn ask(&self, prompt: &Prompt) -> Result<UpdatePromptAnswer, Error> {
+        let prompt_response = process::Command::new("zenity")
+            .args(zenity_args(prompt))
+            .stderr(process::Stdio::inherit())
+            .output()
+            .map_err(|error| {
+                if error.kind() == io::ErrorKind::NotFound {
+                    return Error::Prompt("Can't run zenity. Is zenity installed?".to_string());
+                }
+
+                return Error::Prompt(format!("Failed to run zenity: {error}"));
+            })?;
+
+        return zenity_answer(
+            prompt,
+            prompt_response.status.code(),
+            &String::from_utf8_lossy(&prompt_response.stdout),
+        );
+    }
+
+    fn can_show(&self) -> bool {
+        return display_available();
+    }
+}
+
+// Works out the answer from how zenity exited, and what it printed
+fn zenity_answer(
+    prompt: &Prompt,
+    code: Option<i32>,
+    stdout: &str,
+) -> Result<UpdatePromptAnswer, Error> {
+    match code {
+        Some(0) => return Ok(UpdatePromptAnswer::Update),
+        Some(1) if prompt.detect_close => match stdout.trim() {
+            "" => return Ok(UpdatePromptAnswer::Closed),
+            x if x == prompt.ok_label => return Ok(UpdatePromptAnswer::Update),
+            _ => return Ok(UpdatePromptAnswer::DoNotUpdate),
+        },
+        Some(1) => return Ok(UpdatePromptAnswer::DoNotUpdate),
+        Some(5) => return Ok(UpdatePromptAnswer::Timeout),
+        x => {
+            return Err(Error::Prompt(format!(
+                "zenity ended with {}",
+                describe_status(x)
+            )))
+        }
+    }
+}
+
+/// Asks with a notification that has a button for each answer
+///
+/// Dismissing it, or it expiring, is a timeout
+pub struct NotifyBackend;
+
+// Arguments for notify-send to ask `prompt`. A notification can't be modal,
+// so only the icon is used
+fn notify_prompt_args(prompt: &Prompt) -> Vec<String> {
+    let mut args = vec![
+        "--wait".to_string(),
+        "--app-name=auto_rustup_update".to_string(),
+        format!("--action=update={}", prompt.ok_label),
+        format!("--action=skip={}", prompt.cancel_label),
+    ];
+    if let Some(icon) = &prompt.icon {
+        args.push(format!("--icon={}", icon.display()));
+    }
+    // Without a timeout, it stays until it's answered
+    let milliseconds = prompt.timeout.map(|x| x.saturating_mul(1000)).unwrap_or(0);
+    args.push(format!("--expire-time={milliseconds}"));
+    args.push(prompt.title.clone());
+    args.push(prompt.text.clone());
+
+    return args;
+}
+
+impl PromptBackend for NotifyBackend {
+    fn ask(&self, prompt: &Prompt) -> Result<UpdatePromptAnswer, Error> {
+        let output = process::Command::new("notify-send")
+            .args(notify_prompt_args(prompt))
+            .stderr(process::Stdio::inherit())
+            .output()
+            .map_err(|error| Error::Prompt(format!("Failed to run notify-send: {error}")))?;
+
+        return notify_answer(
+            output.status.code(),
+            &String::from_utf8_lossy(&output.stdout),
+        );
+    }
+
+    fn can_show(&self) -> bool {
+        return display_available()
+            && diagnose::first_on_path(&env::var_os("PATH").unwrap_or_default(), "notify-send")
+                .is_some();
+    }
+}
+
+// Works out the answer from how notify-send exited, and the action it
+// printed. Older versions without `--action` fail, so the next backend is
+// tried
+fn notify_answer(code: Option<i32>, stdout: &str) -> Result<UpdatePromptAnswer, Error> {
+    match (code, stdout.trim()) {
+        (Some(0), "update") => return Ok(UpdatePromptAnswer::Update),
+        (Some(0), "skip") => return Ok(UpdatePromptAnswer::DoNotUpdate),
+        (Some(0), _) => return Ok(UpdatePromptAnswer::Timeout),
+        (x, _) => {
+            return Err(Error::Prompt(format!(
+                "notify-send ended with {}",
+                describe_status(x)
+            )))
+        }
+    }
+}
+
+// Seconds a socket backend's prompt is given on top of its timeout, to
+// answer that it timed out
+const SOCKET_GRACE: u64 = 5;
+
+/// Asks a program listening on a Unix socket, so one long-running UI can
+/// answer the prompts of every run
+///
+/// The prompt is sent as a line of JSON, like
+///
+/// ```text
+/// {"cancel_label":"Not today","ok_label":"Update","text":"stable: 1.80.1\nUpdate?",
+///  "timeout":10,"title":"Rust Update","updates":{"stable-x86_64-unknown-linux-gnu":"1.80.1"}}
+/// ```
+///
+/// and answered with a line like `{"answer":"update"}`. The answer is
+/// `update`, `not_today`, `timeout` or `closed`. `timeout` is null without
+/// a timeout. Not answering within a few seconds of it is a timeout
+pub struct SocketBackend {
+    path: path::PathBuf,
+}
+
+impl SocketBackend {
+    pub fn new(path: impl Into<path::PathBuf>) -> SocketBackend {
+        return SocketBackend { path: path.into() };
+    }
+}
+
+// The line of JSON sent for `prompt`
+fn socket_request(prompt: &Prompt) -> String {
+    let string = |x: &str| json::Value::String(x.to_string());
+    let updates = prompt
+        .updates
+        .iter()
+        .map(|(name, version)| (name.clone(), string(version)))
+        .collect();
+    let timeout = match prompt.timeout {
+        Some(x) => json::Value::Number(f64::from(x)),
+        None => json::Value::Null,
+    };
+
+    let request = json::Value::Object(BTreeMap::from([
+        ("title".to_string(), string(&prompt.title)),
+        ("text".to_string(), string(&prompt.text)),
+        ("updates".to_string(), json::Value::Object(updates)),
+        ("timeout".to_string(), timeout),
+        ("ok_label".to_string(), string(&prompt.ok_label)),
+        ("cancel_label".to_string(), string(&prompt.cancel_label)),
+    ]));
+
+    return format!("{request}\n");
+}
+
+// Reads the line the socket answered with
+fn socket_answer(line: &str) -> Result<UpdatePromptAnswer, Error> {
+    let answer = json::parse(line.trim())
+        .map_err(|error| Error::Prompt(format!("The socket's answer isn't JSON: {error}")))?;
+
+    match answer.get("answer").and_then(|x| x.as_str()) {
+        Some("update") => return Ok(UpdatePromptAnswer::Update),
+        Some("not_today") => return Ok(UpdatePromptAnswer::DoNotUpdate),
+        Some("timeout") => return Ok(UpdatePromptAnswer::Timeout),
+        Some("closed") => return Ok(UpdatePromptAnswer::Closed),
+        _ => {
+            return Err(Error::Prompt(format!(
+                "Unknown answer from the socket: {}",
+                line.trim()
+            )))
+        }
+    }
+}
+
+impl PromptBackend for SocketBackend {
+    fn ask(&self, prompt: &Prompt) -> Result<UpdatePromptAnswer, Error> {
+        let failed = |error: io::Error| {
+            return Error::Prompt(format!("Socket {}: {error}", self.path.display()));
+        };
+
+        let mut stream = UnixStream::connect(&self.path).map_err(failed)?;
+        let timeout = prompt
+            .timeout
+            .map(|x| time::Duration::from_secs(u64::from(x) + SOCKET_GRACE));
+        stream.set_read_timeout(timeout).map_err(failed)?;
+        stream
+            .write_all(socket_request(prompt).as_bytes())
+            .map_err(failed)?;
+
+        let mut line = String::new();
+        match io::BufReader::new(&stream).read_line(&mut line) {
+            io::Result::Ok(0) => {
+                return Err(Error::Prompt(format!(
+                    "Socket {} closed without answering",
+                    self.path.display()
+                )))
+            }
+            io::Result::Ok(_) => return socket_answer(&line),
+            io::Result::Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(UpdatePromptAnswer::Timeout)
+            }
+            io::Result::Err(error) => return Err(failed(error)),
+        }
+    }
+
+    /// Only when something has made the socket, so it's listening
+    fn can_show(&self) -> bool {
+        return fs::metadata(&self.path).is_ok_and(|x| x.file_type().is_socket());
+    }
+}
+
+// Writes `text` to the terminal, without a newline
+fn ask_in_terminal(text: &str) -> Result<(), Error> {
+    let mut stdout = io::stdout();
+
+    return write!(stdout, "{text}")
+        .and_then(|_| stdout.flush())
+        .map_err(|error| Error::Prompt(format!("Couldn't ask in the terminal: {error}")));
+}
+
+// Lines typed into stdin, read on another thread so waiting for one can
+// time out. There's one reader for the whole process, as one left waiting
+// after a timeout would take what's typed at the next prompt, like with
+// `--watch`. A read error ends them, like stdin closing
+static STDIN_LINES: OnceLock<Mutex<mpsc::Receiver<String>>> = OnceLock::new();
+
+// The lines of stdin, with any typed before now, when nothing was asked,
+// thrown away
+fn stdin_lines() -> MutexGuard<'static, mpsc::Receiver<String>> {
+    let lines = STDIN_LINES.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+
+        return Mutex::new(receiver);
+    });
+
+    let lines = lines.lock().unwrap_or_else(|x| x.into_inner());
+    while lines.try_recv().is_ok() {}
+
+    return lines;
+}
+
+// What a line typed to confirm with a word means
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Typed {
+    Confirmed,
+    Declined,
+
+    // Neither, like a typo of the word, so it's asked again
+    Mismatch,
+}
+
+// Reads `answer`, typed to confirm with `word`. Only the word or `yes`,
+// exactly, confirms. Nothing, `n` or `no` declines
+fn typed_answer(answer: &str, word: &str) -> Typed {
+    match answer.trim() {
+        x if x == word || x == "yes" => return Typed::Confirmed,
+        "" | "n" | "N" | "no" => return Typed::Declined,
+        _ => return Typed::Mismatch,
+    }
+}
+
+// Waits for `word` to be typed as one of `lines`, asking again after each
+// mismatch with `reprompt`. Gives up after the prompt's timeout, counted
+// from the start rather than from each mismatch
+fn read_typed_confirmation(
+    prompt: &Prompt,
+    word: &str,
+    lines: &mpsc::Receiver<String>,
+    reprompt: &mut dyn FnMut() -> Result<(), Error>,
+) -> Result<UpdatePromptAnswer, Error> {
+    let deadline = prompt
+        .timeout
+        .map(|x| time::Instant::now() + time::Duration::from_secs(u64::from(x)));
+
+    loop {
+        let line = match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(time::Instant::now());
+                match lines.recv_timeout(left) {
+                    Ok(line) => Some(line),
+                    Err(mpsc::RecvTimeoutError::Timeout) => return Ok(UpdatePromptAnswer::Timeout),
+                    Err(mpsc::RecvTimeoutError::Disconnected) => None,
+                }
+            }
+            None => lines.recv().ok(),
+        };
+
+        let Some(line) = line else {
+            // Stdin closed, like from Ctrl+D
+            if prompt.detect_close {
+                return Ok(UpdatePromptAnswer::Closed);
+            }
+            return Ok(UpdatePromptAnswer::DoNotUpdate);
+        };
+
+        match typed_answer(&line, word) {
+            Typed::Confirmed => return Ok(UpdatePromptAnswer::Update),
+            Typed::Declined => return Ok(UpdatePromptAnswer::DoNotUpdate),
+            Typed::Mismatch => reprompt()?,
+        }
+    }
+}
+
+/// Asks in the terminal, reading the answer from stdin
+///
+/// It waits for an answer, whatever the prompt's timeout, unless the
+/// prompt has a word to confirm with. Then `y` isn't enough, the word or
+/// `yes` has to be typed, and it's asked again until it is or the prompt
+/// times out. It can only be shown when stdin is a terminal
+pub struct TtyBackend;
+
+impl PromptBackend for TtyBackend {
+    fn ask(&self, prompt: &Prompt) -> Result<UpdatePromptAnswer, Error> {
+        // Taken before asking, so nothing typed after is thrown away
+        let lines = stdin_lines();
+        if let Some(word) = &prompt.confirm_word {
+            ask_in_terminal(&format!(
+                "{}\n\n{}\n\n{}? Type {word} or yes to confirm ({} if not) ",
+                prompt.title, prompt.text, prompt.ok_label, prompt.cancel_label
+            ))?;
+
+            return read_typed_confirmation(prompt, word, &lines, &mut || {
+                return ask_in_terminal(&format!(
+                    "That isn't {word} or yes. Type it exactly to confirm, or nothing if not: "
+                ));
+            });
+        }
+
+        ask_in_terminal(&format!(
+            "{}\n\n{}\n\n{} [y/N]? ({} if not) ",
+            prompt.title, prompt.text, prompt.ok_label, prompt.cancel_label
+        ))?;
+
+        // Stdin closed, like from Ctrl+D
+        let Ok(answer) = lines.recv() else {
+            if prompt.detect_close {
+                return Ok(UpdatePromptAnswer::Closed);
+            }
+            return Ok(UpdatePromptAnswer::DoNotUpdate);
+        };
+
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(UpdatePromptAnswer::Update),
+            _ => return Ok(UpdatePromptAnswer::DoNotUpdate),
+        }
+    }
+
+    fn can_show(&self) -> bool {
+        return io::stdin().is_terminal();
+    }
+}
+
+/// Tries each backend in order, skipping those that can't be shown, until
+/// one asks successfully
+pub struct FallbackBackend {
+    backends: Vec<Box<dyn PromptBackend>>,
+}
+
+impl FallbackBackend {
+    pub fn new(backends: Vec<Box<dyn PromptBackend>>) -> FallbackBackend {
+        return FallbackBackend { backends };
+    }
+}
+
+impl PromptBackend for FallbackBackend {
+    /// Errors with the last backend's error if none of them could ask
+    fn ask(&self, prompt: &Prompt) -> Result<UpdatePromptAnswer, Error> {
+        let mut last_error = None;
+
+        for backend in self.backends.iter().filter(|x| x.can_show()) {
+            match backend.ask(prompt) {
+                Ok(answer) => return Ok(answer),
+                Err(error) => {
+                    eprintln!("{error}. Trying the next way to prompt");
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        return Err(last_error.unwrap_or(Error::Prompt("Nothing can show the prompt".to_string())));
+    }
+
+    fn can_show(&self) -> bool {
+        return self.backends.iter().any(|x| x.can_show());
+    }
+}
+
+/// Backend for tests, giving the same answer every time without showing
+/// anything. Records the prompts it was asked
+pub struct FakeBackend {
+    answer: UpdatePromptAnswer,
+    prompts: RefCell<Vec<Prompt>>,
+    can_show: bool,
+    fails: bool,
+}
+
+impl FakeBackend {
+    pub fn new(answer: UpdatePromptAnswer) -> FakeBackend {
+        return FakeBackend {
+            answer,
+            prompts: RefCell::new(Vec::new()),
+            can_show: true,
+            fails: false,
+        };
+    }
+
+    /// A backend that fails to show prompts, like when zenity isn't
+    /// installed
+    pub fn failing() -> FakeBackend {
+        return FakeBackend {
+            fails: true,
+            ..FakeBackend::new(UpdatePromptAnswer::NoUpdateFound)
+        };
+    }
+
+    /// A backend that can't show prompts, as if there's no display
+    pub fn without_display() -> FakeBackend {
+        return FakeBackend {
+            can_show: false,
+            ..FakeBackend::new(UpdatePromptAnswer::NoUpdateFound)
+        };
+    }
+
+    /// Every prompt asked so far, in order
+    pub fn prompts(&self) -> Vec<Prompt> {
+        return self.prompts.borrow().clone();
+    }
+}
+
+impl PromptBackend for FakeBackend {
+    fn ask(&self, prompt: &Prompt) -> Result<UpdatePromptAnswer, Error> {
+        self.prompts.borrow_mut().push(prompt.clone());
+
+        if self.fails {
+            return Err(Error::Prompt("Fake backend failed".to_string()));
+        }
+
+        return Ok(self.answer);
+    }
+
+    fn can_show(&self) -> bool {
+        return self.can_show;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The prompt the tests ask, to change as each needs
+    fn test_prompt() -> Prompt {
+        return Prompt {
+            title: "Rust Update".to_string(),
+            text: "stable: 1.80.1\nUpdate?".to_string(),
+            updates: BTreeMap::new(),
+            timeout: None,
+            ok_label: "Update".to_string(),
+            cancel_label: "Not today".to_string(),
+            accessible: false,
+            detect_close: false,
+            confirm_word: None,
+            icon: None,
+            modal: false,
+        };
+    }
+
+    #[test]
+    fn zenity_answers() {
+        let mut prompt = Prompt {
+            timeout: Some(10),
+            ..test_prompt()
+        };
+
+        assert_eq!(
+            zenity_answer(&prompt, Some(0), "").unwrap(),
+            UpdatePromptAnswer::Update
+        );
+        assert_eq!(
+            zenity_answer(&prompt, Some(5), "").unwrap(),
+            UpdatePromptAnswer::Timeout
+        );
+        assert!(zenity_answer(&prompt, Some(255), "").is_err());
+        assert!(zenity_answer(&prompt, None, "").is_err());
+
+        // Without telling them apart, closing is cancelling
+        assert_eq!(
+            zenity_answer(&prompt, Some(1), "").unwrap(),
+            UpdatePromptAnswer::DoNotUpdate
+        );
+
+        prompt.detect_close = true;
+        assert_eq!(
+            zenity_answer(&prompt, Some(1), "").unwrap(),
+            UpdatePromptAnswer::Closed
+        );
+        assert_eq!(
+            zenity_answer(&prompt, Some(1), "Not today\n").unwrap(),
+            UpdatePromptAnswer::DoNotUpdate
+        );
+        assert_eq!(
+            zenity_answer(&prompt, Some(1), "Update\n").unwrap(),
+            UpdatePromptAnswer::Update
+        );
+    }
+
+    #[test]
+    fn dialog_options() {
+        let mut prompt = test_prompt();
+        let has_flag = |args: &[String], flag: &str| {
+            return args.iter().any(|x| x.starts_with(flag));
+        };
+        assert!(!has_flag(&zenity_args(&prompt), "--window-icon"));
+        assert!(!zenity_args(&prompt).contains(&"--modal".to_string()));
+        assert!(!has_flag(&notify_prompt_args(&prompt), "--icon"));
+
+        prompt.icon = Some(path::PathBuf::from("/usr/share/icons/rust.svg"));
+        prompt.modal = true;
+        let args = zenity_args(&prompt);
+        assert_eq!(
+            &args[..4],
+            [
+                "--question",
+                "--title=Rust Update",
+                "--window-icon=/usr/share/icons/rust.svg",
+                "--modal"
+            ]
+        );
+
+        // A notification takes the icon, but can't be modal
+        let args = notify_prompt_args(&prompt);
+        assert!(args.contains(&"--icon=/usr/share/icons/rust.svg".to_string()));
+        assert!(!args.contains(&"--modal".to_string()));
+    }
+
+    #[test]
+    fn typed_confirmation() {
+        assert_eq!(typed_answer("2.0.0\n", "2.0.0"), Typed::Confirmed);
+        assert_eq!(typed_answer("yes", "2.0.0"), Typed::Confirmed);
+        assert_eq!(typed_answer("", "2.0.0"), Typed::Declined);
+        assert_eq!(typed_answer("no", "2.0.0"), Typed::Declined);
+        assert_eq!(typed_answer("y", "2.0.0"), Typed::Mismatch);
+        assert_eq!(typed_answer("2.0", "2.0.0"), Typed::Mismatch);
+        assert_eq!(typed_answer("YES", "2.0.0"), Typed::Mismatch);
+
+        let mut prompt = Prompt {
+            title: "Rust Update: New Release".to_string(),
+            text: "stable: 2.0.0\nUpdate?".to_string(),
+            confirm_word: Some("2.0.0".to_string()),
+            ..test_prompt()
+        };
+
+        // Asked again after each mismatch, until it's typed
+        let (sender, lines) = mpsc::channel();
+        for line in ["y", "2.0", "2.0.0"] {
+            sender.send(line.to_string()).unwrap();
+        }
+        let mut reprompts = 0;
+        let mut reprompt = || {
+            reprompts += 1;
+            return Ok(());
+        };
+        assert_eq!(
+            read_typed_confirmation(&prompt, "2.0.0", &lines, &mut reprompt).unwrap(),
+            UpdatePromptAnswer::Update
+        );
+        assert_eq!(reprompts, 2);
+
+        // Stdin closing without it declines
+        sender.send("y".to_string()).unwrap();
+        drop(sender);
+        assert_eq!(
+            read_typed_confirmation(&prompt, "2.0.0", &lines, &mut || Ok(())).unwrap(),
+            UpdatePromptAnswer::DoNotUpdate
+        );
+
+        // Mismatches don't restart the timeout
+        prompt.timeout = Some(0);
+        let (sender, lines) = mpsc::channel();
+        sender.send("y".to_string()).unwrap();
+        assert_eq!(
+            read_typed_confirmation(&prompt, "2.0.0", &lines, &mut || Ok(())).unwrap(),
+            UpdatePromptAnswer::Timeout
+        );
+    }
+
+    #[test]
+    fn notify_answers() {
+        assert_eq!(
+            notify_answer(Some(0), "update\n").unwrap(),
+            UpdatePromptAnswer::Update
+        );
+        assert_eq!(
+            notify_answer(Some(0), "skip\n").unwrap(),
+            UpdatePromptAnswer::DoNotUpdate
+        );
+        assert_eq!(
+            notify_answer(Some(0), "").unwrap(),
+            UpdatePromptAnswer::Timeout
+        );
+        assert!(notify_answer(Some(1), "").is_err());
+    }
+
+    // A fallback of `backends`, which are shared so what each was asked can
+    // be checked
+    struct Shared(std::rc::Rc<FakeBackend>);
+
+    impl PromptBackend for Shared {
+        fn ask(&self, prompt: &Prompt) -> Result<UpdatePromptAnswer, Error> {
+            return self.0.ask(prompt);
+        }
+
+        fn can_show(&self) -> bool {
+            return self.0.can_show();
+        }
+    }
+
+    // Which of `backends` the fallback asked with, and its answer
+    fn fallback(backends: Vec<FakeBackend>) -> (Vec<usize>, Result<UpdatePromptAnswer, Error>) {
+        let backends: Vec<std::rc::Rc<FakeBackend>> =
+            backends.into_iter().map(std::rc::Rc::new).collect();
+        let chain = FallbackBackend::new(
+            backends
+                .iter()
+                .map(|x| Box::new(Shared(x.clone())) as Box<dyn PromptBackend>)
+                .collect(),
+        );
+        let prompt = Prompt {
+            timeout: Some(10),
+            ..test_prompt()
+        };
+
+        let answer = chain.ask(&prompt);
+        let asked = (0..backends.len())
+            .filter(|x| !backends[*x].prompts().is_empty())
+            .collect();
+
+        return (asked, answer);
+    }
+
+    #[test]
+    fn fallback_chain() {
+        let update = || FakeBackend::new(UpdatePromptAnswer::Update);
+
+        // The dialog, when it can be shown
+        let (asked, answer) = fallback(vec![update(), update(), update()]);
+        assert_eq!(asked, [0]);
+        assert_eq!(answer.unwrap(), UpdatePromptAnswer::Update);
+
+        // The notification without a display for the dialog, then the
+        // terminal without notifications
+        let (asked, _) = fallback(vec![FakeBackend::without_display(), update(), update()]);
+        assert_eq!(asked, [1]);
+        let (asked, _) = fallback(vec![
+            FakeBackend::without_display(),
+            FakeBackend::without_display(),
+            update(),
+        ]);
+        assert_eq!(asked, [2]);
+
+        // Failing to show is the same as not being able to
+        let (asked, answer) = fallback(vec![
+            FakeBackend::failing(),
+            FakeBackend::failing(),
+            update(),
+        ]);
+        assert_eq!(asked, [0, 1, 2]);
+        assert_eq!(answer.unwrap(), UpdatePromptAnswer::Update);
+
+        // Nothing left
+        let (asked, answer) =
+            fallback(vec![FakeBackend::without_display(), FakeBackend::failing()]);
+        assert_eq!(asked, [1]);
+        assert!(answer.is_err());
+
+        let chain = FallbackBackend::new(vec![Box::new(FakeBackend::without_display())]);
+        assert!(!chain.can_show());
+    }
+
+    // Answers one prompt on a new socket with `answer`, returning the
+    // socket and what it was sent
+    fn socket_responder(
+        name: &str,
+        answer: &'static str,
+    ) -> (path::PathBuf, std::thread::JoinHandle<String>) {
+        let path = crate::test_util::temp_dir(name).join("prompt.sock");
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+
+        let responder = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            io::BufReader::new(&stream).read_line(&mut request).unwrap();
+            stream.write_all(answer.as_bytes()).unwrap();
+
+            return request;
+        });
+
+        return (path, responder);
+    }
+
+    #[test]
+    fn socket_prompt() {
+        let prompt = Prompt {
+            updates: BTreeMap::from([(
+                "stable-x86_64-unknown-linux-gnu".to_string(),
+                "1.80.1".to_string(),
+            )]),
+            timeout: Some(10),
+            ..test_prompt()
+        };
+
+        let (path, responder) = socket_responder("socket_prompt", "{\"answer\":\"update\"}\n");
+        let backend = SocketBackend::new(&path);
+        assert!(backend.can_show());
+        assert_eq!(backend.ask(&prompt).unwrap(), UpdatePromptAnswer::Update);
+
+        let request = json::parse(&responder.join().unwrap()).unwrap();
+        assert_eq!(
+            request
+                .get("updates")
+                .and_then(|x| x.get("stable-x86_64-unknown-linux-gnu"))
+                .and_then(|x| x.as_str()),
+            Some("1.80.1")
+        );
+        assert_eq!(request.get("timeout"), Some(&json::Value::Number(10.0)));
+        assert_eq!(
+            request.get("text").and_then(|x| x.as_str()),
+            Some("stable: 1.80.1\nUpdate?")
+        );
+
+        let (path, _) = socket_responder("socket_prompt_later", "{\"answer\":\"not_today\"}\n");
+        assert_eq!(
+            SocketBackend::new(&path).ask(&prompt).unwrap(),
+            UpdatePromptAnswer::DoNotUpdate
+        );
+
+        let (path, _) = socket_responder("socket_prompt_bogus", "maybe\n");
+        assert!(SocketBackend::new(&path).ask(&prompt).is_err());
+        assert_eq!(
+            socket_answer("{\"answer\":\"closed\"}").unwrap(),
+            UpdatePromptAnswer::Closed
+        );
+
+        // Nothing listening
+        let missing = SocketBackend::new(path.with_file_name("missing.sock"));
+        assert!(!missing.can_show());
+        assert!(matches!(missing.ask(&prompt), Err(Error::Prompt(_))));
+    }
+}
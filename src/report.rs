@@ -0,0 +1,212 @@
+// The status report written by `--report`, for monitoring machines that
+// are only watched, not updated, and the one line summary of `--oneline`
+// and `--aggregate`
+
+use std::{collections::BTreeMap, io, path};
+
+use crate::{
+    json::Value,
+    parse::{Build, ParsedUpdate, UpdateStatus},
+    state_file, Outcome,
+};
+
+fn optional_string<T: ToString>(value: &Option<T>) -> Value {
+    match value {
+        Some(x) => return Value::String(x.to_string()),
+        None => return Value::Null,
+    }
+}
+
+fn build_value(build: &Build) -> Value {
+    return Value::Object(BTreeMap::from([
+        ("commit".to_string(), Value::String(build.commit.clone())),
+        ("date".to_string(), Value::String(build.date.clone())),
+    ]));
+}
+
+/// Builds the report, a JSON object like
+///
+/// `{"outcome":"dry_run","reported_at":1723400000,"updates":[{"current":"1.80.0","name":"stable-x86_64-unknown-linux-gnu","new":"1.80.1"}]}`
+///
+/// Every entry of the check is listed. `new` is null for those up to date.
+/// `current_build` and `new_build`, like `{"commit":"051478957","date":"2024-07-21"}`,
+/// are only there if `rustup check` gave them. A failed update also has
+/// `failed`, the names of the toolchains known to have failed
+pub fn build_report(outcome: &Outcome, updates: &[ParsedUpdate], reported_at: u64) -> String {
+    let mut report = BTreeMap::from([
+        ("reported_at".to_string(), Value::Number(reported_at as f64)),
+        (
+            "outcome".to_string(),
+            Value::String(outcome.name().to_string()),
+        ),
+        ("updates".to_string(), update_entries(updates)),
+    ]);
+    if let Outcome::UpdateFailed { results, .. } = outcome {
+        let failed = results
+            .iter()
+            .filter(|x| x.status == UpdateStatus::Failed)
+            .map(|x| Value::String(x.name.clone()))
+            .collect();
+        report.insert("failed".to_string(), Value::Array(failed));
+    }
+
+    return format!("{}\n", Value::Object(report));
+}
+
+/// The entries of a check, as the `updates` array of the report
+pub fn update_entries(updates: &[ParsedUpdate]) -> Value {
+    let entries = updates
+        .iter()
+        .map(|x| {
+            let mut entry = BTreeMap::from([
+                ("name".to_string(), Value::String(x.name.clone())),
+                ("current".to_string(), optional_string(&x.current)),
+                ("new".to_string(), optional_string(&x.new)),
+            ]);
+            for (key, build) in [
+                ("current_build", &x.current_build),
+                ("new_build", &x.new_build),
+            ] {
+                if let Some(build) = build {
+                    entry.insert(key.to_string(), build_value(build));
+                }
+            }
+
+            return Value::Object(entry);
+        })
+        .collect();
+
+    return Value::Array(entries);
+}
+
+/// Writes the report to `path`, replacing the last one
+pub fn write_report(
+    path: &path::Path,
+    outcome: &Outcome,
+    updates: &[ParsedUpdate],
+    reported_at: u64,
+) -> io::Result<()> {
+    return state_file::write_atomic(path, &build_report(outcome, updates, reported_at));
+}
+
+/// One line describing the updates, for a shell prompt or status bar, like
+///
+/// `rust: 1 update (stable 1.80.0→1.80.1)` or `rust: up to date`
+///
+/// Toolchains are named by their channel, with their target too when two
+/// share a channel, so the same update can be described differently from
+/// one check to the next. `--format json` is for scripts
+pub fn oneline_summary(updates: &[ParsedUpdate]) -> String {
+    return format!("rust: {}", describe_updates(updates));
+}
+
+/// The updates as in `oneline_summary`, without `rust: `, like `1 update
+/// (stable 1.80.0→1.80.1)` or `up to date`
+pub fn describe_updates(updates: &[ParsedUpdate]) -> String {
+    let with_updates: Vec<String> = updates
+        .iter()
+        .filter_map(|x| {
+            let new = x.new?;
+
+            match x.current {
+                Some(current) => return Some(format!("{} {current}→{new}", x.short_name(updates))),
+                None => return Some(format!("{} {new}", x.short_name(updates))),
+            }
+        })
+        .collect();
+
+    match with_updates.len() {
+        0 => return "up to date".to_string(),
+        1 => return format!("1 update ({})", with_updates[0]),
+        x => return format!("{x} updates ({})", with_updates.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{json, parse::UpdateResult, Version};
+
+    #[test]
+    fn report() {
+        let updates = [
+            ParsedUpdate {
+                name: "stable".to_string(),
+                current: Some(Version::new(1, 80, 0)),
+                new: Some(Version::new(1, 80, 1)),
+                current_build: None,
+                new_build: None,
+            },
+            ParsedUpdate {
+                name: "rustup".to_string(),
+                current: Some(Version::new(1, 27, 1)),
+                new: None,
+                current_build: Some(Build {
+                    commit: "a1b2c3d4e".to_string(),
+                    date: "2024-06-01".to_string(),
+                }),
+                new_build: None,
+            },
+        ];
+
+        let report = build_report(&Outcome::DryRun, &updates, 1723400000);
+        assert_eq!(
+            report,
+            "{\"outcome\":\"dry_run\",\"reported_at\":1723400000,\"updates\":[\
+             {\"current\":\"1.80.0\",\"name\":\"stable\",\"new\":\"1.80.1\"},\
+             {\"current\":\"1.27.1\",\"current_build\":{\"commit\":\"a1b2c3d4e\",\
+             \"date\":\"2024-06-01\"},\"name\":\"rustup\",\"new\":null}]}\n"
+        );
+        assert!(json::parse(&report).is_ok());
+
+        let failed = Outcome::UpdateFailed {
+            code: Some(1),
+            results: vec![
+                UpdateResult {
+                    name: "stable".to_string(),
+                    status: UpdateStatus::Updated,
+                },
+                UpdateResult {
+                    name: "nightly".to_string(),
+                    status: UpdateStatus::Failed,
+                },
+            ],
+        };
+        let report = build_report(&failed, &updates[1..], 1723400000);
+        assert!(report.starts_with(
+            "{\"failed\":[\"nightly\"],\"outcome\":\"update_failed\",\"reported_at\":1723400000,"
+        ));
+    }
+
+    #[test]
+    fn oneline() {
+        let mut updates = vec![ParsedUpdate {
+            name: "stable-x86_64-unknown-linux-gnu".to_string(),
+            current: Some(Version::new(1, 80, 1)),
+            new: None,
+            current_build: None,
+            new_build: None,
+        }];
+        assert_eq!(oneline_summary(&updates), "rust: up to date");
+        assert_eq!(oneline_summary(&[]), "rust: up to date");
+
+        updates[0].current = Some(Version::new(1, 80, 0));
+        updates[0].new = Some(Version::new(1, 80, 1));
+        assert_eq!(
+            oneline_summary(&updates),
+            "rust: 1 update (stable 1.80.0→1.80.1)"
+        );
+
+        updates.push(ParsedUpdate {
+            name: "rustup".to_string(),
+            current: None,
+            new: Some(Version::new(1, 27, 1)),
+            current_build: None,
+            new_build: None,
+        });
+        assert_eq!(
+            oneline_summary(&updates),
+            "rust: 2 updates (stable 1.80.0→1.80.1, rustup 1.27.1)"
+        );
+    }
+}
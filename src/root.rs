@@ -0,0 +1,150 @@
+use std::{fs, io, os::linux::fs::MetadataExt, path, str};
+
+/// What to do when running as root
+///
+/// Running as root updates root's toolchains rather than the user's, and
+/// writes the no-update flag in `/root`. This is almost always a service
+/// set up as a system unit instead of a user unit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RootPolicy {
+    /// Stop with an error
+    Refuse,
+
+    /// Print a warning, then carry on
+    Warn,
+}
+
+/// Result of checking who this is running as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RootCheck {
+    NotRoot,
+    Warn,
+    Refuse,
+}
+
+/// How to update a rustup that belongs to another user, like a system
+/// install in `/opt` owned by root
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Elevation {
+    /// Run the update as is, which fails if it can't write there
+    Never,
+
+    /// Run it with `sudo`, which asks for a password in the terminal
+    Sudo,
+
+    /// Run it with `pkexec`, which asks for a password in a window, so it
+    /// works without a terminal, like with `detach`
+    Pkexec,
+}
+
+impl Elevation {
+    /// Its name, as read by `from_str`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Elevation::Never => return "never",
+            Elevation::Sudo => return "sudo",
+            Elevation::Pkexec => return "pkexec",
+        }
+    }
+}
+
+impl str::FromStr for Elevation {
+    type Err = String;
+
+    /// Reads `never`, `sudo` or `pkexec`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "never" => return Ok(Elevation::Never),
+            "sudo" => return Ok(Elevation::Sudo),
+            "pkexec" => return Ok(Elevation::Pkexec),
+            _ => {
+                return Err(format!(
+                    "Unknown elevation '{text}'. Expected never, sudo or pkexec"
+                ))
+            }
+        }
+    }
+}
+
+/// User id of the owner of the file at `path`
+pub fn owner_uid(path: &path::Path) -> io::Result<u32> {
+    return io::Result::Ok(fs::metadata(path)?.st_uid());
+}
+
+/// The program to run the update through with `elevation`, for a rustup
+/// owned by the user `owner`, when running as the user `euid`
+///
+/// None if the update runs as is: rustup is ours, this is already root, or
+/// elevation is off
+pub fn elevation_program(elevation: Elevation, owner: u32, euid: u32) -> Option<&'static str> {
+    if owner == euid || euid == 0 {
+        return None;
+    }
+
+    match elevation {
+        Elevation::Never => return None,
+        Elevation::Sudo => return Some("sudo"),
+        Elevation::Pkexec => return Some("pkexec"),
+    }
+}
+
+/// Effective user id of this process
+///
+/// `/proc/self` is owned by the effective user of the process reading it
+pub fn effective_uid() -> io::Result<u32> {
+    return io::Result::Ok(fs::metadata("/proc/self")?.st_uid());
+}
+
+/// Decides what to do when running as the user `euid`
+pub fn root_check(euid: u32, policy: RootPolicy) -> RootCheck {
+    if euid != 0 {
+        return RootCheck::NotRoot;
+    }
+
+    match policy {
+        RootPolicy::Refuse => return RootCheck::Refuse,
+        RootPolicy::Warn => return RootCheck::Warn,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_root() {
+        assert_eq!(root_check(1000, RootPolicy::Refuse), RootCheck::NotRoot);
+        assert_eq!(root_check(1000, RootPolicy::Warn), RootCheck::NotRoot);
+    }
+
+    #[test]
+    fn root() {
+        assert_eq!(root_check(0, RootPolicy::Refuse), RootCheck::Refuse);
+        assert_eq!(root_check(0, RootPolicy::Warn), RootCheck::Warn);
+    }
+
+    #[test]
+    fn elevation() {
+        // A system install owned by root, updated by a user
+        assert_eq!(elevation_program(Elevation::Sudo, 0, 1000), Some("sudo"));
+        assert_eq!(
+            elevation_program(Elevation::Pkexec, 0, 1000),
+            Some("pkexec")
+        );
+        assert_eq!(elevation_program(Elevation::Never, 0, 1000), None);
+
+        // Another user's is elevated too
+        assert_eq!(elevation_program(Elevation::Sudo, 1001, 1000), Some("sudo"));
+
+        // Our own rustup, or running as root already
+        assert_eq!(elevation_program(Elevation::Sudo, 1000, 1000), None);
+        assert_eq!(elevation_program(Elevation::Pkexec, 1000, 0), None);
+
+        let path = crate::test_util::temp_dir("root_elevation").join("rustup");
+        fs::write(&path, "").unwrap();
+        assert_eq!(owner_uid(&path).unwrap(), effective_uid().unwrap());
+
+        assert_eq!("pkexec".parse(), Ok(Elevation::Pkexec));
+        assert!("doas".parse::<Elevation>().is_err());
+    }
+}
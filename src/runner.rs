@@ -0,0 +1,428 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    io::{self, Read},
+    os::unix::process::CommandExt,
+    path, process, thread, time,
+};
+
+use crate::completion::{FileWatcher, ScriptState, Watcher};
+
+/// A program to run and its arguments
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+
+    /// Environment variables to set, on top of the ones inherited
+    pub env: Vec<(String, String)>,
+
+    /// Directory to run in. None for the current one
+    pub dir: Option<path::PathBuf>,
+
+    /// How long it can run before it's killed. None to wait however long
+    /// it takes
+    pub timeout: Option<time::Duration>,
+}
+
+impl CommandSpec {
+    pub fn new<S: AsRef<str>>(program: &str, args: &[S]) -> CommandSpec {
+        return CommandSpec {
+            program: program.to_string(),
+            args: args.iter().map(|x| x.as_ref().to_string()).collect(),
+            env: Vec::new(),
+            dir: None,
+            timeout: None,
+        };
+    }
+
+    /// The same command, also setting the environment variables `env`
+    pub fn with_env(mut self, env: &[(String, String)]) -> CommandSpec {
+        self.env.extend(env.iter().cloned());
+
+        return self;
+    }
+
+    /// The same command, run in `dir`
+    pub fn in_dir(mut self, dir: &path::Path) -> CommandSpec {
+        self.dir = Some(dir.to_path_buf());
+
+        return self;
+    }
+
+    /// The same command, killed if it takes longer than `timeout`
+    pub fn with_timeout(mut self, timeout: time::Duration) -> CommandSpec {
+        self.timeout = Some(timeout);
+
+        return self;
+    }
+
+    // The command to run for real
+    fn command(&self) -> process::Command {
+        let mut command = process::Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(dir) = &self.dir {
+            command.current_dir(dir);
+        }
+
+        return command;
+    }
+}
+
+impl fmt::Display for CommandSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+
+        return fmt::Result::Ok(());
+    }
+}
+
+/// How a command with the exit code `status` ended, for messages, like
+/// `exit code 1`
+pub fn describe_status(status: Option<i32>) -> String {
+    match status {
+        Some(code) => return format!("exit code {code}"),
+        None => return "a signal".to_string(),
+    }
+}
+
+/// What a finished command returned
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommandOutput {
+    /// Exit code, None if killed by a signal
+    pub status: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl CommandOutput {
+    /// Output of a command that exited with `status`
+    pub fn new(status: i32, stdout: &str, stderr: &str) -> CommandOutput {
+        return CommandOutput {
+            status: Some(status),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        };
+    }
+
+    pub fn success(&self) -> bool {
+        return self.status == Some(0);
+    }
+}
+
+/// Runs external commands, so tests can replace them with canned output
+pub trait CommandRunner {
+    /// Runs `command` to completion, capturing its output
+    ///
+    /// Errors if the command couldn't be started, like when it isn't found
+    /// or isn't executable. Errors with `io::ErrorKind::TimedOut` if it
+    /// started, but was killed for taking longer than its timeout
+    fn run(&self, command: &CommandSpec) -> io::Result<CommandOutput>;
+
+    /// Starts `command` in a process group of its own, without waiting for
+    /// it or keeping its output, so it carries on after this program exits.
+    /// Returns its process id
+    fn spawn_detached(&self, command: &CommandSpec) -> io::Result<u32>;
+
+    /// Watches the update script started in a terminal, which writes how
+    /// it's going to the file `status`
+    fn watcher(&self, status: &path::Path) -> Box<dyn Watcher> {
+        return Box::new(FileWatcher {
+            path: status.to_path_buf(),
+        });
+    }
+}
+
+/// Runs commands for real
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRunner;
+
+// Reads all of `pipe` on another thread, so a command printing a lot
+// doesn't block while it's waited for
+fn read_in_background<R: Read + Send + 'static>(
+    pipe: Option<R>,
+) -> thread::JoinHandle<io::Result<Vec<u8>>> {
+    return thread::spawn(move || {
+        let mut read = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut read)?;
+        }
+
+        return io::Result::Ok(read);
+    });
+}
+
+// Runs `command`, killing it once `timeout` has passed
+fn run_with_timeout(command: &CommandSpec, timeout: time::Duration) -> io::Result<CommandOutput> {
+    // Like `output`, which doesn't give it stdin
+    let mut child = command
+        .command()
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()?;
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let started = time::Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            // The pipes are left to the threads, as something it started
+            // could still have them open
+            child.kill()?;
+            child.wait()?;
+
+            return io::Result::Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "{} was killed after {} seconds",
+                    command.program,
+                    timeout.as_secs()
+                ),
+            ));
+        }
+        thread::sleep(time::Duration::from_millis(50));
+    };
+
+    let join = |handle: thread::JoinHandle<io::Result<Vec<u8>>>| {
+        return handle
+            .join()
+            .unwrap_or_else(|_| io::Result::Err(io::Error::other("Reading output failed")));
+    };
+
+    return io::Result::Ok(CommandOutput {
+        status: status.code(),
+        stdout: join(stdout)?,
+        stderr: join(stderr)?,
+    });
+}
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, command: &CommandSpec) -> io::Result<CommandOutput> {
+        if let Some(timeout) = command.timeout {
+            return run_with_timeout(command, timeout);
+        }
+
+        let output = command.command().output()?;
+
+        return io::Result::Ok(CommandOutput {
+            status: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        });
+    }
+
+    fn spawn_detached(&self, command: &CommandSpec) -> io::Result<u32> {
+        // Out of this process group, so it isn't sent the signals the shell
+        // sends this one, like when the terminal closes
+        let child = command
+            .command()
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .process_group(0)
+            .spawn()?;
+
+        return io::Result::Ok(child.id());
+    }
+}
+
+type MockHandler = Box<dyn Fn(&CommandSpec) -> io::Result<CommandOutput>>;
+
+/// Runner for tests, answering each command with `handler` and recording
+/// every command it was asked to run
+///
+/// A terminal's update script is taken to have run in it, so the update
+/// exits the same as the terminal, unless `without_scripts` says otherwise
+pub struct MockRunner {
+    handler: MockHandler,
+    calls: RefCell<Vec<CommandSpec>>,
+    last_status: Cell<Option<i32>>,
+    runs_scripts: bool,
+}
+
+// The update script as a mock terminal ran it, finished already
+struct MockWatcher {
+    state: ScriptState,
+}
+
+impl Watcher for MockWatcher {
+    fn state(&self) -> ScriptState {
+        return self.state;
+    }
+
+    fn is_running(&self, _: u32) -> bool {
+        return false;
+    }
+
+    fn sleep(&self) {}
+}
+
+impl MockRunner {
+    pub fn new<F>(handler: F) -> MockRunner
+    where
+        F: Fn(&CommandSpec) -> io::Result<CommandOutput> + 'static,
+    {
+        return MockRunner {
+            handler: Box::new(handler),
+            calls: RefCell::new(Vec::new()),
+            last_status: Cell::new(None),
+            runs_scripts: true,
+        };
+    }
+
+    /// The same runner, but terminals never start the update script, like
+    /// ones that fail to open a window
+    pub fn without_scripts(mut self) -> MockRunner {
+        self.runs_scripts = false;
+
+        return self;
+    }
+
+    /// Every command run so far, in order
+    pub fn calls(&self) -> Vec<CommandSpec> {
+        return self.calls.borrow().clone();
+    }
+
+    /// Commands run so far with the program `program`
+    pub fn calls_to(&self, program: &str) -> Vec<CommandSpec> {
+        return self
+            .calls
+            .borrow()
+            .iter()
+            .filter(|x| x.program == program)
+            .cloned()
+            .collect();
+    }
+}
+
+impl CommandRunner for MockRunner {
+    fn run(&self, command: &CommandSpec) -> io::Result<CommandOutput> {
+        self.calls.borrow_mut().push(command.clone());
+
+        let output = (self.handler)(command);
+        if let io::Result::Ok(output) = &output {
+            self.last_status.set(output.status);
+        }
+
+        return output;
+    }
+
+    /// Recorded like any other command. Errors if `handler` does, and
+    /// returns 0 as the process id otherwise
+    fn spawn_detached(&self, command: &CommandSpec) -> io::Result<u32> {
+        self.run(command)?;
+
+        return io::Result::Ok(0);
+    }
+
+    fn watcher(&self, _: &path::Path) -> Box<dyn Watcher> {
+        let state = match self.runs_scripts {
+            true => ScriptState::Finished {
+                code: self.last_status.get(),
+            },
+            false => ScriptState::NotStarted,
+        };
+
+        return Box::new(MockWatcher { state });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_runner() {
+        let output = SystemRunner
+            .run(&CommandSpec::new(
+                "/bin/sh",
+                &["-c", "echo out; echo err >&2; exit 3"],
+            ))
+            .unwrap();
+
+        assert_eq!(output, CommandOutput::new(3, "out\n", "err\n"));
+        assert!(!output.success());
+    }
+
+    #[test]
+    fn system_runner_env() {
+        let command =
+            CommandSpec::new("/bin/sh", &["-c", "echo $RUSTUP_DIST_SERVER"]).with_env(&[(
+                "RUSTUP_DIST_SERVER".to_string(),
+                "https://mirror.example.com".to_string(),
+            )]);
+
+        let output = SystemRunner.run(&command).unwrap();
+        assert_eq!(output.stdout, b"https://mirror.example.com\n");
+    }
+
+    #[test]
+    fn system_runner_timeout() {
+        let finishes = CommandSpec::new("/bin/sh", &["-c", "echo out; exit 3"])
+            .with_timeout(time::Duration::from_secs(10));
+        assert_eq!(
+            SystemRunner.run(&finishes).unwrap(),
+            CommandOutput::new(3, "out\n", "")
+        );
+
+        let hangs = CommandSpec::new("sleep", &["10"]).with_timeout(time::Duration::from_secs(1));
+        let started = time::Instant::now();
+        let error = SystemRunner.run(&hangs).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < time::Duration::from_secs(5));
+
+        // Not being able to start isn't a timeout
+        let missing = CommandSpec::new("/nonexistent/rustup", &["check"])
+            .with_timeout(time::Duration::from_secs(1));
+        let error = SystemRunner.run(&missing).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    // Process group of the process `pid`, from /proc
+    fn process_group(pid: &str) -> String {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).unwrap();
+
+        // After the name in brackets are the state, parent and group
+        let (_, after_name) = stat.rsplit_once(')').unwrap();
+        return after_name.split_whitespace().nth(2).unwrap().to_string();
+    }
+
+    #[test]
+    fn detached_process_group() {
+        let pid = SystemRunner
+            .spawn_detached(&CommandSpec::new("sleep", &["5"]))
+            .unwrap();
+
+        assert_eq!(process_group(&pid.to_string()), pid.to_string());
+        assert_ne!(process_group(&pid.to_string()), process_group("self"));
+
+        SystemRunner
+            .run(&CommandSpec::new("kill", &[pid.to_string()]))
+            .unwrap();
+    }
+
+    #[test]
+    fn mock_runner_records_calls() {
+        let runner = MockRunner::new(|_| io::Result::Ok(CommandOutput::new(0, "", "")));
+
+        runner.run(&CommandSpec::new("rustup", &["check"])).unwrap();
+        runner
+            .run(&CommandSpec::new("cargo", &["--version"]))
+            .unwrap();
+
+        assert_eq!(runner.calls().len(), 2);
+        assert_eq!(
+            runner.calls_to("rustup"),
+            vec![CommandSpec::new("rustup", &["check"])]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+// The versions seen by the last run, to tell what changed since then,
+// rather than only what can be updated
+
+use std::{fmt, fs, io, path};
+
+use crate::{parse::ParsedUpdate, state_file, version::Version};
+
+/// Something that changed between two checks
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// `version` of `name` came out since the last check
+    Available { name: String, version: Version },
+
+    /// `name` moved from `from` to `to`, like after an update
+    Installed {
+        name: String,
+        from: Version,
+        to: Version,
+    },
+
+    /// `name` wasn't there at the last check
+    Added { name: String },
+
+    /// `name` was there at the last check, but isn't now
+    Removed { name: String },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Available { name, version } => {
+                return write!(f, "{name} {version} came out since the last check")
+            }
+            Change::Installed { name, from, to } => {
+                return write!(f, "{name} moved from {from} to {to} since the last check")
+            }
+            Change::Added { name } => {
+                return write!(f, "{name} was installed since the last check")
+            }
+            Change::Removed { name } => {
+                return write!(f, "{name} was removed since the last check")
+            }
+        }
+    }
+}
+
+/// What changed from the `previous` versions to the `current` ones, in the
+/// order of `current`, then anything removed
+pub fn changes(previous: &[ParsedUpdate], current: &[ParsedUpdate]) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    for update in current {
+        let Some(before) = previous.iter().find(|x| x.name == update.name) else {
+            changes.push(Change::Added {
+                name: update.name.clone(),
+            });
+            continue;
+        };
+
+        if let (Some(from), Some(to)) = (before.current, update.current) {
+            if from != to {
+                changes.push(Change::Installed {
+                    name: update.name.clone(),
+                    from,
+                    to,
+                });
+            }
+        }
+
+        // Only new if it's not the version offered last time, or just
+        // installed
+        if let Some(version) = update.new {
+            if before.new != Some(version) && before.current != Some(version) {
+                changes.push(Change::Available {
+                    name: update.name.clone(),
+                    version,
+                });
+            }
+        }
+    }
+
+    for before in previous {
+        if !current.iter().any(|x| x.name == before.name) {
+            changes.push(Change::Removed {
+                name: before.name.clone(),
+            });
+        }
+    }
+
+    return changes;
+}
+
+// Each line is `name=current,new`, with an empty version if it's unknown
+fn serialize(updates: &[ParsedUpdate]) -> String {
+    let version = |x: &Option<Version>| x.map(|x| x.to_string()).unwrap_or_default();
+
+    return updates
+        .iter()
+        .map(|x| format!("{}={},{}\n", x.name, version(&x.current), version(&x.new)))
+        .collect();
+}
+
+fn parse(content: &str) -> Vec<ParsedUpdate> {
+    return state_file::key_values(content)
+        .filter_map(|(name, versions)| {
+            let (current, new) = versions.split_once(',')?;
+
+            return Some(ParsedUpdate {
+                name: name.to_string(),
+                current: current.parse().ok(),
+                new: new.parse().ok(),
+                current_build: None,
+                new_build: None,
+            });
+        })
+        .collect();
+}
+
+/// Reads the versions seen by the last run. None if there wasn't one
+pub fn read_seen(path: &path::Path) -> io::Result<Option<Vec<ParsedUpdate>>> {
+    match fs::read_to_string(path) {
+        io::Result::Ok(content) => return io::Result::Ok(Some(parse(&content))),
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(None)
+        }
+        io::Result::Err(error) => return io::Result::Err(error),
+    }
+}
+
+/// Saves `updates` as the versions seen by this run
+pub fn write_seen(path: &path::Path, updates: &[ParsedUpdate]) -> io::Result<()> {
+    return state_file::write_atomic(path, &serialize(updates));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse::parse_rustup_check, test_util::temp_dir};
+
+    fn check(lines: &[&str]) -> Vec<ParsedUpdate> {
+        let lines: Vec<String> = lines.iter().map(|x| x.to_string()).collect();
+
+        return parse_rustup_check(&lines);
+    }
+
+    #[test]
+    fn changed_versions() {
+        let before = check(&[
+            "stable-x86_64-unknown-linux-gnu - Up to date : 1.80.0",
+            "beta-x86_64-unknown-linux-gnu - Update available : 1.81.0 -> 1.81.1",
+            "nightly-x86_64-unknown-linux-gnu - Up to date : 1.82.0",
+        ]);
+        let after = check(&[
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1",
+            "beta-x86_64-unknown-linux-gnu - Up to date : 1.81.1",
+            "rustup - Up to date : 1.27.1",
+        ]);
+
+        assert_eq!(
+            changes(&before, &after),
+            [
+                Change::Available {
+                    name: "stable-x86_64-unknown-linux-gnu".to_string(),
+                    version: Version::new(1, 80, 1),
+                },
+                Change::Installed {
+                    name: "beta-x86_64-unknown-linux-gnu".to_string(),
+                    from: Version::new(1, 81, 0),
+                    to: Version::new(1, 81, 1),
+                },
+                Change::Added {
+                    name: "rustup".to_string(),
+                },
+                Change::Removed {
+                    name: "nightly-x86_64-unknown-linux-gnu".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            changes(&before, &after)[0].to_string(),
+            "stable-x86_64-unknown-linux-gnu 1.80.1 came out since the last check"
+        );
+
+        // The same update offered again isn't a change
+        assert_eq!(changes(&after, &after), []);
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_dir("seen_round_trip").join("seen_versions");
+        assert_eq!(read_seen(&path).unwrap(), None);
+
+        let updates = check(&[
+            "stable-x86_64-unknown-linux-gnu - Update available : 1.80.0 -> 1.80.1",
+            "rustup - Up to date : 1.27.1",
+        ]);
+        write_seen(&path, &updates).unwrap();
+
+        assert_eq!(read_seen(&path).unwrap(), Some(updates));
+    }
+}
@@ -0,0 +1,299 @@
+use std::{env, fs, path};
+
+use crate::{home, parse, toml};
+
+/// rustup's home directory, from `RUSTUP_HOME` or `~/.rustup`
+pub fn rustup_home() -> Option<path::PathBuf> {
+    if let Some(home) = env::var_os("RUSTUP_HOME") {
+        return Some(path::PathBuf::from(home));
+    }
+
+    let mut path = home::home_dir().ok()?;
+    path.push(".rustup");
+
+    return Some(path);
+}
+
+/// Reads `default_toolchain` from the contents of rustup's `settings.toml`
+///
+/// None if it's not set, or the settings are malformed
+pub fn parse_default_toolchain(settings: &str) -> Option<String> {
+    let table = toml::parse(settings).ok()?;
+
+    return table
+        .get("default_toolchain")?
+        .as_str()
+        .map(|x| x.to_string());
+}
+
+/// Reads `default_host_triple` from the contents of rustup's `settings.toml`
+pub fn parse_host_triple(settings: &str) -> Option<String> {
+    let table = toml::parse(settings).ok()?;
+
+    return table
+        .get("default_host_triple")?
+        .as_str()
+        .map(|x| x.to_string());
+}
+
+// The target this was built for, which rustup picks as the host when
+// `settings.toml` doesn't say
+fn built_in_host_triple() -> String {
+    let abi = if cfg!(target_env = "musl") {
+        "musl"
+    } else {
+        "gnu"
+    };
+
+    return format!("{}-unknown-linux-{abi}", env::consts::ARCH);
+}
+
+/// The full name of `toolchain`, with `host` added if it's only a channel,
+/// like `stable` to `stable-x86_64-unknown-linux-gnu`. A custom toolchain
+/// is left alone
+pub fn full_toolchain_name(toolchain: &str, host: &str) -> String {
+    let (channel, target) = parse::split_toolchain_name(toolchain);
+    let first = channel.split('-').next().unwrap_or(channel);
+
+    match target {
+        None if parse::is_channel(first) => return format!("{toolchain}-{host}"),
+        _ => return toolchain.to_string(),
+    }
+}
+
+/// The user's default toolchain from `settings.toml` in `rustup_home`, in
+/// full. A default of only the channel is for the host
+///
+/// None if the file is missing, malformed, or has no default
+pub fn read_default_toolchain(rustup_home: &path::Path) -> Option<String> {
+    let settings = fs::read_to_string(rustup_home.join("settings.toml")).ok()?;
+    let host = parse_host_triple(&settings).unwrap_or_else(built_in_host_triple);
+
+    return Some(full_toolchain_name(
+        &parse_default_toolchain(&settings)?,
+        &host,
+    ));
+}
+
+/// True if the toolchain `name` from `rustup check` is the default toolchain
+///
+/// rustup check names toolchains in full, like
+/// `stable-x86_64-unknown-linux-gnu`, so `default_toolchain` is too, as
+/// `read_default_toolchain` gives it. Then only the host's `stable` is
+/// the default, not every target's
+pub fn is_default_toolchain(name: &str, default_toolchain: &str) -> bool {
+    return name == default_toolchain;
+}
+
+/// A directory set to use a toolchain other than the default
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolchainOverride {
+    pub directory: path::PathBuf,
+    pub toolchain: String,
+}
+
+/// Reads the `[overrides]` from the contents of rustup's `settings.toml`,
+/// which are what `rustup override list` shows
+pub fn parse_overrides(settings: &str) -> Vec<ToolchainOverride> {
+    let Ok(table) = toml::parse(settings) else {
+        return Vec::new();
+    };
+
+    return table
+        .iter()
+        .filter_map(
+            |(key, value)| match (key.strip_prefix("overrides."), value.as_str()) {
+                (Some(directory), Some(toolchain)) => {
+                    return Some(ToolchainOverride {
+                        directory: path::PathBuf::from(directory),
+                        toolchain: toolchain.to_string(),
+                    })
+                }
+                _ => return None,
+            },
+        )
+        .collect();
+}
+
+/// The overrides from `settings.toml` in `rustup_home`
+pub fn read_overrides(rustup_home: &path::Path) -> Vec<ToolchainOverride> {
+    match fs::read_to_string(rustup_home.join("settings.toml")) {
+        Ok(settings) => return parse_overrides(&settings),
+        Err(_) => return Vec::new(),
+    }
+}
+
+/// Reads the toolchain from the contents of a `rust-toolchain.toml` file,
+/// or an old `rust-toolchain` file with only the toolchain's name in it
+pub fn parse_toolchain_file(content: &str) -> Option<String> {
+    if let Ok(table) = toml::parse(content) {
+        if let Some(channel) = table.get("toolchain.channel").and_then(|x| x.as_str()) {
+            return Some(channel.to_string());
+        }
+    }
+
+    let line = content.lines().map(|x| x.trim()).find(|x| !x.is_empty())?;
+    if line.contains(['=', '[']) {
+        return None;
+    }
+
+    return Some(line.to_string());
+}
+
+/// The toolchain set by a toolchain file in `directory`, if there is one
+pub fn read_toolchain_file(directory: &path::Path) -> Option<String> {
+    return ["rust-toolchain.toml", "rust-toolchain"]
+        .iter()
+        .filter_map(|x| fs::read_to_string(directory.join(x)).ok())
+        .find_map(|x| parse_toolchain_file(&x));
+}
+
+/// True if `toolchain` is pinned to a release, like `1.75.0` or
+/// `nightly-2024-08-01`, so `rustup update` never changes it
+pub fn is_pinned(toolchain: &str) -> bool {
+    let (channel, _) = parse::split_toolchain_name(toolchain);
+
+    // Only dated channels, like `nightly-2024-08-01`, keep a dash
+    return channel.starts_with(|x: char| x.is_ascii_digit())
+        || channel
+            .split_once('-')
+            .is_some_and(|(name, _)| matches!(name, "stable" | "beta" | "nightly"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    const SAMPLE_SETTINGS: &str = r#"default_toolchain = "stable-x86_64-unknown-linux-gnu"
+profile = "default"
+version = "12"
+
+[overrides]
+"/home/daisy/code/old_project" = "1.75.0-x86_64-unknown-linux-gnu"
+"#;
+
+    #[test]
+    fn sample_settings() {
+        assert_eq!(
+            parse_default_toolchain(SAMPLE_SETTINGS),
+            Some("stable-x86_64-unknown-linux-gnu".to_string())
+        );
+    }
+
+    #[test]
+    fn malformed_or_missing_settings() {
+        assert_eq!(parse_default_toolchain("default_toolchain = "), None);
+        assert_eq!(parse_default_toolchain("profile = \"minimal\""), None);
+
+        let dir = temp_dir("settings_missing");
+        assert_eq!(read_default_toolchain(&dir), None);
+    }
+
+    #[test]
+    fn default_toolchain_names() {
+        assert!(is_default_toolchain(
+            "stable-x86_64-unknown-linux-gnu",
+            "stable-x86_64-unknown-linux-gnu"
+        ));
+        assert!(!is_default_toolchain(
+            "nightly-x86_64-unknown-linux-gnu",
+            "stable-x86_64-unknown-linux-gnu"
+        ));
+
+        let host = "aarch64-unknown-linux-gnu";
+        assert_eq!(
+            full_toolchain_name("stable", host),
+            "stable-aarch64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            full_toolchain_name("nightly-2024-08-01", host),
+            "nightly-2024-08-01-aarch64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            full_toolchain_name("1.75", host),
+            "1.75-aarch64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            full_toolchain_name("stable-x86_64-unknown-linux-gnu", host),
+            "stable-x86_64-unknown-linux-gnu"
+        );
+        assert_eq!(full_toolchain_name("my-local", host), "my-local");
+    }
+
+    #[test]
+    fn default_of_two_targets() {
+        let dir = temp_dir("settings_default_of_two_targets");
+        fs::write(
+            dir.join("settings.toml"),
+            "default_host_triple = \"aarch64-unknown-linux-gnu\"\n\
+             default_toolchain = \"stable\"\n",
+        )
+        .unwrap();
+
+        // Only the host's stable is the default
+        let default = read_default_toolchain(&dir).unwrap();
+        assert!(is_default_toolchain(
+            "stable-aarch64-unknown-linux-gnu",
+            &default
+        ));
+        assert!(!is_default_toolchain(
+            "stable-x86_64-unknown-linux-gnu",
+            &default
+        ));
+
+        // Without a host triple, it's the one this was built for
+        fs::write(dir.join("settings.toml"), "default_toolchain = \"beta\"\n").unwrap();
+        assert_eq!(
+            read_default_toolchain(&dir),
+            Some(format!("beta-{}", built_in_host_triple()))
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        assert_eq!(
+            parse_overrides(SAMPLE_SETTINGS),
+            [ToolchainOverride {
+                directory: path::PathBuf::from("/home/daisy/code/old_project"),
+                toolchain: "1.75.0-x86_64-unknown-linux-gnu".to_string(),
+            }]
+        );
+        assert_eq!(parse_overrides("default_toolchain = \"stable\""), []);
+    }
+
+    #[test]
+    fn toolchain_files() {
+        assert_eq!(
+            parse_toolchain_file("[toolchain]\nchannel = \"1.75.0\"\ncomponents = [\"clippy\"]\n"),
+            Some("1.75.0".to_string())
+        );
+        assert_eq!(
+            parse_toolchain_file("\nnightly-2024-08-01\n"),
+            Some("nightly-2024-08-01".to_string())
+        );
+        assert_eq!(
+            parse_toolchain_file("[toolchain]\nprofile = \"minimal\""),
+            None
+        );
+
+        let dir = temp_dir("settings_toolchain_file");
+        assert_eq!(read_toolchain_file(&dir), None);
+
+        fs::write(dir.join("rust-toolchain"), "1.70.0").unwrap();
+        assert_eq!(read_toolchain_file(&dir), Some("1.70.0".to_string()));
+    }
+
+    #[test]
+    fn pinned_toolchains() {
+        assert!(is_pinned("1.75.0"));
+        assert!(is_pinned("1.75.0-x86_64-unknown-linux-gnu"));
+        assert!(is_pinned("nightly-2024-08-01"));
+        assert!(is_pinned("nightly-2024-08-01-x86_64-unknown-linux-gnu"));
+
+        assert!(!is_pinned("stable"));
+        assert!(!is_pinned("stable-x86_64-unknown-linux-gnu"));
+        assert!(!is_pinned("nightly"));
+        assert!(!is_pinned("my-local-2"));
+    }
+}
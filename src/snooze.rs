@@ -0,0 +1,498 @@
+use std::{fs, io, os::linux::fs::MetadataExt, path, time};
+
+use crate::{diagnose::is_writable_dir, state_file};
+
+// Time taken between writing the no-update flag and prompting again
+pub const NO_UPDATE_FLAG_DELAY: u64 = 60 * 60 * 24;
+
+/// Length of the snooze when a prompt times out, with
+/// `TimeoutAction::SnoozeShort`
+pub const SHORT_SNOOZE: u64 = 60 * 60;
+
+// Longest a snooze can be, however many times the user snoozes in a row
+pub const MAX_NO_UPDATE_FLAG_DELAY: u64 = 7 * NO_UPDATE_FLAG_DELAY;
+
+/// Contents of the no-update flag: when the user said not to update, and
+/// when to prompt them again. Both are seconds since the unix epoch
+///
+/// `count` is how many times in a row the user snoozed before this one,
+/// for the same `versions`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnoozeFlag {
+    pub since: u64,
+    pub until: u64,
+    pub count: u32,
+    pub versions: String,
+}
+
+/// Parses a duration like `3d`, `12h`, `30m` or `1d12h` into seconds
+///
+/// Units are `d`, `h`, `m` and `s`. Each number needs a unit
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+
+    for x in text.trim().chars() {
+        if x.is_ascii_digit() {
+            number.push(x);
+            continue;
+        }
+
+        let unit = match x {
+            'd' => 60 * 60 * 24,
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(format!("Unknown unit '{x}' in duration '{text}'")),
+        };
+        let value: u64 = number
+            .parse()
+            .map_err(|_| format!("Expected a number before '{x}' in duration '{text}'"))?;
+
+        seconds = value
+            .checked_mul(unit)
+            .and_then(|x| seconds.checked_add(x))
+            .ok_or(format!("Duration '{text}' is too long"))?;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        return Err(format!(
+            "Duration '{text}' needs a unit after {number}, like {number}h"
+        ));
+    }
+    if seconds == 0 {
+        return Err(format!("Duration '{text}' is empty"));
+    }
+
+    return Ok(seconds);
+}
+
+/// Writes `seconds` as a duration `parse_duration` reads, like `1d12h`
+pub fn format_duration(seconds: u64) -> String {
+    if seconds == 0 {
+        return "0s".to_string();
+    }
+
+    let mut text = String::new();
+    let mut left = seconds;
+    for (unit, length) in [('d', 60 * 60 * 24), ('h', 60 * 60), ('m', 60), ('s', 1)] {
+        if left >= length {
+            text.push_str(&format!("{}{unit}", left / length));
+            left %= length;
+        }
+    }
+
+    return text;
+}
+
+/// Describes a snooze of `seconds`, like `2 day(s)`, or in hours if it
+/// isn't a whole number of days
+pub fn describe_duration(seconds: u64) -> String {
+    if seconds.is_multiple_of(NO_UPDATE_FLAG_DELAY) {
+        return format!("{} day(s)", seconds / NO_UPDATE_FLAG_DELAY);
+    }
+
+    return format!("{} hour(s)", seconds.div_ceil(60 * 60));
+}
+
+/// Length of a snooze after snoozing `count` times in a row already.
+/// Doubles each time from `base`, up to `max`
+pub fn snooze_delay(base: u64, count: u32, max: u64) -> u64 {
+    let multiplier = 1u64.checked_shl(count).unwrap_or(u64::MAX);
+
+    return base.saturating_mul(multiplier).min(max);
+}
+
+impl SnoozeFlag {
+    /// A snooze starting at `now` lasting `duration` seconds
+    pub fn new(now: u64, duration: u64) -> SnoozeFlag {
+        return SnoozeFlag {
+            since: now,
+            until: now.saturating_add(duration),
+            count: 0,
+            versions: String::new(),
+        };
+    }
+
+    /// The snooze to write when the user snoozes the updates `versions` at
+    /// `now`, after the `previous` snooze
+    ///
+    /// Snoozing the same versions again doubles the length of the snooze.
+    /// New versions start again from `base` seconds. It doesn't double past
+    /// a week, or past `base` if that's longer
+    pub fn next(previous: Option<&SnoozeFlag>, versions: &str, now: u64, base: u64) -> SnoozeFlag {
+        let count = match previous {
+            Some(previous) if previous.versions == versions => previous.count.saturating_add(1),
+            _ => 0,
+        };
+
+        let delay = snooze_delay(base, count, MAX_NO_UPDATE_FLAG_DELAY.max(base));
+
+        return SnoozeFlag {
+            count,
+            versions: versions.to_string(),
+            ..SnoozeFlag::new(now, delay)
+        };
+    }
+
+    // Flag file is made of `key=value` lines
+    fn parse(content: &str) -> Option<SnoozeFlag> {
+        let mut since = None;
+        let mut until = None;
+        let mut count = 0;
+        let mut versions = String::new();
+
+        for (key, value) in state_file::key_values(content) {
+            match key {
+                "since" => since = value.trim().parse().ok(),
+                "until" => until = value.trim().parse().ok(),
+                "count" => count = value.trim().parse().unwrap_or(0),
+                "versions" => versions = value.trim().to_string(),
+                _ => {}
+            }
+        }
+
+        return Some(SnoozeFlag {
+            since: since?,
+            until: until?,
+            count,
+            versions,
+        });
+    }
+
+    fn to_file_string(&self) -> String {
+        return format!(
+            "since={}\nuntil={}\ncount={}\nversions={}\n",
+            self.since, self.until, self.count, self.versions
+        );
+    }
+}
+
+/// Reads the no-update flag at `path`, None if it doesn't exist
+///
+/// Flags written by older versions are empty files, which are read as a
+/// day long snooze starting at the modification time of the file
+pub fn read_flag(path: &path::Path) -> io::Result<Option<SnoozeFlag>> {
+    let content = match fs::read_to_string(path) {
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(None)
+        }
+        result => result?,
+    };
+
+    if let Some(flag) = SnoozeFlag::parse(&content) {
+        return io::Result::Ok(Some(flag));
+    }
+
+    // Legacy flag. Write time before 1970 is treated as 1970
+    let write_time = fs::metadata(path)?.st_mtime().max(0) as u64;
+
+    return io::Result::Ok(Some(SnoozeFlag::new(write_time, NO_UPDATE_FLAG_DELAY)));
+}
+
+/// Writes the no-update flag to `path`, replacing any old flag
+///
+/// Its directory is made if it's missing, like before rustup has made
+/// `~/.rustup`. A read-only directory is a `PermissionDenied` error saying so
+///
+/// It's written next to `path`, then renamed over it, so a run reading it
+/// at the same time sees the old flag or the new one, but never a missing
+/// or half written flag
+pub fn write_flag(path: &path::Path, flag: &SnoozeFlag) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|x| !x.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+
+        // Checked first, as root can write to it anyway
+        if !is_writable_dir(dir) {
+            return io::Result::Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Can't save the snooze, as '{}' is read-only. Make it writable to snooze",
+                    dir.display()
+                ),
+            ));
+        }
+    }
+
+    return state_file::write_atomic(path, &flag.to_file_string());
+}
+
+/// Deletes the no-update flag at `path`. Not an error if it doesn't exist
+pub fn remove_flag(path: &path::Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        io::Result::Err(error) if error.kind() != io::ErrorKind::NotFound => {
+            return io::Result::Err(error)
+        }
+        _ => return io::Result::Ok(()),
+    }
+}
+
+/// Whether the user has snoozed update prompts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnoozeStatus {
+    /// No snooze flag is set
+    NotSnoozed,
+
+    /// A snooze flag is set, but has run out. The user will be prompted
+    Expired { until: time::SystemTime },
+
+    /// Prompts are snoozed until `until`, which is `remaining` from now
+    Active {
+        until: time::SystemTime,
+        remaining: time::Duration,
+    },
+}
+
+impl SnoozeStatus {
+    /// True if the user won't be prompted because of a snooze
+    pub fn is_active(&self) -> bool {
+        return matches!(self, SnoozeStatus::Active { .. });
+    }
+
+    /// Works out the snooze status of `flag` at the time `now`, in seconds
+    /// since the unix epoch
+    pub fn from_flag(flag: Option<SnoozeFlag>, now: u64) -> SnoozeStatus {
+        let flag = match flag {
+            None => return SnoozeStatus::NotSnoozed,
+            Some(flag) => flag,
+        };
+
+        let until = time::UNIX_EPOCH + time::Duration::from_secs(flag.until);
+
+        // Flag was written in the apparent future... should prompt
+        if flag.since > now || flag.until <= now {
+            return SnoozeStatus::Expired { until };
+        }
+
+        return SnoozeStatus::Active {
+            until,
+            remaining: time::Duration::from_secs(flag.until - now),
+        };
+    }
+}
+
+/// Reads the flag at `path` and works out its status at the time `now`
+pub fn snooze_status_at(path: &path::Path, now: u64) -> io::Result<SnoozeStatus> {
+    return io::Result::Ok(SnoozeStatus::from_flag(read_flag(path)?, now));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::{os::unix::fs::PermissionsExt, sync::atomic::Ordering};
+
+    const NOW: u64 = 1_723_400_000;
+
+    #[test]
+    fn no_flag() {
+        let path = temp_dir("snooze_no_flag").join("donotupdate");
+
+        assert_eq!(
+            snooze_status_at(&path, NOW).unwrap(),
+            SnoozeStatus::NotSnoozed
+        );
+    }
+
+    #[test]
+    fn active_flag() {
+        let path = temp_dir("snooze_active").join("donotupdate");
+        write_flag(&path, &SnoozeFlag::new(NOW - 100, 1000)).unwrap();
+
+        let status = snooze_status_at(&path, NOW).unwrap();
+
+        assert!(status.is_active());
+        assert_eq!(
+            status,
+            SnoozeStatus::Active {
+                until: time::UNIX_EPOCH + time::Duration::from_secs(NOW + 900),
+                remaining: time::Duration::from_secs(900),
+            }
+        );
+    }
+
+    #[test]
+    fn expired_flag() {
+        let path = temp_dir("snooze_expired").join("donotupdate");
+        write_flag(&path, &SnoozeFlag::new(NOW - 1000, 100)).unwrap();
+
+        let status = snooze_status_at(&path, NOW).unwrap();
+
+        assert!(!status.is_active());
+        assert_eq!(
+            status,
+            SnoozeStatus::Expired {
+                until: time::UNIX_EPOCH + time::Duration::from_secs(NOW - 900)
+            }
+        );
+    }
+
+    #[test]
+    fn future_flag_is_expired() {
+        let flag = SnoozeFlag::new(NOW + 100, 1000);
+
+        assert!(!SnoozeStatus::from_flag(Some(flag), NOW).is_active());
+    }
+
+    #[test]
+    fn legacy_empty_flag() {
+        let path = temp_dir("snooze_legacy").join("donotupdate");
+        fs::File::create(&path).unwrap();
+
+        let flag = read_flag(&path).unwrap().unwrap();
+
+        assert_eq!(flag.until - flag.since, NO_UPDATE_FLAG_DELAY);
+    }
+
+    #[test]
+    fn snooze_doubles_up_to_a_week() {
+        let day = NO_UPDATE_FLAG_DELAY;
+
+        assert_eq!(snooze_delay(day, 0, MAX_NO_UPDATE_FLAG_DELAY), day);
+        assert_eq!(snooze_delay(day, 1, MAX_NO_UPDATE_FLAG_DELAY), 2 * day);
+        assert_eq!(snooze_delay(day, 2, MAX_NO_UPDATE_FLAG_DELAY), 4 * day);
+        assert_eq!(snooze_delay(day, 3, MAX_NO_UPDATE_FLAG_DELAY), 7 * day);
+        assert_eq!(snooze_delay(day, 200, MAX_NO_UPDATE_FLAG_DELAY), 7 * day);
+    }
+
+    #[test]
+    fn next_snooze() {
+        let first = SnoozeFlag::next(None, "stable=1.80.1", NOW, NO_UPDATE_FLAG_DELAY);
+        assert_eq!(first.count, 0);
+        assert_eq!(first.until - first.since, NO_UPDATE_FLAG_DELAY);
+
+        let second = SnoozeFlag::next(Some(&first), "stable=1.80.1", NOW, NO_UPDATE_FLAG_DELAY);
+        assert_eq!(second.count, 1);
+        assert_eq!(second.until - second.since, 2 * NO_UPDATE_FLAG_DELAY);
+
+        // A new version starts the snoozes again
+        let new_version =
+            SnoozeFlag::next(Some(&second), "stable=1.81.0", NOW, NO_UPDATE_FLAG_DELAY);
+        assert_eq!(new_version.count, 0);
+        assert_eq!(new_version.until - new_version.since, NO_UPDATE_FLAG_DELAY);
+
+        // Snoozes from a longer base are capped at it, not a week
+        let fortnight = 2 * MAX_NO_UPDATE_FLAG_DELAY;
+        let long = SnoozeFlag::next(Some(&first), "stable=1.80.1", NOW, fortnight);
+        assert_eq!(long.until - long.since, fortnight);
+    }
+
+    #[test]
+    fn described_durations() {
+        assert_eq!(describe_duration(2 * NO_UPDATE_FLAG_DELAY), "2 day(s)");
+        assert_eq!(describe_duration(SHORT_SNOOZE), "1 hour(s)");
+        assert_eq!(describe_duration(90 * 60), "2 hour(s)");
+    }
+
+    #[test]
+    fn flag_round_trip() {
+        let path = temp_dir("snooze_round_trip").join("donotupdate");
+        let flag = SnoozeFlag::next(
+            None,
+            "rustup=1.27.2,stable=1.80.1",
+            NOW,
+            NO_UPDATE_FLAG_DELAY,
+        );
+
+        write_flag(&path, &flag).unwrap();
+
+        assert_eq!(read_flag(&path).unwrap(), Some(flag));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("3d"), Ok(3 * 24 * 60 * 60));
+        assert_eq!(parse_duration("12h"), Ok(12 * 60 * 60));
+        assert_eq!(parse_duration("45m"), Ok(45 * 60));
+        assert_eq!(parse_duration("90s"), Ok(90));
+        assert_eq!(parse_duration("1d12h"), Ok(36 * 60 * 60));
+        assert_eq!(format_duration(36 * 60 * 60), "1d12h");
+        assert_eq!(format_duration(90), "1m30s");
+        assert_eq!(parse_duration(&format_duration(98765)), Ok(98765));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("12").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("3w").is_err());
+        assert!(parse_duration("0h").is_err());
+        assert!(parse_duration("99999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn flag_directory() {
+        let flag = SnoozeFlag::new(NOW, NO_UPDATE_FLAG_DELAY);
+
+        let path = temp_dir("flag_missing_directory").join("missing/donotupdate");
+        write_flag(&path, &flag).unwrap();
+        assert_eq!(read_flag(&path).unwrap(), Some(flag.clone()));
+
+        let dir = temp_dir("flag_read_only_directory");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o555)).unwrap();
+        let error = write_flag(&dir.join("donotupdate"), &flag).unwrap_err();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(error.to_string().contains("is read-only"));
+        assert!(!dir.join("donotupdate").exists());
+    }
+
+    #[test]
+    fn concurrent_flag_writes() {
+        let dir = temp_dir("snooze_concurrent");
+        let path = dir.join("donotupdate");
+        let flags = [
+            SnoozeFlag::next(None, "stable=1.80.1", NOW, NO_UPDATE_FLAG_DELAY),
+            SnoozeFlag::next(
+                None,
+                "nightly=1.83.0,stable=1.80.1",
+                NOW,
+                NO_UPDATE_FLAG_DELAY,
+            ),
+        ];
+        write_flag(&path, &flags[0]).unwrap();
+
+        // Readers only ever see one of the flags written, while they're
+        // written over each other
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    while !done.load(Ordering::SeqCst) {
+                        let flag = read_flag(&path).unwrap().unwrap();
+                        assert!(flags.contains(&flag), "Read a half written flag");
+                        assert!(snooze_status_at(&path, NOW).unwrap().is_active());
+                    }
+                });
+            }
+
+            let writers: Vec<_> = (0..2)
+                .map(|_| {
+                    return scope.spawn(|| {
+                        for x in 0..200 {
+                            write_flag(&path, &flags[x % 2]).unwrap();
+                        }
+                    });
+                })
+                .collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            done.store(true, Ordering::SeqCst);
+        });
+
+        // No temporary files left over
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn remove_missing_flag() {
+        let path = temp_dir("snooze_remove").join("donotupdate");
+
+        remove_flag(&path).unwrap();
+        write_flag(&path, &SnoozeFlag::new(NOW, 10)).unwrap();
+        remove_flag(&path).unwrap();
+
+        assert_eq!(read_flag(&path).unwrap(), None);
+    }
+}
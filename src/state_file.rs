@@ -0,0 +1,84 @@
+// Reading and writing the small files kept between runs, like the snooze,
+// the stats and the last check. Most are made of `key=value` lines
+
+use std::{
+    fs, io, path, process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// Counts files written by this process, so each has its own temporary file
+static FILES_WRITTEN: AtomicU64 = AtomicU64::new(0);
+
+/// The `key=value` lines of `content`, in order, with the key trimmed.
+/// Lines without a `=` are skipped, and a key can be repeated
+pub fn key_values(content: &str) -> impl Iterator<Item = (&str, &str)> {
+    return content.lines().filter_map(|line| {
+        let (key, value) = line.split_once('=')?;
+
+        return Some((key.trim(), value));
+    });
+}
+
+/// Writes `content` to `path`, replacing what was there
+///
+/// It's written next to `path`, then renamed over it, so a run reading it
+/// at the same time sees the old file or the new one, but never half of
+/// one. The temporary file's name ends in `.tmp`
+pub fn write_atomic(path: &path::Path, content: &str) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        FILES_WRITTEN.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temporary = path::PathBuf::from(temporary);
+
+    fs::write(&temporary, content)?;
+    if let io::Result::Err(error) = fs::rename(&temporary, path) {
+        let _ = fs::remove_file(&temporary);
+        return io::Result::Err(error);
+    }
+
+    return io::Result::Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn key_value_lines() {
+        let pairs: Vec<(&str, &str)> =
+            key_values("since=1\nbogus\n count =2\nline=a=b\nline= c \n").collect();
+        assert_eq!(
+            pairs,
+            [
+                ("since", "1"),
+                ("count", "2"),
+                ("line", "a=b"),
+                ("line", " c ")
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_whole_file() {
+        let dir = temp_dir("state_file_replaces_whole_file");
+        let path = dir.join("stats");
+
+        write_atomic(&path, "succeeded=1\n").unwrap();
+        write_atomic(&path, "succeeded=2\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "succeeded=2\n");
+
+        // Nothing is left next to it
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // Nor when it can't be renamed over
+        let other = dir.join("directory");
+        fs::create_dir(&other).unwrap();
+        fs::write(other.join("file"), "").unwrap();
+        assert!(write_atomic(&other, "").is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,154 @@
+// A tally of how updates went, kept with `keep_stats` and shown by
+// `--stats`. It's only ever kept on this machine
+
+use std::{fs, io, path};
+
+use crate::{age, state_file};
+
+/// How many updates worked and failed, and why the last failure did
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct UpdateStats {
+    pub succeeded: u64,
+    pub failed: u64,
+
+    /// Seconds since the unix epoch
+    pub last_failure_at: Option<u64>,
+
+    /// What went wrong, like `The update failed with exit code 1`
+    pub last_failure_reason: Option<String>,
+}
+
+impl UpdateStats {
+    // File is made of `key=value` lines. Unknown or malformed lines are
+    // ignored, so a damaged file starts the counts again
+    fn parse(content: &str) -> UpdateStats {
+        let mut stats = UpdateStats::default();
+
+        for (key, value) in state_file::key_values(content) {
+            match key {
+                "succeeded" => stats.succeeded = value.trim().parse().unwrap_or(0),
+                "failed" => stats.failed = value.trim().parse().unwrap_or(0),
+                "last_failure_at" => stats.last_failure_at = value.trim().parse().ok(),
+                "last_failure_reason" => stats.last_failure_reason = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+
+        return stats;
+    }
+
+    fn serialize(&self) -> String {
+        let mut content = format!("succeeded={}\nfailed={}\n", self.succeeded, self.failed);
+        if let Some(at) = self.last_failure_at {
+            content.push_str(&format!("last_failure_at={at}\n"));
+        }
+        if let Some(reason) = &self.last_failure_reason {
+            // One line, so it reads back
+            content.push_str(&format!(
+                "last_failure_reason={}\n",
+                reason.replace('\n', " ")
+            ));
+        }
+
+        return content;
+    }
+
+    /// Describes the tally for `--stats`, with the last failure's age as of
+    /// `now`
+    pub fn describe(&self, now: u64) -> String {
+        let total = self.succeeded + self.failed;
+        if total == 0 {
+            return "No updates yet\n".to_string();
+        }
+
+        let mut text = format!(
+            "Updates: {total}, {} succeeded, {} failed ({}% succeeded)\n",
+            self.succeeded,
+            self.failed,
+            self.succeeded * 100 / total
+        );
+        if let Some(reason) = &self.last_failure_reason {
+            let when = match self.last_failure_at {
+                Some(at) => format!(" {}", age::describe_age(now.saturating_sub(at))),
+                None => String::new(),
+            };
+            text.push_str(&format!("Last failure{when}: {reason}\n"));
+        }
+
+        return text;
+    }
+}
+
+/// Reads the stats at `path`, all 0 if there isn't a file yet
+pub fn read_stats(path: &path::Path) -> io::Result<UpdateStats> {
+    match fs::read_to_string(path) {
+        io::Result::Ok(content) => return io::Result::Ok(UpdateStats::parse(&content)),
+        io::Result::Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return io::Result::Ok(UpdateStats::default())
+        }
+        io::Result::Err(error) => return io::Result::Err(error),
+    }
+}
+
+// Saves `stats` at `path`, so a crash never leaves half of it
+fn write_stats(path: &path::Path, stats: &UpdateStats) -> io::Result<()> {
+    return state_file::write_atomic(path, &stats.serialize());
+}
+
+/// Counts an update that worked in the stats at `path`
+pub fn record_success(path: &path::Path) -> io::Result<()> {
+    let mut stats = read_stats(path)?;
+    stats.succeeded += 1;
+
+    return write_stats(path, &stats);
+}
+
+/// Counts an update that failed at `now` because of `reason` in the stats
+/// at `path`
+pub fn record_failure(path: &path::Path, now: u64, reason: &str) -> io::Result<()> {
+    let mut stats = read_stats(path)?;
+    stats.failed += 1;
+    stats.last_failure_at = Some(now);
+    stats.last_failure_reason = Some(reason.to_string());
+
+    return write_stats(path, &stats);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    const NOW: u64 = 1_723_400_000;
+
+    #[test]
+    fn counts() {
+        let path = temp_dir("stats_counts").join("stats");
+        assert_eq!(read_stats(&path).unwrap(), UpdateStats::default());
+        assert_eq!(read_stats(&path).unwrap().describe(NOW), "No updates yet\n");
+
+        record_success(&path).unwrap();
+        record_success(&path).unwrap();
+        record_success(&path).unwrap();
+        record_failure(&path, NOW - 2 * 24 * 60 * 60, "exit code 1\nmore").unwrap();
+
+        let stats = read_stats(&path).unwrap();
+        assert_eq!(
+            stats,
+            UpdateStats {
+                succeeded: 3,
+                failed: 1,
+                last_failure_at: Some(NOW - 2 * 24 * 60 * 60),
+                last_failure_reason: Some("exit code 1 more".to_string()),
+            }
+        );
+        assert_eq!(
+            stats.describe(NOW),
+            "Updates: 4, 3 succeeded, 1 failed (75% succeeded)\n\
+             Last failure 2 days ago: exit code 1 more\n"
+        );
+
+        fs::write(&path, "bogus\nfailed=two").unwrap();
+        assert_eq!(read_stats(&path).unwrap(), UpdateStats::default());
+    }
+}
@@ -0,0 +1,142 @@
+// Colors for output in a terminal. Everything colored goes through here, so
+// NO_COLOR and FORCE_COLOR work the same for all of it
+
+use std::{
+    env, ffi,
+    io::{self, IsTerminal},
+};
+
+use regex::Regex;
+
+/// Where output is written, to tell if it's a terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A color for text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    Red,
+    Yellow,
+    Green,
+}
+
+impl Color {
+    // ANSI code of the color
+    fn code(&self) -> &'static str {
+        match self {
+            Color::Red => return "31",
+            Color::Yellow => return "33",
+            Color::Green => return "32",
+        }
+    }
+}
+
+/// Whether output is colored, given the values of `NO_COLOR` and
+/// `FORCE_COLOR`, and if it's written to a terminal
+///
+/// `NO_COLOR` turns color off. Otherwise `FORCE_COLOR` turns it on, even
+/// when it isn't a terminal, unless it's `0`. Otherwise only terminals get
+/// color. Empty variables are the same as unset ones
+pub fn color_enabled(
+    no_color: Option<&ffi::OsStr>,
+    force_color: Option<&ffi::OsStr>,
+    is_terminal: bool,
+) -> bool {
+    if no_color.is_some_and(|x| !x.is_empty()) {
+        return false;
+    }
+    if let Some(force_color) = force_color.filter(|x| !x.is_empty()) {
+        return force_color != "0";
+    }
+
+    return is_terminal;
+}
+
+// `color_enabled` for `stream`, from the environment of this process
+fn use_color(stream: Stream) -> bool {
+    let is_terminal = match stream {
+        Stream::Stdout => io::stdout().is_terminal(),
+        Stream::Stderr => io::stderr().is_terminal(),
+    };
+
+    return color_enabled(
+        env::var_os("NO_COLOR").as_deref(),
+        env::var_os("FORCE_COLOR").as_deref(),
+        is_terminal,
+    );
+}
+
+// `text` in `color`, if `enabled`
+fn paint_if(text: &str, color: Color, enabled: bool) -> String {
+    if !enabled {
+        return text.to_string();
+    }
+
+    return format!("\x1b[{}m{text}\x1b[0m", color.code());
+}
+
+/// `text` in `color`, if output to `stream` is colored
+pub fn paint(text: &str, color: Color, stream: Stream) -> String {
+    return paint_if(text, color, use_color(stream));
+}
+
+/// `message` after a `Warning:` for stderr, in yellow if it's colored
+pub fn warning(message: &str) -> String {
+    return format!(
+        "{} {message}",
+        paint("Warning:", Color::Yellow, Stream::Stderr)
+    );
+}
+
+/// `text` without ANSI escape codes, like the colors of a rustup set to
+/// always color its output
+pub fn strip_ansi(text: &str) -> String {
+    // Control sequences like `\x1b[1;32m`, and the two byte escapes
+    let ansi_regex = Regex::new(r"\x1b(\[[0-?]*[ -/]*[@-~]|[@-Z\\-_])").unwrap();
+
+    return ansi_regex.replace_all(text, "").to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_precedence() {
+        let set = Some(ffi::OsStr::new("1"));
+
+        // NO_COLOR wins, even in a terminal or with FORCE_COLOR
+        assert!(!color_enabled(set, None, true));
+        assert!(!color_enabled(set, set, true));
+
+        // FORCE_COLOR colors output that isn't a terminal
+        assert!(color_enabled(None, set, false));
+        assert!(!color_enabled(None, Some(ffi::OsStr::new("0")), true));
+
+        // Neither, so only a terminal. Empty is unset
+        assert!(!color_enabled(None, None, false));
+        assert!(color_enabled(None, None, true));
+        let empty = Some(ffi::OsStr::new(""));
+        assert!(color_enabled(empty, empty, true));
+        assert!(!color_enabled(empty, empty, false));
+
+        assert_eq!(
+            paint_if("Error:", Color::Red, true),
+            "\x1b[31mError:\x1b[0m"
+        );
+        assert_eq!(paint_if("Error:", Color::Red, false), "Error:");
+    }
+
+    #[test]
+    fn stripped() {
+        assert_eq!(strip_ansi(&paint_if("Error:", Color::Red, true)), "Error:");
+        assert_eq!(
+            strip_ansi("\x1b[1mstable\x1b[0m - \x1b[1;33mUpdate available\x1b[K"),
+            "stable - Update available"
+        );
+        assert_eq!(strip_ansi("no codes [1m here"), "no codes [1m here");
+    }
+}
@@ -0,0 +1,44 @@
+// Terminal emulators the update can be run in, and how to run a command in
+// each of them
+
+/// A terminal emulator that can run a command in a new window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Terminal {
+    pub program: &'static str,
+
+    /// Argument after which the rest of the arguments are the command to
+    /// run, like `--` or `-e`
+    pub exec_flag: &'static str,
+}
+
+/// The known terminal called `name`, like `konsole`, by its program or
+/// the file name of it
+pub fn find_terminal(name: &str) -> Option<Terminal> {
+    return TERMINALS.iter().copied().find(|x| {
+        return x.program == name || x.program.rsplit('/').next() == Some(name);
+    });
+}
+
+/// Terminals to try, in order, until one launches
+pub const TERMINALS: [Terminal; 5] = [
+    Terminal {
+        program: "/bin/gnome-terminal",
+        exec_flag: "--",
+    },
+    Terminal {
+        program: "konsole",
+        exec_flag: "-e",
+    },
+    Terminal {
+        program: "xfce4-terminal",
+        exec_flag: "-x",
+    },
+    Terminal {
+        program: "x-terminal-emulator",
+        exec_flag: "-e",
+    },
+    Terminal {
+        program: "xterm",
+        exec_flag: "-e",
+    },
+];
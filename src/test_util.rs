@@ -0,0 +1,15 @@
+use std::{env, fs, path, process};
+
+/// Makes a new empty directory for a test to write into
+///
+/// The directory is unique to the test name and process, and is emptied if
+/// left over from a previous run
+pub fn temp_dir(name: &str) -> path::PathBuf {
+    let mut dir = env::temp_dir();
+    dir.push(format!("auto_rustup_update_{}_{}", process::id(), name));
+
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Failed to create test directory");
+
+    return dir;
+}
@@ -0,0 +1,379 @@
+//! A small reader for the subset of TOML used by rustup, the Rust dist
+//! server and our own config file, and writer, for `--show-config`
+//!
+//! Keys are flattened into their full dotted path, so `version` under
+//! `[pkg.rust]` is read as `pkg.rust.version`. Supports strings, integers,
+//! booleans and arrays. Inline tables and dates aren't supported
+
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(x) => return Some(x),
+            _ => return None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(x) => return Some(*x),
+            _ => return None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(x) => return Some(*x),
+            _ => return None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(x) => return Some(x),
+            _ => return None,
+        }
+    }
+}
+
+/// Writes the value as it would be in a document
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(x) => {
+                write!(f, "\"")?;
+                for x in x.chars() {
+                    match x {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        x => write!(f, "{x}")?,
+                    }
+                }
+
+                return write!(f, "\"");
+            }
+            Value::Integer(x) => return write!(f, "{x}"),
+            Value::Boolean(x) => return write!(f, "{x}"),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+
+                return write!(f, "]");
+            }
+        }
+    }
+}
+
+/// Every key in a document, by its full dotted path
+pub type Table = BTreeMap<String, Value>;
+
+/// Writes `table` as a document of `key = value` lines, with the dotted
+/// keys as they are, which reads back as the same table
+pub fn write(table: &Table) -> String {
+    return table
+        .iter()
+        .map(|(key, value)| format!("{key} = {value}\n"))
+        .collect();
+}
+
+// Removes a comment from the end of a line, ignoring '#'s in strings
+fn strip_comment(line: &str) -> &str {
+    let mut in_string: Option<char> = None;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        match in_string {
+            Some(quote) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && quote == '"' {
+                    escaped = true;
+                } else if c == quote {
+                    in_string = None;
+                }
+            }
+            None if c == '"' || c == '\'' => in_string = Some(c),
+            None if c == '#' => return &line[..i],
+            None => {}
+        }
+    }
+
+    return line;
+}
+
+// Count of '[' not closed by ']', ignoring brackets in strings
+fn open_brackets(text: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string: Option<char> = None;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        match in_string {
+            Some(quote) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && quote == '"' {
+                    escaped = true;
+                } else if c == quote {
+                    in_string = None;
+                }
+            }
+            None if c == '"' || c == '\'' => in_string = Some(c),
+            None if c == '[' => depth += 1,
+            None if c == ']' => depth -= 1,
+            None => {}
+        }
+    }
+
+    return depth;
+}
+
+// Parses a string starting at the opening quote. Returns the string and
+// the rest of the text after the closing quote
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    let quote = text.chars().next().ok_or("Expected a string")?;
+    let mut result = String::new();
+    let mut chars = text.char_indices().skip(1);
+
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return Ok((result, &text[i + 1..]));
+        }
+
+        if c == '\\' && quote == '"' {
+            match chars.next().map(|x| x.1) {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('r') => result.push('\r'),
+                Some('"') => result.push('"'),
+                Some('\\') => result.push('\\'),
+                x => return Err(format!("Unknown escape '\\{}'", x.unwrap_or(' '))),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+
+    return Err("Unterminated string".to_string());
+}
+
+// Parses a single value, returning it and the text left after it
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    let text = text.trim_start();
+
+    if text.starts_with('"') || text.starts_with('\'') {
+        let (string, rest) = parse_string(text)?;
+        return Ok((Value::String(string), rest));
+    }
+
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut items = Vec::new();
+
+        loop {
+            rest = rest.trim_start();
+
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+
+            let (item, after) = parse_value(rest)?;
+            items.push(item);
+
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("Expected ',' or ']' in array".to_string());
+            }
+        }
+    }
+
+    // Bare values run until a separator
+    let end = text
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(text.len());
+    let (bare, rest) = text.split_at(end);
+
+    let value = match bare {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => match bare.replace('_', "").parse::<i64>() {
+            Ok(x) => Value::Integer(x),
+            Err(_) => return Err(format!("Unsupported value '{bare}'")),
+        },
+    };
+
+    return Ok((value, rest));
+}
+
+// Splits a dotted key into its parts, removing quotes from quoted parts
+fn parse_key(text: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    let mut rest = text.trim();
+
+    while !rest.is_empty() {
+        if rest.starts_with('"') || rest.starts_with('\'') {
+            let (part, after) = parse_string(rest)?;
+            parts.push(part);
+            rest = after.trim_start();
+        } else {
+            let end = rest.find('.').unwrap_or(rest.len());
+            let part = rest[..end].trim();
+            if part.is_empty() {
+                return Err(format!("Empty key in '{text}'"));
+            }
+            parts.push(part.to_string());
+            rest = &rest[end..];
+        }
+
+        if let Some(after) = rest.strip_prefix('.') {
+            rest = after.trim_start();
+        } else if !rest.trim().is_empty() {
+            return Err(format!("Malformed key '{text}'"));
+        }
+    }
+
+    if parts.is_empty() {
+        return Err("Empty key".to_string());
+    }
+
+    return Ok(parts);
+}
+
+/// Reads a TOML document into a flat table of dotted keys
+///
+/// The error contains the line number and what was wrong with it
+pub fn parse(document: &str) -> Result<Table, String> {
+    let mut table = Table::new();
+    let mut section: Vec<String> = Vec::new();
+    let mut lines = document.lines().enumerate();
+
+    while let Some((number, line)) = lines.next() {
+        let error = |message: String| format!("Line {}: {}", number + 1, message);
+        let line = strip_comment(line).trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        // Table headers. Arrays of tables are merged into one table
+        if line.starts_with('[') {
+            let header = line
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string();
+            section = parse_key(&header).map_err(error)?;
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error(format!("Expected 'key = value', found '{line}'")))?;
+
+        // Arrays can be split over many lines
+        let mut value = value.to_string();
+        while open_brackets(&value) > 0 {
+            match lines.next() {
+                Some((_, next)) => {
+                    value.push(' ');
+                    value.push_str(strip_comment(next));
+                }
+                None => return Err(error("Unterminated array".to_string())),
+            }
+        }
+
+        let (parsed, rest) = parse_value(&value).map_err(error)?;
+        if !rest.trim().is_empty() {
+            return Err(error(format!("Unexpected '{}' after value", rest.trim())));
+        }
+
+        let mut full_key = section.clone();
+        full_key.extend(parse_key(key).map_err(error)?);
+
+        table.insert(full_key.join("."), parsed);
+    }
+
+    return Ok(table);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_and_values() {
+        let table = parse(
+            r#"
+            # A comment
+            name = "auto # not a comment"
+            count = 1_000
+            enabled = true
+
+            [section.inner]
+            list = ["a", 'b',
+                    "c"] # The end
+            "quoted.key" = false
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(table["name"].as_str(), Some("auto # not a comment"));
+        assert_eq!(table["count"], Value::Integer(1000));
+        assert_eq!(table["enabled"], Value::Boolean(true));
+        assert_eq!(
+            table["section.inner.list"],
+            Value::Array(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string()),
+                Value::String("c".to_string()),
+            ])
+        );
+        assert_eq!(table["section.inner.quoted.key"], Value::Boolean(false));
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let table = Table::from([
+            (
+                "name".to_string(),
+                Value::String("quote \" and \\ # not a comment".to_string()),
+            ),
+            ("count".to_string(), Value::Integer(-3)),
+            ("enabled".to_string(), Value::Boolean(true)),
+            (
+                "section.list".to_string(),
+                Value::Array(vec![
+                    Value::String("a".to_string()),
+                    Value::String("b\nc".to_string()),
+                ]),
+            ),
+        ]);
+
+        let document = write(&table);
+        assert!(document.contains("section.list = [\"a\", \"b\\nc\"]\n"));
+        assert_eq!(parse(&document).unwrap(), table);
+    }
+
+    #[test]
+    fn malformed_line() {
+        let error = parse("name = \"ok\"\nthis is wrong").unwrap_err();
+        assert!(error.starts_with("Line 2"));
+    }
+}
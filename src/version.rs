@@ -0,0 +1,78 @@
+use std::{fmt, str};
+
+use regex::Regex;
+
+/// A `major.minor.patch` version, like `1.80.1`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Version {
+        return Version {
+            major,
+            minor,
+            patch,
+        };
+    }
+
+    /// Finds the first version in some text, like the output of
+    /// `rustc --version`
+    pub fn find(text: &str) -> Option<Version> {
+        let sem_ver_regex = Regex::new(r"[0-9]+\.[0-9]+\.[0-9]+").unwrap();
+
+        return sem_ver_regex.find(text)?.as_str().parse().ok();
+    }
+}
+
+impl str::FromStr for Version {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<u64> = text
+            .trim()
+            .split('.')
+            .map(|x| x.parse::<u64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("'{text}' isn't a version"))?;
+
+        match parts.as_slice() {
+            [major, minor, patch] => return Ok(Version::new(*major, *minor, *patch)),
+            _ => return Err(format!("'{text}' isn't a major.minor.patch version")),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}.{}.{}", self.major, self.minor, self.patch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_compare() {
+        let old: Version = "1.80.0".parse().unwrap();
+        let new: Version = "1.80.1".parse().unwrap();
+
+        assert!(old < new);
+        assert!(Version::new(1, 9, 0) < Version::new(1, 10, 0));
+        assert_eq!(new.to_string(), "1.80.1");
+        assert!("1.80".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn find_in_text() {
+        assert_eq!(
+            Version::find("rustc 1.80.1 (3f5fd8dd4 2024-08-06)"),
+            Some(Version::new(1, 80, 1))
+        );
+        assert_eq!(Version::find("no version here"), None);
+    }
+}
@@ -0,0 +1,122 @@
+// `--watch`, which stays running and checks on an interval, for machines
+// without systemd timers
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread, time,
+};
+
+use crate::{Error, Outcome};
+
+// Set when SIGTERM is received, to stop watching after the current check
+static STOP: AtomicBool = AtomicBool::new(false);
+
+const SIGTERM: i32 = 15;
+
+extern "C" {
+    // From the C library, which std already links
+    fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+}
+
+extern "C" fn on_sigterm(_: i32) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+/// Makes SIGTERM stop watching, between checks, rather than killing the
+/// program in the middle of one
+pub fn handle_sigterm() {
+    // Only sets an atomic, which is safe in a signal handler
+    unsafe {
+        signal(SIGTERM, on_sigterm);
+    }
+}
+
+/// True once SIGTERM has been received
+pub fn stop_requested() -> bool {
+    return STOP.load(Ordering::SeqCst);
+}
+
+/// Waits between checks, so tests can skip the wait
+pub trait Sleeper {
+    /// Waits `seconds`, or less if asked to stop. Returns false if stopped
+    fn sleep(&self, seconds: u64) -> bool;
+}
+
+/// Sleeps for real, a second at a time so SIGTERM stops it quickly
+pub struct SystemSleeper;
+
+impl Sleeper for SystemSleeper {
+    fn sleep(&self, seconds: u64) -> bool {
+        for _ in 0..seconds {
+            if stop_requested() {
+                return false;
+            }
+            thread::sleep(time::Duration::from_secs(1));
+        }
+
+        return !stop_requested();
+    }
+}
+
+/// Runs `check`, then again every `interval` seconds, until `sleeper` is
+/// stopped. A failed check is printed, and checked again next time, as it's
+/// often the network
+pub fn watch_loop(
+    interval: u64,
+    sleeper: &dyn Sleeper,
+    check: &mut dyn FnMut() -> Result<Outcome, Error>,
+) {
+    loop {
+        match check() {
+            Ok(outcome) => println!("Checked: {}", outcome.name()),
+            Err(error) => eprintln!("Error: {error}. Checking again later"),
+        }
+
+        if !sleeper.sleep(interval) {
+            println!("Stopped watching");
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    // Records each sleep, and stops after `sleeps` of them
+    struct FakeSleeper {
+        slept: RefCell<Vec<u64>>,
+        sleeps: usize,
+    }
+
+    impl Sleeper for FakeSleeper {
+        fn sleep(&self, seconds: u64) -> bool {
+            self.slept.borrow_mut().push(seconds);
+            return self.slept.borrow().len() < self.sleeps;
+        }
+    }
+
+    #[test]
+    fn checks_each_interval() {
+        let sleeper = FakeSleeper {
+            slept: RefCell::new(Vec::new()),
+            sleeps: 3,
+        };
+        let mut checks = 0;
+
+        // Errors don't stop it
+        watch_loop(6 * 60 * 60, &sleeper, &mut || {
+            checks += 1;
+            match checks {
+                2 => return Err(Error::NoInternet),
+                _ => return Ok(Outcome::UpToDate),
+            }
+        });
+
+        // Straight away, then after each of the first two intervals. Stopped
+        // during the third
+        assert_eq!(checks, 3);
+        assert_eq!(*sleeper.slept.borrow(), [6 * 60 * 60; 3]);
+    }
+}
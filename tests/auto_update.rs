@@ -0,0 +1,118 @@
+// Clippy configurations
+#![allow(clippy::needless_return)]
+
+use std::{env, fs, io, path};
+
+use auto_rustup_update::{
+    auto_update_with_runner, CommandOutput, Config, FakeBackend, MockRunner, Outcome, StatePaths,
+    UpdatePromptAnswer,
+};
+
+// A new empty directory for a test to keep its state in
+fn temp_dir(name: &str) -> path::PathBuf {
+    let dir = env::temp_dir().join(format!(
+        "auto_rustup_update_integration_{}_{name}",
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    return dir;
+}
+
+// Answers `rustup check` with an update for stable, and succeeds at
+// everything else
+fn runner() -> MockRunner {
+    return MockRunner::new(|command| {
+        if command.args.contains(&"check".to_string()) {
+            return io::Result::Ok(CommandOutput::new(
+                0,
+                "stable - Update available : 1.80.0 -> 1.80.1\nrustup - Up to date : 1.27.1\n",
+                "",
+            ));
+        }
+
+        return io::Result::Ok(CommandOutput::new(0, "", ""));
+    });
+}
+
+#[test]
+fn update_answered() {
+    let paths = StatePaths::in_dir(&temp_dir("update_answered"));
+    let runner = runner();
+    let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+
+    let outcome = auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+    assert_eq!(outcome, Outcome::Updated);
+
+    // Prompted once, listing the new version
+    let prompts = backend.prompts();
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0].text.contains("stable: 1.80.1"));
+
+    // The update command was run in a terminal, for only stable
+    let updates = runner.calls_to("/bin/gnome-terminal");
+    assert_eq!(updates.len(), 1);
+    assert!(updates[0].args.ends_with(&[
+        "rustup".to_string(),
+        "update".to_string(),
+        "stable".to_string()
+    ]));
+
+    // Nothing snoozed, and the next run checks again
+    assert!(!paths.flag.exists());
+    assert!(!paths.last_check.exists());
+}
+
+#[test]
+fn dry_run_report() {
+    let dir = temp_dir("dry_run_report");
+    let paths = StatePaths::in_dir(&dir);
+    let runner = runner();
+    let backend = FakeBackend::new(UpdatePromptAnswer::Update);
+
+    let config = Config {
+        dry_run: true,
+        report_path: Some(dir.join("report.json")),
+        ..Config::default()
+    };
+
+    let outcome = auto_update_with_runner(&config, &runner, &backend, &paths).unwrap();
+    assert_eq!(outcome, Outcome::DryRun);
+
+    let report = fs::read_to_string(dir.join("report.json")).unwrap();
+    assert!(report.contains("\"outcome\":\"dry_run\""));
+    assert!(report.contains("{\"current\":\"1.80.0\",\"name\":\"stable\",\"new\":\"1.80.1\"}"));
+
+    // Nothing but the check was run, and the snooze wasn't touched
+    assert!(backend.prompts().is_empty());
+    assert!(runner
+        .calls()
+        .iter()
+        .all(|x| x.args.contains(&"check".to_string())));
+    assert!(!paths.flag.exists());
+}
+
+#[test]
+fn declined_then_snoozed() {
+    let paths = StatePaths::in_dir(&temp_dir("declined_then_snoozed"));
+    let runner = runner();
+    let backend = FakeBackend::new(UpdatePromptAnswer::DoNotUpdate);
+
+    let outcome = auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+    assert_eq!(outcome, Outcome::Declined);
+    assert!(paths.flag.exists());
+
+    // Running again soon after uses the saved check, and doesn't prompt
+    let outcome = auto_update_with_runner(&Config::default(), &runner, &backend, &paths).unwrap();
+    assert_eq!(outcome, Outcome::Snoozed);
+
+    assert_eq!(backend.prompts().len(), 1);
+    assert!(runner.calls_to("/bin/gnome-terminal").is_empty());
+    let checks = runner
+        .calls()
+        .iter()
+        .filter(|x| x.args.contains(&"check".to_string()))
+        .count();
+    assert_eq!(checks, 1);
+}